@@ -0,0 +1,48 @@
+//! Timings of the hot paths, run with `cargo bench`.
+//!
+//! Each path runs for a fixed number of rounds and reports the time a round
+//! took on average. Like `--bench`, it plays on the crowded field of
+//! [`term_invaders::bench::crowded`].
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use term_invaders::bench;
+use term_invaders::collision::{Aabb, Columns};
+use term_invaders::game;
+use term_invaders::render::snapshot::Snapshot;
+use term_invaders::render::theme::CLASSIC;
+use term_invaders::render::{self, Layout};
+
+/// Runs `round` `rounds` times and prints how long one took.
+fn time(name: &str, rounds: u32, mut round: impl FnMut()) {
+    let started = Instant::now();
+    for _ in 0..rounds {
+        round();
+    }
+    let each = started.elapsed() / rounds;
+    println!("{:<20} {:>10.2?} per round, {} rounds", name, each, rounds);
+}
+
+fn main() {
+    // Ticks from the same field every round, so each does the same work
+    let state = bench::crowded(1);
+    time("update_state", 2_000, || {
+        let mut state = state.clone();
+        game::update_state(&mut state);
+        black_box(&state);
+    });
+
+    let mut columns = Columns::default();
+    time("shots vs aliens", 2_000, || {
+        columns.fill(state.world.aliens.bboxes());
+        let hits = state.world.shots.iter().map(|shot| shot.cell());
+        black_box(hits.filter_map(|(x, y)| columns.first_hit(Aabb::cell(x, y))).count());
+    });
+
+    let layout = Layout::new(state.settings.width, state.settings.height);
+    let mut snapshot = Snapshot::new(layout.width() as usize, layout.height() as usize);
+    time("draw_game", 2_000, || {
+        render::draw_game(&mut snapshot, &layout, &state, 0, &CLASSIC);
+    });
+}
@@ -0,0 +1,135 @@
+//! Achievements, unlocked once and for all by feats in a run, persisted in
+//! the data directory.
+//!
+//! The file lists the names of the achievements unlocked, one per line.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::game::GameState;
+use crate::locale::tr;
+use crate::storage;
+
+const FILE_NAME: &str = "achievements";
+
+/// The wave a run has to reach for [`Achievement::Wave10`].
+const WAVE_GOAL: u32 = 10;
+/// The accuracy, in percent, a wave has to be cleared with for
+/// [`Achievement::Sharpshooter`].
+const SHARPSHOOTER_ACCURACY: u32 = 90;
+/// How long the ship has to hold its fire for [`Achievement::Pacifist`].
+const PACIFIST_TIME: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Achievement {
+    /// Destroy an alien.
+    FirstBlood,
+    /// Clear a wave with 90% accuracy or better.
+    Sharpshooter,
+    /// Reach wave 10.
+    Wave10,
+    /// Survive 30 seconds without shooting.
+    Pacifist,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 4] =
+        [Achievement::FirstBlood, Achievement::Sharpshooter, Achievement::Wave10, Achievement::Pacifist];
+
+    /// The name the file knows the achievement by.
+    fn name(self) -> &'static str {
+        match self {
+            Achievement::FirstBlood => "first_blood",
+            Achievement::Sharpshooter => "sharpshooter",
+            Achievement::Wave10 => "wave_10",
+            Achievement::Pacifist => "pacifist",
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        tr(match self {
+            Achievement::FirstBlood => "achievement_first_blood",
+            Achievement::Sharpshooter => "achievement_sharpshooter",
+            Achievement::Wave10 => "achievement_wave_10",
+            Achievement::Pacifist => "achievement_pacifist",
+        })
+    }
+}
+
+/// The achievements unlocked so far.
+pub struct Achievements {
+    path: Option<PathBuf>,
+    unlocked: Vec<Achievement>,
+}
+
+impl Achievements {
+    /// Reads the unlocked achievements; unknown names are dropped and a
+    /// missing file means none yet.
+    pub fn load() -> Self {
+        let path = storage::data_file(FILE_NAME);
+        let text = path.as_ref().and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+        let unlocked = Achievement::ALL
+            .into_iter()
+            .filter(|achievement| text.lines().any(|line| line.trim() == achievement.name()))
+            .collect();
+        Achievements { path, unlocked }
+    }
+
+    pub fn unlocked(&self) -> &[Achievement] {
+        &self.unlocked
+    }
+
+    /// Unlocks whichever of `earned` was still locked, saving them straight
+    /// away, and returns those.
+    pub fn unlock(&mut self, earned: Vec<Achievement>) -> Vec<Achievement> {
+        let new: Vec<Achievement> = earned.into_iter().filter(|achievement| !self.unlocked.contains(achievement)).collect();
+        if new.is_empty() {
+            return new;
+        }
+        self.unlocked.extend(&new);
+        if let Some(path) = &self.path {
+            let text: String = self.unlocked.iter().map(|achievement| format!("{}\n", achievement.name())).collect();
+            // Losing the file only costs the unlocks, never the running game
+            let _ = storage::write_atomic(path, &text);
+        }
+        new
+    }
+}
+
+/// Keeps an eye on a run for the achievements it earns.
+pub struct Watcher {
+    /// Shots fired as of the last look, to tell when the ship fires.
+    shots_fired: u32,
+    /// Game time since which the ship has not fired.
+    quiet_since: Duration,
+}
+
+impl Watcher {
+    /// Starts watching a run as it stands in `state`.
+    pub fn new(state: &GameState) -> Self {
+        Watcher { shots_fired: state.run_stats.shots_fired, quiet_since: state.clock }
+    }
+
+    /// The achievements the run has earned as of now, locked or not.
+    pub fn earned(&mut self, state: &GameState) -> Vec<Achievement> {
+        if state.run_stats.shots_fired != self.shots_fired || state.laser.is_some() {
+            self.shots_fired = state.run_stats.shots_fired;
+            self.quiet_since = state.clock;
+        }
+        let mut earned = Vec::new();
+        if state.run_stats.aliens_destroyed > 0 {
+            earned.push(Achievement::FirstBlood);
+        }
+        if state.summary.is_some_and(|summary| summary.shots_fired > 0 && summary.accuracy >= SHARPSHOOTER_ACCURACY) {
+            earned.push(Achievement::Sharpshooter);
+        }
+        if state.wave >= WAVE_GOAL {
+            earned.push(Achievement::Wave10);
+        }
+        if !state.game_over && state.clock - self.quiet_since >= PACIFIST_TIME {
+            earned.push(Achievement::Pacifist);
+        }
+        earned
+    }
+}
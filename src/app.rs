@@ -0,0 +1,339 @@
+use std::collections::VecDeque;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io;
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::TcpListener;
+use std::path::Path;
+use std::ptr;
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::game::Settings;
+#[cfg(all(feature = "gamepad", target_os = "linux"))]
+use crate::gamepad::Gamepad;
+use crate::bot::Controller;
+use crate::config::Config;
+use crate::keymap::{Binding, KeyMap};
+use crate::input::Key;
+use crate::level::Level;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::locale::tr_with;
+use crate::net::Link;
+use crate::render::pixels::PixelMode;
+use crate::render::sprites::SpriteSet;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::render::telnet::TelnetRenderer;
+use crate::render::theme::Theme;
+use crate::render::{Backend, Layout, Renderer};
+use crate::replay::{Playback, Replay};
+use crate::scene::editor::Editor;
+use crate::scene::title::TitleScreen;
+use crate::scene::{Context, Run, Scene};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::screenshot;
+use crate::spectate::Feed;
+
+/// How often a frame is drawn, independently of the game's ticks.
+const FRAME_INTERVAL: Duration = Duration::from_millis(50);
+/// The most ticks run in one go to catch up after the loop fell behind;
+/// beyond that, the game slows down instead of jumping ahead.
+const MAX_CATCH_UP_TICKS: u32 = 5;
+
+// --- Main Game Loop ---
+
+/// Runs the game on `backend`, starting at the title screen, until the
+/// player quits. With `pixels` set, the playfield is drawn in pixels.
+pub fn run(backend: &mut dyn Backend, settings: Settings, pixels: Option<PixelMode>) {
+    let ctx = Context { pixels, ..Context::load(settings) };
+    let title = TitleScreen::new(&ctx);
+    run_scene(backend, ctx, Scene::TitleScreen(title));
+}
+
+/// Plays `replay` back on `backend` at the speed it was recorded.
+pub fn play_replay(backend: &mut dyn Backend, replay: &Replay, pixels: Option<PixelMode>) {
+    let ctx = Context { pixels, ..Context::load(replay.settings.clone()) };
+    run_scene(backend, ctx, Scene::Replay(Playback::new(replay)));
+}
+
+/// Edits the level file at `path` on `backend`, starting from `level` as
+/// read from it, or from a new level if there was none.
+pub fn edit(
+    backend: &mut dyn Backend,
+    settings: Settings,
+    pixels: Option<PixelMode>,
+    path: &Path,
+    level: Option<&Level>,
+) {
+    let ctx = Context { pixels, ..Context::load(settings) };
+    run_scene(backend, ctx, Scene::Editor(Editor::new(path, level)));
+}
+
+/// Has `controller` play a run of `settings` on `backend`, which then ends
+/// like any other run.
+pub fn play_bot(backend: &mut dyn Backend, settings: Settings, pixels: Option<PixelMode>, controller: Box<dyn Controller>) {
+    let mut run = Run::new(&settings);
+    run.hand_to(controller);
+    let ctx = Context { pixels, ..Context::load(settings) };
+    run_scene(backend, ctx, Scene::Playing(run));
+}
+
+/// Fits `settings` to `backend`: hitboxes follow the sprites the run is
+/// drawn with, and the playfield is `size` or else fills the screen, with
+/// `pixels` fitting several game cells into each of its cells.
+pub fn fit(backend: &dyn Backend, settings: Settings, size: Option<(u16, u16)>, pixels: Option<PixelMode>) -> Settings {
+    let sprites = backend.sprites();
+    let (columns, lines) = size.unwrap_or_else(|| Layout::field_size_for(backend.size()));
+    let (per_column, per_line) = pixels.map_or((1, 1), PixelMode::cell_size);
+    Settings {
+        player_size: sprites.player_size(),
+        alien_size: sprites.alien_size(),
+        width: columns * per_column,
+        height: lines * per_line,
+        ..settings
+    }
+}
+
+/// Serves the game to telnet clients connecting on `port`, each playing a
+/// session of its own from the title screen, on a thread of its own. Every
+/// session's playfield is `size` or fills its client's screen, drawn in
+/// `pixels` if set and with `sprites` instead of plain ASCII if given. Only
+/// failing to listen ends it.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn serve(
+    port: u16,
+    settings: Settings,
+    size: Option<(u16, u16)>,
+    pixels: Option<PixelMode>,
+    sprites: Option<&'static SpriteSet>,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    for stream in listener.incoming() {
+        // A client that is gone before it is set up is no reason to stop
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let settings = settings.clone();
+        thread::spawn(move || {
+            let Ok(mut renderer) = TelnetRenderer::new(stream) else {
+                return;
+            };
+            if let Some(sprites) = sprites {
+                renderer.set_sprites(sprites);
+            }
+            let settings = fit(&renderer, settings, size, pixels);
+            let ctx = Context { pixels, ..Context::load(settings) };
+            // Sounds would only play here, not to the client
+            #[cfg(feature = "sound")]
+            let ctx = Context { sound: None, ..ctx };
+            let title = TitleScreen::new(&ctx);
+            run_scene(&mut renderer, ctx, Scene::TitleScreen(title));
+        });
+    }
+    Ok(())
+}
+
+/// Hosts a game of `settings` on `backend` for the guest on the other end
+/// of `link`, starting it straight away.
+pub fn host(backend: &mut dyn Backend, settings: Settings, pixels: Option<PixelMode>, mut link: Link) {
+    let mut run = Run::new(&settings);
+    link.send_settings(&run.state);
+    run.connect(link, 0);
+    let ctx = Context { pixels, networked: true, ..Context::load(settings) };
+    run_scene(backend, ctx, Scene::Playing(run));
+}
+
+/// Plays the game of `settings` the host on the other end of `link` runs,
+/// as its second player.
+pub fn join(backend: &mut dyn Backend, settings: Settings, pixels: Option<PixelMode>, link: Link) {
+    let mut run = Run::new(&settings);
+    run.connect(link, 1);
+    let ctx = Context { pixels, networked: true, ..Context::load(settings) };
+    run_scene(backend, ctx, Scene::Playing(run));
+}
+
+/// Shows the frames of the game `feed` is of on `backend` as they arrive,
+/// until the game ends or the quit key is pressed.
+pub fn watch(backend: &mut dyn Backend, mut feed: Feed) {
+    let keys = KeyMap::from_entries(&Config::load().keys);
+    let mut theme = None;
+    while !feed.is_closed() {
+        if let Some(key) = backend.poll_key(FRAME_INTERVAL) {
+            if keys.binding(key) == Some(Binding::Quit) {
+                return;
+            }
+        }
+        if let Some(frame) = feed.latest() {
+            if theme != Some(frame.theme) {
+                backend.set_theme(frame.theme);
+                theme = Some(frame.theme);
+            }
+            frame.show(backend);
+        }
+    }
+}
+
+fn run_scene(backend: &mut dyn Backend, ctx: Context, scene: Scene) {
+    let started = Instant::now();
+    let clock = || started.elapsed();
+    let mut session = Session::new(backend, ctx, scene, clock());
+    #[cfg(all(feature = "gamepad", target_os = "linux"))]
+    let mut gamepad = Gamepad::open();
+    loop {
+        // Gamepad presses join the keys, read once a round
+        #[cfg(all(feature = "gamepad", target_os = "linux"))]
+        let pending = VecDeque::from(gamepad.as_mut().map(Gamepad::poll).unwrap_or_default());
+        #[cfg(not(all(feature = "gamepad", target_os = "linux")))]
+        let pending = VecDeque::new();
+        match session.step(backend, pending, &clock) {
+            Some(next) => session = next,
+            None => return,
+        }
+    }
+}
+
+/// A scene running on a backend, one round of the main loop at a time, for
+/// frontends that cannot wait for keys and are called back instead. Times
+/// are counted from any moment the caller likes, as given by its clock.
+pub struct Session {
+    ctx: Context,
+    scene: Scene,
+    next_tick: Duration,
+    next_frame: Duration,
+    theme: Option<&'static Theme>,
+    sprites: &'static SpriteSet,
+}
+
+impl Session {
+    /// Starts `scene` on `backend` at time `now`.
+    pub fn new(backend: &mut dyn Backend, ctx: Context, scene: Scene, now: Duration) -> Self {
+        if ctx.mouse {
+            backend.capture_mouse();
+        }
+        let sprites = backend.sprites();
+        let ctx = Context { base_sprites: sprites, ..ctx };
+        Session {
+            next_tick: now + ctx.settings.tick,
+            next_frame: now,
+            ctx,
+            scene,
+            theme: None,
+            sprites,
+        }
+    }
+
+    /// Takes the keys that arrived, `pending` first, runs the ticks that
+    /// are due and draws a frame if one is. Returns the session to go on
+    /// with, or `None` once the player has quit.
+    pub fn step(
+        mut self,
+        backend: &mut dyn Backend,
+        mut pending: VecDeque<Key>,
+        clock: &dyn Fn() -> Duration,
+    ) -> Option<Self> {
+        // A player at the other end of a connection may have hung up
+        if backend.is_closed() {
+            self.scene.end();
+            return None;
+        }
+
+        // Nothing moves while the terminal is too small to show the game,
+        // and a game in progress stays paused once it is big enough again
+        let ctx = &mut self.ctx;
+        ctx.screen = backend.size();
+        let fits = self.scene.layout(ctx, ctx.screen).fits(ctx.screen);
+        if !fits {
+            self.scene = self.scene.suspend();
+        }
+
+        // Wait for input until the next tick or frame is due, then take every
+        // key that arrived, so held keys never hold up the game
+        let wait = self.next_tick.min(self.next_frame).saturating_sub(clock());
+        let mut key = pending.pop_front().or_else(|| backend.poll_key(wait));
+        while let Some(pressed) = key {
+            // F12 takes a screenshot of whatever is on screen
+            #[cfg(not(target_arch = "wasm32"))]
+            if pressed == Key::Function(12) {
+                match screenshot::save(&self.scene, ctx, ctx.screen, self.sprites) {
+                    Ok(path) => self.scene.toast(tr_with("toast_screenshot", &[&path.display()])),
+                    Err(err) => self.scene.toast(err),
+                }
+                key = pending.pop_front().or_else(|| backend.poll_key(Duration::ZERO));
+                continue;
+            }
+            log::debug!("key {:?} in {}", pressed, self.scene.name());
+            if fits || ctx.keys.binding(pressed) == Some(Binding::Quit) {
+                let before = self.scene.name();
+                let Some(next) = self.scene.handle_key(pressed, ctx) else {
+                    log::info!("quit from {}", before);
+                    return None;
+                };
+                self.scene = next;
+                log_change(before, &self.scene);
+            }
+            key = pending.pop_front().or_else(|| backend.poll_key(Duration::ZERO));
+        }
+
+        // Update game state on a fixed timestep, whatever the input did
+        let now = clock();
+        if !fits {
+            self.next_tick = now + ctx.settings.tick;
+        }
+        let mut ticks = 0;
+        while fits && now >= self.next_tick && ticks < MAX_CATCH_UP_TICKS {
+            let before = self.scene.name();
+            self.scene = self.scene.update(ctx);
+            log_change(before, &self.scene);
+            self.next_tick += ctx.settings.tick;
+            ticks += 1;
+            if let Some(stats) = &mut ctx.debug {
+                stats.tick();
+            }
+        }
+        if now >= self.next_tick {
+            self.next_tick = now + ctx.settings.tick;
+        }
+        if ctx.ring {
+            ctx.ring = false;
+            backend.bell(ctx.bell.flash);
+        }
+
+        // Render the current state, repainting if the theme changed, and
+        // drawing with a mod's sprites once one is picked; the runs to come
+        // are hit where they are drawn
+        if now < self.next_frame && ticks == 0 {
+            return Some(self);
+        }
+        if self.theme != Some(ctx.theme) {
+            backend.set_theme(ctx.theme);
+            self.theme = Some(ctx.theme);
+        }
+        let wanted = ctx.sprites.unwrap_or(ctx.base_sprites);
+        if !ptr::eq(self.sprites, wanted) {
+            backend.set_sprites(wanted);
+            ctx.settings.player_size = wanted.player_size();
+            ctx.settings.alien_size = wanted.alien_size();
+            self.sprites = wanted;
+        }
+        let started = clock();
+        self.scene.render(backend, ctx);
+        if let Some(stats) = &mut ctx.debug {
+            stats.render_time = clock().saturating_sub(started);
+        }
+        self.next_frame = started + FRAME_INTERVAL;
+        Some(self)
+    }
+
+    /// Ends the scene, as when the player at the other end hangs up.
+    pub fn end(self) {
+        self.scene.end();
+    }
+}
+
+/// Logs the scene having gone from the one called `before` to `scene`, if
+/// it did.
+fn log_change(before: &str, scene: &Scene) {
+    if scene.name() != before {
+        log::debug!("scene {} -> {}", before, scene.name());
+    }
+}
@@ -0,0 +1,131 @@
+//! The benchmark `--bench` runs, which times the game logic on a field far
+//! more crowded than any wave gets, to measure changes to the hot paths.
+//!
+//! The field is kept crowded throughout: shots that fly off are fired
+//! again, and the formation is brought back as it thins out, so every tick
+//! has about as much to move and hit as the first.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::game::{self, Alien, AlienKind, GameState, Position, Settings, Velocity, SHOT_SPEED};
+use crate::game::{HORIZONTAL_SPACING, VERTICAL_SPACING};
+
+/// Ticks played when `--bench` is not told how many.
+pub const DEFAULT_TICKS: u64 = 1_000_000;
+
+const FIELD_WIDTH: u16 = 240;
+const FIELD_HEIGHT: u16 = 80;
+const ALIEN_ROWS: u16 = 10;
+const ALIEN_COLS: u16 = 40;
+/// Shots kept in the air, each way.
+const SHOTS: usize = 100;
+const ALIEN_SHOTS: usize = 100;
+/// Lives the ship is given back every tick, so it never runs out.
+const LIVES: u16 = 100;
+
+/// How a benchmark went.
+pub struct Report {
+    pub ticks: u64,
+    pub elapsed: Duration,
+    /// What was on the field per tick, on average.
+    pub aliens: f64,
+    pub shots: f64,
+    pub alien_shots: f64,
+    pub particles: f64,
+}
+
+impl Report {
+    pub fn ticks_per_second(&self) -> f64 {
+        self.ticks as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} ticks in {:.2}s: {:.0} ticks/s",
+            self.ticks,
+            self.elapsed.as_secs_f64(),
+            self.ticks_per_second()
+        )?;
+        writeln!(
+            f,
+            "with {:.0} aliens, {:.0} shots, {:.0} alien shots and {:.0} particles on the field on average",
+            self.aliens, self.shots, self.alien_shots, self.particles
+        )
+    }
+}
+
+/// A field of 240x80 cells with a formation of 400 aliens and 100 shots
+/// in the air each way, played from `seed`.
+pub fn crowded(seed: u64) -> GameState {
+    let settings = Settings { seed: Some(seed), width: FIELD_WIDTH, height: FIELD_HEIGHT, ..Settings::default() };
+    let mut state = GameState::new(&settings);
+    state.intermission_until = None;
+    state.world.ship_mut(0).health.hp = LIVES;
+    top_up(&mut state, &mut ChaCha8Rng::seed_from_u64(seed));
+    state
+}
+
+/// Brings the field back to as crowded as [`crowded`] makes it: the ship's
+/// lives, the shots each way and, once it has lost half its aliens, the
+/// formation, back at the top.
+fn top_up(state: &mut GameState, rng: &mut ChaCha8Rng) {
+    state.world.ship_mut(0).health.hp = LIVES;
+    let count = (ALIEN_ROWS * ALIEN_COLS) as usize;
+    if state.world.aliens.len() < count / 2 {
+        state.world.aliens.clear();
+        let hitbox = state.settings.alien_size.into();
+        for row in 0..ALIEN_ROWS {
+            for col in 0..ALIEN_COLS {
+                let (x, y) = (col * HORIZONTAL_SPACING + 2, row * VERTICAL_SPACING + 2);
+                state.world.spawn_alien(x, y, hitbox, Alien::new(AlienKind::Grunt));
+            }
+        }
+    }
+    let (width, height) = (state.settings.width, state.max_y());
+    let (shots, alien_shots) = (state.world.shots.len(), state.world.alien_shots.len());
+    for _ in shots..SHOTS {
+        let position = Position::at(rng.gen_range(0..width), rng.gen_range(1..height));
+        state.world.spawn_shot(position, Velocity { dx: 0.0, dy: -SHOT_SPEED }, 0);
+    }
+    for _ in alien_shots..ALIEN_SHOTS {
+        let position = Position::at(rng.gen_range(0..width), rng.gen_range(1..height));
+        state.world.spawn_alien_shot(position, Velocity { dx: 0.0, dy: SHOT_SPEED / 2.0 });
+    }
+}
+
+/// Plays `ticks` ticks of a [`crowded`] field as fast as they go. A run the
+/// aliens manage to end anyway starts over.
+pub fn run(ticks: u64) -> Report {
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    let mut state = crowded(1);
+    let (mut aliens, mut shots, mut alien_shots, mut particles) = (0, 0, 0, 0);
+    let started = Instant::now();
+    for _ in 0..ticks {
+        if state.game_over {
+            state = crowded(rng.gen());
+        }
+        top_up(&mut state, &mut rng);
+        game::update_state(&mut state);
+        aliens += state.world.aliens.len();
+        shots += state.world.shots.len();
+        alien_shots += state.world.alien_shots.len();
+        particles += state.particles.iter().count();
+    }
+    let elapsed = started.elapsed();
+    let mean = |total: usize| total as f64 / ticks.max(1) as f64;
+    Report {
+        ticks,
+        elapsed,
+        aliens: mean(aliens),
+        shots: mean(shots),
+        alien_shots: mean(alien_shots),
+        particles: mean(particles),
+    }
+}
@@ -0,0 +1,154 @@
+//! Bots, which play the game in the player's stead.
+//!
+//! A [`Controller`] looks at the game every tick and decides what player
+//! one does, taking the same actions as the keys do. [`BOTS`] are the
+//! built-in ones `--bot` and `--tournament` pick from; others can be handed
+//! to [`crate::app::play_bot`] and [`crate::tournament::run`].
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::ecs::Row;
+use crate::game::{Action, Alien, AlienDirection, GameState, BOSS_WIDTH, SHOT_SPEED, UFO_SPRITE};
+
+/// Rows above the ship within which a falling alien shot is dodged.
+const DANGER_ROWS: u16 = 4;
+/// Rows above the ship within which a diver is kept away from, as it fires
+/// on the way down.
+const DIVER_DANGER_ROWS: u16 = 8;
+/// Columns either side of the ship a threat is kept out of.
+const DODGE_MARGIN: u16 = 1;
+/// How many moves a controller gets in a tick, about as many as a held
+/// movement key repeats in one.
+pub const MOVES_PER_TICK: usize = 4;
+
+/// Something that plays player one's ship.
+pub trait Controller {
+    /// What to do at the tick `state` is about to play, or `None` for
+    /// nothing. After a move it is asked again, with the ship where the
+    /// move took it, up to [`MOVES_PER_TICK`] times. Firing is held to the
+    /// rate the fire key fires at, however often it is decided on.
+    fn decide(&mut self, state: &GameState) -> Option<Action>;
+}
+
+/// Makes a fresh bot, for each run it plays.
+pub type Maker = fn() -> Box<dyn Controller>;
+
+/// The built-in bots by name, the one `--bot` plays with first.
+pub const BOTS: [(&str, Maker); 2] = [
+    ("heuristic", || Box::new(Heuristic)),
+    ("random", || Box::new(Random::default())),
+];
+
+/// The built-in bot called `name`, by the name it goes by.
+pub fn by_name(name: &str) -> Option<(&'static str, Maker)> {
+    BOTS.iter().find(|(other, _)| other.eq_ignore_ascii_case(name)).copied()
+}
+
+/// A bot that gets out of the way of alien shots falling towards it, and
+/// otherwise lines up under the lowest alien and fires.
+#[derive(Default)]
+pub struct Heuristic;
+
+impl Controller for Heuristic {
+    fn decide(&mut self, state: &GameState) -> Option<Action> {
+        let player = state.world.ships.get(0).filter(|ship| !ship.is_out() && !state.game_over)?;
+        let (x, y, width) = (player.x(), player.y(), state.settings.player_size.width);
+        let center = x + width / 2;
+        let (can_left, can_right) = (x > 0, x + width <= state.max_x());
+
+        // Dodging comes first: alien shots falling towards the ship, and
+        // divers about to ram it, go by the middle of the columns they take.
+        // A ship at `x` is in the way of those that come near its columns
+        let alien_size = state.settings.alien_size;
+        let near = |bottom: u16, rows: u16| bottom + rows >= y && bottom <= state.player_bottom() + 1;
+        let shots = state.world.alien_shots.iter().map(|shot| shot.cell());
+        let shots = shots.filter(|&(_, y)| near(y, DANGER_ROWS)).map(|(x, _)| (x, x));
+        let divers = state.world.aliens.iter().filter(|alien| alien.dive.is_some()).map(|alien| alien.cell());
+        let divers = divers.filter(|&(_, y)| near(y + alien_size.height - 1, DIVER_DANGER_ROWS));
+        let threats: Vec<(u16, u16)> = shots.chain(divers.map(|(x, _)| (x, x + alien_size.width - 1))).collect();
+        let threat = |x: u16| {
+            let (ship_left, ship_right) = (x.saturating_sub(DODGE_MARGIN), x + width - 1 + DODGE_MARGIN);
+            threats.iter().copied().find(|&(left, right)| right >= ship_left && left <= ship_right)
+        };
+        if let Some((left, right)) = threat(x) {
+            // Against an edge, the only way out is the other way
+            let right = if (left + right) / 2 <= center { can_right } else { !can_left };
+            return Some(if right { Action::MoveRight } else { Action::MoveLeft });
+        }
+
+        // The boss, else the lowest alien in formation, the nearest of those,
+        // else the UFO, taken as the columns it spans. The formation is led
+        // by as far as it moves while a shot flies up to it
+        let span = |x: u16, width: u16| (x, x + width - 1);
+        let shot_speed = SHOT_SPEED * state.settings.tick.as_secs_f32();
+        let lead = |alien: Row<Alien>| {
+            let steps = state.alien_speed() as f32 / 100.0 * y.saturating_sub(alien.y()) as f32 / shot_speed;
+            let x = alien.x() as i32 + drift(state, steps as u32);
+            span(x.clamp(0, (state.max_x() + 1 - alien_size.width) as i32) as u16, alien_size.width)
+        };
+        let (left, right) = match state.world.boss() {
+            Some(boss) => Some(span(boss.x(), BOSS_WIDTH)),
+            None => state
+                .world
+                .aliens
+                .iter()
+                .filter(|alien| alien.dive.is_none())
+                .max_by_key(|alien| (alien.y(), u16::MAX - (alien.x() + alien_size.width / 2).abs_diff(center)))
+                .map(lead)
+                .or_else(|| state.world.ufo().map(|ufo| span(ufo.x(), UFO_SPRITE.len() as u16))),
+        }?;
+        // Moving back into the way of a threat, it waits where it is instead
+        if center < left && can_right {
+            Some(if threat(x + 1).is_none() { Action::MoveRight } else { Action::Fire })
+        } else if center > right && can_left {
+            Some(if threat(x - 1).is_none() { Action::MoveLeft } else { Action::Fire })
+        } else {
+            Some(Action::Fire)
+        }
+    }
+}
+
+/// A bot that mashes the keys, moving either way and firing at random, to
+/// measure other bots against.
+pub struct Random {
+    rng: ChaCha8Rng,
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Random { rng: ChaCha8Rng::seed_from_u64(0) }
+    }
+}
+
+impl Controller for Random {
+    fn decide(&mut self, _state: &GameState) -> Option<Action> {
+        match self.rng.gen_range(0..4) {
+            0 => Some(Action::MoveLeft),
+            1 => Some(Action::MoveRight),
+            2 => Some(Action::Fire),
+            _ => None,
+        }
+    }
+}
+
+/// How many cells right of where it is the formation will be after
+/// `steps` more steps, turning around at the walls as it does.
+fn drift(state: &GameState, steps: u32) -> i32 {
+    let homes = || state.world.aliens.iter().map(|alien| alien.home().0 as i32);
+    let (Some(left), Some(right)) = (homes().min(), homes().max()) else {
+        return 0;
+    };
+    let right = right + state.settings.alien_size.width as i32;
+    let (mut direction, mut offset) = (state.alien_direction, 0);
+    for _ in 0..steps {
+        // Hitting a wall takes a step down rather than sideways
+        match direction {
+            AlienDirection::Left if left + offset == 0 => direction = AlienDirection::Right,
+            AlienDirection::Left => offset -= 1,
+            AlienDirection::Right if right + offset >= state.settings.width as i32 => direction = AlienDirection::Left,
+            AlienDirection::Right => offset += 1,
+        }
+    }
+    offset
+}
@@ -0,0 +1,76 @@
+//! How far the campaign has been played, persisted in the data directory.
+//!
+//! The file holds one `name value` line per counter, like the stats file.
+//! A mod's level pack is a campaign of its own, kept in a file of its own.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::game::GameState;
+use crate::level::{self, LevelPack};
+use crate::storage;
+
+const FILE_NAME: &str = "campaign";
+
+/// The file the progress through `pack`, or the campaign, is kept in.
+fn path(pack: Option<&LevelPack>) -> Option<PathBuf> {
+    match pack {
+        Some(pack) => storage::data_file(&format!("{}.{}", FILE_NAME, pack.id)),
+        None => storage::data_file(FILE_NAME),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// The most levels cleared in a single campaign run.
+    pub best_cleared: u32,
+    /// Campaign runs played through to the end.
+    pub completions: u32,
+}
+
+impl Progress {
+    /// Reads the stored progress through `pack`, or the campaign; a missing
+    /// file means none played.
+    pub fn load(pack: Option<&LevelPack>) -> Self {
+        path(pack)
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| Progress::from_text(&text))
+            .unwrap_or_default()
+    }
+
+    fn from_text(text: &str) -> Self {
+        let mut progress = Progress::default();
+        for line in text.lines() {
+            let Some((name, value)) = line.split_once(' ') else {
+                continue;
+            };
+            let Ok(count) = value.trim().parse::<u32>() else {
+                continue;
+            };
+            match name {
+                "best_cleared" => progress.best_cleared = count,
+                "completions" => progress.completions = count,
+                _ => {}
+            }
+        }
+        progress
+    }
+
+    fn to_text(self) -> String {
+        format!("best_cleared {}\ncompletions {}\n", self.best_cleared, self.completions)
+    }
+
+    /// Adds a finished campaign run to the stored progress.
+    pub fn record(state: &GameState) {
+        let Some(path) = path(state.settings.pack) else {
+            return;
+        };
+        let mut progress = Progress::load(state.settings.pack);
+        let levels = state.settings.levels().len() as u32;
+        let cleared = if state.victory { levels } else { level::cleared_before(state.wave) };
+        progress.best_cleared = progress.best_cleared.max(cleared);
+        progress.completions = progress.completions.saturating_add(u32::from(state.victory));
+        // As with the stats, losing the file never stops the game
+        let _ = storage::write_atomic(&path, &progress.to_text());
+    }
+}
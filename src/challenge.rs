@@ -0,0 +1,258 @@
+//! Challenges: runs set up by a seed, a difficulty and modifiers rather than
+//! the player's own settings, so that everyone playing one gets the same run.
+//!
+//! The daily and weekly challenges take theirs from the date, in UTC so the
+//! day changes at the same moment everywhere. Any such run can be passed on
+//! as a [`RunCode`], which plays it again wherever it is entered.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::game::{
+    Assist, Difficulty, GameState, Modifier, Modifiers, Settings, DEFAULT_EXTRA_LIFE_EVERY, DEFAULT_FIELD_HEIGHT,
+    DEFAULT_FIELD_WIDTH,
+};
+use crate::locale::tr;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+/// 1970-01-01 was a Thursday; weeks start on the Monday before it.
+const DAYS_BEFORE_FIRST_MONDAY: u64 = 3;
+/// Mixed into the week to seed the weekly challenge, so that it plays
+/// nothing like the daily challenge of the same number.
+const WEEKLY_SEED: u64 = 0x5745_454B;
+
+/// The digits of a code: Crockford's base 32, which leaves out letters that
+/// pass for others (I, L, O and U).
+const DIGITS: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+/// Digits in a code, written out in groups of `GROUP_LEN`.
+pub const CODE_LEN: usize = 16;
+pub const GROUP_LEN: usize = 4;
+/// Bits of a code given to the check that catches mistyped codes.
+const CHECK_BITS: u32 = 10;
+
+/// Today, in days since 1970-01-01 (UTC).
+pub fn today() -> u64 {
+    since_epoch().as_secs() / SECONDS_PER_DAY
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn since_epoch() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+/// A browser page has the only clock there is.
+#[cfg(target_arch = "wasm32")]
+fn since_epoch() -> Duration {
+    crate::web::since_epoch()
+}
+
+/// This week, in weeks since the one 1970-01-01 fell in.
+pub fn this_week() -> u64 {
+    (today() + DAYS_BEFORE_FIRST_MONDAY) / 7
+}
+
+/// The challenge of `day`: normal difficulty, at least one modifier.
+pub fn daily(day: u64) -> RunCode {
+    RunCode::from_seed(mix(day), Difficulty::Normal)
+}
+
+/// The settings of the challenge of `day`, which mark the run as that
+/// day's for the daily table.
+pub fn daily_settings(day: u64, base: &Settings) -> Settings {
+    Settings { daily: Some(day), ..daily(day).settings(base) }
+}
+
+/// The challenge of `week`, a notch harder than the daily ones.
+pub fn weekly(week: u64) -> RunCode {
+    RunCode::from_seed(mix(week ^ WEEKLY_SEED), Difficulty::Hard)
+}
+
+/// The date of `day` as `YYYY-MM-DD`.
+pub fn date(day: u64) -> String {
+    // Howard Hinnant's days-to-civil algorithm, for days after 1970
+    let z = day + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day_of_month)
+}
+
+/// Scrambles `value` into a seed (SplitMix64), so that neighbouring days
+/// play nothing alike.
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Everything that sets a challenge apart, written as a short code such as
+/// `3F0A-Q7ZK-M2C9-XW41` to pass around.
+///
+/// The code holds the seed, the difficulty and the modifiers, and a check
+/// so that a mistyped code is turned down rather than played.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunCode {
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub modifiers: Modifiers,
+}
+
+impl RunCode {
+    /// The code that plays `state`'s run again, if one can: a code plays on
+    /// the default playfield with the difficulty's lives and no assist
+    /// options, so a run with anything else has none.
+    pub fn of(state: &GameState) -> Option<Self> {
+        let settings = &state.settings;
+        let reproducible = settings.assist == Assist::default()
+            && settings.lives.is_none()
+            && settings.extra_life_every == DEFAULT_EXTRA_LIFE_EVERY
+            && (settings.width, settings.height) == (DEFAULT_FIELD_WIDTH, DEFAULT_FIELD_HEIGHT)
+            && settings.pack.is_none();
+        let code = RunCode { seed: state.seed, difficulty: settings.difficulty, modifiers: settings.modifiers };
+        reproducible.then_some(code)
+    }
+
+    /// A challenge at `difficulty` whose modifiers come from `seed` as well:
+    /// at least one, and now and then a few.
+    fn from_seed(seed: u64, difficulty: Difficulty) -> Self {
+        let mut modifiers = Modifiers::from_bits((seed >> 56) as u8);
+        if modifiers.iter().next().is_none() {
+            modifiers.set(Modifier::ALL[(seed % Modifier::ALL.len() as u64) as usize], true);
+        }
+        RunCode { seed, difficulty, modifiers }
+    }
+
+    /// The settings of the challenge: its own seed, difficulty and modifiers
+    /// on the default playfield and the campaign's levels, with the
+    /// difficulty's lives and no assist options. Only the tick and the
+    /// sprite sizes are taken from `base`, the session's settings.
+    pub fn settings(&self, base: &Settings) -> Settings {
+        Settings {
+            difficulty: self.difficulty,
+            assist: Assist::default(),
+            modifiers: self.modifiers,
+            daily: None,
+            lives: None,
+            extra_life_every: DEFAULT_EXTRA_LIFE_EVERY,
+            seed: Some(self.seed),
+            width: DEFAULT_FIELD_WIDTH,
+            height: DEFAULT_FIELD_HEIGHT,
+            pack: None,
+            ..base.clone()
+        }
+    }
+
+    /// Everything but the check, which `check` derives from it.
+    fn payload(&self) -> u128 {
+        let difficulty = Difficulty::ALL.iter().position(|&difficulty| difficulty == self.difficulty).unwrap_or(0);
+        (self.seed as u128) << 6 | (difficulty as u128) << 4 | self.modifiers.bits() as u128
+    }
+
+    fn check(payload: u128) -> u128 {
+        (mix(payload as u64 ^ mix((payload >> 64) as u64)) & ((1 << CHECK_BITS) - 1)) as u128
+    }
+}
+
+impl fmt::Display for RunCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let payload = self.payload();
+        let bits = payload << CHECK_BITS | RunCode::check(payload);
+        for i in 0..CODE_LEN {
+            if i > 0 && i % GROUP_LEN == 0 {
+                write!(f, "-")?;
+            }
+            let digit = (bits >> (5 * (CODE_LEN - 1 - i)) & 31) as usize;
+            write!(f, "{}", DIGITS[digit] as char)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for RunCode {
+    type Err = String;
+
+    /// Reads a code the way people type them: in either case, with or
+    /// without the dashes, and with I, L and O for 1, 1 and 0.
+    fn from_str(text: &str) -> Result<Self, String> {
+        let mut bits: u128 = 0;
+        let mut len = 0;
+        for c in text.chars().filter(|&c| c != '-' && !c.is_whitespace()) {
+            let c = match c.to_ascii_uppercase() {
+                'I' | 'L' => '1',
+                'O' => '0',
+                c => c,
+            };
+            let digit = DIGITS.iter().position(|&digit| digit as char == c).ok_or(tr("code_invalid"))?;
+            bits = bits << 5 | digit as u128;
+            len += 1;
+            if len > CODE_LEN {
+                return Err(tr("code_too_long").to_string());
+            }
+        }
+        if len < CODE_LEN {
+            return Err(tr("code_too_short").to_string());
+        }
+        let payload = bits >> CHECK_BITS;
+        if bits & ((1 << CHECK_BITS) - 1) != RunCode::check(payload) {
+            return Err(tr("code_mistyped").to_string());
+        }
+        Ok(RunCode {
+            seed: (payload >> 6) as u64,
+            difficulty: Difficulty::ALL[(payload >> 4 & 3) as usize],
+            modifiers: Modifiers::from_bits((payload & 15) as u8),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::AssistOption;
+
+    #[test]
+    fn a_code_reads_back_as_written() {
+        for seed in [0, 1, 0x1234_5678_9ABC_DEF0, u64::MAX] {
+            for &difficulty in &Difficulty::ALL {
+                let mut modifiers = Modifiers::default();
+                modifiers.set(Modifier::ALL[seed as usize % Modifier::ALL.len()], true);
+                let code = RunCode { seed, difficulty, modifiers };
+                let text = code.to_string();
+                assert_eq!(text.len(), CODE_LEN + CODE_LEN / GROUP_LEN - 1, "{}", text);
+                assert_eq!(text.parse::<RunCode>(), Ok(code));
+                assert_eq!(text.to_lowercase().replace('-', "").parse::<RunCode>(), Ok(code));
+            }
+        }
+    }
+
+    #[test]
+    fn a_mistyped_digit_fails_the_check() {
+        let text = daily(20_000).to_string();
+        for (i, typed) in text.char_indices().filter(|&(_, c)| c != '-') {
+            for &digit in DIGITS.iter().filter(|&&digit| digit as char != typed) {
+                let mut mistyped = text.clone();
+                mistyped.replace_range(i..i + 1, &(digit as char).to_string());
+                assert_eq!(mistyped.parse::<RunCode>(), Err(tr("code_mistyped").to_string()), "{}", mistyped);
+            }
+        }
+    }
+
+    #[test]
+    fn only_runs_a_code_can_play_again_have_one() {
+        let settings = daily(20_000).settings(&Settings::default());
+        assert_eq!(RunCode::of(&GameState::new(&settings)), Some(daily(20_000)));
+        let mut assisted = Settings { ..settings.clone() };
+        assisted.assist.set(AssistOption::ForgivingHitbox, true);
+        assert_eq!(RunCode::of(&GameState::new(&assisted)), None);
+        assert_eq!(RunCode::of(&GameState::new(&Settings { lives: Some(9), ..settings.clone() })), None);
+        assert_eq!(RunCode::of(&GameState::new(&Settings { width: 100, ..settings })), None);
+    }
+}
@@ -0,0 +1,259 @@
+//! Command-line options.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::LevelFilter;
+
+use crate::bench;
+use crate::bot::{self, Maker, BOTS};
+use crate::game::{self, Difficulty, Settings};
+use crate::net::DEFAULT_PORT;
+use crate::render::pixels::PixelMode;
+use crate::spectate::{self, Place};
+
+pub const USAGE: &str = "\
+usage: term_invaders [options]
+
+options:
+  --backend NAME       ncurses, crossterm, or sixel and kitty in builds with
+                       those features
+  --difficulty LEVEL   easy, normal, hard or invader (default: normal)
+  --lives N            lives at the start of a run (default: 5 on easy, 1 on
+                       invader, 3 otherwise)
+  --extra-life N       award a life every N points, 0 for never (default: 1000)
+  --seed N             seed the game's randomness for a repeatable run
+  --tick-ms N          milliseconds per game tick (default: 200)
+  --no-color           draw in monochrome, with bold, dim and reverse text
+  --size WxH           playfield size in cells (default: fill the terminal)
+  --sprites FILE       draw with the sprite pack in FILE
+  --half-blocks        draw the playfield in half-block pixels, doubling its rows
+  --braille            draw the playfield in Braille dots, 2x4 to a cell (experimental)
+  --replay FILE        watch a recorded run instead of playing
+  --edit FILE          edit the level in FILE, or a new one, and playtest it
+  --bot[=NAME]         watch a built-in bot play a run: heuristic (the
+                       default) or random
+  --tournament GAMES   have the built-in bots, or those picked with --bot,
+                       play GAMES runs each without drawing them, and
+                       compare how they did
+  --bench[=TICKS]      time TICKS ticks of the game logic on a crowded field,
+                       without drawing them (default: 1000000)
+  --host[=PORT]        wait for a second player to join over the network
+                       and play co-op with them (default port: 7777)
+  --versus             with --host, have the second player command the aliens
+  --join ADDR          join the game hosted at ADDR, as HOST[:PORT]
+  --serve PORT         serve the game to telnet clients on PORT, a session
+                       of its own for each
+  --broadcast[=WHERE]  let other terminals watch the game, at WHERE: a port
+                       or the path of a unix socket (default port: 7778)
+  --watch WHERE        watch the game broadcast at WHERE, as HOST[:PORT]
+                       or the path of a unix socket
+  --record FILE        record what the screen shows to FILE, as an
+                       asciinema cast
+  --log LEVEL          log what happens up to LEVEL (error, warn, info, debug
+                       or trace) to invaders.log
+  -h, --help           show this help";
+
+/// Everything that can be set from the command line.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// The requested backend, or `None` for the build's default.
+    pub backend: Option<String>,
+    pub color: bool,
+    /// How to draw the playfield in pixels, if not as text.
+    pub pixels: Option<PixelMode>,
+    pub help: bool,
+    /// A replay to watch instead of starting a game.
+    pub replay: Option<PathBuf>,
+    /// A level file to edit instead of starting a game.
+    pub edit: Option<PathBuf>,
+    /// The built-in bots picked to play, by name.
+    pub bots: Vec<(&'static str, Maker)>,
+    /// The number of games each bot plays in a tournament.
+    pub tournament: Option<u32>,
+    /// The number of ticks to benchmark the game logic for.
+    pub bench: Option<u64>,
+    /// The requested playfield size, or `None` to fit the terminal.
+    pub size: Option<(u16, u16)>,
+    /// A sprite pack to draw with instead of the one in the config directory.
+    pub sprites: Option<PathBuf>,
+    /// The port to host a network game on.
+    pub host: Option<u16>,
+    /// Whether the hosted game is a versus one rather than co-op.
+    pub versus: bool,
+    /// The address of a network game to join.
+    pub join: Option<String>,
+    /// The port to serve the game to telnet clients on.
+    pub serve: Option<u16>,
+    /// Where to let other terminals watch the game from.
+    pub broadcast: Option<Place>,
+    /// Where the game to watch is broadcast.
+    pub watch: Option<Place>,
+    /// The asciinema cast to record the game to.
+    pub record: Option<PathBuf>,
+    /// How much to write to the log file, if anything.
+    pub log: Option<LevelFilter>,
+    pub settings: Settings,
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+fn parse_size(value: &str) -> Result<(u16, u16), String> {
+    let invalid = || format!("invalid size '{}', expected WxH such as 40x19", value);
+    let (width, height) = value.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let width: u16 = width.parse().map_err(|_| invalid())?;
+    let height: u16 = height.parse().map_err(|_| invalid())?;
+    game::check_field_size(width, height)?;
+    Ok((width, height))
+}
+
+/// Parses the arguments that follow the program name, on top of the game
+/// `settings` they start from.
+///
+/// Flag values may be given either as the next argument or after an `=`.
+pub fn parse<I: IntoIterator<Item = String>>(args: I, settings: Settings) -> Result<Options, String> {
+    let mut options = Options {
+        backend: None,
+        color: true,
+        pixels: None,
+        help: false,
+        replay: None,
+        edit: None,
+        bots: Vec::new(),
+        tournament: None,
+        bench: None,
+        size: None,
+        sprites: None,
+        host: None,
+        versus: false,
+        join: None,
+        serve: None,
+        broadcast: None,
+        watch: None,
+        record: None,
+        log: None,
+        settings,
+    };
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg, None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+
+        match flag.as_str() {
+            "-h" | "--help" => options.help = true,
+            "--backend" => options.backend = Some(value()?),
+            "--difficulty" => {
+                let name = value()?;
+                options.settings.difficulty =
+                    Difficulty::from_name(&name).ok_or_else(|| format!("unknown difficulty '{}'", name))?;
+            }
+            "--lives" => {
+                let lives: u8 = parse_number(&flag, &value()?)?;
+                if lives == 0 {
+                    return Err("--lives must be at least 1".to_string());
+                }
+                options.settings.lives = Some(lives);
+            }
+            "--extra-life" => options.settings.extra_life_every = parse_number(&flag, &value()?)?,
+            "--seed" => options.settings.seed = Some(parse_number(&flag, &value()?)?),
+            "--tick-ms" => {
+                let millis: u64 = parse_number(&flag, &value()?)?;
+                if millis == 0 {
+                    return Err("--tick-ms must be at least 1".to_string());
+                }
+                options.settings.tick = Duration::from_millis(millis);
+            }
+            "--no-color" => options.color = false,
+            "--half-blocks" => options.pixels = Some(PixelMode::HalfBlocks),
+            "--braille" => options.pixels = Some(PixelMode::Braille),
+            "--size" => options.size = Some(parse_size(&value()?)?),
+            "--sprites" => options.sprites = Some(PathBuf::from(value()?)),
+            "--replay" => options.replay = Some(PathBuf::from(value()?)),
+            "--edit" => options.edit = Some(PathBuf::from(value()?)),
+            // The bot is optional, so it can only come after an `=`
+            "--bot" => {
+                let name = inline.as_deref().unwrap_or(BOTS[0].0);
+                options.bots.push(bot::by_name(name).ok_or_else(|| format!("unknown bot '{}'", name))?);
+            }
+            "--tournament" => options.tournament = Some(parse_number(&flag, &value()?)?),
+            // As are the ticks to benchmark
+            "--bench" => {
+                options.bench = Some(match &inline {
+                    Some(ticks) => parse_number(&flag, ticks)?,
+                    None => bench::DEFAULT_TICKS,
+                })
+            }
+            // The port is optional, so it can only come after an `=`
+            "--host" => {
+                options.host = Some(match &inline {
+                    Some(port) => parse_number(&flag, port)?,
+                    None => DEFAULT_PORT,
+                })
+            }
+            "--versus" => options.versus = true,
+            "--join" => options.join = Some(value()?),
+            "--serve" => options.serve = Some(parse_number(&flag, &value()?)?),
+            // Likewise for where to broadcast, which is on every address
+            "--broadcast" => {
+                options.broadcast = Some(match inline.as_deref() {
+                    Some(path) if path.contains('/') => Place::Socket(PathBuf::from(path)),
+                    Some(port) => Place::Address(format!("0.0.0.0:{}", parse_number::<u16>(&flag, port)?)),
+                    None => Place::Address(format!("0.0.0.0:{}", spectate::DEFAULT_PORT)),
+                })
+            }
+            "--watch" => options.watch = Some(Place::parse(&value()?)),
+            "--record" => options.record = Some(PathBuf::from(value()?)),
+            "--log" => {
+                let level = value()?;
+                let parsed = level.parse().map_err(|_| format!("invalid log level '{}'", level))?;
+                options.log = Some(parsed);
+            }
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+    let modes = [
+        options.host.is_some(),
+        options.join.is_some(),
+        options.serve.is_some(),
+        options.replay.is_some(),
+        options.watch.is_some(),
+        options.edit.is_some(),
+        options.bench.is_some(),
+        options.tournament.is_some(),
+    ];
+    if modes.iter().filter(|&&set| set).count() > 1 {
+        return Err(
+            "only one of --host, --join, --serve, --replay, --watch, --edit, --bench and --tournament can be used"
+                .to_string()
+        );
+    }
+    if !options.bots.is_empty() && modes[..modes.len() - 1].contains(&true) {
+        let modes = "--host, --join, --serve, --replay, --watch, --edit or --bench";
+        return Err(format!("--bot cannot be used with {}", modes));
+    }
+    if options.bots.len() > 1 && options.tournament.is_none() {
+        return Err("only one --bot can play, outside of --tournament".to_string());
+    }
+    if options.broadcast.is_some() && (options.serve.is_some() || options.watch.is_some()) {
+        return Err("--broadcast cannot be used with --serve or --watch".to_string());
+    }
+    let unseen = options.serve.is_some() || options.bench.is_some() || options.tournament.is_some();
+    if options.record.is_some() && unseen {
+        return Err("--record cannot be used with --serve, --bench or --tournament".to_string());
+    }
+    if options.versus && options.host.is_none() {
+        return Err("--versus needs --host".to_string());
+    }
+    Ok(options)
+}
@@ -0,0 +1,269 @@
+//! Hit tests between the things on the playfield.
+//!
+//! Everything takes up whole cells: a shot is the cell it is nearest to,
+//! and a sprite the box of cells its size covers from its top-left corner.
+//! Two things collide when their boxes share a cell, which [`intersects`]
+//! decides for every kind of thing alike. Where many things can hit many
+//! others, [`Columns`] narrows down which pairs are worth testing.
+
+use std::cmp::Ordering;
+
+use crate::game::SpriteSize;
+
+/// A box of `width` x `height` cells whose top-left cell is (x, y).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Aabb {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Aabb {
+    pub fn new(x: u16, y: u16, width: u16, height: u16) -> Self {
+        Aabb { x, y, width, height }
+    }
+
+    /// The single cell (x, y), as a shot or a pickup takes up.
+    pub fn cell(x: u16, y: u16) -> Self {
+        Aabb::new(x, y, 1, 1)
+    }
+
+    /// The cells a sprite of `size` covers with its top-left corner at (x, y).
+    pub fn sprite(x: u16, y: u16, size: SpriteSize) -> Self {
+        Aabb::new(x, y, size.width, size.height)
+    }
+
+    /// Every cell of column `x`, top to bottom, as a laser beam burns through.
+    pub fn column(x: u16) -> Self {
+        Aabb::new(x, 0, 1, u16::MAX)
+    }
+
+    /// The column just right of the box. Boxes reaching past the edge of
+    /// the `u16` range do not wrap around.
+    pub fn right(&self) -> u32 {
+        self.x as u32 + self.width as u32
+    }
+
+    /// The row just below the box.
+    pub fn bottom(&self) -> u32 {
+        self.y as u32 + self.height as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Whether (x, y) is one of the box's cells.
+    pub fn contains(&self, x: u16, y: u16) -> bool {
+        intersects(*self, Aabb::cell(x, y))
+    }
+}
+
+/// Whether `a` and `b` share at least one cell. An empty box shares none,
+/// and boxes that only touch along an edge do not collide.
+pub fn intersects(a: Aabb, b: Aabb) -> bool {
+    !a.is_empty()
+        && !b.is_empty()
+        && (a.x as u32) < b.right()
+        && (b.x as u32) < a.right()
+        && (a.y as u32) < b.bottom()
+        && (b.y as u32) < a.bottom()
+}
+
+/// Columns of the playfield each bucket of [`Columns`] takes, about as many
+/// as an alien is wide.
+pub const BUCKET_WIDTH: u16 = 4;
+
+fn bucket_of(x: u32) -> usize {
+    (x / BUCKET_WIDTH as u32) as usize
+}
+
+/// A broad phase for hit tests against many boxes. Each box is sorted into
+/// buckets by the columns it spans, so a hit test only tries the boxes
+/// sharing a bucket with it instead of every one. The buckets keep their
+/// room from one fill to the next.
+#[derive(Clone, Default)]
+pub struct Columns {
+    boxes: Vec<Aabb>,
+    /// The indices of the boxes in each bucket, in increasing order.
+    buckets: Vec<Vec<usize>>,
+}
+
+impl Columns {
+    /// Holds `boxes` from now on, in place of what it held before.
+    pub fn fill(&mut self, boxes: impl IntoIterator<Item = Aabb>) {
+        self.boxes.clear();
+        self.boxes.extend(boxes);
+        for bucket in &mut self.buckets {
+            bucket.clear();
+        }
+        for (i, aabb) in self.boxes.iter().enumerate().filter(|(_, aabb)| !aabb.is_empty()) {
+            let (first, last) = (bucket_of(aabb.x as u32), bucket_of(aabb.right() - 1));
+            if self.buckets.len() <= last {
+                self.buckets.resize_with(last + 1, Vec::new);
+            }
+            for bucket in &mut self.buckets[first..=last] {
+                bucket.push(i);
+            }
+        }
+    }
+
+    /// The index of the first box that intersects `aabb`, if any does.
+    pub fn first_hit(&self, aabb: Aabb) -> Option<usize> {
+        self.first_hit_where(aabb, |_| true)
+    }
+
+    /// The index of the first box that intersects `aabb` and whose index
+    /// passes `keep`, if any does.
+    pub fn first_hit_where(&self, aabb: Aabb, keep: impl Fn(usize) -> bool) -> Option<usize> {
+        if aabb.is_empty() {
+            return None;
+        }
+        let buckets = bucket_of(aabb.x as u32)..=bucket_of(aabb.right() - 1);
+        let buckets = buckets.filter_map(|bucket| self.buckets.get(bucket));
+        // Each bucket is in order, so its first hit is the first it holds
+        let first = |bucket: &Vec<usize>| bucket.iter().copied().find(|&i| intersects(self.boxes[i], aabb) && keep(i));
+        buckets.filter_map(first).min()
+    }
+
+    /// Takes out the box at `index`, the boxes after it moving down one
+    /// index, as they do in a `Vec` removed from alongside.
+    pub fn remove(&mut self, index: usize) {
+        self.boxes.remove(index);
+        for bucket in &mut self.buckets {
+            bucket.retain_mut(|i| match (*i).cmp(&index) {
+                Ordering::Less => true,
+                Ordering::Equal => false,
+                Ordering::Greater => {
+                    *i -= 1;
+                    true
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn a_cell_hits_a_sprite_only_inside_it() {
+        let alien = Aabb::sprite(10, 5, SpriteSize { width: 3, height: 2 });
+        for (x, y) in [(10, 5), (12, 5), (10, 6), (12, 6), (11, 5)] {
+            assert!(alien.contains(x, y), "({}, {}) is inside", x, y);
+        }
+        for (x, y) in [(9, 5), (13, 5), (10, 4), (10, 7), (13, 7)] {
+            assert!(!alien.contains(x, y), "({}, {}) is outside", x, y);
+        }
+    }
+
+    #[test]
+    fn boxes_touching_along_an_edge_miss() {
+        let ship = Aabb::new(4, 10, 3, 2);
+        assert!(!intersects(ship, Aabb::new(7, 10, 3, 2)));
+        assert!(!intersects(ship, Aabb::new(1, 10, 3, 2)));
+        assert!(!intersects(ship, Aabb::new(4, 12, 3, 2)));
+        assert!(intersects(ship, Aabb::new(6, 11, 3, 2)));
+    }
+
+    #[test]
+    fn empty_boxes_never_collide() {
+        let field = Aabb::new(0, 0, 100, 100);
+        assert!(!intersects(field, Aabb::new(5, 5, 0, 3)));
+        assert!(!intersects(Aabb::new(5, 5, 3, 0), field));
+    }
+
+    #[test]
+    fn boxes_at_the_far_edge_do_not_wrap_around() {
+        let edge = Aabb::new(u16::MAX - 1, 0, 5, 1);
+        assert!(edge.contains(u16::MAX, 0));
+        assert!(!edge.contains(0, 0));
+        assert!(Aabb::column(7).contains(7, u16::MAX - 1));
+    }
+
+    fn random_box(rng: &mut ChaCha8Rng) -> Aabb {
+        Aabb::new(rng.gen_range(0..20), rng.gen_range(0..20), rng.gen_range(0..6), rng.gen_range(0..6))
+    }
+
+    fn cells(aabb: Aabb) -> impl Iterator<Item = (u16, u16)> {
+        (aabb.x..aabb.x + aabb.width).flat_map(move |x| (aabb.y..aabb.y + aabb.height).map(move |y| (x, y)))
+    }
+
+    #[test]
+    fn intersecting_is_symmetric() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        for _ in 0..10_000 {
+            let (a, b) = (random_box(&mut rng), random_box(&mut rng));
+            assert_eq!(intersects(a, b), intersects(b, a), "{:?} and {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn intersecting_is_sharing_a_cell() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        for _ in 0..10_000 {
+            let (a, b) = (random_box(&mut rng), random_box(&mut rng));
+            let shared = cells(a).any(|(x, y)| b.contains(x, y));
+            assert_eq!(intersects(a, b), shared, "{:?} and {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn every_cell_of_a_box_is_in_it() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        for _ in 0..1_000 {
+            let aabb = random_box(&mut rng);
+            assert_eq!(cells(aabb).count(), aabb.width as usize * aabb.height as usize);
+            assert!(cells(aabb).all(|(x, y)| aabb.contains(x, y)), "{:?}", aabb);
+        }
+    }
+
+    #[test]
+    fn columns_find_the_first_box_hit() {
+        let mut columns = Columns::default();
+        let boxes = [Aabb::new(0, 0, 3, 3), Aabb::new(2, 2, 6, 1), Aabb::new(5, 0, 2, 2), Aabb::new(2, 2, 1, 1)];
+        columns.fill(boxes);
+        assert_eq!(columns.first_hit(Aabb::cell(2, 2)), Some(0));
+        assert_eq!(columns.first_hit(Aabb::cell(6, 2)), Some(1));
+        assert_eq!(columns.first_hit(Aabb::cell(6, 1)), Some(2));
+        assert_eq!(columns.first_hit(Aabb::cell(40, 1)), None);
+        columns.remove(0);
+        assert_eq!(columns.first_hit(Aabb::cell(2, 2)), Some(0));
+        assert_eq!(columns.first_hit(Aabb::cell(0, 0)), None);
+        columns.remove(0);
+        assert_eq!(columns.first_hit(Aabb::cell(2, 2)), Some(1));
+        assert_eq!(columns.first_hit(Aabb::cell(6, 1)), Some(0));
+    }
+
+    #[test]
+    fn columns_skip_the_boxes_left_out() {
+        let mut columns = Columns::default();
+        columns.fill([Aabb::new(0, 0, 3, 3), Aabb::new(2, 2, 6, 1), Aabb::new(1, 1, 2, 2)]);
+        assert_eq!(columns.first_hit_where(Aabb::cell(2, 2), |i| i != 0), Some(1));
+        assert_eq!(columns.first_hit_where(Aabb::cell(1, 1), |i| i > 0), Some(2));
+        assert_eq!(columns.first_hit_where(Aabb::cell(1, 1), |i| i == 1), None);
+    }
+
+    #[test]
+    fn columns_agree_with_testing_every_box() {
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        let mut columns = Columns::default();
+        for _ in 0..200 {
+            let mut boxes: Vec<Aabb> = (0..rng.gen_range(0..40)).map(|_| random_box(&mut rng)).collect();
+            columns.fill(boxes.iter().copied());
+            for _ in 0..50 {
+                let query = random_box(&mut rng);
+                let expected = boxes.iter().position(|&aabb| intersects(aabb, query));
+                assert_eq!(columns.first_hit(query), expected, "{:?} in {:?}", query, boxes);
+                if let Some(i) = expected.filter(|_| rng.gen_bool(0.5)) {
+                    boxes.remove(i);
+                    columns.remove(i);
+                }
+            }
+        }
+    }
+}
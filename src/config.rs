@@ -0,0 +1,306 @@
+//! The optional config file, read once at startup and written back by the
+//! settings screen.
+//!
+//! It lives at `config` in the config directory and holds `key = value`
+//! lines; blank lines and lines starting with `#` are skipped, as are keys
+//! this version does not know. A line that is neither, or a setting with a
+//! value it cannot have, stops the game at startup with the line it is on;
+//! see [`Config::check`]. Lines after a `[keys]` header rebind keys,
+//! see [`crate::keymap`], and lines after an `[assist]` header turn assist
+//! options on:
+//!
+//! ```text
+//! [assist]
+//! slow_shots = true
+//! extra_lives = true
+//! ```
+//!
+//! Lines after a `[bell]` header ring the terminal bell on events, see
+//! [`Bell`], for players with no sound to hear:
+//!
+//! ```text
+//! [bell]
+//! player_hit = true
+//! game_over = true
+//! flash = true
+//! ```
+
+use std::fs;
+use std::io;
+
+use crate::error::StartError;
+use crate::storage;
+
+const FILE_NAME: &str = "config";
+
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    /// Name of the color theme to start with.
+    pub theme: Option<String>,
+    /// The id of the mod in use, see [`crate::mods`]; none plays the game
+    /// as it comes.
+    pub mod_id: Option<String>,
+    /// The language to show the game in, see [`crate::locale`]; none goes
+    /// by the environment.
+    pub language: Option<String>,
+    /// Name of the difficulty runs are played at, unless `--difficulty`
+    /// says otherwise.
+    pub difficulty: Option<String>,
+    /// Whether the ship follows the mouse, for terminals that leave mouse
+    /// reporting to the programs they run.
+    pub mouse: bool,
+    /// The `name = key` entries of the `[keys]` section, in file order.
+    pub keys: Vec<(String, String)>,
+    /// The assist options set to `true` in the `[assist]` section.
+    pub assist: Vec<String>,
+    /// The entries set to `true` in the `[bell]` section.
+    pub bell: Vec<String>,
+    /// How loud builds with the `sound` feature play, from the `volume`,
+    /// `sfx_volume`, `music_volume` and `mute` settings.
+    pub volume: Volume,
+    /// The URL of the online leaderboard, for builds with the `online`
+    /// feature; none means staying offline.
+    pub online: Option<String>,
+}
+
+impl Config {
+    /// Reads the config file; a missing or unreadable one means defaults.
+    pub fn load() -> Self {
+        storage::config_file(FILE_NAME)
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| Config::from_text(&text))
+            .unwrap_or_default()
+    }
+
+    /// Reads the config file like [`Config::load`], but fails on one that
+    /// cannot be read or has a line that makes no sense, for startup to say
+    /// what is wrong before the terminal is taken over.
+    pub fn check() -> Result<Self, StartError> {
+        let Some(path) = storage::config_file(FILE_NAME) else {
+            return Ok(Config::default());
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(err) => return Err(StartError::ConfigUnreadable(path, err)),
+        };
+        match Config::read(&text) {
+            (config, None) => Ok(config),
+            (_, Some((line, message))) => Err(StartError::Config { path, line, message }),
+        }
+    }
+
+    /// The config of `text`, skipping whatever makes no sense.
+    pub fn from_text(text: &str) -> Self {
+        Config::read(text).0
+    }
+
+    /// The config of `text`, and the first line that makes no sense, if
+    /// any: its number, from 1, and what is wrong with it.
+    fn read(text: &str) -> (Self, Option<(usize, String)>) {
+        let mut config = Config::default();
+        let mut problem = None;
+        let mut section = None;
+        for (i, line) in text.lines().map(str::trim).enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut complain = |message: String| {
+                problem.get_or_insert((i + 1, message));
+            };
+            if let Some(rest) = line.strip_prefix('[') {
+                match rest.strip_suffix(']') {
+                    Some(name) => section = Some(name.trim().to_string()),
+                    None => complain(format!("'{}' is missing its closing ']'", line)),
+                }
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                complain(format!("expected 'key = value', found '{}'", line));
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let mut flag = |on: &mut bool| match value {
+                "true" | "false" => *on = value == "true",
+                _ => complain(format!("{} must be true or false, not '{}'", key, value)),
+            };
+            match section.as_deref() {
+                None if key == "theme" => config.theme = Some(value.to_string()),
+                None if key == "mod" => config.mod_id = Some(value.to_string()),
+                None if key == "language" => config.language = Some(value.to_string()),
+                None if key == "difficulty" => config.difficulty = Some(value.to_string()),
+                None if key == "mouse" => flag(&mut config.mouse),
+                None if key == "online" => config.online = Some(value.to_string()),
+                None if key == "mute" => flag(&mut config.volume.muted),
+                None if ["volume", "sfx_volume", "music_volume"].contains(&key) => {
+                    let level = match key {
+                        "volume" => &mut config.volume.master,
+                        "sfx_volume" => &mut config.volume.effects,
+                        _ => &mut config.volume.music,
+                    };
+                    match percent(value) {
+                        Some(percent) => *level = percent,
+                        None => complain(format!("{} must be a number from 0 to 100, not '{}'", key, value)),
+                    }
+                }
+                Some("keys") => config.keys.push((key.to_string(), value.to_string())),
+                Some(name @ ("assist" | "bell")) => {
+                    let mut on = false;
+                    flag(&mut on);
+                    let list = if name == "assist" { &mut config.assist } else { &mut config.bell };
+                    if on {
+                        list.push(key.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        (config, problem)
+    }
+
+    /// Writes the config file back. Losing it only costs the settings.
+    pub fn save(&self) {
+        let Some(path) = storage::config_file(FILE_NAME) else {
+            return;
+        };
+        let old = fs::read_to_string(&path).unwrap_or_default();
+        let _ = storage::write_atomic(&path, &self.to_text(&old));
+    }
+
+    /// The config as the text of a file that used to read `old`. Settings
+    /// are changed on the lines they were on, so comments and whatever this
+    /// version does not know stay, but the `[keys]`, `[assist]` and `[bell]`
+    /// entries are all written anew.
+    pub fn to_text(&self, old: &str) -> String {
+        let top = [
+            ("theme", self.theme.clone()),
+            ("mod", self.mod_id.clone()),
+            ("language", self.language.clone()),
+            ("difficulty", self.difficulty.clone()),
+            ("mouse", self.mouse.then(|| "true".to_string())),
+            ("online", self.online.clone()),
+            ("volume", percent_entry(self.volume.master)),
+            ("sfx_volume", percent_entry(self.volume.effects)),
+            ("music_volume", percent_entry(self.volume.music)),
+            ("mute", self.volume.muted.then(|| "true".to_string())),
+        ];
+        let unset: Vec<&str> = top.iter().filter(|(_, value)| value.is_none()).map(|(key, _)| *key).collect();
+        let top = top.into_iter().filter_map(|(key, value)| Some((key.to_string(), value?))).collect();
+        let flags = |names: &[String]| names.iter().map(|name| (name.clone(), "true".to_string())).collect();
+        // The entries not written out yet, by section; the top of the file
+        // has no section name
+        let mut pending: [Section; 4] = [
+            (None, top),
+            (Some("keys"), self.keys.clone()),
+            (Some("assist"), flags(&self.assist)),
+            (Some("bell"), flags(&self.bell)),
+        ];
+        let entry = |(key, value): (String, String)| format!("{} = {}", key, value);
+
+        let mut lines = Vec::new();
+        let mut section: Option<String> = None;
+        for line in old.lines() {
+            let trimmed = line.trim();
+            if let Some(name) = trimmed.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                // Whatever was not on a line of its own yet ends its section
+                lines.extend(take_section(&mut pending, section.as_deref()).map(entry));
+                section = Some(name.trim().to_string());
+                lines.push(line.to_string());
+                continue;
+            }
+            let key = trimmed.split_once('=').filter(|_| !trimmed.starts_with('#')).map(|(key, _)| key.trim());
+            let entries = pending.iter_mut().find(|(name, _)| *name == section.as_deref());
+            if let (Some(key), Some((name, entries))) = (key, entries) {
+                if let Some(i) = entries.iter().position(|(known, _)| known == key) {
+                    lines.push(entry(entries.remove(i)));
+                    continue;
+                }
+                // Entries of the sections written anew that are gone go,
+                // as do settings put back to how they come
+                if name.is_some() || unset.contains(&key) {
+                    continue;
+                }
+            }
+            lines.push(line.to_string());
+        }
+        lines.extend(take_section(&mut pending, section.as_deref()).map(entry));
+        for (name, entries) in &mut pending {
+            let Some(name) = name.filter(|_| !entries.is_empty()) else {
+                continue;
+            };
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(format!("[{}]", name));
+            lines.extend(entries.drain(..).map(entry));
+        }
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+}
+
+/// What rings the terminal bell, as the `[bell]` section has it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Bell {
+    /// Ring when a ship is hit.
+    pub player_hit: bool,
+    /// Ring when a run is over.
+    pub game_over: bool,
+    /// Flash the screen rather than beep, where the terminal can.
+    pub flash: bool,
+}
+
+impl Bell {
+    pub fn from_config(config: &Config) -> Bell {
+        let on = |name: &str| config.bell.iter().any(|entry| entry == name);
+        Bell { player_hit: on("player_hit"), game_over: on("game_over"), flash: on("flash") }
+    }
+}
+
+/// How loud the sound is played, each volume in percent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Volume {
+    pub master: u8,
+    /// The sound effects' volume, under the master one.
+    pub effects: u8,
+    /// The heartbeat's volume, under the master one.
+    pub music: u8,
+    pub muted: bool,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Volume { master: 100, effects: 100, music: 100, muted: false }
+    }
+}
+
+impl Volume {
+    /// What samples are multiplied by for something played at `level`,
+    /// under the master volume; nothing when muted. A volume counts as its
+    /// square, which is closer to how loud it sounds.
+    pub fn gain(self, level: u8) -> f32 {
+        let gain = |percent: u8| (percent.min(100) as f32 / 100.0).powi(2);
+        if self.muted { 0.0 } else { gain(self.master) * gain(level) }
+    }
+}
+
+/// A volume setting's value, from 0 to 100.
+fn percent(value: &str) -> Option<u8> {
+    value.parse().ok().filter(|&percent| percent <= 100)
+}
+
+/// A volume's entry, left out at full volume, as it comes.
+fn percent_entry(percent: u8) -> Option<String> {
+    (percent != 100).then(|| percent.to_string())
+}
+
+/// A section's name, `None` for the top of the file, and its entries.
+type Section<'a> = (Option<&'a str>, Vec<(String, String)>);
+
+/// Takes the entries of `section` not written out yet.
+fn take_section(
+    pending: &mut [Section],
+    section: Option<&str>,
+) -> std::vec::IntoIter<(String, String)> {
+    let entries = pending.iter_mut().find(|(name, _)| *name == section).map(|(_, entries)| std::mem::take(entries));
+    entries.unwrap_or_default().into_iter()
+}
@@ -0,0 +1,32 @@
+//! Measurements for the debug overlay, toggled with F3.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How far back ticks are counted towards the tick rate.
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// How fast the main loop has been running.
+#[derive(Default)]
+pub struct FrameStats {
+    /// When each tick within the last [`WINDOW`] happened.
+    ticks: VecDeque<Instant>,
+    /// How long drawing and presenting the last frame took.
+    pub render_time: Duration,
+}
+
+impl FrameStats {
+    /// Notes that the game advanced by a tick just now.
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        self.ticks.push_back(now);
+        while self.ticks.front().is_some_and(|&tick| now - tick > WINDOW) {
+            self.ticks.pop_front();
+        }
+    }
+
+    /// Ticks over the last second.
+    pub fn ticks_per_second(&self) -> usize {
+        self.ticks.iter().filter(|&&tick| tick.elapsed() <= WINDOW).count()
+    }
+}
@@ -0,0 +1,530 @@
+//! A small entity-component store for everything on the field: the ships,
+//! the formation, the boss, the mystery ship, the shots either way and the
+//! pickups.
+//!
+//! Each of them is an [`Id`] with the components it was spawned with: a
+//! [`Position`], a [`Velocity`], a [`Sprite`], a [`Hitbox`], its
+//! [`Health`] and the [`Faction`] it fights for, and a component of its
+//! own kind's, such as a ship's score or an alien's dive. Entities of one
+//! kind are kept together in a [`Table`], one column per component, the
+//! rows of all of them lining up, so a system goes down the columns
+//! without looking anything up. Rows are in the order the entities were
+//! spawned, and ids only ever grow: the game hits things in that order, and
+//! plays the same way from the same seed because of it. A ship's row is
+//! its player's index, as ships are never taken off the field.
+//!
+//! Systems are functions over the tables: [`World::movement`] moves
+//! everything along its velocity, [`World::fire`] fires a shot of the
+//! shooter's side from its muzzle, and [`collision`] runs one table's
+//! entities into another's.
+
+use std::ops::{Deref, DerefMut};
+use std::time::Duration;
+
+use crate::collision::{Aabb, Columns};
+use crate::entity::{Entity, Sprite};
+use crate::game::{Alien, Boss, Faction, Health, Hitbox, PickupKind, Player, Position, Ufo, Velocity};
+use crate::game::{BOSS_HEIGHT, BOSS_WIDTH, UFO_ROW, UFO_WIDTH};
+
+/// An entity, standing for the components spawned with it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Id(u32);
+
+/// The components of one entity, as a query finds them.
+#[derive(Clone, Copy, Debug)]
+pub struct Row<T> {
+    pub id: Id,
+    pub position: Position,
+    pub velocity: Velocity,
+    pub sprite: Sprite,
+    pub hitbox: Hitbox,
+    pub health: Health,
+    pub faction: Faction,
+    /// The component only the entities of its kind have.
+    pub tag: T,
+}
+
+impl<T> Row<T> {
+    /// The cell it is in, the top-left one of its box.
+    pub fn cell(&self) -> (u16, u16) {
+        self.position.cell()
+    }
+
+    pub fn x(&self) -> u16 {
+        self.cell().0
+    }
+
+    pub fn y(&self) -> u16 {
+        self.cell().1
+    }
+}
+
+impl<T> Deref for Row<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.tag
+    }
+}
+
+impl<T> Entity for Row<T> {
+    fn sprite(&self) -> Sprite {
+        self.sprite
+    }
+
+    fn bbox(&self) -> Aabb {
+        bbox(self.position, self.hitbox)
+    }
+}
+
+/// The components of one entity that can change once it is spawned, to be
+/// changed in place.
+pub struct RowMut<'a, T> {
+    pub id: Id,
+    pub position: &'a mut Position,
+    pub velocity: &'a mut Velocity,
+    pub health: &'a mut Health,
+    pub tag: &'a mut T,
+}
+
+impl<T> RowMut<'_, T> {
+    pub fn cell(&self) -> (u16, u16) {
+        self.position.cell()
+    }
+
+    pub fn x(&self) -> u16 {
+        self.cell().0
+    }
+
+    pub fn y(&self) -> u16 {
+        self.cell().1
+    }
+
+    /// Puts it in cell (x, y).
+    pub fn place(&mut self, x: u16, y: u16) {
+        *self.position = Position::at(x, y);
+    }
+}
+
+impl<T> Deref for RowMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.tag
+    }
+}
+
+impl<T> DerefMut for RowMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.tag
+    }
+}
+
+/// The entities of one kind, in the order they were spawned: row `i` of
+/// every column is the components of `ids[i]`.
+#[derive(Clone, Debug)]
+pub struct Table<T> {
+    ids: Vec<Id>,
+    positions: Vec<Position>,
+    velocities: Vec<Velocity>,
+    sprites: Vec<Sprite>,
+    hitboxes: Vec<Hitbox>,
+    healths: Vec<Health>,
+    factions: Vec<Faction>,
+    tags: Vec<T>,
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Table {
+            ids: Vec::new(),
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            sprites: Vec::new(),
+            hitboxes: Vec::new(),
+            healths: Vec::new(),
+            factions: Vec::new(),
+            tags: Vec::new(),
+        }
+    }
+}
+
+impl<T: Copy> Table<T> {
+    /// Adds `row`, spawned after every entity already here.
+    fn insert(&mut self, row: Row<T>) {
+        debug_assert!(self.ids.last() < Some(&row.id), "entities join a table in the order they were spawned");
+        self.ids.push(row.id);
+        self.positions.push(row.position);
+        self.velocities.push(row.velocity);
+        self.sprites.push(row.sprite);
+        self.hitboxes.push(row.hitbox);
+        self.healths.push(row.health);
+        self.factions.push(row.faction);
+        self.tags.push(row.tag);
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// The entity in row `row`, if there are that many.
+    pub fn get(&self, row: usize) -> Option<Row<T>> {
+        (row < self.len()).then(|| Row {
+            id: self.ids[row],
+            position: self.positions[row],
+            velocity: self.velocities[row],
+            sprite: self.sprites[row],
+            hitbox: self.hitboxes[row],
+            health: self.healths[row],
+            faction: self.factions[row],
+            tag: self.tags[row],
+        })
+    }
+
+    pub fn get_mut(&mut self, row: usize) -> Option<RowMut<'_, T>> {
+        (row < self.len()).then(|| RowMut {
+            id: self.ids[row],
+            position: &mut self.positions[row],
+            velocity: &mut self.velocities[row],
+            health: &mut self.healths[row],
+            tag: &mut self.tags[row],
+        })
+    }
+
+    /// Every entity here, in the order they were spawned.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Row<T>> + '_ {
+        let rows = self.ids.iter().zip(&self.positions).zip(&self.velocities).zip(&self.sprites);
+        let rows = rows.zip(&self.hitboxes).zip(&self.healths).zip(&self.factions).zip(&self.tags);
+        rows.map(|(((((((&id, &position), &velocity), &sprite), &hitbox), &health), &faction), &tag)| Row {
+            id,
+            position,
+            velocity,
+            sprite,
+            hitbox,
+            health,
+            faction,
+            tag,
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = RowMut<'_, T>> {
+        let rows = self.ids.iter().zip(&mut self.positions).zip(&mut self.velocities);
+        let rows = rows.zip(&mut self.healths).zip(&mut self.tags);
+        rows.map(|((((&id, position), velocity), health), tag)| RowMut { id, position, velocity, health, tag })
+    }
+
+    /// The boxes of every entity here, row by row.
+    pub fn bboxes(&self) -> impl ExactSizeIterator<Item = Aabb> + '_ {
+        self.positions.iter().zip(&self.hitboxes).map(|(&position, &hitbox)| bbox(position, hitbox))
+    }
+
+    /// Takes the entity in row `row` out, the rows after it moving up one.
+    pub fn remove(&mut self, row: usize) -> Row<T> {
+        let removed = self.get(row).expect("only rows in range are removed");
+        self.ids.remove(row);
+        self.positions.remove(row);
+        self.velocities.remove(row);
+        self.sprites.remove(row);
+        self.hitboxes.remove(row);
+        self.healths.remove(row);
+        self.factions.remove(row);
+        self.tags.remove(row);
+        removed
+    }
+
+    /// Takes every entity out.
+    pub fn clear(&mut self) {
+        *self = Table::default();
+    }
+
+    /// Takes out every entity with no health left, all at once.
+    fn remove_spent(&mut self) {
+        let spent = self.ids.iter().zip(&self.healths).filter(|(_, health)| health.is_spent());
+        let spent: Vec<Id> = spent.map(|(&id, _)| id).collect();
+        self.remove_all(&spent);
+    }
+
+    /// Takes out whichever of `gone`, which are in order, are here, all at
+    /// once.
+    fn remove_all(&mut self, gone: &[Id]) {
+        if self.is_empty() {
+            return;
+        }
+        let rows: Vec<usize> = gone.iter().filter_map(|id| self.ids.binary_search(id).ok()).collect();
+        if rows.is_empty() {
+            return;
+        }
+        remove_rows(&mut self.ids, &rows);
+        remove_rows(&mut self.positions, &rows);
+        remove_rows(&mut self.velocities, &rows);
+        remove_rows(&mut self.sprites, &rows);
+        remove_rows(&mut self.hitboxes, &rows);
+        remove_rows(&mut self.healths, &rows);
+        remove_rows(&mut self.factions, &rows);
+        remove_rows(&mut self.tags, &rows);
+    }
+
+    /// Moves every entity here along its velocity for `elapsed` game time.
+    fn movement(&mut self, elapsed: Duration) {
+        for (position, &velocity) in self.positions.iter_mut().zip(&self.velocities) {
+            position.advance(velocity, elapsed);
+        }
+    }
+}
+
+/// The cells an entity at `position` with `hitbox` takes up.
+fn bbox(position: Position, hitbox: Hitbox) -> Aabb {
+    let (x, y) = position.cell();
+    Aabb::new(x, y, hitbox.width, hitbox.height)
+}
+
+/// Takes `rows`, which are in order, out of `column`: each stretch of the
+/// rows kept between two of them moves up in one go.
+fn remove_rows<T: Copy>(column: &mut Vec<T>, rows: &[usize]) {
+    let mut kept = rows[0];
+    for (n, &row) in rows.iter().enumerate() {
+        let end = rows.get(n + 1).copied().unwrap_or(column.len());
+        column.copy_within(row + 1..end, kept);
+        kept += end - row - 1;
+    }
+    column.truncate(kept);
+}
+
+/// A player shot's own component: which way it leans is its sprite's, and
+/// the player it scores for its faction's.
+#[derive(Clone, Copy, Debug)]
+pub struct Shot;
+
+/// An alien shot's own component; it has nothing the others lack.
+#[derive(Clone, Copy, Debug)]
+pub struct AlienShot;
+
+/// Every entity and its components, a table to each kind.
+#[derive(Clone, Debug, Default)]
+pub struct World {
+    next: u32,
+    /// One ship per player, player one's first.
+    pub ships: Table<Player>,
+    /// The formation, divers and all, from its top-left alien on.
+    pub aliens: Table<Alien>,
+    /// The boss, while there is one.
+    pub bosses: Table<Boss>,
+    /// The mystery ship, while there is one.
+    pub ufos: Table<Ufo>,
+    pub shots: Table<Shot>,
+    pub alien_shots: Table<AlienShot>,
+    /// The pickups, with what catching each gives.
+    pub pickups: Table<PickupKind>,
+}
+
+impl World {
+    fn next_id(&mut self) -> Id {
+        let id = Id(self.next);
+        self.next += 1;
+        id
+    }
+
+    /// Components standing in cell (x, y) and fighting for `faction`, as
+    /// everything but the shots and pickups is spawned with.
+    fn row<T>(
+        &mut self,
+        (x, y): (u16, u16),
+        sprite: Sprite,
+        hitbox: Hitbox,
+        health: Health,
+        faction: Faction,
+        tag: T,
+    ) -> Row<T> {
+        let velocity = Velocity { dx: 0.0, dy: 0.0 };
+        Row { id: self.next_id(), position: Position::at(x, y), velocity, sprite, hitbox, health, faction, tag }
+    }
+
+    /// Brings in player `index`'s ship at (x, y), `hitbox` in size, with
+    /// `lives` lives.
+    pub fn spawn_ship(&mut self, x: u16, y: u16, hitbox: Hitbox, lives: Health, index: usize, ship: Player) -> Id {
+        debug_assert_eq!(self.ships.len(), index, "ships are spawned in the order of their players");
+        let row = self.row((x, y), Sprite::Player, hitbox, lives, Faction::Player(index), ship);
+        self.ships.insert(row);
+        row.id
+    }
+
+    /// Adds an alien to the formation at (x, y), as tough as its kind.
+    pub fn spawn_alien(&mut self, x: u16, y: u16, hitbox: Hitbox, alien: Alien) -> Id {
+        let health = Health::full(u16::from(alien.kind.toughness()));
+        let row = self.row((x, y), Sprite::Alien, hitbox, health, Faction::Aliens, alien);
+        self.aliens.insert(row);
+        row.id
+    }
+
+    pub fn spawn_boss(&mut self, x: u16, y: u16, health: Health, boss: Boss) -> Id {
+        let hitbox = Hitbox { width: BOSS_WIDTH, height: BOSS_HEIGHT };
+        let row = self.row((x, y), Sprite::Boss, hitbox, health, Faction::Aliens, boss);
+        self.bosses.insert(row);
+        row.id
+    }
+
+    /// Sends the mystery ship along the top row from column `x`.
+    pub fn spawn_ufo(&mut self, x: u16, ufo: Ufo) -> Id {
+        let hitbox = Hitbox { width: UFO_WIDTH, height: 1 };
+        let row = self.row((x, UFO_ROW), Sprite::Ufo, hitbox, Health::full(1), Faction::Aliens, ufo);
+        self.ufos.insert(row);
+        row.id
+    }
+
+    /// Fires a shot of player `owner`'s from `position`, leaning the way
+    /// `velocity` does.
+    pub fn spawn_shot(&mut self, position: Position, velocity: Velocity, owner: usize) -> Id {
+        let lean = velocity.dx.partial_cmp(&0.0).unwrap_or(std::cmp::Ordering::Equal);
+        let (hitbox, health) = (Hitbox::CELL, Health::full(1));
+        let (id, faction) = (self.next_id(), Faction::Player(owner));
+        let sprite = Sprite::Shot(lean);
+        self.shots.insert(Row { id, position, velocity, sprite, hitbox, health, faction, tag: Shot });
+        id
+    }
+
+    pub fn spawn_alien_shot(&mut self, position: Position, velocity: Velocity) -> Id {
+        let (id, sprite, hitbox, health) = (self.next_id(), Sprite::AlienShot, Hitbox::CELL, Health::full(1));
+        let faction = Faction::Aliens;
+        self.alien_shots.insert(Row { id, position, velocity, sprite, hitbox, health, faction, tag: AlienShot });
+        id
+    }
+
+    pub fn spawn_pickup(&mut self, position: Position, velocity: Velocity, kind: PickupKind) -> Id {
+        let (id, sprite, hitbox, health) = (self.next_id(), Sprite::Pickup(kind), Hitbox::CELL, Health::full(1));
+        let faction = Faction::Neutral;
+        self.pickups.insert(Row { id, position, velocity, sprite, hitbox, health, faction, tag: kind });
+        id
+    }
+
+    /// Takes all of `ids` off the field at once. A system collects what it
+    /// does away with and takes it all off in one call.
+    pub fn despawn_all(&mut self, ids: &[Id]) {
+        if ids.is_empty() {
+            return;
+        }
+        if !ids.is_sorted() {
+            let mut ids = ids.to_vec();
+            ids.sort_unstable();
+            return self.despawn_all(&ids);
+        }
+        self.aliens.remove_all(ids);
+        self.bosses.remove_all(ids);
+        self.ufos.remove_all(ids);
+        self.shots.remove_all(ids);
+        self.alien_shots.remove_all(ids);
+        self.pickups.remove_all(ids);
+    }
+
+    /// Takes every shot, either side's, off the field.
+    pub fn despawn_shots(&mut self) {
+        self.shots.clear();
+        self.alien_shots.clear();
+    }
+
+    // --- Queries ---
+
+    /// Player `index`'s ship.
+    pub fn ship(&self, index: usize) -> Row<Player> {
+        self.ships.get(index).expect("every player has a ship")
+    }
+
+    pub fn ship_mut(&mut self, index: usize) -> RowMut<'_, Player> {
+        self.ships.get_mut(index).expect("every player has a ship")
+    }
+
+    pub fn boss(&self) -> Option<Row<Boss>> {
+        self.bosses.get(0)
+    }
+
+    pub fn boss_mut(&mut self) -> Option<RowMut<'_, Boss>> {
+        self.bosses.get_mut(0)
+    }
+
+    pub fn ufo(&self) -> Option<Row<Ufo>> {
+        self.ufos.get(0)
+    }
+
+    pub fn ufo_mut(&mut self) -> Option<RowMut<'_, Ufo>> {
+        self.ufos.get_mut(0)
+    }
+
+    // --- Systems ---
+
+    /// Moves everything along its velocity for `elapsed` game time.
+    pub fn movement(&mut self, elapsed: Duration) {
+        self.shots.movement(elapsed);
+        self.alien_shots.movement(elapsed);
+        self.pickups.movement(elapsed);
+    }
+
+    /// Fires a shot of `shooter`'s side flying at `velocity`. It leaves from
+    /// the shooter's muzzle: the middle of the edge of its box that faces
+    /// the other side, moved `offset` cells along it.
+    pub fn fire<T>(&mut self, shooter: &Row<T>, offset: i16, velocity: Velocity) -> Id {
+        let (x, y) = shooter.cell();
+        let x = (x + shooter.hitbox.width / 2).saturating_add_signed(offset);
+        match shooter.faction {
+            Faction::Player(owner) => self.spawn_shot(Position::at(x, y - 1), velocity, owner),
+            Faction::Aliens | Faction::Neutral => {
+                self.spawn_alien_shot(Position::at(x, y + shooter.hitbox.height), velocity)
+            }
+        }
+    }
+}
+
+/// One entity running into another, as [`collision`] found it.
+#[derive(Clone, Copy, Debug)]
+pub struct Hit<A, B> {
+    pub attacker: Row<A>,
+    /// What was hit, with the health the hit left it.
+    pub target: Row<B>,
+}
+
+impl<A, B> Hit<A, B> {
+    /// Whether the hit took the last of the target's health, and it off the
+    /// field.
+    pub fn destroyed(&self) -> bool {
+        self.target.health.is_spent()
+    }
+}
+
+/// Runs each of `attackers` in turn into the first of `targets` whose box
+/// shares a cell with it, as `target_box` makes it out, that fights on the
+/// other side and has health left. A hit takes a point of health off both,
+/// and what has none left hits nothing more; it is all taken off the field
+/// at once when every attacker has had its turn. `columns` holds the
+/// targets' boxes while this runs. Returns the hits in the order they
+/// happened.
+pub fn collision<A: Copy, B: Copy>(
+    attackers: &mut Table<A>,
+    targets: &mut Table<B>,
+    columns: &mut Columns,
+    target_box: impl Fn(Aabb) -> Aabb,
+) -> Vec<Hit<A, B>> {
+    let mut hits = Vec::new();
+    if attackers.is_empty() || targets.is_empty() {
+        return hits;
+    }
+    columns.fill(targets.bboxes().map(target_box));
+    for i in 0..attackers.len() {
+        let faction = attackers.factions[i];
+        let (healths, factions) = (&targets.healths, &targets.factions);
+        let hittable = |j: usize| !healths[j].is_spent() && factions[j].opposes(faction);
+        let Some(j) = columns.first_hit_where(bbox(attackers.positions[i], attackers.hitboxes[i]), hittable) else {
+            continue;
+        };
+        attackers.healths[i].damage(1);
+        targets.healths[j].damage(1);
+        let (attacker, target) = (attackers.get(i), targets.get(j));
+        hits.push(Hit { attacker: attacker.expect("rows are in range"), target: target.expect("hits are in range") });
+    }
+    if !hits.is_empty() {
+        attackers.remove_spent();
+        targets.remove_spent();
+    }
+    hits
+}
@@ -0,0 +1,40 @@
+//! What the things on the playfield have in common: where they are and what
+//! they look like, so drawing and collisions can go through them all alike.
+//!
+//! Everything on the field is an entity of the [`crate::ecs::World`], as its
+//! queries find them: its box is the cells its [`crate::game::Hitbox`]
+//! covers from the one it is in. How they move is no part of it. That is
+//! decided for the whole field at once, such as the formation's march, a
+//! ship following its player's keys or the world moving the shots, each by
+//! a step of [`crate::game::update_state`].
+
+use std::cmp::Ordering;
+
+use crate::collision::{intersects, Aabb};
+use crate::game::PickupKind;
+
+/// What an entity is drawn as, for the renderer to find the sprite of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sprite {
+    Player,
+    Alien,
+    /// A player shot, leaning left for `Less`, right for `Greater` and
+    /// flying straight up for `Equal`.
+    Shot(Ordering),
+    AlienShot,
+    Ufo,
+    Boss,
+    Pickup(PickupKind),
+}
+
+pub trait Entity {
+    fn sprite(&self) -> Sprite;
+
+    /// The cells it takes up, its sprite drawn in the top-left one.
+    fn bbox(&self) -> Aabb;
+}
+
+/// Whether `a` and `b` take up a cell in common.
+pub fn collide(a: &impl Entity, b: &impl Entity) -> bool {
+    intersects(a.bbox(), b.bbox())
+}
@@ -0,0 +1,100 @@
+//! A step-by-step interface to the game for training agents, in the
+//! manner of reinforcement learning environments.
+//!
+//! An [`Env`] plays the real game logic with no terminal involved: every
+//! [`Env::step`] takes one action, plays one tick and tells what the agent
+//! now sees, what it earned and whether the run is over.
+
+use crate::game::{self, Action, GameState, Settings};
+
+/// What an agent sees of the game, in cells. Positions are `(x, y)` from
+/// the top-left corner of the playfield, for the top-left cell of a sprite.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Observation {
+    /// The size of the playfield.
+    pub width: u16,
+    pub height: u16,
+    /// The column of player one's ship, or `None` once it is out.
+    pub ship: Option<u16>,
+    pub lives: u8,
+    pub aliens: Vec<(u16, u16)>,
+    /// The ship's shots flying up.
+    pub shots: Vec<(u16, u16)>,
+    /// The aliens' shots falling down.
+    pub alien_shots: Vec<(u16, u16)>,
+    /// The blocks of the bunkers still standing.
+    pub bunkers: Vec<(u16, u16)>,
+    /// The column of the mystery ship, while it flies.
+    pub ufo: Option<u16>,
+    /// Where the boss is and the hits it has left, in a boss wave.
+    pub boss: Option<(u16, u16, u16)>,
+    pub wave: u32,
+    pub score: u32,
+    /// Whether the countdown before a wave is on, when no action does
+    /// anything.
+    pub intermission: bool,
+}
+
+impl Observation {
+    fn of(state: &GameState) -> Self {
+        let (world, player) = (&state.world, state.world.ship(0));
+        Observation {
+            width: state.settings.width,
+            height: state.settings.height,
+            ship: (!player.is_out()).then_some(player.x()),
+            lives: player.lives(),
+            aliens: world.aliens.iter().map(|alien| alien.cell()).collect(),
+            shots: world.shots.iter().map(|shot| shot.cell()).collect(),
+            alien_shots: world.alien_shots.iter().map(|shot| shot.cell()).collect(),
+            bunkers: state.bunkers.clone(),
+            ufo: world.ufo().map(|ufo| ufo.x()),
+            boss: world.boss().map(|boss| (boss.x(), boss.y(), boss.health.hp)),
+            wave: state.wave,
+            score: state.score,
+            intermission: state.intermission_until.is_some(),
+        }
+    }
+}
+
+/// A game an agent plays player one of, a tick at a time.
+pub struct Env {
+    settings: Settings,
+    state: GameState,
+}
+
+impl Env {
+    /// An environment playing runs of `settings`, which begins with a run
+    /// already under way as [`Env::reset`] would start it.
+    pub fn new(settings: Settings) -> Self {
+        let state = GameState::new(&settings);
+        Env { settings, state }
+    }
+
+    /// Starts a fresh run seeded with `seed`, so equal seeds and equal
+    /// actions always play out the same way.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.state = GameState::new(&Settings { seed: Some(seed), ..self.settings.clone() });
+        Observation::of(&self.state)
+    }
+
+    /// Takes `action`, if any, and plays a tick, handing back what the agent
+    /// sees then, the points it scored in the tick, and whether the run is
+    /// over. Unlike the fire key, firing is only held back by the limit on
+    /// shots in the air.
+    pub fn step(&mut self, action: Option<Action>) -> (Observation, u32, bool) {
+        let score = self.state.score;
+        if let Some(action) = action.filter(|_| !self.state.game_over) {
+            game::apply_action(&mut self.state, 0, action);
+        }
+        if !self.state.game_over {
+            game::update_state(&mut self.state);
+        }
+        (Observation::of(&self.state), self.state.score - score, self.state.game_over)
+    }
+
+    /// The whole of the game, for agents that want more than the
+    /// observation.
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+}
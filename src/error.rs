@@ -0,0 +1,42 @@
+//! What can stop the game from starting, with messages that say what to do
+//! about it.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum StartError {
+    /// `TERM` is not set, so ncurses cannot tell what the terminal does.
+    NoTerm,
+    /// ncurses has no description of the terminal `TERM` names.
+    UnknownTerminal(String),
+    /// The terminal could not be put in the state the game draws in.
+    Terminal(io::Error),
+    /// The config file exists but could not be read.
+    ConfigUnreadable(PathBuf, io::Error),
+    /// A line of the config file makes no sense, counting from 1.
+    Config { path: PathBuf, line: usize, message: String },
+}
+
+impl fmt::Display for StartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StartError::NoTerm => {
+                write!(f, "TERM not set; set it to your terminal's type, such as xterm-256color, or pass --backend crossterm")
+            }
+            StartError::UnknownTerminal(term) => write!(
+                f,
+                "unknown terminal type '{}'; set TERM to one ncurses knows, such as xterm-256color, or pass --backend crossterm",
+                term
+            ),
+            StartError::Terminal(err) => write!(f, "cannot set up the terminal: {}", err),
+            StartError::ConfigUnreadable(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            StartError::Config { path, line, message } => {
+                write!(f, "config parse error at line {} of {}: {}", line, path.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StartError {}
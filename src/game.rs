@@ -0,0 +1,2365 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::time::Duration;
+
+use crate::collision::{intersects, Aabb, Columns};
+use crate::ecs;
+use crate::entity::{collide, Entity};
+use crate::level::{self, Level, LevelPack};
+use crate::locale::tr;
+use crate::particles::Particles;
+#[cfg(feature = "scripting")]
+use crate::script::{Actor, Script, World};
+
+// --- Game Constants ---
+/// Playfield size in cells when none is requested.
+pub const DEFAULT_FIELD_WIDTH: u16 = 40;
+pub const DEFAULT_FIELD_HEIGHT: u16 = 19;
+/// The smallest playfield the alien formation has room to move in and the
+/// menus fit into.
+pub const MIN_FIELD_WIDTH: u16 = 32;
+pub const MIN_FIELD_HEIGHT: u16 = 19;
+/// The biggest playfield, well short of where positions in cells would
+/// overflow.
+pub const MAX_FIELD_WIDTH: u16 = 1_000;
+pub const MAX_FIELD_HEIGHT: u16 = 1_000;
+/// The fewest columns in the alien formation; wider playfields get more.
+pub const ALIEN_COLS: usize = 6;
+/// The most rows a level's formation can have.
+pub const MAX_ALIEN_ROWS: usize = 5;
+pub const HORIZONTAL_SPACING: u16 = 5;
+pub const VERTICAL_SPACING: u16 = 4;
+/// Sizes of the built-in player and alien sprites.
+pub const PLAYER_SIZE: SpriteSize = SpriteSize { width: 3, height: 2 };
+pub const ALIEN_SIZE: SpriteSize = SpriteSize { width: 3, height: 2 };
+/// The biggest sprites that still leave the formation a gap between aliens
+/// and keep the ship on its two bottom rows.
+pub const MAX_PLAYER_SIZE: SpriteSize = SpriteSize { width: 5, height: 2 };
+pub const MAX_ALIEN_SIZE: SpriteSize = SpriteSize { width: HORIZONTAL_SPACING - 1, height: VERTICAL_SPACING - 1 };
+pub const MAX_SHOTS: usize = 10;
+/// How fast the player's shots fly up, in cells per second.
+pub const SHOT_SPEED: f32 = 5.0;
+/// What the slow shots assist multiplies the speed of alien shots by.
+const SLOW_SHOTS_FACTOR: f32 = 0.5;
+/// Lives the extra lives assist adds to the start of a run.
+const ASSIST_EXTRA_LIVES: u8 = 2;
+/// Points between two extra lives, by default.
+pub const DEFAULT_EXTRA_LIFE_EVERY: u32 = 1000;
+/// Extra lives are never awarded beyond this many.
+pub const MAX_LIVES: u8 = 9;
+/// Ships a run can have: one, or two in co-op.
+pub const MAX_PLAYERS: usize = 2;
+/// How long the HUD flashes "1UP!" after an extra life.
+const ONE_UP_FLASH: Duration = Duration::from_millis(1_500);
+/// How long the ship is immune to alien fire after losing a life.
+pub const INVINCIBILITY: Duration = Duration::from_millis(2_000);
+/// How long the screen shakes after the ship is hit or a bomb goes off.
+const SCREEN_SHAKE: Duration = Duration::from_millis(600);
+/// The mystery ship flies along the top row of the playfield.
+pub const UFO_ROW: u16 = 1;
+/// Cells the mystery ship moves per tick.
+pub const UFO_SPEED: u16 = 1;
+const UFO_MIN_INTERVAL_MS: u64 = 15_000;
+const UFO_MAX_INTERVAL_MS: u64 = 30_000;
+const UFO_BONUSES: [u32; 4] = [50, 100, 150, 300];
+
+// --- Wave Progression ---
+/// Alien speeds are in hundredths of a cell per tick; this is one full step.
+const STEP: u32 = 100;
+/// Extra formation speed gained with every wave, up to `MAX_ALIEN_SPEED`.
+const ALIEN_SPEED_PER_WAVE: u32 = 15;
+const MAX_ALIEN_SPEED: u32 = 250;
+/// Fastest the formation ever gets, reached as its last aliens fall.
+const FRANTIC_ALIEN_SPEED: u32 = 400;
+/// Time shaved off the alien fire interval with every wave.
+const FIRE_INTERVAL_PER_WAVE: Duration = Duration::from_millis(50);
+const MIN_FIRE_INTERVAL: Duration = Duration::from_millis(250);
+/// Rows kept free between a freshly spawned formation and the player.
+const SPAWN_CLEARANCE: u16 = 6;
+
+// --- Boss Fights ---
+/// Every this many waves a boss replaces the alien formation.
+pub const BOSS_WAVE_INTERVAL: u32 = 5;
+/// The row the boss's sprite starts on.
+const BOSS_ROW: u16 = 3;
+/// Hit points of the first boss; each later one has `BOSS_HP_PER_FIGHT` more.
+const BOSS_BASE_HP: u16 = 20;
+const BOSS_HP_PER_FIGHT: u16 = 10;
+/// Points for destroying a boss, multiplied by how many bosses there have
+/// been, this one included.
+const BOSS_POINTS: u32 = 500;
+/// Shots the boss drops at random columns during a barrage.
+const BOSS_BARRAGE_SHOTS: usize = 4;
+
+// --- Versus ---
+/// How soon the alien commander may turn the formation around again.
+const TURN_COOLDOWN: Duration = Duration::from_millis(1_500);
+
+// --- Dive Bombers ---
+/// Game time between two aliens breaking off to dive at the player.
+const DIVE_INTERVAL: Duration = Duration::from_millis(6_000);
+/// At most this many aliens are away from the formation at once.
+const MAX_DIVERS: usize = 2;
+/// Ticks a diver first swings sideways before turning towards the player.
+const DIVE_SWING_TICKS: u16 = 3;
+/// A diver fires once every this many ticks while swooping down.
+const DIVE_FIRE_TICKS: u16 = 4;
+// --- Smart Bombs ---
+pub const INITIAL_BOMBS: u8 = 2;
+pub const MAX_BOMBS: u8 = 5;
+/// Hit points a smart bomb takes off the boss.
+const BOMB_BOSS_DAMAGE: u16 = 5;
+/// Chance that a shot-down alien drops a bomb pickup; the mystery ship
+/// always drops one.
+const BOMB_DROP_CHANCE: f64 = 0.05;
+/// How fast pickups fall, in cells per second.
+const PICKUP_SPEED: f32 = 2.5;
+
+// --- Laser ---
+/// Ticks the fire key must be held for the laser to be ready.
+pub const LASER_FULL_CHARGE: u16 = 10;
+/// Hit points a laser blast takes off the boss.
+const LASER_BOSS_DAMAGE: u16 = 5;
+
+// --- Combos ---
+/// Consecutive kills needed for each step up of the score multiplier.
+const COMBO_KILLS_PER_STEP: u32 = 3;
+pub const MAX_MULTIPLIER: u32 = 5;
+
+/// Points for shooting down an alien shot with one of the player's.
+const SHOT_CLASH_POINTS: u32 = 5;
+/// Shooting down an alien while it dives scores this many times its points.
+const DIVER_MULTIPLIER: u32 = 2;
+
+/// Bonus points per percent of accuracy when a wave is cleared.
+const ACCURACY_BONUS: u32 = 5;
+/// Clearing a wave faster than this earns `SPEED_BONUS` per second saved.
+const PAR_TIME: Duration = Duration::from_secs(60);
+const SPEED_BONUS: u32 = 5;
+
+/// How long the "WAVE n" countdown freezes play before each wave.
+pub const INTERMISSION: Duration = Duration::from_millis(3_000);
+
+/// Game time that passes with every call to `update_state`, by default.
+pub const DEFAULT_TICK: Duration = Duration::from_millis(200);
+
+/// The most sounds kept waiting for a frontend to play them.
+const MAX_QUEUED_SOUNDS: usize = 64;
+
+// --- Settings ---
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+    /// For players who have beaten Hard: one life and a formation that
+    /// comes down fast.
+    Invader,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard, Difficulty::Invader];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+            Difficulty::Invader => "invader",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|difficulty| difficulty.name().eq_ignore_ascii_case(name))
+    }
+
+    /// What the menus call it, in the language in use.
+    pub fn label(self) -> &'static str {
+        tr(match self {
+            Difficulty::Easy => "difficulty_easy",
+            Difficulty::Normal => "difficulty_normal",
+            Difficulty::Hard => "difficulty_hard",
+            Difficulty::Invader => "difficulty_invader",
+        })
+    }
+
+    /// The next difficulty up, wrapping around to the easiest.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&difficulty| difficulty == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn previous(self) -> Self {
+        let index = Self::ALL.iter().position(|&difficulty| difficulty == self).unwrap_or(0);
+        Self::ALL[(index + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+
+    /// Minimum time between two alien shots in the first wave.
+    pub fn alien_fire_interval(self) -> Duration {
+        match self {
+            Difficulty::Easy => Duration::from_millis(1000),
+            Difficulty::Normal => Duration::from_millis(750),
+            Difficulty::Hard => Duration::from_millis(500),
+            Difficulty::Invader => Duration::from_millis(350),
+        }
+    }
+
+    /// How fast alien shots fall, in cells per second.
+    pub fn alien_shot_speed(self) -> f32 {
+        match self {
+            Difficulty::Easy => 4.0,
+            Difficulty::Normal => 5.0,
+            Difficulty::Hard => 6.0,
+            Difficulty::Invader => 8.0,
+        }
+    }
+
+    /// Lives at the start of a run, unless `--lives` says otherwise.
+    pub fn lives(self) -> u8 {
+        match self {
+            Difficulty::Easy => 5,
+            Difficulty::Normal | Difficulty::Hard => 3,
+            Difficulty::Invader => 1,
+        }
+    }
+
+    /// Rows the formation comes down every time it reaches a wall.
+    pub fn descent(self) -> u16 {
+        match self {
+            Difficulty::Easy | Difficulty::Normal => 1,
+            Difficulty::Hard | Difficulty::Invader => 2,
+        }
+    }
+}
+
+/// An assist option, which makes the game easier in one way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssistOption {
+    /// Alien shots fall at half speed.
+    SlowShots,
+    /// Alien shots only hit the middle of the ship, not its edges.
+    ForgivingHitbox,
+    /// The ship fires on its own, as fast as it can.
+    Autofire,
+    /// Runs start with two more lives.
+    ExtraLives,
+}
+
+impl AssistOption {
+    pub const ALL: [AssistOption; 4] =
+        [AssistOption::SlowShots, AssistOption::ForgivingHitbox, AssistOption::Autofire, AssistOption::ExtraLives];
+
+    /// The name the config file and replays know the option by.
+    pub fn name(self) -> &'static str {
+        match self {
+            AssistOption::SlowShots => "slow_shots",
+            AssistOption::ForgivingHitbox => "forgiving_hitbox",
+            AssistOption::Autofire => "autofire",
+            AssistOption::ExtraLives => "extra_lives",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|option| option.name() == name)
+    }
+
+    pub fn label(self) -> &'static str {
+        tr(match self {
+            AssistOption::SlowShots => "assist_slow_shots",
+            AssistOption::ForgivingHitbox => "assist_forgiving_hitbox",
+            AssistOption::Autofire => "assist_autofire",
+            AssistOption::ExtraLives => "assist_extra_lives",
+        })
+    }
+}
+
+/// The assist options turned on. Each works on its own, whatever the
+/// difficulty; scores made with any of them are marked as assisted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Assist {
+    on: [bool; AssistOption::ALL.len()],
+}
+
+impl Assist {
+    pub fn is_on(self, option: AssistOption) -> bool {
+        self.on[option as usize]
+    }
+
+    pub fn set(&mut self, option: AssistOption, on: bool) {
+        self.on[option as usize] = on;
+    }
+
+    /// Whether any option is on.
+    pub fn any(self) -> bool {
+        self.on.contains(&true)
+    }
+
+    /// The options turned on.
+    pub fn options(self) -> impl Iterator<Item = AssistOption> {
+        AssistOption::ALL.into_iter().filter(move |&option| self.is_on(option))
+    }
+}
+
+/// A twist on the rules, which challenges turn on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Modifier {
+    /// The formation moves half again as fast.
+    FastAliens,
+    /// Aliens fire twice as often.
+    HeavyFire,
+    /// No smart bombs, neither to start with nor dropped.
+    NoBombs,
+    /// The ship starts with the spread weapon.
+    SpreadShot,
+}
+
+impl Modifier {
+    pub const ALL: [Modifier; 4] = [Modifier::FastAliens, Modifier::HeavyFire, Modifier::NoBombs, Modifier::SpreadShot];
+
+    /// The name replays know the modifier by.
+    pub fn name(self) -> &'static str {
+        match self {
+            Modifier::FastAliens => "fast_aliens",
+            Modifier::HeavyFire => "heavy_fire",
+            Modifier::NoBombs => "no_bombs",
+            Modifier::SpreadShot => "spread_shot",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|modifier| modifier.name() == name)
+    }
+
+    pub fn label(self) -> &'static str {
+        tr(match self {
+            Modifier::FastAliens => "modifier_fast_aliens",
+            Modifier::HeavyFire => "modifier_heavy_fire",
+            Modifier::NoBombs => "modifier_no_bombs",
+            Modifier::SpreadShot => "modifier_spread_shot",
+        })
+    }
+}
+
+/// The modifiers turned on, one bit each.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    /// The modifiers whose bits are set in `bits`; other bits are ignored.
+    pub fn from_bits(bits: u8) -> Self {
+        Modifiers(bits & ((1 << Modifier::ALL.len()) - 1))
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn is_on(self, modifier: Modifier) -> bool {
+        self.0 & 1 << modifier as u8 != 0
+    }
+
+    pub fn set(&mut self, modifier: Modifier, on: bool) {
+        if on {
+            self.0 |= 1 << modifier as u8;
+        } else {
+            self.0 &= !(1 << modifier as u8);
+        }
+    }
+
+    /// The modifiers turned on.
+    pub fn iter(self) -> impl Iterator<Item = Modifier> {
+        Modifier::ALL.into_iter().filter(move |&modifier| self.is_on(modifier))
+    }
+}
+
+/// Options chosen before a run starts and fixed for its whole duration.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub difficulty: Difficulty,
+    pub assist: Assist,
+    pub modifiers: Modifiers,
+    /// The day of the daily challenge this run is, in days since 1970, if
+    /// it is one.
+    pub daily: Option<u64>,
+    /// Lives at the start of a run; `None` takes the difficulty's.
+    pub lives: Option<u8>,
+    /// Ships in the run, each with its own lives and score: 1, or 2 for co-op.
+    pub players: u8,
+    /// Whether a second player commands the aliens against the ship.
+    pub versus: bool,
+    /// Whether the run plays the campaign through once, ending after its
+    /// last level, rather than going on for as long as the ships last.
+    pub campaign: bool,
+    /// A level to play every wave with, boss waves and all, in place of the
+    /// campaign's: a playtest from the level editor.
+    pub level: Option<&'static Level>,
+    /// A mod's levels to play in place of the campaign's.
+    pub pack: Option<&'static LevelPack>,
+    /// Points between extra lives; 0 turns them off.
+    pub extra_life_every: u32,
+    /// Seed for the game's randomness; `None` picks a fresh one per run.
+    pub seed: Option<u64>,
+    pub tick: Duration,
+    /// Playfield size in cells, not counting the HUD or border.
+    pub width: u16,
+    pub height: u16,
+    /// Hitboxes of the ship and the aliens, matching the sprites drawn.
+    pub player_size: SpriteSize,
+    pub alien_size: SpriteSize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            difficulty: Difficulty::Normal,
+            assist: Assist::default(),
+            modifiers: Modifiers::default(),
+            daily: None,
+            lives: None,
+            players: 1,
+            versus: false,
+            campaign: false,
+            level: None,
+            pack: None,
+            extra_life_every: DEFAULT_EXTRA_LIFE_EVERY,
+            seed: None,
+            tick: DEFAULT_TICK,
+            width: DEFAULT_FIELD_WIDTH,
+            height: DEFAULT_FIELD_HEIGHT,
+            player_size: PLAYER_SIZE,
+            alien_size: ALIEN_SIZE,
+        }
+    }
+}
+
+impl Settings {
+    /// The levels the waves go through: the level pack's, or the campaign's.
+    pub fn levels(&self) -> &'static [Level] {
+        self.pack.map_or(level::campaign(), |pack| &pack.levels)
+    }
+
+    pub fn starting_lives(&self) -> u8 {
+        let lives = self.lives.unwrap_or_else(|| self.difficulty.lives());
+        if self.assist.is_on(AssistOption::ExtraLives) {
+            return lives.saturating_add(ASSIST_EXTRA_LIVES);
+        }
+        lives
+    }
+
+    /// Whether the run is played by one player alone, as the leaderboards
+    /// want their runs.
+    pub fn is_solo(&self) -> bool {
+        self.players <= 1 && !self.versus
+    }
+
+    /// How fast alien shots fall, in cells per second.
+    pub fn alien_shot_speed(&self) -> f32 {
+        let speed = self.difficulty.alien_shot_speed();
+        if self.assist.is_on(AssistOption::SlowShots) {
+            return speed * SLOW_SHOTS_FACTOR;
+        }
+        speed
+    }
+}
+
+/// The size of a sprite in cells, which is also the box it can be hit in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpriteSize {
+    pub width: u16,
+    pub height: u16,
+}
+
+impl SpriteSize {
+    /// Whether a sprite of this size fits inside one of `max`.
+    pub fn fits_in(self, max: SpriteSize) -> bool {
+        self.width <= max.width && self.height <= max.height
+    }
+}
+
+/// Turns down a playfield of `width` x `height` cells that the formation
+/// has no room to move in, or too big to play on.
+pub fn check_field_size(width: u16, height: u16) -> Result<(), String> {
+    if width < MIN_FIELD_WIDTH || height < MIN_FIELD_HEIGHT {
+        return Err(format!("size must be at least {}x{}", MIN_FIELD_WIDTH, MIN_FIELD_HEIGHT));
+    }
+    if width > MAX_FIELD_WIDTH || height > MAX_FIELD_HEIGHT {
+        return Err(format!("size must be at most {}x{}", MAX_FIELD_WIDTH, MAX_FIELD_HEIGHT));
+    }
+    Ok(())
+}
+
+// --- Data Structures ---
+/// A ship's own component. Its lives are its [`Health`], which extra lives
+/// bring up to `MAX_LIVES`.
+#[derive(Clone, Copy, Debug)]
+pub struct Player {
+    /// This ship's share of the run's score.
+    pub score: u32,
+    /// The score at which the next extra life is awarded.
+    pub next_extra_life: u32,
+    /// Game time until which the HUD shows "1UP!".
+    pub one_up_until: Duration,
+    /// Game time until which the freshly respawned ship cannot be hit.
+    pub invincible_until: Duration,
+}
+
+impl Player {
+    fn new(settings: &Settings) -> Self {
+        Player {
+            score: 0,
+            next_extra_life: settings.extra_life_every,
+            one_up_until: Duration::ZERO,
+            invincible_until: Duration::ZERO,
+        }
+    }
+}
+
+impl ecs::Row<Player> {
+    /// Lives the ship has left.
+    pub fn lives(&self) -> u8 {
+        self.health.hp as u8
+    }
+
+    /// Whether the ship has lost its last life and left the run.
+    pub fn is_out(&self) -> bool {
+        self.health.is_spent()
+    }
+}
+
+/// Brings in player `index`'s ship, standing at its starting column.
+fn spawn_ship(state: &mut GameState, index: usize) {
+    let (x, y) = (start_x(&state.settings, index), state.max_y());
+    let lives = Health { hp: u16::from(state.settings.starting_lives()), max: u16::from(MAX_LIVES) };
+    let ship = Player::new(&state.settings);
+    state.world.spawn_ship(x, y, state.settings.player_size.into(), lives, index, ship);
+}
+
+/// The column player `index` starts and respawns at: the middle of the
+/// field alone, or the middle of their own half in co-op.
+fn start_x(settings: &Settings, index: usize) -> u16 {
+    let players = settings.players.max(1) as u16;
+    (settings.width - 2) * (2 * index as u16 + 1) / (2 * players)
+}
+
+/// What sort of alien fills a slot of the formation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlienKind {
+    Grunt,
+    /// Takes a hit more to bring down, and is worth more for it.
+    Armored,
+}
+
+impl AlienKind {
+    pub fn points(self) -> u32 {
+        match self {
+            AlienKind::Grunt => 10,
+            AlienKind::Armored => 20,
+        }
+    }
+
+    /// Shots it takes to bring down.
+    pub fn toughness(self) -> u8 {
+        match self {
+            AlienKind::Grunt => 1,
+            AlienKind::Armored => 2,
+        }
+    }
+}
+
+/// An alien's own component. The shots it takes come off its [`Health`],
+/// of which its kind gives it as much as it is tough.
+#[derive(Clone, Copy, Debug)]
+pub struct Alien {
+    /// Set while the alien is away from the formation on a dive.
+    pub dive: Option<Dive>,
+    pub kind: AlienKind,
+}
+
+impl Alien {
+    pub fn new(kind: AlienKind) -> Self {
+        Alien { dive: None, kind }
+    }
+
+    /// What shooting it down scores, before any multiplier.
+    pub fn points(&self) -> u32 {
+        match self.dive {
+            Some(_) => self.kind.points() * DIVER_MULTIPLIER,
+            None => self.kind.points(),
+        }
+    }
+}
+
+impl ecs::Row<Alien> {
+    /// Whether it would take another shot after the next.
+    pub fn is_armored(&self) -> bool {
+        self.health.hp > 1
+    }
+
+    /// Where the alien belongs in the formation, even while it is diving.
+    pub fn home(&self) -> (u16, u16) {
+        match &self.dive {
+            Some(dive) => (dive.slot_x, dive.slot_y),
+            None => self.cell(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DivePhase {
+    /// Curving down towards the player, firing on the way.
+    Swoop,
+    /// Back at the top after wrapping around, flying to its slot.
+    Return,
+}
+
+/// A single alien's flight away from the formation.
+#[derive(Clone, Copy, Debug)]
+pub struct Dive {
+    /// The formation slot the alien returns to; it moves with the formation.
+    pub slot_x: u16,
+    pub slot_y: u16,
+    pub phase: DivePhase,
+    /// Which way the diver swings out before turning on the player.
+    pub swing: AlienDirection,
+    /// Ticks spent in the current phase.
+    pub ticks: u16,
+}
+
+/// A position in cells that can fall between them, for things moving at
+/// speeds of their own. Collisions and drawing go by the nearest cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// The nearest whole number to `value`, halves rounded up, clamped to the
+/// `u16` range. It is what `value.round() as u16` is, without the call to
+/// the maths library rounding takes on most targets.
+fn nearest(value: f32) -> u16 {
+    let whole = value as i32;
+    let nearest = if value - whole as f32 >= 0.5 { whole + 1 } else { whole };
+    nearest.clamp(0, i32::from(u16::MAX)) as u16
+}
+
+/// Cells moved per second along each axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Velocity {
+    pub dx: f32,
+    pub dy: f32,
+}
+
+impl Position {
+    pub fn at(x: u16, y: u16) -> Self {
+        Position { x: x as f32, y: y as f32 }
+    }
+
+    /// The nearest cell, clamped to the field's top-left corner.
+    pub fn cell(self) -> (u16, u16) {
+        (nearest(self.x), nearest(self.y))
+    }
+
+    /// Whether the nearest cell lies left of or above the field.
+    fn is_off_top_left(self) -> bool {
+        self.x.round() < 0.0 || self.y.round() < 0.0
+    }
+
+    /// Moves along `velocity` for `elapsed` game time.
+    pub fn advance(&mut self, velocity: Velocity, elapsed: Duration) {
+        self.x += velocity.dx * elapsed.as_secs_f32();
+        self.y += velocity.dy * elapsed.as_secs_f32();
+    }
+}
+
+/// The cells an entity can be hit in, from the one it is in on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hitbox {
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Hitbox {
+    /// A single cell, as a shot or a pickup takes up.
+    pub const CELL: Hitbox = Hitbox { width: 1, height: 1 };
+}
+
+impl From<SpriteSize> for Hitbox {
+    fn from(size: SpriteSize) -> Self {
+        Hitbox { width: size.width, height: size.height }
+    }
+}
+
+/// Hits an entity can take: a ship's lives, an alien's toughness or the
+/// boss's hit points. A shot has the one hit that spends it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Health {
+    pub hp: u16,
+    /// The most it can be brought back up to.
+    pub max: u16,
+}
+
+impl Health {
+    pub fn full(hp: u16) -> Self {
+        Health { hp, max: hp }
+    }
+
+    pub fn damage(&mut self, hits: u16) {
+        self.hp = self.hp.saturating_sub(hits);
+    }
+
+    /// Gives back one hit, if it is short of its most.
+    pub fn heal(&mut self) {
+        if self.hp < self.max {
+            self.hp += 1;
+        }
+    }
+
+    /// Hits taken so far.
+    pub fn taken(self) -> u16 {
+        self.max.saturating_sub(self.hp)
+    }
+
+    pub fn is_spent(self) -> bool {
+        self.hp == 0
+    }
+}
+
+/// The side an entity fights on; only entities of opposing sides can hurt
+/// each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Faction {
+    /// A ship, or a shot scoring for the player of this index.
+    Player(usize),
+    Aliens,
+    /// Pickups, which no one shoots down.
+    Neutral,
+}
+
+impl Faction {
+    pub fn opposes(self, other: Faction) -> bool {
+        matches!((self, other), (Faction::Player(_), Faction::Aliens) | (Faction::Aliens, Faction::Player(_)))
+    }
+
+    /// The player it scores for, if it is on theirs.
+    pub fn player(self) -> Option<usize> {
+        match self {
+            Faction::Player(index) => Some(index),
+            Faction::Aliens | Faction::Neutral => None,
+        }
+    }
+}
+
+/// How fast a player shot flies: up, and sideways for `dx` of -1 or 1.
+fn shot_velocity(dx: i16) -> Velocity {
+    Velocity { dx: dx as f32 * SHOT_SPEED, dy: -SHOT_SPEED }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PickupKind {
+    /// Adds one smart bomb to the player's stock.
+    Bomb,
+}
+
+impl PickupKind {
+    pub fn glyph(self) -> &'static str {
+        match self {
+            PickupKind::Bomb => "B",
+        }
+    }
+}
+
+/// Drops something for the player to catch from (x, y).
+fn spawn_pickup(world: &mut ecs::World, x: u16, y: u16, kind: PickupKind) {
+    world.spawn_pickup(Position::at(x, y), Velocity { dx: 0.0, dy: PICKUP_SPEED }, kind);
+}
+
+/// A laser beam filling one column of the playfield.
+#[derive(Clone)]
+pub struct Laser {
+    pub x: u16,
+    /// The player who fired it.
+    pub owner: usize,
+    /// Ticks since the beam was fired; it hits on the first update and is
+    /// gone after the second.
+    pub age: u16,
+}
+
+/// How the player's ship fires.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weapon {
+    /// One shot straight up.
+    Single,
+    /// Three shots in a fan: one straight up and two diagonal.
+    Spread,
+}
+
+impl Weapon {
+    pub fn next(self) -> Self {
+        match self {
+            Weapon::Single => Weapon::Spread,
+            Weapon::Spread => Weapon::Single,
+        }
+    }
+}
+
+/// How fast an alien shot falls, straight down, `speed` cells per second.
+fn alien_shot_velocity(speed: f32) -> Velocity {
+    Velocity { dx: 0.0, dy: speed }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlienDirection {
+    Left,
+    Right,
+}
+
+/// The aliens' side of a versus run, played by the second player, who
+/// picks which alien of the front rank fires and when.
+#[derive(Clone)]
+pub struct Commander {
+    /// Which alien of the front rank is aimed with, counted from the left.
+    pub aim: usize,
+    /// Set by a fire command until the aliens are ready to fire again.
+    pub fire: bool,
+    /// Game time from which the formation can be turned around again.
+    pub next_turn: Duration,
+}
+
+/// The mystery ship's own component: it crosses the top row once and is
+/// worth a random bonus.
+#[derive(Clone, Copy, Debug)]
+pub struct Ufo {
+    pub direction: AlienDirection,
+    pub points: u32,
+}
+
+/// The attacks a boss cycles through, one per firing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BossAttack {
+    /// One shot from each of its cannons.
+    Spread,
+    /// A single shot from the cannon closest to the player.
+    Aimed,
+    /// Several shots from random points along its hull.
+    Barrage,
+}
+
+impl BossAttack {
+    fn next(self) -> Self {
+        match self {
+            BossAttack::Spread => BossAttack::Aimed,
+            BossAttack::Aimed => BossAttack::Barrage,
+            BossAttack::Barrage => BossAttack::Spread,
+        }
+    }
+}
+
+/// The boss's own component: a large alien whose [`Health`] takes many
+/// hits, appearing every `BOSS_WAVE_INTERVAL` waves in place of the
+/// formation.
+#[derive(Clone, Copy, Debug)]
+pub struct Boss {
+    pub direction: AlienDirection,
+    /// Progress towards the next step, in hundredths of a cell.
+    pub step_progress: u32,
+    pub next_attack: BossAttack,
+    /// The player who last hurt it, by shot, bomb or laser, who takes the
+    /// credit for finishing it.
+    pub last_hit_by: usize,
+}
+
+/// A short-lived visual left behind by something being destroyed.
+#[derive(Clone)]
+pub struct Effect {
+    pub x: u16,
+    pub y: u16,
+    /// Ticks since the effect appeared.
+    pub age: u16,
+}
+
+impl Effect {
+    fn explosion(x: u16, y: u16) -> Self {
+        Effect { x, y, age: 0 }
+    }
+
+    /// The animation frame to draw this tick, as an index into
+    /// [`EXPLOSION_FRAMES`], or `None` once the animation is over.
+    pub fn frame(&self) -> Option<usize> {
+        Some((self.age / EXPLOSION_FRAME_TICKS) as usize).filter(|&frame| frame < EXPLOSION_FRAMES.len())
+    }
+}
+
+/// A sound the game makes, for frontends that play them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioEvent {
+    /// A player fired.
+    Shot,
+    /// An alien, the boss or the mystery ship was hit.
+    AlienExplosion,
+    /// A ship was hit.
+    PlayerHit,
+    /// The mystery ship is flying over, once every tick it is.
+    Ufo,
+}
+
+/// The points an alien was worth, floating up from where it was destroyed.
+#[derive(Clone)]
+pub struct Popup {
+    pub x: u16,
+    pub y: u16,
+    pub points: u32,
+    /// Ticks since the popup appeared.
+    pub age: u16,
+}
+
+impl Popup {
+    fn new(x: u16, y: u16, points: u32) -> Self {
+        Popup { x, y, points, age: 0 }
+    }
+
+    pub fn text(&self) -> String {
+        format!("+{}", self.points)
+    }
+}
+
+/// Counters for the wave in progress.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WaveStats {
+    pub shots_fired: u32,
+    /// Shots that flew off the field without hitting anything.
+    pub shots_missed: u32,
+    /// Game time at which the wave appeared.
+    pub started: Duration,
+}
+
+/// Counters for the whole run, which go into the lifetime stats.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RunStats {
+    pub aliens_destroyed: u32,
+    pub shots_fired: u32,
+    /// Shots that flew off the field without hitting anything.
+    pub shots_missed: u32,
+}
+
+/// The stats card shown between a cleared wave and the next one.
+#[derive(Clone, Copy, Debug)]
+pub struct WaveSummary {
+    pub wave: u32,
+    pub shots_fired: u32,
+    /// Percentage of shots that hit something.
+    pub accuracy: u32,
+    pub time: Duration,
+    pub bonus: u32,
+}
+
+#[derive(Clone)]
+pub struct GameState {
+    pub settings: Settings,
+    /// The seed `rng` started from, kept so a run can be reproduced.
+    pub seed: u64,
+    /// All of the game's randomness comes from here, so equal seeds and
+    /// equal inputs always play out the same way.
+    pub rng: ChaCha8Rng,
+    /// The weapon, laser and bombs are shared by all ships.
+    pub weapon: Weapon,
+    /// Ticks the laser has charged for, while the fire key is held.
+    pub laser_charge: Option<u16>,
+    pub laser: Option<Laser>,
+    /// Smart bombs left to detonate.
+    pub bombs: u8,
+    /// Everything on the field: the ships, the formation, the boss and the
+    /// mystery ship, the shots in flight and the pickups falling.
+    pub world: ecs::World,
+    /// How many aliens the wave started with.
+    pub formation_size: u32,
+    /// The blocks of the bunkers standing, by cell.
+    pub bunkers: Vec<(u16, u16)>,
+    /// Number of updates so far; replays key their inputs to it.
+    pub ticks: u64,
+    /// Game time, advanced by one tick on every update; frozen while paused.
+    pub clock: Duration,
+    pub last_alien_shot: Duration,
+    /// Game time at which the last alien broke off to dive.
+    pub last_dive: Duration,
+    pub effects: Vec<Effect>,
+    pub particles: Particles,
+    /// Sounds made since a frontend last took them; a queue nobody takes is
+    /// emptied rather than let grow.
+    pub audio: Vec<AudioEvent>,
+    pub popups: Vec<Popup>,
+    /// Game time at which the next mystery ship appears.
+    pub next_ufo: Duration,
+    pub alien_direction: AlienDirection,
+    /// Whoever commands the aliens in versus; without one they fire at random.
+    pub commander: Option<Commander>,
+    /// Progress towards the formation's next step, in hundredths of a cell.
+    pub alien_step_progress: u32,
+    /// The wave being played, or about to be, starting at 1.
+    pub wave: u32,
+    /// Game time at which the countdown before the current wave ends; `None`
+    /// once the wave is under way.
+    pub intermission_until: Option<Duration>,
+    pub wave_stats: WaveStats,
+    pub run_stats: RunStats,
+    /// How the last wave went, shown during the countdown after it.
+    pub summary: Option<WaveSummary>,
+    /// The run's score: what all of the players scored together.
+    pub score: u32,
+    /// Aliens shot in a row without a shot flying off the field.
+    pub combo: u32,
+    /// Game time until which the screen shakes.
+    pub shake_until: Duration,
+    pub game_over: bool,
+    /// Whether the game ended with the campaign cleared, not the ships lost.
+    pub victory: bool,
+    /// The broad phase of the hit tests, kept from tick to tick so its
+    /// buckets are reused.
+    pub columns: Columns,
+    /// What the wave's aliens, or its boss, run every tick, if the level
+    /// gave them a script.
+    #[cfg(feature = "scripting")]
+    pub script: Option<Script>,
+}
+
+impl GameState {
+    /// Creates a fresh game with the first wave of aliens already spawned.
+    pub fn new(settings: &Settings) -> Self {
+        let seed = settings.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        let mut state = GameState {
+            settings: settings.clone(),
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            weapon: if settings.modifiers.is_on(Modifier::SpreadShot) { Weapon::Spread } else { Weapon::Single },
+            laser_charge: None,
+            laser: None,
+            bombs: if settings.modifiers.is_on(Modifier::NoBombs) { 0 } else { INITIAL_BOMBS },
+            world: ecs::World::default(),
+            ticks: 0,
+            clock: Duration::ZERO,
+            last_alien_shot: Duration::ZERO,
+            last_dive: Duration::ZERO,
+            effects: Vec::new(),
+            // A stream of its own, so particles never shift the game's randomness
+            particles: Particles::new(seed ^ PARTICLE_SEED),
+            audio: Vec::new(),
+            popups: Vec::new(),
+            next_ufo: Duration::ZERO,
+            formation_size: 0,
+            bunkers: Vec::new(),
+            alien_direction: AlienDirection::Right,
+            commander: settings.versus.then_some(Commander { aim: 0, fire: false, next_turn: Duration::ZERO }),
+            alien_step_progress: 0,
+            wave: 0, // start_wave moves on to wave 1
+            intermission_until: None,
+            wave_stats: WaveStats::default(),
+            run_stats: RunStats::default(),
+            summary: None,
+            score: 0,
+            combo: 0,
+            shake_until: Duration::ZERO,
+            game_over: false,
+            victory: false,
+            columns: Columns::default(),
+            #[cfg(feature = "scripting")]
+            script: None,
+        };
+        for index in 0..settings.players.clamp(1, MAX_PLAYERS as u8) as usize {
+            spawn_ship(&mut state, index);
+        }
+        start_wave(&mut state);
+        schedule_ufo(&mut state);
+        state
+    }
+
+    /// The rightmost column a sprite can start at and still be in bounds of
+    /// the alien sweep.
+    pub fn max_x(&self) -> u16 {
+        self.settings.width - 2
+    }
+
+    /// The row the player's ship stands on.
+    pub fn max_y(&self) -> u16 {
+        self.settings.height - 1
+    }
+
+    /// The bottom row of the players' ships.
+    pub fn player_bottom(&self) -> u16 {
+        self.max_y() + self.settings.player_size.height - 1
+    }
+
+    /// Whether this is a co-op run.
+    pub fn is_co_op(&self) -> bool {
+        self.world.ships.len() > 1
+    }
+
+    /// Lives left in the run, all ships together.
+    pub fn lives(&self) -> u8 {
+        self.world.ships.iter().map(|ship| ship.lives()).sum()
+    }
+
+    /// What alien kills by shots are currently worth, times their points.
+    pub fn multiplier(&self) -> u32 {
+        (1 + self.combo / COMBO_KILLS_PER_STEP).min(MAX_MULTIPLIER)
+    }
+
+    /// Whole seconds left in the countdown before the wave starts, rounded
+    /// up, or `None` outside of it.
+    pub fn countdown(&self) -> Option<u64> {
+        let remaining = self.intermission_until?.saturating_sub(self.clock);
+        Some(remaining.as_millis().div_ceil(1000) as u64)
+    }
+
+    /// Whether the HUD is celebrating a life freshly earned by any ship.
+    pub fn is_one_up(&self) -> bool {
+        self.world.ships.iter().any(|ship| self.clock < ship.one_up_until)
+    }
+
+    /// Whether player `index`'s ship is still shrugging off hits after a
+    /// respawn.
+    pub fn is_invincible(&self, index: usize) -> bool {
+        self.clock < self.world.ship(index).invincible_until
+    }
+
+    /// The ships still in the run, by index, that can be hit right now.
+    fn targets(&self) -> Vec<usize> {
+        let ships = self.world.ships.iter().enumerate();
+        ships.filter(|(_, ship)| !ship.is_out() && self.clock >= ship.invincible_until).map(|(i, _)| i).collect()
+    }
+
+    /// The aliens of the formation with no other below them, by index; only
+    /// they fire, while divers do their own shooting.
+    fn front_rank(&self) -> Vec<usize> {
+        let formation = self.world.aliens.iter().enumerate().filter(|(_, alien)| alien.dive.is_none());
+        let formation: Vec<(usize, (u16, u16))> = formation.map(|(i, alien)| (i, alien.cell())).collect();
+        let mut cells: Vec<(u16, u16)> = formation.iter().map(|&(_, cell)| cell).collect();
+        cells.sort_unstable();
+        let width = self.settings.alien_size.width;
+        formation
+            .iter()
+            .filter(|&&(_, (x, y))| {
+                // Only the aliens whose columns take in this one's can be below it
+                let from = cells.partition_point(|&(left, _)| left + width <= x);
+                let to = cells.partition_point(|&(left, _)| left <= x);
+                !cells[from..to].iter().any(|&(_, below)| y < below)
+            })
+            .map(|&(i, _)| i)
+            .collect()
+    }
+
+    /// The alien the commander of a versus run has their aim on, by index.
+    pub fn aimed_alien(&self) -> Option<usize> {
+        let commander = self.commander.as_ref()?;
+        let mut rank = self.front_rank();
+        rank.sort_by_key(|&i| self.world.aliens.get(i).map_or(0, |alien| alien.x()));
+        rank.get(commander.aim.min(rank.len().saturating_sub(1))).copied()
+    }
+
+    /// The columns of the ships still in the run.
+    fn ships(&self) -> Vec<u16> {
+        self.world.ships.iter().filter(|ship| !ship.is_out()).map(|ship| ship.x()).collect()
+    }
+
+    /// Whether the screen is still shaking from a big hit.
+    pub fn is_shaking(&self) -> bool {
+        self.clock < self.shake_until
+    }
+
+    /// Columns in the alien formation, enough to span most of the field's width.
+    pub fn alien_cols(&self) -> usize {
+        let span = self.settings.width as usize * 7 / 10;
+        (span / HORIZONTAL_SPACING as usize).max(ALIEN_COLS)
+    }
+
+    /// The level this wave plays, unless it is a boss wave.
+    pub fn level(&self) -> Option<&'static Level> {
+        self.settings.level.or_else(|| level::for_wave(self.settings.levels(), self.wave))
+    }
+
+    /// The base speed of everything alien this wave, in hundredths of a cell
+    /// per tick.
+    pub fn wave_speed(&self) -> u32 {
+        let speed = (STEP + ALIEN_SPEED_PER_WAVE * self.wave.saturating_sub(1)).min(MAX_ALIEN_SPEED);
+        if self.settings.modifiers.is_on(Modifier::FastAliens) {
+            return speed * 3 / 2;
+        }
+        speed
+    }
+
+    /// How far the formation moves per tick, in hundredths of a cell.
+    ///
+    /// Like the arcade original, the time between steps shrinks with the
+    /// number of aliens left, so a wave speeds up as it is shot down.
+    pub fn alien_speed(&self) -> u32 {
+        let wave_speed = self.wave_speed() * self.level().map_or(100, |level| level.speed) / 100;
+        let full = self.formation_size.max(1);
+        let remaining = (self.world.aliens.len() as u32).clamp(1, full);
+        (wave_speed * full / remaining).min(FRANTIC_ALIEN_SPEED.max(wave_speed))
+    }
+
+    /// Minimum time between two alien shots this wave.
+    pub fn alien_fire_interval(&self) -> Duration {
+        let speedup = FIRE_INTERVAL_PER_WAVE * self.wave.saturating_sub(1);
+        let interval = self.settings.difficulty.alien_fire_interval().saturating_sub(speedup).max(MIN_FIRE_INTERVAL);
+        let interval = interval * self.level().map_or(100, |level| level.fire_interval) / 100;
+        if self.settings.modifiers.is_on(Modifier::HeavyFire) {
+            return interval / 2;
+        }
+        interval
+    }
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new(&Settings::default())
+    }
+}
+
+// --- Sprites ---
+pub const ALIEN_SPRITE: [&str; 2] = ["<O>", "/-\\" ];
+pub const PLAYER_SPRITE: [&str; 2] = ["/A\\", "===" ];
+pub const UFO_SPRITE: &str = "<=UFO=>";
+pub const UFO_WIDTH: u16 = UFO_SPRITE.len() as u16;
+pub const BOSS_SPRITE: [&str; 3] = ["/=========\\", "|(O) | (O)|", "\\=V==V==V=/"];
+pub const BOSS_WIDTH: u16 = 11;
+pub const BOSS_HEIGHT: u16 = BOSS_SPRITE.len() as u16;
+/// Columns of the boss sprite its cannons (the `V`s) fire from.
+const BOSS_CANNONS: [u16; 3] = [2, 5, 8];
+/// An explosion's animation, each frame shown for `EXPLOSION_FRAME_TICKS`.
+pub const EXPLOSION_FRAMES: [&str; 2] = ["\\*/", ".:."];
+const EXPLOSION_FRAME_TICKS: u16 = 2;
+/// Ticks a score popup stays up, rising a row on each.
+const POPUP_TICKS: u16 = 4;
+/// Mixed into the run's seed to seed the particles.
+const PARTICLE_SEED: u64 = 0x5041_5254_4943_4c45;
+
+// --- Helper Functions ---
+/// Moves on to the next wave, which appears once its countdown is over.
+pub fn start_wave(state: &mut GameState) {
+    if state.wave > 0 {
+        summarize_wave(state);
+    }
+    // A campaign is over once its last level is cleared
+    if state.settings.campaign && state.wave >= level::final_wave(state.settings.levels()) {
+        state.victory = true;
+        state.game_over = true;
+        return;
+    }
+    state.wave += 1;
+    state.intermission_until = Some(state.clock + INTERMISSION);
+
+    // Clear any remaining shots from the previous level
+    state.world.despawn_shots();
+    state.laser = None;
+}
+
+/// Sums up the wave just cleared and pays out its bonus.
+fn summarize_wave(state: &mut GameState) {
+    // Shots still in flight never got to hit or miss, so they don't count
+    let stats = state.wave_stats;
+    let shots_fired = stats.shots_fired.saturating_sub(state.world.shots.len() as u32);
+    let hits = shots_fired.saturating_sub(stats.shots_missed);
+    let accuracy = (hits * 100).checked_div(shots_fired).unwrap_or(0);
+    let time = state.clock.saturating_sub(stats.started);
+    let seconds_saved = PAR_TIME.saturating_sub(time).as_secs() as u32;
+    let bonus = accuracy * ACCURACY_BONUS + seconds_saved * SPEED_BONUS;
+    // Every ship that made it through the wave earns the bonus
+    for index in 0..state.world.ships.len() {
+        if !state.world.ship(index).is_out() {
+            award(state, index, bonus);
+        }
+    }
+    state.summary = Some(WaveSummary { wave: state.wave, shots_fired, accuracy, time, bonus });
+}
+
+pub fn spawn_new_wave(state: &mut GameState) {
+    state.alien_step_progress = 0;
+    state.wave_stats = WaveStats { started: state.clock, ..WaveStats::default() };
+    state.summary = None;
+
+    state.bunkers.clear();
+    let Some(level) = state.level() else {
+        spawn_boss(state);
+        return;
+    };
+    #[cfg(feature = "scripting")]
+    {
+        state.script = compile(level.script);
+    }
+
+    // The level's rows, as many as leave the player some room, and every
+    // wave a row lower than the last as long as they still do
+    let alien_height = state.settings.alien_size.height;
+    let room = state.max_y().saturating_sub(SPAWN_CLEARANCE + 2 + alien_height) / VERTICAL_SPACING + 1;
+    let (rows, cols) = (level.layout.len().min(room as usize), state.alien_cols());
+    let formation_bottom = (rows as u16 - 1) * VERTICAL_SPACING + 2 + alien_height;
+    let max_drop = state.max_y().saturating_sub(formation_bottom + SPAWN_CLEARANCE);
+    let drop = (state.wave as u16 - 1).min(max_drop);
+
+    // Repopulate aliens, into the room the last wave's took up
+    state.world.aliens.clear();
+    let hitbox = state.settings.alien_size.into();
+    for row in 0..rows {
+        for col in 0..cols {
+            if let Some(kind) = level.alien(row, col) {
+                let (x, y) = ((col as u16) * HORIZONTAL_SPACING + 2, (row as u16) * VERTICAL_SPACING + 2 + drop);
+                state.world.spawn_alien(x, y, hitbox, Alien::new(kind));
+            }
+        }
+    }
+    state.formation_size = state.world.aliens.len() as u32;
+    build_bunkers(state, level.bunkers);
+    log::debug!("wave {} spawned: {} aliens in {} rows", state.wave, state.world.aliens.len(), rows);
+}
+
+/// Puts up bunkers of `shape` across the field, evenly spaced, with a row
+/// left between them and the ships.
+fn build_bunkers(state: &mut GameState, shape: &[&str]) {
+    let width = shape.iter().map(|row| row.chars().count()).max().unwrap_or(0) as u16;
+    let height = shape.len() as u16;
+    if width == 0 || state.max_y() < height + SPAWN_CLEARANCE {
+        return;
+    }
+    // About a bunker's width of open field either side of each
+    let count = (state.settings.width / (width * 3)).max(1);
+    let gap = state.settings.width.saturating_sub(count * width) / (count + 1);
+    let top = state.max_y() - 1 - height;
+    for i in 0..count {
+        let left = gap + i * (width + gap);
+        for (dy, row) in shape.iter().enumerate() {
+            for (dx, _) in row.chars().enumerate().filter(|&(_, cell)| cell == '#') {
+                state.bunkers.push((left + dx as u16, top + dy as u16));
+            }
+        }
+    }
+}
+
+/// Brings in the boss, tougher with every fight.
+fn spawn_boss(state: &mut GameState) {
+    let fights_before = (state.wave / BOSS_WAVE_INTERVAL - 1) as u16;
+    let max_hp = BOSS_BASE_HP + BOSS_HP_PER_FIGHT * fights_before;
+    state.world.aliens.clear();
+    state.world.ufos.clear();
+    state.world.bosses.clear();
+    let (direction, next_attack) = (AlienDirection::Right, BossAttack::Spread);
+    let boss = Boss { direction, step_progress: 0, next_attack, last_hit_by: 0 };
+    state.world.spawn_boss((state.settings.width - BOSS_WIDTH) / 2, BOSS_ROW, Health::full(max_hp), boss);
+    log::debug!("wave {} spawned: the boss, with {} hp", state.wave, max_hp);
+    // The boss follows the level before it, whose script it runs
+    #[cfg(feature = "scripting")]
+    {
+        let level = level::for_wave(state.settings.levels(), state.wave - 1);
+        state.script = level.and_then(|level| compile(level.boss_script));
+    }
+}
+
+/// The script of `lines`, or `None` for no script; levels are only made of
+/// scripts that compile.
+#[cfg(feature = "scripting")]
+fn compile(lines: &[&str]) -> Option<Script> {
+    (!lines.is_empty()).then(|| Script::compile(lines).ok()).flatten()
+}
+
+/// Picks a random delay before the next mystery ship.
+fn schedule_ufo(state: &mut GameState) {
+    let delay = state.rng.gen_range(UFO_MIN_INTERVAL_MS..=UFO_MAX_INTERVAL_MS);
+    state.next_ufo = state.clock + Duration::from_millis(delay);
+}
+
+/// Takes a life after player `index`'s ship was hit, ending the game once
+/// every ship has lost its last.
+fn hit_player(state: &mut GameState, index: usize) {
+    // A ship already out, or just back, has no life to lose
+    if state.world.ship(index).is_out() || state.is_invincible(index) {
+        return;
+    }
+    let start = start_x(&state.settings, index);
+    let mut ship = state.world.ship_mut(index);
+    let (x, y) = ship.cell();
+    state.effects.push(Effect::explosion(x, y));
+    state.audio.push(AudioEvent::PlayerHit);
+    ship.health.damage(1);
+    ship.place(start, y); // Reset player position
+    ship.invincible_until = state.clock + INVINCIBILITY;
+    state.shake_until = state.clock + SCREEN_SHAKE;
+    log::debug!("player {} hit, {} lives left", index, ship.health.hp);
+    if state.world.ships.iter().all(|ship| ship.is_out()) {
+        state.game_over = true;
+    }
+}
+
+/// Scores `points` for player `index`, and so for the run.
+fn award(state: &mut GameState, index: usize, points: u32) {
+    state.world.ship_mut(index).score += points;
+    state.score += points;
+}
+
+/// The column of whichever of `ships` is closest to column `x`.
+fn nearest_ship(ships: &[u16], x: u16) -> Option<u16> {
+    ships.iter().copied().min_by_key(|ship| ship.abs_diff(x))
+}
+
+// --- Player Actions ---
+
+/// A player input, in game terms. Everything the players do to a run goes
+/// through [`apply_action`], which is what makes runs replayable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Fire,
+    SwitchWeapon,
+    /// The fire key is being held down.
+    ChargeLaser,
+    /// The fire key was let go of; fires the laser if it is fully charged.
+    ReleaseLaser,
+    Bomb,
+    /// The alien commander of a versus run moves their aim along the front
+    /// rank.
+    AimLeft,
+    AimRight,
+    /// The aimed alien fires, as soon as the aliens are ready to.
+    AlienFire,
+    /// The formation turns around.
+    TurnFormation,
+}
+
+/// Applies `action`, taken by player `player`; a ship that is out of the
+/// run has no say any more.
+pub fn apply_action(state: &mut GameState, player: usize, action: Action) {
+    // Everything stands still during the countdown before a wave
+    if state.intermission_until.is_some() {
+        return;
+    }
+    match action {
+        Action::AimLeft | Action::AimRight | Action::AlienFire | Action::TurnFormation => command_aliens(state, action),
+        _ if state.world.ships.get(player).is_none_or(|ship| ship.is_out()) => {}
+        Action::MoveLeft => move_player_left(state, player),
+        Action::MoveRight => move_player_right(state, player),
+        Action::Fire => fire_shot(state, player),
+        Action::SwitchWeapon => switch_weapon(state),
+        Action::ChargeLaser => charge_laser(state),
+        Action::ReleaseLaser => release_laser(state, player),
+        Action::Bomb => detonate_bomb(state, player),
+    }
+}
+
+/// Carries out an order to the aliens; they take none without a commander.
+fn command_aliens(state: &mut GameState, action: Action) {
+    let (rank, clock) = (state.front_rank().len(), state.clock);
+    let Some(commander) = state.commander.as_mut().filter(|_| !state.game_over) else {
+        return;
+    };
+    match action {
+        Action::AimLeft => commander.aim = commander.aim.min(rank.saturating_sub(1)).saturating_sub(1),
+        Action::AimRight => commander.aim = (commander.aim + 1).min(rank.saturating_sub(1)),
+        Action::AlienFire => commander.fire = true,
+        Action::TurnFormation if clock >= commander.next_turn => {
+            commander.next_turn = clock + TURN_COOLDOWN;
+            state.alien_direction = match state.alien_direction {
+                AlienDirection::Left => AlienDirection::Right,
+                AlienDirection::Right => AlienDirection::Left,
+            };
+        }
+        _ => {}
+    }
+}
+
+pub fn move_player_left(state: &mut GameState, index: usize) {
+    let bottom = state.player_bottom();
+    let mut ship = state.world.ship_mut(index);
+    let (x, y) = ship.cell();
+    if x > 0 && !state.game_over {
+        ship.place(x - 1, y);
+        let right = x - 1 + state.settings.player_size.width;
+        state.particles.exhaust(right, bottom, -1);
+    }
+}
+
+pub fn move_player_right(state: &mut GameState, index: usize) {
+    let (bottom, max_x) = (state.player_bottom(), state.max_x());
+    let mut ship = state.world.ship_mut(index);
+    let (x, y) = ship.cell();
+    // Adjust boundary for the sprite's width
+    if x + state.settings.player_size.width <= max_x && !state.game_over {
+        ship.place(x + 1, y);
+        state.particles.exhaust(x, bottom, 1);
+    }
+}
+
+pub fn fire_shot(state: &mut GameState, index: usize) {
+    if state.game_over {
+        return;
+    }
+    // Fire from the center of the vessel; a spread fires as much of its fan
+    // as still fits under the shot limit
+    let ship = state.world.ship(index);
+    let directions: &[i16] = match state.weapon {
+        Weapon::Single => &[0],
+        Weapon::Spread => &[0, -1, 1],
+    };
+    let fired = state.world.shots.len();
+    let mut shots = fired;
+    for &dx in directions {
+        if shots < MAX_SHOTS {
+            state.world.fire(&ship, 0, shot_velocity(dx));
+            shots += 1;
+            state.wave_stats.shots_fired += 1;
+            state.run_stats.shots_fired += 1;
+        }
+    }
+    if shots > fired {
+        state.audio.push(AudioEvent::Shot);
+    }
+}
+
+pub fn switch_weapon(state: &mut GameState) {
+    if !state.game_over {
+        state.weapon = state.weapon.next();
+    }
+}
+
+pub fn charge_laser(state: &mut GameState) {
+    if state.laser_charge.is_none() && !state.game_over {
+        state.laser_charge = Some(0);
+    }
+}
+
+pub fn release_laser(state: &mut GameState, index: usize) {
+    if state.laser_charge.take() == Some(LASER_FULL_CHARGE) && !state.game_over {
+        let x = state.world.ship(index).x() + state.settings.player_size.width / 2;
+        state.laser = Some(Laser { x, owner: index, age: 0 });
+    }
+}
+
+/// Destroys every alien and alien shot, and hurts the boss, for one bomb
+/// detonated by player `index`.
+pub fn detonate_bomb(state: &mut GameState, index: usize) {
+    if state.bombs == 0 || state.game_over {
+        return;
+    }
+    state.bombs -= 1;
+    state.shake_until = state.clock + SCREEN_SHAKE;
+    state.audio.push(AudioEvent::AlienExplosion);
+    log::debug!("player {} bombed {} aliens", index, state.world.aliens.len());
+    state.world.alien_shots.clear();
+    let mut score = 0;
+    for alien in state.world.aliens.iter() {
+        let (x, y) = alien.cell();
+        state.effects.push(Effect::explosion(x, y));
+        let points = alien.points();
+        state.popups.push(Popup::new(x, y, points));
+        score += points;
+        state.run_stats.aliens_destroyed += 1;
+    }
+    state.world.aliens.clear();
+    award(state, index, score);
+    if let Some(mut boss) = state.world.boss_mut() {
+        boss.health.damage(BOMB_BOSS_DAMAGE);
+        boss.last_hit_by = index;
+        let (x, y) = boss.cell();
+        state.effects.push(Effect::explosion(x + BOSS_WIDTH / 2 - 1, y + 1));
+    }
+}
+
+/// Burns through everything in the laser's column, for player `owner`.
+fn fire_laser(state: &mut GameState, x: u16, owner: usize) {
+    let beam = Aabb::column(x);
+    let in_column = |entity: &dyn Entity| intersects(beam, entity.bbox());
+
+    let mut score = 0;
+    let exploded = state.effects.len();
+    let mut burnt = Vec::new();
+    for alien in state.world.aliens.iter().filter(|alien| in_column(alien)) {
+        burnt.push(alien.id);
+        let (x, y) = alien.cell();
+        state.effects.push(Effect::explosion(x, y));
+        let points = alien.points();
+        state.popups.push(Popup::new(x, y, points));
+        score += points;
+        state.run_stats.aliens_destroyed += 1;
+    }
+    state.world.despawn_all(&burnt);
+    let burnt = state.world.alien_shots.iter().filter(|shot| in_column(shot));
+    let burnt: Vec<ecs::Id> = burnt.map(|shot| shot.id).collect();
+    state.world.despawn_all(&burnt);
+    if let Some(ufo) = state.world.ufo().filter(|ufo| in_column(ufo)) {
+        score += ufo.points;
+        state.effects.push(Effect::explosion(ufo.x() + UFO_WIDTH / 2 - 1, UFO_ROW));
+        state.world.ufos.clear();
+        schedule_ufo(state);
+    }
+    let boss_hit = state.world.boss().is_some_and(|boss| in_column(&boss));
+    if let Some(mut boss) = state.world.boss_mut().filter(|_| boss_hit) {
+        boss.health.damage(LASER_BOSS_DAMAGE);
+        boss.last_hit_by = owner;
+        state.effects.push(Effect::explosion(x.saturating_sub(1), boss.y() + BOSS_HEIGHT - 1));
+    }
+    if state.effects.len() > exploded {
+        state.audio.push(AudioEvent::AlienExplosion);
+        log::debug!("player {}'s laser hit {} things at x {}", owner, state.effects.len() - exploded, x);
+    }
+    award(state, owner, score);
+}
+
+// --- Game Logic ---
+
+pub fn update_state(state: &mut GameState) {
+    if state.game_over {
+        return;
+    }
+    state.ticks += 1;
+    state.clock += state.settings.tick;
+    if state.audio.len() > MAX_QUEUED_SOUNDS {
+        state.audio.clear();
+    }
+
+    // --- Effects ---
+    // Age explosions and drop the ones whose animation has finished; the
+    // ones that just went off throw debris first
+    state.particles.update(state.settings.width, state.settings.height);
+    for effect in &mut state.effects {
+        if effect.age == 0 {
+            state.particles.debris(effect.x + 1, effect.y);
+        }
+        effect.age += 1;
+    }
+    state.effects.retain(|effect| effect.frame().is_some());
+    for popup in &mut state.popups {
+        popup.age += 1;
+        popup.y = popup.y.saturating_sub(1).max(UFO_ROW);
+    }
+    state.popups.retain(|popup| popup.age < POPUP_TICKS);
+
+    // --- Extra Lives ---
+    // Each threshold pays out once, even if a big score jumps over several;
+    // a ship already out of the run stays out
+    let every = state.settings.extra_life_every;
+    for mut ship in state.world.ships.iter_mut() {
+        while every > 0 && ship.score >= ship.next_extra_life {
+            ship.next_extra_life += every;
+            if ship.health.hp < ship.health.max && !ship.health.is_spent() {
+                ship.health.heal();
+                ship.one_up_until = state.clock + ONE_UP_FLASH;
+            }
+        }
+    }
+
+    // --- Intermission ---
+    // Only effects play out during the countdown; the wave appears after it
+    if let Some(until) = state.intermission_until {
+        if state.clock < until {
+            return;
+        }
+        state.intermission_until = None;
+        spawn_new_wave(state);
+    }
+
+    // --- Laser ---
+    if let Some(charge) = &mut state.laser_charge {
+        *charge = (*charge + 1).min(LASER_FULL_CHARGE);
+    }
+    match state.laser.as_mut().map(|laser| (laser.x, laser.owner, laser.age)) {
+        Some((x, owner, 0)) => {
+            fire_laser(state, x, owner);
+            if let Some(laser) = &mut state.laser {
+                laser.age += 1;
+            }
+        }
+        Some(_) => state.laser = None,
+        None => {}
+    }
+
+    // --- Movement ---
+    // Shots either way and falling pickups all fly on at their own speeds
+    state.world.movement(state.settings.tick);
+
+    // --- Player Logic ---
+    // Remove off-screen shots
+    let width = state.settings.width;
+    let gone: Vec<ecs::Id> = state
+        .world
+        .shots
+        .iter()
+        .filter(|shot| {
+            // Shots travel all the way up to the mystery ship's row
+            let (x, y) = shot.cell();
+            shot.position.is_off_top_left() || x >= width || y < UFO_ROW
+        })
+        .map(|shot| shot.id)
+        .collect();
+    state.world.despawn_all(&gone);
+    // A shot leaving the field is a miss and breaks the combo
+    let missed = gone.len() as u32;
+    state.wave_stats.shots_missed += missed;
+    state.run_stats.shots_missed += missed;
+    if missed > 0 {
+        state.combo = 0;
+    }
+
+    // --- Alien Logic ---
+    // Remove off-screen alien shots
+    let bottom = state.max_y() + 2;
+    let gone = state.world.alien_shots.iter().filter(|shot| shot.y() >= bottom);
+    let gone: Vec<ecs::Id> = gone.map(|shot| shot.id).collect();
+    state.world.despawn_all(&gone);
+
+    // --- Pickups ---
+    // Pickups drift down and are caught by flying any ship into them
+    let ships = state.targets();
+    let (mut caught, mut gone) = (Vec::new(), Vec::new());
+    for pickup in state.world.pickups.iter() {
+        if ships.iter().any(|&i| collide(&state.world.ship(i), &pickup)) {
+            caught.push(*pickup);
+            gone.push(pickup.id);
+        } else if pickup.y() >= bottom {
+            gone.push(pickup.id);
+        }
+    }
+    state.world.despawn_all(&gone);
+    for kind in caught {
+        match kind {
+            PickupKind::Bomb => state.bombs = (state.bombs + 1).min(MAX_BOMBS),
+        }
+    }
+
+    // --- Collision Detection ---
+    // Player shots and alien shots cancel each other out, whether they end up
+    // in the same cell or swapped places during this tick: a player shot
+    // sweeps its cell and the one below it, which it flew up out of
+    let swept = |shot: Aabb| Aabb { height: 2, ..shot };
+    let clashes = ecs::collision(&mut state.world.alien_shots, &mut state.world.shots, &mut state.columns, swept);
+    for clash in clashes {
+        let (x, y) = clash.target.cell();
+        if let Some(owner) = clash.target.faction.player() {
+            award(state, owner, SHOT_CLASH_POINTS);
+        }
+        state.effects.push(Effect::explosion(x.saturating_sub(1), y));
+    }
+
+    // --- Bunkers ---
+    // A shot either way takes out the block of bunker it flies into, and
+    // the formation flattens whatever it comes down on
+    if !state.bunkers.is_empty() {
+        let bunkers = &mut state.bunkers;
+        let mut chip = |(x, y): (u16, u16)| match bunkers.iter().position(|&block| block == (x, y)) {
+            Some(block) => {
+                bunkers.swap_remove(block);
+                false
+            }
+            None => true,
+        };
+        let shots: Vec<ecs::Id> =
+            state.world.shots.iter().filter(|shot| !chip(shot.cell())).map(|shot| shot.id).collect();
+        let alien_shots: Vec<ecs::Id> =
+            state.world.alien_shots.iter().filter(|shot| !chip(shot.cell())).map(|shot| shot.id).collect();
+        state.world.despawn_all(&shots);
+        state.world.despawn_all(&alien_shots);
+        // Shooting the bunkers is a miss like any other
+        let missed = shots.len() as u32;
+        state.wave_stats.shots_missed += missed;
+        state.run_stats.shots_missed += missed;
+        if missed > 0 {
+            state.combo = 0;
+        }
+        let columns = &mut state.columns;
+        columns.fill(state.world.aliens.bboxes());
+        state.bunkers.retain(|&(x, y)| columns.first_hit(Aabb::cell(x, y)).is_none());
+    }
+
+    // --- UFO Logic ---
+    if let Some(mut ufo) = state.world.ufo_mut() {
+        let (x, width) = (ufo.x(), state.settings.width);
+        // Remove the ship once it has flown off the far edge
+        let gone = match ufo.direction {
+            AlienDirection::Left => x < UFO_SPEED,
+            AlienDirection::Right => x + UFO_WIDTH + UFO_SPEED > width,
+        };
+        if gone {
+            state.world.ufos.clear();
+            schedule_ufo(state);
+        } else {
+            match ufo.direction {
+                AlienDirection::Left => ufo.place(x - UFO_SPEED, UFO_ROW),
+                AlienDirection::Right => ufo.place(x + UFO_SPEED, UFO_ROW),
+            }
+            state.audio.push(AudioEvent::Ufo);
+        }
+    } else if state.clock >= state.next_ufo && state.world.boss().is_none() {
+        // The top row shows the boss's health bar, so no mystery ship then
+        let direction = if state.rng.gen() { AlienDirection::Left } else { AlienDirection::Right };
+        let x = match direction {
+            AlienDirection::Left => state.settings.width - UFO_WIDTH,
+            AlienDirection::Right => 0,
+        };
+        let points = UFO_BONUSES[state.rng.gen_range(0..UFO_BONUSES.len())];
+        state.world.spawn_ufo(x, Ufo { direction, points });
+    }
+
+    // Check if a player shot hits the mystery ship
+    let hits = ecs::collision(&mut state.world.shots, &mut state.world.ufos, &mut state.columns, |hitbox| hitbox);
+    for hit in hits.into_iter().filter(|hit| hit.destroyed()) {
+        let (ufo_x, points) = (hit.target.x(), hit.target.points);
+        let owner = hit.attacker.faction.player().unwrap_or(0);
+        log::debug!("player {} hit the mystery ship for {} points", owner, points);
+        award(state, owner, points);
+        state.effects.push(Effect::explosion(ufo_x + UFO_WIDTH / 2 - 1, UFO_ROW));
+        state.audio.push(AudioEvent::AlienExplosion);
+        spawn_pickup(&mut state.world, ufo_x + UFO_WIDTH / 2, UFO_ROW + 1, PickupKind::Bomb);
+        schedule_ufo(state);
+    }
+
+    // Check if alien shots hit a ship; right after a respawn they fly through
+    let mut players_hit = Vec::new();
+    // The forgiving hitbox leaves out a column on either side of the ship
+    let forgiving = state.settings.assist.is_on(AssistOption::ForgivingHitbox);
+    let hitboxes: Vec<(usize, Aabb)> = state
+        .targets()
+        .into_iter()
+        .map(|i| match state.world.ship(i).bbox() {
+            ship if forgiving && ship.width > 2 => (i, Aabb { x: ship.x + 1, width: ship.width - 2, ..ship }),
+            ship => (i, ship),
+        })
+        .collect();
+    let mut gone = Vec::new();
+    if !hitboxes.is_empty() {
+        for shot in state.world.alien_shots.iter() {
+            let hit = shot.bbox();
+            let Some(&(i, _)) = hitboxes.iter().find(|&&(_, hitbox)| intersects(hitbox, hit)) else {
+                continue;
+            };
+            if !players_hit.contains(&i) {
+                players_hit.push(i);
+            }
+            gone.push(shot.id);
+        }
+    }
+    state.world.despawn_all(&gone);
+
+    for i in players_hit {
+        hit_player(state, i);
+    }
+    if state.game_over {
+        return;
+    }
+
+    // Player shots hitting aliens. An armored alien shrugs off all but the
+    // last shot it can take, and a shot and the alien it downs are taken out
+    // in place, so no shot can hit an alien already gone
+    let alien_size = state.settings.alien_size;
+    let hits = ecs::collision(&mut state.world.shots, &mut state.world.aliens, &mut state.columns, |hitbox| hitbox);
+    for hit in hits {
+        let (alien, owner) = (hit.target, hit.attacker.faction.player().unwrap_or(0));
+        let (x, y) = alien.cell();
+        if !hit.destroyed() {
+            let (x, y) = hit.attacker.cell();
+            log::debug!("shot hit an armored {:?} alien at ({}, {})", alien.kind, x, y);
+            state.particles.sparks(x, y);
+            continue;
+        }
+        state.effects.push(Effect::explosion(x, y));
+        state.audio.push(AudioEvent::AlienExplosion);
+        log::debug!("player {} shot a {:?} alien at ({}, {})", owner, alien.kind, x, y);
+        let points = alien.points() * state.multiplier();
+        state.popups.push(Popup::new(x, y, points));
+        award(state, owner, points);
+        state.combo += 1;
+        state.run_stats.aliens_destroyed += 1;
+        if state.rng.gen_bool(BOMB_DROP_CHANCE) && !state.settings.modifiers.is_on(Modifier::NoBombs) {
+            spawn_pickup(&mut state.world, x + alien_size.width / 2, y + alien_size.height / 2, PickupKind::Bomb);
+        }
+    }
+
+    // Player shots chip away at the boss, if a bomb or the laser has not
+    // finished it already
+    if let Some(boss) = state.world.boss().filter(|boss| boss.health.is_spent()) {
+        destroy_boss(state, boss, boss.last_hit_by);
+    }
+    let hits = ecs::collision(&mut state.world.shots, &mut state.world.bosses, &mut state.columns, |hitbox| hitbox);
+    for hit in &hits {
+        let (x, y) = hit.attacker.cell();
+        state.effects.push(Effect::explosion(x.saturating_sub(1), y));
+        state.particles.sparks(x, y + 1);
+    }
+    if let Some(last) = hits.last() {
+        let owner = last.attacker.faction.player().unwrap_or(0);
+        state.audio.push(AudioEvent::AlienExplosion);
+        log::debug!("boss hit {} times, {} hp left", hits.len(), last.target.health.hp);
+        match state.world.boss_mut() {
+            Some(mut boss) => boss.last_hit_by = owner,
+            None => destroy_boss(state, last.target, owner),
+        }
+    }
+
+    // --- Alien Firing Logic ---
+    let fire_interval = state.alien_fire_interval();
+    if state.clock - state.last_alien_shot > fire_interval && !state.world.aliens.is_empty() {
+        // In versus the aimed alien fires when it is told to; otherwise a
+        // random one of the front rank does
+        let shooter = match state.commander.as_ref().map(|commander| commander.fire) {
+            Some(true) => state.aimed_alien(),
+            Some(false) => None,
+            None => {
+                let potential_shooters = state.front_rank();
+                (!potential_shooters.is_empty())
+                    .then(|| potential_shooters[state.rng.gen_range(0..potential_shooters.len())])
+            }
+        };
+
+        if let Some(shooter) = shooter.and_then(|shooter| state.world.aliens.get(shooter)) {
+            if let Some(commander) = &mut state.commander {
+                commander.fire = false;
+            }
+            let speed = state.settings.alien_shot_speed();
+            state.world.fire(&shooter, 0, alien_shot_velocity(speed));
+            state.last_alien_shot = state.clock;
+        }
+    }
+
+    if state.clock - state.last_alien_shot > fire_interval && state.world.boss().is_some() {
+        boss_attack(state);
+        state.last_alien_shot = state.clock;
+    }
+
+    // --- Dive Bombers ---
+    update_divers(state);
+    if state.game_over {
+        return;
+    }
+
+    // --- Level Progression ---
+    if state.world.aliens.is_empty() && state.world.boss().is_none() {
+        start_wave(state);
+        return;
+    }
+
+    // Update alien positions, one cell per step; a formation faster than one
+    // cell per tick takes several steps, each checked against the walls
+    state.alien_step_progress += state.alien_speed();
+    while state.alien_step_progress >= STEP {
+        state.alien_step_progress -= STEP;
+        step_aliens(state);
+        if state.game_over {
+            return;
+        }
+    }
+
+    // The boss keeps the wave's pace no matter how few aliens are left
+    let (speed, width) = (state.wave_speed(), state.settings.width);
+    if let Some(mut boss) = state.world.boss_mut() {
+        boss.step_progress += speed;
+        while boss.step_progress >= STEP {
+            boss.step_progress -= STEP;
+            step_boss(&mut boss, width);
+        }
+    }
+
+    // --- Scripts ---
+    #[cfg(feature = "scripting")]
+    run_script(state);
+}
+
+/// Blows up `boss`, finished by player `killer`, who scores for it.
+fn destroy_boss(state: &mut GameState, boss: ecs::Row<Boss>, killer: usize) {
+    let (x, y) = boss.cell();
+    for dx in (0..BOSS_WIDTH).step_by(4) {
+        state.effects.push(Effect::explosion(x + dx, y));
+        state.effects.push(Effect::explosion(x + dx, y + 2));
+    }
+    state.world.bosses.clear();
+    award(state, killer, BOSS_POINTS * state.wave / BOSS_WAVE_INTERVAL);
+}
+
+/// Runs the wave's script for each alien in the formation, divers aside,
+/// or for the boss, moving them where it says within the field and firing
+/// for those it fires.
+#[cfg(feature = "scripting")]
+fn run_script(state: &mut GameState) {
+    let Some(script) = state.script.take() else {
+        return;
+    };
+    let elapsed = state.clock.saturating_sub(state.wave_stats.started);
+    let ticks = elapsed.as_millis() / state.settings.tick.as_millis().max(1);
+    let field = World {
+        player_x: 0,
+        player_y: i64::from(state.max_y()),
+        wave: i64::from(state.wave),
+        tick: ticks as i64,
+        width: i64::from(state.settings.width),
+        height: i64::from(state.settings.height),
+    };
+    // The ship each alien sees is the one nearest it
+    let ships = state.ships();
+    let world = |x: u16| World { player_x: i64::from(nearest_ship(&ships, x).unwrap_or(x)), ..field };
+    let clamp = |value: i64, max: u16| value.clamp(0, i64::from(max)) as u16;
+
+    let (alien_size, velocity) = (state.settings.alien_size, alien_shot_velocity(state.settings.alien_shot_speed()));
+    let (max_x, max_y) = (state.settings.width - alien_size.width, state.max_y() - alien_size.height);
+    for index in 0..state.world.aliens.len() {
+        let Some(alien) = state.world.aliens.get(index).filter(|alien| alien.dive.is_none()) else {
+            continue;
+        };
+        let world = world(alien.x());
+        let (x, y, hits) = (i64::from(alien.x()), i64::from(alien.y()), i64::from(alien.health.taken()));
+        let mut actor = Actor { x, y, hits, index: index as i64, fire: false };
+        script.run(&mut actor, &world, &mut state.rng);
+        let alien = ecs::Row { position: Position::at(clamp(actor.x, max_x), clamp(actor.y, max_y)), ..alien };
+        state.world.aliens.get_mut(index).expect("the alien is still in").place(alien.x(), alien.y());
+        if actor.fire {
+            state.world.fire(&alien, 0, velocity);
+        }
+    }
+
+    if let Some(boss) = state.world.boss() {
+        let world = world(boss.x() + BOSS_WIDTH / 2);
+        let hits = i64::from(boss.health.taken());
+        let mut actor = Actor { x: i64::from(boss.x()), y: i64::from(boss.y()), hits, index: 0, fire: false };
+        script.run(&mut actor, &world, &mut state.rng);
+        let max_y = state.max_y().saturating_sub(BOSS_HEIGHT + SPAWN_CLEARANCE);
+        let max_x = state.settings.width - BOSS_WIDTH;
+        if let Some(mut boss) = state.world.boss_mut() {
+            boss.place(clamp(actor.x, max_x), clamp(actor.y, max_y));
+        }
+        if actor.fire {
+            boss_attack(state);
+        }
+    }
+    state.script = Some(script);
+}
+
+/// Moves the formation one cell sideways, or down a row when it hits a wall.
+/// Divers keep flying on their own, but their slots move along.
+fn step_aliens(state: &mut GameState) {
+    let (width, alien_size) = (state.settings.width, state.settings.alien_size);
+    let wall_hit = state.world.aliens.iter().any(|alien| match state.alien_direction {
+        AlienDirection::Left => alien.home().0 == 0,
+        AlienDirection::Right => alien.home().0 + alien_size.width >= width,
+    });
+
+    if wall_hit {
+        state.alien_direction = match state.alien_direction {
+            AlienDirection::Left => AlienDirection::Right,
+            AlienDirection::Right => AlienDirection::Left,
+        };
+        let descent = state.settings.difficulty.descent() * state.level().map_or(1, |level| level.descent);
+        let max_y = state.max_y();
+        for mut alien in state.world.aliens.iter_mut() {
+            let (_, y) = shift_home(&mut alien, 0, descent);
+            if y + alien_size.height > max_y {
+                state.game_over = true;
+                return;
+            }
+        }
+    } else {
+        let dx = match state.alien_direction {
+            AlienDirection::Left => -1,
+            AlienDirection::Right => 1,
+        };
+        for mut alien in state.world.aliens.iter_mut() {
+            shift_home(&mut alien, dx, 0);
+        }
+    }
+}
+
+/// Slides the boss one cell sideways, turning around at the walls. Unlike
+/// the formation it never descends.
+fn step_boss(boss: &mut ecs::RowMut<Boss>, width: u16) {
+    let (x, y) = boss.cell();
+    match boss.direction {
+        AlienDirection::Left if x == 0 => boss.direction = AlienDirection::Right,
+        AlienDirection::Right if x + BOSS_WIDTH >= width => boss.direction = AlienDirection::Left,
+        AlienDirection::Left => boss.place(x - 1, y),
+        AlienDirection::Right => boss.place(x + 1, y),
+    }
+}
+
+/// Fires the boss's next attack and moves on to the one after it.
+fn boss_attack(state: &mut GameState) {
+    let Some(mut boss) = state.world.boss_mut() else {
+        return;
+    };
+    let attack = boss.next_attack;
+    boss.next_attack = attack.next();
+    let boss = state.world.boss().expect("the boss is in");
+    let velocity = alien_shot_velocity(state.settings.alien_shot_speed());
+    // Cannons are counted from the middle of the hull, which shots leave from
+    let cannon = |column: u16| column as i16 - (BOSS_WIDTH / 2) as i16;
+
+    match attack {
+        BossAttack::Spread => {
+            for column in BOSS_CANNONS {
+                state.world.fire(&boss, cannon(column), velocity);
+            }
+        }
+        BossAttack::Aimed => {
+            let (x, middle) = (boss.x(), state.settings.player_size.width / 2);
+            let target = nearest_ship(&state.ships(), (x + BOSS_WIDTH / 2).saturating_sub(middle)).unwrap_or(x) + middle;
+            let column = BOSS_CANNONS
+                .into_iter()
+                .min_by_key(|column| (x + column).abs_diff(target))
+                .unwrap_or(BOSS_WIDTH / 2);
+            state.world.fire(&boss, cannon(column), velocity);
+        }
+        BossAttack::Barrage => {
+            for _ in 0..BOSS_BARRAGE_SHOTS {
+                let column = state.rng.gen_range(0..BOSS_WIDTH);
+                state.world.fire(&boss, cannon(column), velocity);
+            }
+        }
+    }
+}
+
+/// Moves the formation position of `alien` by (dx, dy), along with the
+/// alien unless it is away on a dive, and returns where it moved to.
+fn shift_home(alien: &mut ecs::RowMut<Alien>, dx: i16, dy: u16) -> (u16, u16) {
+    match &mut alien.dive {
+        Some(dive) => {
+            (dive.slot_x, dive.slot_y) = (dive.slot_x.saturating_add_signed(dx), dive.slot_y + dy);
+            (dive.slot_x, dive.slot_y)
+        }
+        None => {
+            alien.position.x += f32::from(dx);
+            alien.position.y += f32::from(dy);
+            alien.cell()
+        }
+    }
+}
+
+/// Sends a new diver down now and then and flies the ones already out.
+fn update_divers(state: &mut GameState) {
+    let diving = state.world.aliens.iter().filter(|alien| alien.dive.is_some()).count();
+    if state.clock - state.last_dive >= DIVE_INTERVAL && diving < MAX_DIVERS {
+        let candidates: Vec<usize> = (0..state.world.aliens.len())
+            .filter(|&i| state.world.aliens.get(i).is_some_and(|alien| alien.dive.is_none()))
+            .collect();
+        if !candidates.is_empty() {
+            let chosen = candidates[state.rng.gen_range(0..candidates.len())];
+            let swing = if state.rng.gen() { AlienDirection::Left } else { AlienDirection::Right };
+            let mut alien = state.world.aliens.get_mut(chosen).expect("candidates are in the formation");
+            let (slot_x, slot_y) = alien.cell();
+            alien.dive = Some(Dive { slot_x, slot_y, phase: DivePhase::Swoop, swing, ticks: 0 });
+            state.last_dive = state.clock;
+        }
+    }
+
+    let (alien_size, player_size) = (state.settings.alien_size, state.settings.player_size);
+    let rightmost = state.settings.width - alien_size.width;
+    let (bottom, player_y) = (state.max_y() + 1, state.max_y());
+    let ships = state.ships();
+    let targets: Vec<(usize, u16)> = state.targets().into_iter().map(|i| (i, state.world.ship(i).x())).collect();
+    let (mut firing, mut rammed, mut players_hit) = (Vec::new(), Vec::new(), Vec::new());
+    for (i, mut alien) in state.world.aliens.iter_mut().enumerate() {
+        let Some(mut dive) = alien.dive else {
+            continue;
+        };
+        let (mut x, mut y) = alien.cell();
+        dive.ticks += 1;
+        match dive.phase {
+            DivePhase::Swoop => {
+                // Swing out first, then curve in on the nearest ship while dropping
+                let target = nearest_ship(&ships, x).unwrap_or(x);
+                let towards = if dive.ticks <= DIVE_SWING_TICKS {
+                    dive.swing
+                } else if target < x {
+                    AlienDirection::Left
+                } else {
+                    AlienDirection::Right
+                };
+                match towards {
+                    AlienDirection::Left => x = x.saturating_sub(1),
+                    AlienDirection::Right => x = (x + 1).min(rightmost),
+                }
+                y += 1;
+                if dive.ticks.is_multiple_of(DIVE_FIRE_TICKS) && y + alien_size.height < player_y {
+                    firing.push(i);
+                }
+                if y > bottom {
+                    // Wrap around to the top and head home from there
+                    y = UFO_ROW + 1;
+                    dive.phase = DivePhase::Return;
+                    dive.ticks = 0;
+                }
+            }
+            DivePhase::Return => {
+                (x, y) = (step_towards(x, dive.slot_x), step_towards(y, dive.slot_y));
+                if (x, y) == (dive.slot_x, dive.slot_y) {
+                    alien.place(x, y);
+                    alien.dive = None;
+                    continue;
+                }
+            }
+        }
+        alien.place(x, y);
+        alien.dive = Some(dive);
+        let diver = Aabb::sprite(x, y, alien_size);
+        let ship = targets.iter().find(|&&(_, player_x)| intersects(diver, Aabb::sprite(player_x, player_y, player_size)));
+        if let Some(&(player, _)) = ship {
+            rammed.push(i);
+            if !players_hit.contains(&player) {
+                players_hit.push(player);
+            }
+        }
+    }
+
+    // Divers fire from where they swooped to
+    let velocity = alien_shot_velocity(state.settings.alien_shot_speed());
+    for i in firing {
+        let diver = state.world.aliens.get(i).expect("divers are in the formation");
+        state.world.fire(&diver, 0, velocity);
+    }
+    // A diver that flies into a ship takes it down along with itself; a
+    // ship rammed by two at once still loses only the one life
+    for i in rammed.into_iter().rev() {
+        let (x, y) = state.world.aliens.remove(i).cell();
+        state.effects.push(Effect::explosion(x, y));
+        state.run_stats.aliens_destroyed += 1;
+    }
+    for player in players_hit {
+        hit_player(state, player);
+    }
+}
+
+/// Moves `from` one cell closer to `to`.
+fn step_towards(from: u16, to: u16) -> u16 {
+    match from.cmp(&to) {
+        std::cmp::Ordering::Less => from + 1,
+        std::cmp::Ordering::Greater => from - 1,
+        std::cmp::Ordering::Equal => from,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A two-player run on the first boss wave, with the boss in.
+    fn boss_fight() -> GameState {
+        let mut state = GameState::new(&Settings { seed: Some(1), players: 2, ..Settings::default() });
+        state.wave = BOSS_WAVE_INTERVAL;
+        state.intermission_until = None;
+        spawn_new_wave(&mut state);
+        state
+    }
+
+    /// A one-player run with its first wave in, past the countdown.
+    fn first_wave() -> GameState {
+        let mut state = GameState::new(&Settings { seed: Some(1), ..Settings::default() });
+        state.intermission_until = None;
+        spawn_new_wave(&mut state);
+        state
+    }
+
+    #[test]
+    fn kills_build_the_combo_and_a_miss_breaks_it() {
+        let mut state = first_wave();
+        // A full step of kills doubles what the next one is worth
+        state.combo = COMBO_KILLS_PER_STEP;
+        let alien = state.world.aliens.iter().find(|alien| !alien.is_armored()).expect("the wave has aliens");
+        let (size, (x, y)) = (state.settings.alien_size, alien.cell());
+        state.world.spawn_shot(Position::at(x + size.width / 2, y + size.height - 1), shot_velocity(0), 0);
+        update_state(&mut state);
+        assert_eq!(state.combo, COMBO_KILLS_PER_STEP + 1);
+        assert_eq!(state.score, alien.points() * 2);
+        // A shot already past the top is gone the next tick
+        state.world.spawn_shot(Position::at(0, 0), shot_velocity(0), 0);
+        update_state(&mut state);
+        assert_eq!(state.combo, 0);
+        assert_eq!(state.multiplier(), 1);
+    }
+
+    #[test]
+    fn a_hit_ship_shrugs_off_hits_until_its_invincibility_wears_off() {
+        let mut state = first_wave();
+        let lives = state.lives();
+        hit_player(&mut state, 0);
+        assert_eq!(state.lives(), lives - 1);
+        assert!(state.is_invincible(0));
+        hit_player(&mut state, 0);
+        assert_eq!(state.lives(), lives - 1);
+        state.clock += INVINCIBILITY;
+        assert!(!state.is_invincible(0));
+        hit_player(&mut state, 0);
+        assert_eq!(state.lives(), lives - 2);
+    }
+
+    #[test]
+    fn extra_lives_pay_out_once_per_threshold_up_to_the_cap() {
+        // The countdown keeps the aliens out of it
+        let mut state = GameState::new(&Settings { seed: Some(1), ..Settings::default() });
+        let (lives, every) = (state.lives(), state.settings.extra_life_every);
+        state.world.ship_mut(0).score = every * 2 + every / 2;
+        update_state(&mut state);
+        assert_eq!(state.lives(), lives + 2);
+        assert_eq!(state.world.ship(0).next_extra_life, every * 3);
+        assert!(state.is_one_up());
+        state.world.ship_mut(0).health.hp = u16::from(MAX_LIVES);
+        state.world.ship_mut(0).score = every * 3;
+        update_state(&mut state);
+        assert_eq!(state.lives(), MAX_LIVES);
+        assert_eq!(state.world.ship(0).next_extra_life, every * 4);
+    }
+
+    #[test]
+    fn a_cleared_wave_pays_its_bonus_to_every_ship_still_in() {
+        let mut state = GameState::new(&Settings { seed: Some(1), players: 2, ..Settings::default() });
+        state.wave_stats = WaveStats { shots_fired: 10, shots_missed: 5, started: Duration::ZERO };
+        state.clock = PAR_TIME - Duration::from_secs(20);
+        state.world.ship_mut(1).health.hp = 0;
+        start_wave(&mut state);
+        let summary = state.summary.expect("a cleared wave is summed up");
+        assert_eq!((summary.wave, summary.accuracy), (1, 50));
+        assert_eq!(summary.bonus, 50 * ACCURACY_BONUS + 20 * SPEED_BONUS);
+        assert_eq!(state.world.ship(0).score, summary.bonus);
+        assert_eq!(state.world.ship(1).score, 0);
+        assert_eq!(state.wave, 2);
+    }
+
+    #[test]
+    fn each_boss_is_tougher_and_loses_a_hit_point_a_shot() {
+        let mut state = boss_fight();
+        let boss = state.world.boss().expect("a boss wave brings in the boss");
+        assert_eq!(boss.health, Health::full(BOSS_BASE_HP));
+        let (x, y) = boss.cell();
+        state.world.spawn_shot(Position::at(x + BOSS_WIDTH / 2, y + BOSS_HEIGHT - 1), shot_velocity(0), 1);
+        update_state(&mut state);
+        let boss = state.world.boss().expect("one shot does not finish the boss");
+        assert_eq!(boss.health.hp, BOSS_BASE_HP - 1);
+        assert_eq!(boss.last_hit_by, 1);
+
+        state.wave = BOSS_WAVE_INTERVAL * 2;
+        spawn_new_wave(&mut state);
+        let boss = state.world.boss().expect("a boss wave brings in the boss");
+        assert_eq!(boss.health, Health::full(BOSS_BASE_HP + BOSS_HP_PER_FIGHT));
+    }
+
+    #[test]
+    fn a_diver_ramming_a_ship_takes_a_life_and_itself() {
+        let mut state = first_wave();
+        state.last_dive = state.clock;
+        let (aliens, lives) = (state.world.aliens.len(), state.lives());
+        // One row above the ship, swung out and curving in on it
+        let (x, y) = (state.world.ship(0).x(), state.max_y() - state.settings.alien_size.height);
+        let mut alien = state.world.aliens.get_mut(0).expect("the wave has aliens");
+        let (slot_x, slot_y) = alien.cell();
+        let swing = AlienDirection::Left;
+        alien.dive = Some(Dive { slot_x, slot_y, phase: DivePhase::Swoop, swing, ticks: DIVE_SWING_TICKS });
+        alien.place(x, y);
+        update_divers(&mut state);
+        assert_eq!(state.world.aliens.len(), aliens - 1);
+        assert_eq!(state.lives(), lives - 1);
+        assert!(state.is_invincible(0));
+    }
+
+    #[test]
+    fn a_boss_finished_by_a_bomb_is_the_bombers() {
+        let mut state = boss_fight();
+        let boss = state.world.boss_mut().expect("a boss wave brings in the boss");
+        assert_eq!(boss.health.hp, BOSS_BASE_HP);
+        boss.health.hp = BOMB_BOSS_DAMAGE;
+        detonate_bomb(&mut state, 1);
+        update_state(&mut state);
+        assert!(state.world.boss().is_none());
+        // Both share the wave's bonus; only the bomber has the boss's points
+        assert_eq!(state.world.ship(1).score - state.world.ship(0).score, BOSS_POINTS);
+    }
+}
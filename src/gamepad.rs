@@ -0,0 +1,124 @@
+//! Gamepad input, read from the Linux joystick interface.
+//!
+//! A gamepad's `/dev/input/js*` device speaks a simple enough protocol to
+//! read without a gamepad library. Its buttons and axes come out as
+//! [`Key::Pad`] presses, so they go through the [`crate::keymap`] like any
+//! key does: by default the d-pad or left stick moves, A fires and Start
+//! pauses.
+//!
+//! Held directions and A repeat, the way held keys do on a keyboard.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::time::{Duration, Instant};
+
+use crate::input::{Key, PadButton};
+
+/// Where the joystick interface puts its devices.
+const DEVICES: [&str; 4] = ["/dev/input/js0", "/dev/input/js1", "/dev/input/js2", "/dev/input/js3"];
+
+/// How long a button is held before it starts repeating, and how often it
+/// repeats after that.
+const REPEAT_DELAY: Duration = Duration::from_millis(250);
+const REPEAT_INTERVAL: Duration = Duration::from_millis(50);
+/// How far a stick has to be pushed to count as a direction, out of 32767.
+const DEAD_ZONE: i16 = 16_384;
+
+// --- The Joystick Protocol ---
+
+/// The size of a `struct js_event`: time (u32), value (i16), type (u8) and
+/// number (u8).
+const EVENT_SIZE: usize = 8;
+const EVENT_BUTTON: u8 = 0x01;
+const EVENT_AXIS: u8 = 0x02;
+/// Set on the events describing the state the device was in when opened.
+const EVENT_INIT: u8 = 0x80;
+
+/// Button numbers, in the layout the Linux drivers give Xbox-style pads.
+const BUTTON_A: u8 = 0;
+const BUTTON_START: u8 = 7;
+
+/// The axes of the left stick and of the d-pad, which most drivers report
+/// as a pair of axes too.
+const STICK_X: u8 = 0;
+const STICK_Y: u8 = 1;
+const DPAD_X: u8 = 6;
+const DPAD_Y: u8 = 7;
+
+pub struct Gamepad {
+    device: File,
+    /// Buttons held down, with when each next repeats.
+    held: Vec<(PadButton, Instant)>,
+    pending: VecDeque<Key>,
+}
+
+impl Gamepad {
+    /// Opens the first gamepad plugged in, if there is one.
+    pub fn open() -> Option<Gamepad> {
+        let device = DEVICES
+            .iter()
+            .find_map(|path| OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(path).ok())?;
+        Some(Gamepad { device, held: Vec::new(), pending: VecDeque::new() })
+    }
+
+    /// Takes every press since the last call, repeats of held buttons
+    /// included.
+    pub fn poll(&mut self) -> Vec<Key> {
+        let now = Instant::now();
+        let mut event = [0; EVENT_SIZE];
+        loop {
+            match self.device.read(&mut event) {
+                Ok(EVENT_SIZE) => self.handle(event, now),
+                // Unplugged or nothing left to read; a pad that is gone
+                // simply stops sending
+                Ok(_) => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+        }
+        for (button, next) in &mut self.held {
+            if *button == PadButton::Start {
+                continue;
+            }
+            while *next <= now {
+                self.pending.push_back(Key::Pad(*button));
+                *next += REPEAT_INTERVAL;
+            }
+        }
+        self.pending.drain(..).collect()
+    }
+
+    fn handle(&mut self, event: [u8; EVENT_SIZE], now: Instant) {
+        let value = i16::from_ne_bytes([event[4], event[5]]);
+        let (kind, number) = (event[6], event[7]);
+        if kind & EVENT_INIT != 0 {
+            return;
+        }
+        match (kind, number) {
+            (EVENT_BUTTON, BUTTON_A) => self.set(PadButton::A, value != 0, now),
+            (EVENT_BUTTON, BUTTON_START) => self.set(PadButton::Start, value != 0, now),
+            (EVENT_AXIS, STICK_X | DPAD_X) => {
+                self.set(PadButton::Left, value < -DEAD_ZONE, now);
+                self.set(PadButton::Right, value > DEAD_ZONE, now);
+            }
+            (EVENT_AXIS, STICK_Y | DPAD_Y) => {
+                self.set(PadButton::Up, value < -DEAD_ZONE, now);
+                self.set(PadButton::Down, value > DEAD_ZONE, now);
+            }
+            _ => {}
+        }
+    }
+
+    /// Notes `button` being down or up, pressing it if it just went down.
+    fn set(&mut self, button: PadButton, down: bool, now: Instant) {
+        let was_down = self.held.iter().any(|&(held, _)| held == button);
+        if down && !was_down {
+            self.pending.push_back(Key::Pad(button));
+            self.held.push((button, now + REPEAT_DELAY));
+        } else if !down && was_down {
+            self.held.retain(|&(held, _)| held != button);
+        }
+    }
+}
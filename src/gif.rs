@@ -0,0 +1,168 @@
+//! A small animated GIF encoder, for exporting highlights.
+//!
+//! Every frame covers the whole image and indexes one global palette of
+//! up to 256 colors, which grows as frames bring colors it lacks. Frames
+//! are compressed as they are added, so only the compressed bytes are
+//! kept; the palette goes out in front of them once they are all in.
+
+use std::collections::HashMap;
+
+use crate::render::theme::Rgb;
+
+/// Bits of the palette indices, and of the LZW codes they start from.
+const INDEX_BITS: u8 = 8;
+const CLEAR_CODE: u16 = 1 << INDEX_BITS;
+const END_CODE: u16 = CLEAR_CODE + 1;
+const MAX_CODE_BITS: u8 = 12;
+/// The longest data sub-block.
+const BLOCK_LEN: usize = 255;
+
+pub struct Encoder {
+    width: u16,
+    height: u16,
+    palette: Vec<Rgb>,
+    indices: HashMap<Rgb, u8>,
+    /// The frames added so far, encoded.
+    frames: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new(width: u16, height: u16) -> Self {
+        Encoder { width, height, palette: Vec::new(), indices: HashMap::new(), frames: Vec::new() }
+    }
+
+    /// The palette index of `rgb`, adding it if there is room, else the
+    /// index of the nearest color there is.
+    pub fn index(&mut self, rgb: Rgb) -> u8 {
+        if let Some(&index) = self.indices.get(&rgb) {
+            return index;
+        }
+        if self.palette.len() < 1 << INDEX_BITS {
+            let index = self.palette.len() as u8;
+            self.palette.push(rgb);
+            self.indices.insert(rgb, index);
+            return index;
+        }
+        let distance = |other: &Rgb| {
+            let channel = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+            channel(rgb.0, other.0) + channel(rgb.1, other.1) + channel(rgb.2, other.2)
+        };
+        (0..self.palette.len()).min_by_key(|&i| distance(&self.palette[i])).unwrap_or(0) as u8
+    }
+
+    /// Adds a frame of `pixels`, palette indices row by row, shown for
+    /// `delay` hundredths of a second.
+    pub fn add_frame(&mut self, pixels: &[u8], delay: u16) {
+        // Graphic control: the delay, nothing transparent
+        self.frames.extend_from_slice(&[0x21, 0xf9, 4, 0]);
+        self.frames.extend_from_slice(&delay.to_le_bytes());
+        self.frames.extend_from_slice(&[0, 0]);
+        // The image, at the top-left corner and as big as the screen
+        self.frames.push(0x2c);
+        for value in [0, 0, self.width, self.height] {
+            self.frames.extend_from_slice(&value.to_le_bytes());
+        }
+        self.frames.push(0);
+        self.frames.push(INDEX_BITS);
+        for block in compress(pixels).chunks(BLOCK_LEN) {
+            self.frames.push(block.len() as u8);
+            self.frames.extend_from_slice(block);
+        }
+        self.frames.push(0);
+    }
+
+    /// The GIF file, looping forever.
+    pub fn finish(self) -> Vec<u8> {
+        let mut out = b"GIF89a".to_vec();
+        out.extend_from_slice(&self.width.to_le_bytes());
+        out.extend_from_slice(&self.height.to_le_bytes());
+        // A global palette of 256 colors, the unused ones black
+        out.extend_from_slice(&[0xf0 | (INDEX_BITS - 1), 0, 0]);
+        for i in 0..1 << INDEX_BITS {
+            let Rgb(red, green, blue) = self.palette.get(i).copied().unwrap_or(Rgb(0, 0, 0));
+            out.extend_from_slice(&[red, green, blue]);
+        }
+        out.extend_from_slice(&[0x21, 0xff, 11]);
+        out.extend_from_slice(b"NETSCAPE2.0");
+        out.extend_from_slice(&[3, 1, 0, 0, 0]);
+        out.extend(self.frames);
+        out.push(0x3b);
+        out
+    }
+}
+
+// --- LZW ---
+
+/// Writes codes of varying widths, least significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u32,
+    len: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u8) {
+        self.bits |= (code as u32) << self.len;
+        self.len += width;
+        while self.len >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+/// `pixels` LZW-compressed the way GIF has it: codes one bit wider than
+/// the indices to start with, widening up to 12 bits, and clearing the
+/// table once it is full.
+fn compress(pixels: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut width = INDEX_BITS + 1;
+    let mut next = END_CODE + 1;
+    writer.write(CLEAR_CODE, width);
+    let Some((&first, rest)) = pixels.split_first() else {
+        writer.write(END_CODE, width);
+        return writer.finish();
+    };
+    let mut prefix = first as u16;
+    // Counts a code as taken, widening the codes once the next one needs it
+    let take = |next: &mut u16, width: &mut u8| {
+        *next += 1;
+        if *next > 1 << *width && *width < MAX_CODE_BITS {
+            *width += 1;
+        }
+    };
+    for &pixel in rest {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+        writer.write(prefix, width);
+        if next < 1 << MAX_CODE_BITS {
+            table.insert((prefix, pixel), next);
+            take(&mut next, &mut width);
+        } else {
+            writer.write(CLEAR_CODE, width);
+            table.clear();
+            width = INDEX_BITS + 1;
+            next = END_CODE + 1;
+        }
+        prefix = pixel as u16;
+    }
+    writer.write(prefix, width);
+    // The decoder takes a code after the last one too, and may widen
+    if next < 1 << MAX_CODE_BITS {
+        take(&mut next, &mut width);
+    }
+    writer.write(END_CODE, width);
+    writer.finish()
+}
@@ -0,0 +1,412 @@
+//! Exporting the end of a run as an animated GIF, to share a death or a
+//! clutch save.
+//!
+//! The game-over screen's `g` key plays the run's replay back, which is
+//! saved as `last.replay` whenever a run ends, and draws its last
+//! [`HIGHLIGHT`] into a `highlight-<time>.gif` in the current directory,
+//! one frame a tick. Frames are drawn in a [`FrameBuffer`], as the screen
+//! would show them, then every cell in a bitmap font that comes with the
+//! game: 5x7 glyphs for ASCII, and the box-drawing, block, Braille and
+//! other symbols the game draws with built from lines and dots.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::game::GameState;
+use crate::gif::Encoder;
+use crate::locale::tr;
+use crate::render::buffer::FrameBuffer;
+use crate::render::sprites::SpriteSet;
+use crate::render::theme::{Rgb, Theme};
+use crate::render::{self, Color, Layout, Renderer};
+use crate::replay::{Playback, Replay, LAST_RUN_FILE};
+use crate::storage;
+
+/// How much of the end of a run goes into a highlight.
+pub const HIGHLIGHT: Duration = Duration::from_secs(15);
+/// The size of a cell in font pixels, and of a font pixel in the GIF's.
+const CELL_WIDTH: usize = 6;
+const CELL_HEIGHT: usize = 10;
+const SCALE: usize = 2;
+
+/// Writes the highlight of `state`, the run that just ended, drawn in
+/// `layout` at the top-left corner of the screen, in `theme` and with
+/// `sprites`. Returns the file written.
+pub fn export(
+    state: &GameState,
+    layout: &Layout,
+    high_score: u32,
+    theme: &Theme,
+    sprites: &'static SpriteSet,
+) -> Result<PathBuf, String> {
+    let replay = storage::data_file(LAST_RUN_FILE).ok_or_else(|| tr("highlight_no_replay").to_string())?;
+    let replay = Replay::load(&replay)?;
+    let mut playback = Playback::new(&replay);
+    let tick = replay.settings.tick.max(Duration::from_millis(1));
+    let start = state.ticks.saturating_sub((HIGHLIGHT.as_millis() / tick.as_millis()) as u64);
+    while playback.state.ticks < start && !playback.finished() {
+        playback.tick();
+    }
+
+    let (columns, lines) = (layout.width().max(0) as usize, layout.height().max(0) as usize);
+    let size = |cells: usize, cell: usize| u16::try_from(cells * cell * SCALE).map_err(|_| tr("highlight_too_big"));
+    let mut encoder = Encoder::new(size(columns, CELL_WIDTH)?, size(lines, CELL_HEIGHT)?);
+    let mut film = Film { frame: FrameBuffer::new(columns, lines), sprites };
+    // GIF delays are in hundredths of a second, and viewers slow down
+    // anything under two
+    let delay = (tick.as_millis() / 10).clamp(2, u16::MAX as u128) as u16;
+    loop {
+        film.frame.clear();
+        render::draw_game(&mut film, layout, &playback.state, high_score, theme);
+        let pixels = rasterize(&film.frame, theme, &mut encoder);
+        encoder.add_frame(&pixels, delay);
+        if playback.finished() || playback.state.ticks >= state.ticks {
+            break;
+        }
+        playback.tick();
+    }
+    // The last replay is of another run if this one was not recorded
+    if playback.state.ticks != state.ticks || playback.state.score != state.score {
+        return Err(tr("highlight_other_run").to_string());
+    }
+    let path = storage::timestamped("highlight", "gif");
+    fs::write(&path, encoder.finish()).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(path)
+}
+
+/// The frames of a highlight, drawn with the sprites the run was played with.
+struct Film {
+    frame: FrameBuffer,
+    sprites: &'static SpriteSet,
+}
+
+impl Renderer for Film {
+    fn clear(&mut self) {
+        self.frame.clear();
+    }
+
+    fn draw_str(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        self.frame.draw_str(x, y, text, color);
+    }
+
+    fn present(&mut self) {}
+
+    fn size(&self) -> (i32, i32) {
+        self.frame.size()
+    }
+
+    fn rich_colors(&self) -> bool {
+        true
+    }
+
+    fn sprites(&self) -> &'static SpriteSet {
+        self.sprites
+    }
+
+    fn draw_pixels(&mut self, x: i32, y: i32, top: Option<Color>, bottom: Option<Color>) {
+        self.frame.draw_pixels(x, y, top, bottom);
+    }
+}
+
+/// The GIF pixels of `frame`, as palette indices of `encoder`, on black.
+fn rasterize(frame: &FrameBuffer, theme: &Theme, encoder: &mut Encoder) -> Vec<u8> {
+    let width = frame.width() * CELL_WIDTH * SCALE;
+    let mut pixels = vec![0; width * frame.height() * CELL_HEIGHT * SCALE];
+    let black = encoder.index(Rgb(0, 0, 0));
+    for y in 0..frame.height() {
+        for (x, cell) in frame.row(y).iter().enumerate() {
+            let ink = encoder.index(theme.rgb(cell.color.unwrap_or(Color::Ui)));
+            let paper = cell.background.map_or(black, |color| encoder.index(theme.rgb(color)));
+            for (row, bits) in glyph(cell.ch).into_iter().enumerate() {
+                for column in 0..CELL_WIDTH {
+                    let index = if bits >> (CELL_WIDTH - 1 - column) & 1 == 1 { ink } else { paper };
+                    for line in 0..SCALE {
+                        let start = ((y * CELL_HEIGHT + row) * SCALE + line) * width + (x * CELL_WIDTH + column) * SCALE;
+                        pixels[start..start + SCALE].fill(index);
+                    }
+                }
+            }
+        }
+    }
+    pixels
+}
+
+// --- Font ---
+
+/// Font pixel rows above the glyphs, where capitals have their accents.
+const GLYPH_TOP: usize = 2;
+/// The bits of a font pixel row, leftmost first.
+type Row = u8;
+const FULL_ROW: Row = (1 << CELL_WIDTH) - 1;
+
+/// Where the 5x7 glyphs of ASCII start.
+const FIRST_GLYPH: char = ' ';
+/// The glyphs from space to `~`, a row of five bits to each line.
+const FONT: [[Row; 7]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // !
+    [0x0a, 0x0a, 0x0a, 0x00, 0x00, 0x00, 0x00], // "
+    [0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a], // #
+    [0x04, 0x0f, 0x14, 0x0e, 0x05, 0x1e, 0x04], // $
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // %
+    [0x0c, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0d], // &
+    [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00], // '
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // (
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // )
+    [0x00, 0x04, 0x15, 0x0e, 0x15, 0x04, 0x00], // *
+    [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08], // ,
+    [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c], // .
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // /
+    [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e], // 0
+    [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e], // 1
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f], // 2
+    [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e], // 3
+    [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02], // 4
+    [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e], // 5
+    [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e], // 6
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // 7
+    [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e], // 8
+    [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c], // 9
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00], // :
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x04, 0x08], // ;
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // <
+    [0x00, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x00], // =
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // >
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // ?
+    [0x0e, 0x11, 0x01, 0x0d, 0x15, 0x15, 0x0e], // @
+    [0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11], // A
+    [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e], // B
+    [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e], // C
+    [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c], // D
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f], // E
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10], // F
+    [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f], // G
+    [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11], // H
+    [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e], // I
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c], // J
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // K
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f], // L
+    [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11], // M
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // N
+    [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // O
+    [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10], // P
+    [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d], // Q
+    [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11], // R
+    [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e], // S
+    [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // T
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // U
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04], // V
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a], // W
+    [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11], // X
+    [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04], // Y
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f], // Z
+    [0x0e, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0e], // [
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // \
+    [0x0e, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0e], // ]
+    [0x04, 0x0a, 0x11, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f], // _
+    [0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00], // `
+    [0x00, 0x00, 0x0e, 0x01, 0x0f, 0x11, 0x0f], // a
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1e], // b
+    [0x00, 0x00, 0x0e, 0x10, 0x10, 0x11, 0x0e], // c
+    [0x01, 0x01, 0x0d, 0x13, 0x11, 0x11, 0x0f], // d
+    [0x00, 0x00, 0x0e, 0x11, 0x1f, 0x10, 0x0e], // e
+    [0x06, 0x09, 0x08, 0x1c, 0x08, 0x08, 0x08], // f
+    [0x00, 0x0f, 0x11, 0x11, 0x0f, 0x01, 0x0e], // g
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11], // h
+    [0x04, 0x00, 0x0c, 0x04, 0x04, 0x04, 0x0e], // i
+    [0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0c], // j
+    [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12], // k
+    [0x0c, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e], // l
+    [0x00, 0x00, 0x1a, 0x15, 0x15, 0x11, 0x11], // m
+    [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11], // n
+    [0x00, 0x00, 0x0e, 0x11, 0x11, 0x11, 0x0e], // o
+    [0x00, 0x00, 0x1e, 0x11, 0x1e, 0x10, 0x10], // p
+    [0x00, 0x00, 0x0d, 0x13, 0x0f, 0x01, 0x01], // q
+    [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10], // r
+    [0x00, 0x00, 0x0e, 0x10, 0x0e, 0x01, 0x1e], // s
+    [0x08, 0x08, 0x1c, 0x08, 0x08, 0x09, 0x06], // t
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0d], // u
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0a, 0x04], // v
+    [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0a], // w
+    [0x00, 0x00, 0x11, 0x0a, 0x04, 0x0a, 0x11], // x
+    [0x00, 0x00, 0x11, 0x11, 0x0f, 0x01, 0x0e], // y
+    [0x00, 0x00, 0x1f, 0x02, 0x04, 0x08, 0x1f], // z
+    [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02], // {
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // |
+    [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08], // }
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00], // ~
+];
+/// The middle of a cell, where lines cross it.
+const MID_COLUMN: usize = 2;
+const MID_ROW: usize = 5;
+
+/// Accented letters, drawn as the letter with a mark over or under it.
+const ACCENTED: [(char, char, Mark); 13] = [
+    ('à', 'a', Mark::Grave),
+    ('â', 'a', Mark::Circumflex),
+    ('ç', 'c', Mark::Cedilla),
+    ('é', 'e', Mark::Acute),
+    ('è', 'e', Mark::Grave),
+    ('ê', 'e', Mark::Circumflex),
+    ('ë', 'e', Mark::Diaeresis),
+    ('ô', 'o', Mark::Circumflex),
+    ('ù', 'u', Mark::Grave),
+    ('û', 'u', Mark::Circumflex),
+    ('É', 'E', Mark::Acute),
+    ('È', 'E', Mark::Grave),
+    ('Ç', 'C', Mark::Cedilla),
+];
+
+#[derive(Clone, Copy)]
+enum Mark {
+    Acute,
+    Grave,
+    Circumflex,
+    Diaeresis,
+    Cedilla,
+}
+
+/// The font pixel rows of `ch`. Characters the font lacks are an empty box.
+fn glyph(ch: char) -> [Row; CELL_HEIGHT] {
+    let mut rows = [0; CELL_HEIGHT];
+    let ascii = (ch as usize).checked_sub(FIRST_GLYPH as usize).and_then(|i| FONT.get(i));
+    if let Some(font) = ascii {
+        // The glyphs take the left five columns, leaving a gap between them
+        for (row, bits) in rows[GLYPH_TOP..].iter_mut().zip(font) {
+            *row = bits << 1;
+        }
+    } else if let Some(&(_, base, mark)) = ACCENTED.iter().find(|&&(accented, ..)| accented == ch) {
+        rows = glyph(base);
+        // Over the letter, which starts lower in the lowercase
+        let top = if base.is_uppercase() { 0 } else { GLYPH_TOP };
+        match mark {
+            Mark::Acute => {
+                set(&mut rows, top, 3);
+                set(&mut rows, top + 1, 2);
+            }
+            Mark::Grave => {
+                set(&mut rows, top, 1);
+                set(&mut rows, top + 1, 2);
+            }
+            Mark::Circumflex => {
+                set(&mut rows, top, 2);
+                set(&mut rows, top + 1, 1);
+                set(&mut rows, top + 1, 3);
+            }
+            Mark::Diaeresis => {
+                set(&mut rows, top + 1, 1);
+                set(&mut rows, top + 1, 3);
+            }
+            Mark::Cedilla => hline(&mut rows, CELL_HEIGHT - 1, 1, 2),
+        }
+    } else if !symbol(ch, &mut rows) {
+        hline(&mut rows, GLYPH_TOP, 0, 4);
+        hline(&mut rows, GLYPH_TOP + 6, 0, 4);
+        vline(&mut rows, 0, GLYPH_TOP, GLYPH_TOP + 6);
+        vline(&mut rows, 4, GLYPH_TOP, GLYPH_TOP + 6);
+    }
+    rows
+}
+
+/// Draws the box-drawing, block, Braille or other symbol `ch` into
+/// `rows`, if it is one the game draws with. Lines meet those of the
+/// neighbouring cells.
+fn symbol(ch: char, rows: &mut [Row; CELL_HEIGHT]) -> bool {
+    let last_column = CELL_WIDTH - 1;
+    let last_row = CELL_HEIGHT - 1;
+    match ch {
+        '─' => hline(rows, MID_ROW, 0, last_column),
+        '│' => vline(rows, MID_COLUMN, 0, last_row),
+        '═' => {
+            hline(rows, MID_ROW - 1, 0, last_column);
+            hline(rows, MID_ROW + 1, 0, last_column);
+        }
+        '║' => {
+            vline(rows, MID_COLUMN - 1, 0, last_row);
+            vline(rows, MID_COLUMN + 1, 0, last_row);
+        }
+        '┌' | '┐' | '└' | '┘' => corner(rows, ch == '┌' || ch == '└', ch == '┌' || ch == '┐', &[(MID_COLUMN, MID_ROW)]),
+        '╔' | '╗' | '╚' | '╝' => {
+            // The outer line turns a pixel before the middle, the inner one
+            // a pixel after it
+            let (right, down) = (ch == '╔' || ch == '╚', ch == '╔' || ch == '╗');
+            let (before, after) = ((MID_COLUMN - 1, MID_ROW - 1), (MID_COLUMN + 1, MID_ROW + 1));
+            let outer = (if right { before.0 } else { after.0 }, if down { before.1 } else { after.1 });
+            let inner = (if right { after.0 } else { before.0 }, if down { after.1 } else { before.1 });
+            corner(rows, right, down, &[outer, inner]);
+        }
+        '▀' => rows[..CELL_HEIGHT / 2].fill(FULL_ROW),
+        '▄' => rows[CELL_HEIGHT / 2..].fill(FULL_ROW),
+        '█' => rows.fill(FULL_ROW),
+        '╱' | '╲' => {
+            for row in 0..CELL_HEIGHT {
+                let step = if ch == '╲' { row } else { last_row - row };
+                set(rows, row, step * CELL_WIDTH / CELL_HEIGHT);
+            }
+        }
+        '▲' | '▼' => {
+            let widths = [0b001100, 0b001100, 0b011110, 0b011110, 0b111111];
+            for (i, bits) in widths.into_iter().enumerate() {
+                let row = if ch == '▲' { MID_ROW - 2 + i } else { MID_ROW + 2 - i };
+                rows[row] = bits;
+            }
+        }
+        '◄' | '►' => {
+            let widths: [Row; 5] = [0b000011, 0b001111, 0b111111, 0b001111, 0b000011];
+            for (i, bits) in widths.into_iter().enumerate() {
+                rows[MID_ROW - 2 + i] = if ch == '◄' { bits } else { bits.reverse_bits() >> 2 };
+            }
+        }
+        '·' => set(rows, MID_ROW, MID_COLUMN),
+        '\u{2800}'..='\u{28ff}' => {
+            // Two columns of four dots, numbered down the left column, then
+            // the right, with the bottom row last
+            let dots = ch as u32 - 0x2800;
+            let positions = [(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (1, 2), (0, 3), (1, 3)];
+            for (bit, (column, row)) in positions.into_iter().enumerate() {
+                if dots >> bit & 1 == 1 {
+                    hline(rows, 1 + row * 2, 1 + column * 3, 2 + column * 3);
+                }
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Draws lines turning at each of `turns`, a (column, row), on to the
+/// right edge of the cell or the left one, and on to its bottom or its top.
+fn corner(rows: &mut [Row; CELL_HEIGHT], right: bool, down: bool, turns: &[(usize, usize)]) {
+    for &(column, row) in turns {
+        if right {
+            hline(rows, row, column, CELL_WIDTH - 1);
+        } else {
+            hline(rows, row, 0, column);
+        }
+        if down {
+            vline(rows, column, row, CELL_HEIGHT - 1);
+        } else {
+            vline(rows, column, 0, row);
+        }
+    }
+}
+
+fn set(rows: &mut [Row; CELL_HEIGHT], row: usize, column: usize) {
+    rows[row] |= 1 << (CELL_WIDTH - 1 - column);
+}
+
+fn hline(rows: &mut [Row; CELL_HEIGHT], row: usize, from: usize, to: usize) {
+    for column in from..=to {
+        set(rows, row, column);
+    }
+}
+
+fn vline(rows: &mut [Row; CELL_HEIGHT], column: usize, from: usize, to: usize) {
+    for row in from..=to {
+        set(rows, row, column);
+    }
+}
@@ -0,0 +1,76 @@
+//! The best score ever reached, persisted in the data directory.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::storage;
+
+const FILE_NAME: &str = "highscore";
+
+pub struct HighScore {
+    path: Option<PathBuf>,
+    best: u32,
+}
+
+/// Reads a stored high score; anything unreadable counts as none.
+fn parse(text: &str) -> u32 {
+    text.trim().parse().unwrap_or(0)
+}
+
+impl HighScore {
+    /// Reads the stored high score. A missing or unreadable file counts as no
+    /// record yet; it is simply overwritten by the next one.
+    pub fn load() -> Self {
+        let path = storage::data_file(FILE_NAME);
+        let best = path.as_ref().and_then(|path| fs::read_to_string(path).ok()).map_or(0, |text| parse(&text));
+        HighScore { path, best }
+    }
+
+    pub fn best(&self) -> u32 {
+        self.best
+    }
+
+    /// Records `score` if it beats the best so far, writing it straight to
+    /// disk. Returns true on a new record.
+    ///
+    /// The best so far is the file's as it is now, which another session of
+    /// the same server may have raised since this one read it.
+    pub fn submit(&mut self, score: u32) -> bool {
+        if score <= self.best {
+            return false;
+        }
+        let Some(path) = &self.path else {
+            self.best = score;
+            return true;
+        };
+        let mut record = false;
+        // Losing the file only costs the record, never the running game
+        let _ = storage::update(path, |text| {
+            self.best = self.best.max(parse(text));
+            record = score > self.best;
+            self.best = self.best.max(score);
+            record.then(|| format!("{}\n", score))
+        });
+        record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_record_set_elsewhere_is_not_written_over() {
+        let path = std::env::temp_dir().join(format!("term_invaders-highscore-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut first = HighScore { path: Some(path.clone()), best: 0 };
+        let mut second = HighScore { path: Some(path.clone()), best: 0 };
+        assert!(first.submit(500));
+        assert!(!second.submit(300));
+        assert_eq!(second.best(), 500);
+        assert!(second.submit(700));
+        assert!(!first.submit(600));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "700\n");
+        let _ = fs::remove_file(&path);
+    }
+}
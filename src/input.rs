@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+/// A backend-independent key press.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Left,
+    Right,
+    Up,
+    Down,
+    Enter,
+    Escape,
+    Backspace,
+    /// A function key, counted from 1 for F1.
+    Function(u8),
+    /// A character key being let go of. Only some backends can tell, so
+    /// nothing may depend on receiving it.
+    Released(char),
+    /// The mouse pointer moving over screen cell (x, y), or the left button
+    /// going down there. Only sent once [`Input::capture_mouse`] was called.
+    Mouse { x: i32, y: i32, clicked: bool },
+    /// A gamepad button going down, or repeating while held.
+    Pad(PadButton),
+}
+
+/// The gamepad buttons the game reads, the d-pad and the left stick both
+/// counting as directions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PadButton {
+    Left,
+    Right,
+    Up,
+    Down,
+    A,
+    Start,
+}
+
+/// Something the game can read key presses from.
+pub trait Input {
+    /// Waits at most `timeout` for a key press.
+    fn poll_key(&mut self, timeout: Duration) -> Option<Key>;
+
+    /// Starts reporting the mouse as [`Key::Mouse`] events, on backends that
+    /// can read it.
+    fn capture_mouse(&mut self) {}
+
+    /// Whether the keys have stopped coming for good, as when a player at
+    /// the other end of a connection hangs up.
+    fn is_closed(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,339 @@
+//! Which keys do what.
+//!
+//! A [`KeyMap`] turns the keys backends report into [`Binding`]s, the
+//! logical inputs the scenes act on. The defaults can be changed in the
+//! `[keys]` section of the config file, one `name = key` line per binding:
+//!
+//! ```text
+//! [keys]
+//! fire = "z"
+//! move_left = ["a", "left"]
+//! ```
+//!
+//! Keys are single characters or the names `space`, `left`, `right`, `up`,
+//! `down`, `enter`, `escape`, `backspace` and `f1` to `f12`, and gamepad
+//! buttons are `pad_left`, `pad_right`, `pad_up`, `pad_down`, `pad_a` and
+//! `pad_start`. Binding a key takes it away from whatever it did before.
+//!
+//! The second ship of a co-op run has keys of its own, `p2_move_left`,
+//! `p2_move_right` and `p2_fire`, and so has the alien commander of a
+//! versus run, `aliens_aim_left`, `aliens_aim_right`, `aliens_fire` and
+//! `aliens_turn`. They take precedence over the others in those runs only.
+
+use crate::game::Action;
+use crate::input::{Key, PadButton};
+use crate::locale::tr;
+
+/// Something a key can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binding {
+    /// An input to the game being played.
+    Action(Action),
+    Pause,
+    Quit,
+    /// Moving the selection through a menu.
+    MenuUp,
+    MenuDown,
+}
+
+/// Bindings by the names the config file knows them as.
+const NAMES: [(&str, Binding); 9] = [
+    ("move_left", Binding::Action(Action::MoveLeft)),
+    ("move_right", Binding::Action(Action::MoveRight)),
+    ("fire", Binding::Action(Action::Fire)),
+    ("weapon", Binding::Action(Action::SwitchWeapon)),
+    ("bomb", Binding::Action(Action::Bomb)),
+    ("pause", Binding::Pause),
+    ("quit", Binding::Quit),
+    ("menu_up", Binding::MenuUp),
+    ("menu_down", Binding::MenuDown),
+];
+
+/// Player two's actions by the names the config file knows them as.
+const PLAYER_TWO_NAMES: [(&str, Action); 3] =
+    [("p2_move_left", Action::MoveLeft), ("p2_move_right", Action::MoveRight), ("p2_fire", Action::Fire)];
+
+/// Player two plays on the left of the keyboard, leaving the arrows and
+/// space to player one.
+const PLAYER_TWO_DEFAULTS: [(Key, Action); 3] =
+    [(Key::Char('a'), Action::MoveLeft), (Key::Char('d'), Action::MoveRight), (Key::Char('w'), Action::Fire)];
+
+/// The alien commander's orders by the names the config file knows them as.
+const ALIENS_NAMES: [(&str, Action); 4] = [
+    ("aliens_aim_left", Action::AimLeft),
+    ("aliens_aim_right", Action::AimRight),
+    ("aliens_fire", Action::AlienFire),
+    ("aliens_turn", Action::TurnFormation),
+];
+
+/// The alien commander sits where player two would.
+const ALIENS_DEFAULTS: [(Key, Action); 4] = [
+    (Key::Char('a'), Action::AimLeft),
+    (Key::Char('d'), Action::AimRight),
+    (Key::Char('w'), Action::AlienFire),
+    (Key::Char('s'), Action::TurnFormation),
+];
+
+/// Besides the arrows, movement also answers to `a`/`d` and vi's `h`/`l`,
+/// and menus to vi's `k`/`j`.
+const DEFAULTS: [(Key, Binding); 21] = [
+    (Key::Char('a'), Binding::Action(Action::MoveLeft)),
+    (Key::Char('h'), Binding::Action(Action::MoveLeft)),
+    (Key::Left, Binding::Action(Action::MoveLeft)),
+    (Key::Char('d'), Binding::Action(Action::MoveRight)),
+    (Key::Char('l'), Binding::Action(Action::MoveRight)),
+    (Key::Right, Binding::Action(Action::MoveRight)),
+    (Key::Char(' '), Binding::Action(Action::Fire)),
+    (Key::Char('w'), Binding::Action(Action::SwitchWeapon)),
+    (Key::Char('b'), Binding::Action(Action::Bomb)),
+    (Key::Char('p'), Binding::Pause),
+    (Key::Char('q'), Binding::Quit),
+    (Key::Up, Binding::MenuUp),
+    (Key::Char('k'), Binding::MenuUp),
+    (Key::Down, Binding::MenuDown),
+    (Key::Char('j'), Binding::MenuDown),
+    (Key::Pad(PadButton::Left), Binding::Action(Action::MoveLeft)),
+    (Key::Pad(PadButton::Right), Binding::Action(Action::MoveRight)),
+    (Key::Pad(PadButton::A), Binding::Action(Action::Fire)),
+    (Key::Pad(PadButton::Start), Binding::Pause),
+    (Key::Pad(PadButton::Up), Binding::MenuUp),
+    (Key::Pad(PadButton::Down), Binding::MenuDown),
+];
+
+#[derive(Clone, Debug)]
+pub struct KeyMap {
+    bindings: Vec<(Key, Binding)>,
+    /// The keys of the second ship in co-op.
+    player_two: ActionKeys,
+    /// The keys of the alien commander in versus.
+    aliens: ActionKeys,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        KeyMap {
+            bindings: DEFAULTS.to_vec(),
+            player_two: ActionKeys { names: &PLAYER_TWO_NAMES, keys: PLAYER_TWO_DEFAULTS.to_vec() },
+            aliens: ActionKeys { names: &ALIENS_NAMES, keys: ALIENS_DEFAULTS.to_vec() },
+        }
+    }
+}
+
+impl KeyMap {
+    /// The default bindings with the config file's `[keys]` entries applied.
+    /// Entries naming an unknown binding or key are skipped, like unknown
+    /// settings are.
+    pub fn from_entries(entries: &[(String, String)]) -> Self {
+        let mut keymap = KeyMap::default();
+        for (name, value) in entries {
+            let keys: Option<Vec<Key>> = split_list(value).into_iter().map(parse_key).collect();
+            let Some(keys) = keys.filter(|keys| !keys.is_empty()) else {
+                continue;
+            };
+            if let Some(&(_, binding)) = NAMES.iter().find(|(known, _)| known == name) {
+                keymap.bind(binding, &keys);
+            } else {
+                keymap.player_two.bind_named(name, &keys);
+                keymap.aliens.bind_named(name, &keys);
+            }
+        }
+        keymap
+    }
+
+    /// Makes `keys`, and only them, trigger `binding`.
+    pub fn bind(&mut self, binding: Binding, keys: &[Key]) {
+        self.bindings.retain(|&(key, bound)| bound != binding && !keys.contains(&key));
+        self.bindings.extend(keys.iter().map(|&key| (key, binding)));
+    }
+
+    /// The keys bound to `binding`, in the order they were bound.
+    pub fn keys_for(&self, binding: Binding) -> Vec<Key> {
+        self.bindings.iter().filter(|&&(_, bound)| bound == binding).map(|&(key, _)| key).collect()
+    }
+
+    /// The `[keys]` entries that give these bindings, for the bindings that
+    /// differ from the defaults.
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let defaults = KeyMap::default();
+        let entry = |name: &str, keys: Vec<Key>| {
+            let keys: Vec<String> = keys.into_iter().map(config_name).collect();
+            let value = match keys.as_slice() {
+                [key] => key.clone(),
+                keys => format!("[{}]", keys.join(", ")),
+            };
+            (name.to_string(), value)
+        };
+        let second = [(&self.player_two, &defaults.player_two), (&self.aliens, &defaults.aliens)];
+        let second = second.into_iter().flat_map(|(keys, defaults)| {
+            keys.names
+                .iter()
+                .filter(move |&&(_, action)| keys.keys_for(action) != defaults.keys_for(action))
+                .map(move |&(name, action)| (name, keys.keys_for(action)))
+        });
+        NAMES
+            .iter()
+            .filter(|&&(_, binding)| self.keys_for(binding) != defaults.keys_for(binding))
+            .map(|&(name, binding)| (name, self.keys_for(binding)))
+            .chain(second)
+            .map(|(name, keys)| entry(name, keys))
+            .collect()
+    }
+
+    /// The co-op action of player two `key` is bound to, if any.
+    pub fn player_two(&self, key: Key) -> Option<Action> {
+        self.player_two.action(key)
+    }
+
+    /// The order to the aliens `key` gives in versus, if any.
+    pub fn aliens(&self, key: Key) -> Option<Action> {
+        self.aliens.action(key)
+    }
+
+    /// What `key` is bound to, if anything. A released key maps to the
+    /// binding of the key itself.
+    pub fn binding(&self, key: Key) -> Option<Binding> {
+        let key = match key {
+            Key::Released(c) => Key::Char(c),
+            key => key,
+        };
+        self.bindings.iter().find(|&&(bound, _)| bound == key).map(|&(_, binding)| binding)
+    }
+
+    /// The name of the first key bound to `binding`, for prompts.
+    pub fn key_name(&self, binding: Binding) -> String {
+        self.bindings
+            .iter()
+            .find(|&&(_, bound)| bound == binding)
+            .map_or_else(|| "?".to_string(), |&(key, _)| key_name(key))
+    }
+}
+
+/// The keys of the second player's actions, which have a set of keys of
+/// their own.
+#[derive(Clone, Debug)]
+struct ActionKeys {
+    /// The actions by the names the config file knows them as.
+    names: &'static [(&'static str, Action)],
+    keys: Vec<(Key, Action)>,
+}
+
+impl ActionKeys {
+    /// Makes `keys`, and only them, trigger the action called `name`, if
+    /// it is one of these.
+    fn bind_named(&mut self, name: &str, keys: &[Key]) {
+        let Some(&(_, action)) = self.names.iter().find(|(known, _)| *known == name) else {
+            return;
+        };
+        self.keys.retain(|&(key, bound)| bound != action && !keys.contains(&key));
+        self.keys.extend(keys.iter().map(|&key| (key, action)));
+    }
+
+    fn keys_for(&self, action: Action) -> Vec<Key> {
+        self.keys.iter().filter(|&&(_, bound)| bound == action).map(|&(key, _)| key).collect()
+    }
+
+    /// The action `key` is bound to, if any. A released key maps to the
+    /// action of the key itself.
+    fn action(&self, key: Key) -> Option<Action> {
+        let key = match key {
+            Key::Released(c) => Key::Char(c),
+            key => key,
+        };
+        self.keys.iter().find(|&&(bound, _)| bound == key).map(|&(_, action)| action)
+    }
+}
+
+impl Binding {
+    /// Every binding, in the order settings list them.
+    pub fn all() -> impl Iterator<Item = Binding> {
+        NAMES.iter().map(|&(_, binding)| binding)
+    }
+
+    pub fn label(self) -> &'static str {
+        tr(match self {
+            Binding::Action(Action::MoveLeft) => "key_move_left",
+            Binding::Action(Action::MoveRight) => "key_move_right",
+            Binding::Action(Action::Fire) => "key_fire",
+            Binding::Action(Action::SwitchWeapon) => "key_weapon",
+            Binding::Action(Action::Bomb) => "key_bomb",
+            Binding::Action(_) => "key_other",
+            Binding::Pause => "key_pause",
+            Binding::Quit => "key_quit",
+            Binding::MenuUp => "key_menu_up",
+            Binding::MenuDown => "key_menu_down",
+        })
+    }
+}
+
+/// The items of a `["a", "b"]` list, or the value itself if it is none.
+fn split_list(value: &str) -> Vec<&str> {
+    let value = value.trim();
+    match value.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+        Some(items) => items.split(',').map(str::trim).filter(|item| !item.is_empty()).collect(),
+        None => vec![value],
+    }
+}
+
+/// Reads a key as written in the config file, quoted or not.
+fn parse_key(text: &str) -> Option<Key> {
+    let unquoted = ['"', '\''].iter().find_map(|&quote| text.strip_prefix(quote)?.strip_suffix(quote));
+    let text = unquoted.unwrap_or(text);
+    let mut chars = text.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(Key::Char(c));
+    }
+    let name = text.to_ascii_lowercase();
+    let key = match name.as_str() {
+        "space" => Key::Char(' '),
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "enter" => Key::Enter,
+        "escape" | "esc" => Key::Escape,
+        "backspace" => Key::Backspace,
+        "pad_left" => Key::Pad(PadButton::Left),
+        "pad_right" => Key::Pad(PadButton::Right),
+        "pad_up" => Key::Pad(PadButton::Up),
+        "pad_down" => Key::Pad(PadButton::Down),
+        "pad_a" => Key::Pad(PadButton::A),
+        "pad_start" => Key::Pad(PadButton::Start),
+        _ => {
+            let n: u8 = name.strip_prefix('f')?.parse().ok()?;
+            if !(1..=12).contains(&n) {
+                return None;
+            }
+            Key::Function(n)
+        }
+    };
+    Some(key)
+}
+
+/// How `key` is written in the config file, the way [`parse_key`] reads it.
+fn config_name(key: Key) -> String {
+    match key {
+        Key::Char(' ') => "space".to_string(),
+        Key::Char('"') | Key::Released('"') => "'\"'".to_string(),
+        Key::Char(c) | Key::Released(c) => format!("\"{}\"", c),
+        Key::Pad(button) => format!("pad_{:?}", button).to_lowercase(),
+        key => key_name(key).to_lowercase(),
+    }
+}
+
+/// The name `key` is shown by.
+pub fn key_name(key: Key) -> String {
+    match key {
+        Key::Char(' ') => "SPACE".to_string(),
+        Key::Char(c) | Key::Released(c) => c.to_string(),
+        Key::Left => "LEFT".to_string(),
+        Key::Right => "RIGHT".to_string(),
+        Key::Up => "UP".to_string(),
+        Key::Down => "DOWN".to_string(),
+        Key::Enter => "ENTER".to_string(),
+        Key::Escape => "ESC".to_string(),
+        Key::Backspace => "BACKSPACE".to_string(),
+        Key::Function(n) => format!("F{}", n),
+        Key::Mouse { .. } => "MOUSE".to_string(),
+        Key::Pad(button) => format!("PAD {:?}", button).to_uppercase(),
+    }
+}
@@ -0,0 +1,177 @@
+//! The top-ten table, persisted in the data directory.
+//!
+//! Each line of the file is an entry's initials and score, followed by
+//! `assisted` for runs played with assist options on.
+//!
+//! The daily challenge has a table of its own, which the first day that
+//! is not its own empties; its file starts with a `day N` line.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::storage;
+
+const FILE_NAME: &str = "leaderboard";
+const DAILY_FILE_NAME: &str = "daily_leaderboard";
+pub const MAX_ENTRIES: usize = 10;
+pub const INITIALS_LEN: usize = 3;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub initials: String,
+    pub score: u32,
+    /// Whether the run had assist options on.
+    pub assisted: bool,
+}
+
+pub struct Leaderboard {
+    path: Option<PathBuf>,
+    entries: Vec<Entry>,
+    /// For the daily table, the day its scores were made on.
+    day: Option<u64>,
+}
+
+/// Parses one `INITIALS SCORE [assisted]` line, rejecting anything
+/// malformed.
+fn parse_entry(line: &str) -> Option<Entry> {
+    let mut fields = line.split_whitespace();
+    let initials = fields.next()?;
+    let score = fields.next()?.parse().ok()?;
+    let assisted = match fields.next() {
+        None => false,
+        Some("assisted") => true,
+        Some(_) => return None,
+    };
+    if fields.next().is_some() || initials.chars().count() != INITIALS_LEN {
+        return None;
+    }
+    Some(Entry { initials: initials.to_string(), score, assisted })
+}
+
+/// Reads a table's lines, best first. Malformed lines are dropped.
+fn parse_entries<'a>(lines: impl Iterator<Item = &'a str>) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = lines.filter_map(parse_entry).collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+    entries.truncate(MAX_ENTRIES);
+    entries
+}
+
+impl Leaderboard {
+    /// Reads the stored table. Malformed lines are dropped and a missing file
+    /// is an empty table.
+    pub fn load() -> Self {
+        let path = storage::data_file(FILE_NAME);
+        let text = path.as_ref().and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+        Leaderboard::from_text(path, &text, None)
+    }
+
+    /// Reads the daily challenge's table for `day`; a table left from
+    /// another day counts as empty.
+    pub fn load_daily(day: u64) -> Self {
+        let path = storage::data_file(DAILY_FILE_NAME);
+        let text = path.as_ref().and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+        Leaderboard::from_text(path, &text, Some(day))
+    }
+
+    fn from_text(path: Option<PathBuf>, text: &str, day: Option<u64>) -> Self {
+        let mut board = Leaderboard { path, entries: Vec::new(), day };
+        board.read(text);
+        board
+    }
+
+    /// Replaces the entries with those of `text`, the file's contents.
+    fn read(&mut self, text: &str) {
+        let mut lines = text.lines();
+        let current = match self.day {
+            Some(day) => lines.next() == Some(&format!("day {}", day)),
+            None => true,
+        };
+        self.entries = if current { parse_entries(lines) } else { Vec::new() };
+    }
+
+    /// For the daily table, the day it is of.
+    pub fn day(&self) -> Option<u64> {
+        self.day
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Whether `score` would earn a place in the table.
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0
+            && (self.entries.len() < MAX_ENTRIES
+                || self.entries.last().is_some_and(|last| score > last.score))
+    }
+
+    /// Adds a score and saves the table, returning the new entry's rank
+    /// (0-based), or `None` if it did not make the cut.
+    ///
+    /// The table it goes into is the file's as it is now, so the entries
+    /// other sessions of the same server have made since are kept.
+    pub fn insert(&mut self, initials: &str, score: u32, assisted: bool) -> Option<usize> {
+        let Some(path) = self.path.clone() else {
+            return self.add(initials, score, assisted);
+        };
+        let mut rank = None;
+        // Losing the file only costs the table, never the running game
+        let _ = storage::update(&path, |text| {
+            self.read(text);
+            rank = self.add(initials, score, assisted);
+            rank.map(|_| self.to_text())
+        });
+        rank
+    }
+
+    fn add(&mut self, initials: &str, score: u32, assisted: bool) -> Option<usize> {
+        if !self.qualifies(score) {
+            return None;
+        }
+        // Ties go below existing entries: the earlier run keeps its rank
+        let rank = self.entries.iter().position(|entry| score > entry.score).unwrap_or(self.entries.len());
+        self.entries.insert(rank, Entry { initials: initials.to_string(), score, assisted });
+        self.entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+
+    fn to_text(&self) -> String {
+        let header = self.day.map(|day| format!("day {}\n", day)).unwrap_or_default();
+        let text: String = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let tag = if entry.assisted { " assisted" } else { "" };
+                format!("{} {}{}\n", entry.initials, entry.score, tag)
+            })
+            .collect();
+        header + &text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_made_elsewhere_are_kept() {
+        let path = std::env::temp_dir().join(format!("term_invaders-leaderboard-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut first = Leaderboard::from_text(Some(path.clone()), "", Some(7));
+        let mut second = Leaderboard::from_text(Some(path.clone()), "", Some(7));
+        assert_eq!(first.insert("AAA", 100, false), Some(0));
+        assert_eq!(second.insert("BBB", 300, true), Some(0));
+        assert_eq!(first.insert("CCC", 200, false), Some(1));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "day 7\nBBB 300 assisted\nCCC 200\nAAA 100\n");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn another_days_table_counts_as_empty() {
+        let board = Leaderboard::from_text(None, "day 6\nAAA 100\n", Some(7));
+        assert!(board.entries().is_empty());
+        let board = Leaderboard::from_text(None, "AAA 100\nnot an entry\nBB 5\nCCC 300\n", None);
+        let scores: Vec<u32> = board.entries().iter().map(|entry| entry.score).collect();
+        assert_eq!(scores, [300, 100]);
+    }
+}
@@ -0,0 +1,306 @@
+//! Levels, which say what each wave of aliens is made of.
+//!
+//! A level is a data file in the format of [`crate::pack`], such as:
+//!
+//! ```toml
+//! name = "Cover"
+//! # Shown before the level in the campaign, a line to a string
+//! story = ["Bunkers have gone up."]
+//! # One row of the formation to a string: A is an alien, B an armored one
+//! # taking two hits, and a dot or a space is a gap
+//! layout = ["AAAAAA", "A.AA.A"]
+//! speed = 100          # percent of the usual pace of the formation
+//! descent = 1          # rows the formation comes down at each wall
+//! fire_interval = 100  # percent of the usual time between alien shots
+//! # A bunker, built a few times over across the field above the ships
+//! bunkers = [".###.", "##.##"]
+//! # In builds with the scripting feature, scripts run for each alien and
+//! # for the boss fought after the level, a line to a string
+//! script = ["if rand(50) == 0 { alien.fire(); }"]
+//! boss_script = ["alien.y = 2 + tick / 20 % 3;"]
+//! ```
+//!
+//! Formation rows repeat across fields wider than they are, and leave out
+//! whatever rows a short field has no room for. The campaign is built into
+//! the binary, and [`crate::mods`] can bring level packs to play in its
+//! place; endless runs count waves through the levels, the boss waves
+//! aside, and start them over after the last, while campaign runs end there.
+
+use std::sync::OnceLock;
+
+use crate::game::{AlienKind, BOSS_WAVE_INTERVAL, MAX_ALIEN_ROWS};
+use crate::pack::Parser;
+#[cfg(feature = "scripting")]
+use crate::script::Script;
+
+/// The campaign's level files, in the order they are played.
+const CAMPAIGN: [&str; 10] = [
+    include_str!("../levels/01-first-contact.toml"),
+    include_str!("../levels/02-cover.toml"),
+    include_str!("../levels/03-checkerboard.toml"),
+    include_str!("../levels/04-vanguard.toml"),
+    include_str!("../levels/05-wedge.toml"),
+    include_str!("../levels/06-iron-line.toml"),
+    include_str!("../levels/07-swarm.toml"),
+    include_str!("../levels/08-fortress.toml"),
+    include_str!("../levels/09-downpour.toml"),
+    include_str!("../levels/10-last-stand.toml"),
+];
+
+/// The longest a bunker can be, in rows.
+pub const MAX_BUNKER_ROWS: usize = 3;
+/// The most lines a story can have, and the most characters to a line, so
+/// it fits the smallest playfield.
+const MAX_STORY_LINES: usize = 4;
+const MAX_STORY_WIDTH: usize = 30;
+
+/// Levels from a mod, played in place of the campaign's.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LevelPack {
+    /// The mod they come from, which replays name them by.
+    pub id: &'static str,
+    pub levels: Vec<Level>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Level {
+    pub name: &'static str,
+    /// The lines introducing the level in the campaign; may be empty.
+    pub story: &'static [&'static str],
+    /// The formation by rows, from the top.
+    pub layout: &'static [&'static str],
+    /// Percent of the usual pace of the formation.
+    pub speed: u32,
+    /// Rows the formation comes down each time it reaches a wall,
+    /// multiplied by the difficulty's.
+    pub descent: u16,
+    /// Percent of the difficulty's usual time between alien shots.
+    pub fire_interval: u32,
+    /// The shape of a bunker, `#` for a block; empty for no bunkers.
+    pub bunkers: &'static [&'static str],
+    /// The [`crate::script`] each alien runs, by lines; may be empty.
+    pub script: &'static [&'static str],
+    /// The script of the boss fought after the level; may be empty.
+    pub boss_script: &'static [&'static str],
+}
+
+impl Level {
+    /// Parses a level file.
+    pub fn parse(text: &str) -> Result<Level, String> {
+        let mut level = Level {
+            name: "",
+            story: &[],
+            layout: &[],
+            speed: 100,
+            descent: 1,
+            fire_interval: 100,
+            bunkers: &[],
+            script: &[],
+            boss_script: &[],
+        };
+        let mut parser = Parser::new(text);
+        while let Some(key) = parser.key()? {
+            let line = parser.line();
+            let bad = |what: &str| format!("line {}: {} {}", line, key, what);
+            let value = parser.value()?;
+            let percent = || match value.number() {
+                Some(percent @ 10..=1000) => Ok(percent),
+                _ => Err(bad("must be a percentage from 10 to 1000")),
+            };
+            match key {
+                "name" => level.name = value.text().ok_or_else(|| bad("must be a string"))?,
+                "story" => {
+                    let most = format!("must be a list of up to {} lines", MAX_STORY_LINES);
+                    let lines = value.rows().filter(|lines| lines.len() <= MAX_STORY_LINES).ok_or_else(|| bad(&most))?;
+                    if lines.iter().any(|line| line.chars().count() > MAX_STORY_WIDTH) {
+                        return Err(bad(&format!("lines can be at most {} characters", MAX_STORY_WIDTH)));
+                    }
+                    level.story = lines;
+                }
+                "layout" => {
+                    let rows = value.rows().filter(|rows| (1..=MAX_ALIEN_ROWS).contains(&rows.len()));
+                    let rows = rows.ok_or_else(|| bad(&format!("must be a list of 1 to {} rows", MAX_ALIEN_ROWS)))?;
+                    if rows.iter().any(|row| row.is_empty() || row.chars().any(|cell| cell_kind(cell).is_err())) {
+                        return Err(bad("rows can only hold A, B, dots and spaces"));
+                    }
+                    if !rows.iter().flat_map(|row| row.chars()).any(|cell| matches!(cell_kind(cell), Ok(Some(_)))) {
+                        return Err(bad("needs at least one alien"));
+                    }
+                    if !same_length(rows) {
+                        return Err(bad("rows must all be as long as each other"));
+                    }
+                    level.layout = rows;
+                }
+                "speed" => level.speed = percent()?,
+                "descent" => match value.number() {
+                    Some(rows @ 1..=3) => level.descent = rows as u16,
+                    _ => return Err(bad("must be 1, 2 or 3 rows")),
+                },
+                "fire_interval" => level.fire_interval = percent()?,
+                "bunkers" => {
+                    let rows = value.rows().filter(|rows| rows.len() <= MAX_BUNKER_ROWS);
+                    let rows = rows.ok_or_else(|| bad(&format!("must be a list of up to {} rows", MAX_BUNKER_ROWS)))?;
+                    if rows.iter().flat_map(|row| row.chars()).any(|cell| !matches!(cell, '#' | '.' | ' ')) {
+                        return Err(bad("rows can only hold #, dots and spaces"));
+                    }
+                    if !same_length(rows) {
+                        return Err(bad("rows must all be as long as each other"));
+                    }
+                    level.bunkers = rows;
+                }
+                "script" | "boss_script" => {
+                    let lines = value.rows().ok_or_else(|| bad("must be a list of lines"))?;
+                    compile(lines).map_err(|err| bad(&err))?;
+                    if key == "script" {
+                        level.script = lines;
+                    } else {
+                        level.boss_script = lines;
+                    }
+                }
+                _ => return Err(format!("line {}: unknown setting '{}'", line, key)),
+            }
+        }
+        if level.layout.is_empty() {
+            return Err("a level needs a layout".to_string());
+        }
+        Ok(level)
+    }
+
+    /// The level as a level file, which [`Level::parse`] reads back.
+    pub fn to_text(&self) -> String {
+        let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+        let list = |rows: &[&str]| {
+            let rows: String = rows.iter().map(|row| format!("    {},\n", quote(row))).collect();
+            format!("[\n{}]", rows)
+        };
+        let mut text = format!("name = {}\n", quote(self.name));
+        if !self.story.is_empty() {
+            text.push_str(&format!("story = {}\n", list(self.story)));
+        }
+        text.push_str(&format!("layout = {}\n", list(self.layout)));
+        text.push_str(&format!(
+            "speed = {}\ndescent = {}\nfire_interval = {}\n",
+            self.speed, self.descent, self.fire_interval
+        ));
+        if !self.bunkers.is_empty() {
+            text.push_str(&format!("bunkers = {}\n", list(self.bunkers)));
+        }
+        if !self.script.is_empty() {
+            text.push_str(&format!("script = {}\n", list(self.script)));
+        }
+        if !self.boss_script.is_empty() {
+            text.push_str(&format!("boss_script = {}\n", list(self.boss_script)));
+        }
+        text
+    }
+
+    /// What stands in column `col` of formation row `row`, if anything,
+    /// with the row repeating past its end.
+    pub fn alien(&self, row: usize, col: usize) -> Option<AlienKind> {
+        let cells = self.layout.get(row)?;
+        let cell = cells.chars().nth(col % cells.chars().count())?;
+        cell_kind(cell).ok().flatten()
+    }
+}
+
+/// What a layout cell stands for, or an error for a cell that is neither
+/// an alien nor a gap.
+fn cell_kind(cell: char) -> Result<Option<AlienKind>, ()> {
+    match cell {
+        'A' => Ok(Some(AlienKind::Grunt)),
+        'B' => Ok(Some(AlienKind::Armored)),
+        '.' | ' ' => Ok(None),
+        _ => Err(()),
+    }
+}
+
+fn same_length(rows: &[&str]) -> bool {
+    rows.windows(2).all(|pair| pair[0].chars().count() == pair[1].chars().count())
+}
+
+/// Checks that a script compiles, which without the scripting feature none
+/// does.
+#[cfg(feature = "scripting")]
+fn compile(lines: &[&str]) -> Result<(), String> {
+    Script::compile(lines).map(|_| ())
+}
+
+#[cfg(not(feature = "scripting"))]
+fn compile(_: &[&str]) -> Result<(), String> {
+    Err("needs a build with the scripting feature".to_string())
+}
+
+/// The levels of the campaign, in the order they are played.
+pub fn campaign() -> &'static [Level] {
+    static LEVELS: OnceLock<Vec<Level>> = OnceLock::new();
+    LEVELS.get_or_init(|| {
+        CAMPAIGN
+            .iter()
+            .enumerate()
+            .map(|(i, text)| Level::parse(text).unwrap_or_else(|err| panic!("campaign level {}: {}", i + 1, err)))
+            .collect()
+    })
+}
+
+/// The one of `levels` wave `wave` plays, or `None` for a boss wave.
+pub fn for_wave(levels: &'static [Level], wave: u32) -> Option<&'static Level> {
+    number(wave).map(|number| &levels[(number as usize - 1) % levels.len()])
+}
+
+/// The number of the level wave `wave` plays, counting from 1 and on past
+/// the end of the campaign, or `None` for a boss wave.
+pub fn number(wave: u32) -> Option<u32> {
+    if wave == 0 || wave.is_multiple_of(BOSS_WAVE_INTERVAL) {
+        return None;
+    }
+    Some(wave - wave / BOSS_WAVE_INTERVAL)
+}
+
+/// The levels cleared by the time wave `wave` starts.
+pub fn cleared_before(wave: u32) -> u32 {
+    let wave = wave.saturating_sub(1);
+    wave - wave / BOSS_WAVE_INTERVAL
+}
+
+/// The wave the last of `levels` is played in.
+pub fn final_wave(levels: &[Level]) -> u32 {
+    let count = levels.len() as u32;
+    (1..).find(|&wave| number(wave) == Some(count)).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_bundled_level_parses_and_reads_back() {
+        assert_eq!(campaign().len(), CAMPAIGN.len());
+        for (i, text) in CAMPAIGN.iter().enumerate() {
+            let level = Level::parse(text).unwrap_or_else(|err| panic!("campaign level {}: {}", i + 1, err));
+            assert!(!level.name.is_empty(), "campaign level {} has a name", i + 1);
+            assert_eq!(Level::parse(&level.to_text()), Ok(level));
+        }
+    }
+
+    #[test]
+    fn malformed_levels_are_turned_down() {
+        let cases = [
+            ("layout = [\"AA\"]\ncolour = 3", "line 2: unknown setting 'colour'"),
+            ("layout = [\"AXA\"]", "line 1: layout rows can only hold A, B, dots and spaces"),
+            ("layout = [\"...\"]", "line 1: layout needs at least one alien"),
+            ("layout = [\"AAAA\", \"AA\"]", "line 1: layout rows must all be as long as each other"),
+            ("layout = [\"AA\"]\nbunkers = [\"###\", \"#\"]", "line 2: bunkers rows must all be as long as each other"),
+            ("layout = [\"AA\"]\nbunkers = [\"#=#\"]", "line 2: bunkers rows can only hold #, dots and spaces"),
+            ("layout = [\"AA\"]\nspeed = 5", "line 2: speed must be a percentage from 10 to 1000"),
+            ("layout = [\"AA\"]\ndescent = 4", "line 2: descent must be 1, 2 or 3 rows"),
+            ("name = \"Empty\"", "a level needs a layout"),
+        ];
+        for (text, error) in cases {
+            assert_eq!(Level::parse(text), Err(error.to_string()), "{:?}", text);
+        }
+        // Broken syntax comes back as an error too, whatever it says
+        for text in ["layout = [\"AA\"", "layout = [\"AA]", "layout \"AA\"", "name = ", "= 3", "layout = [AA]"] {
+            assert!(Level::parse(text).is_err(), "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Terminal Space Invaders.
+//!
+//! The game logic lives in [`game`] and knows nothing about terminals; the
+//! [`render`] module defines the [`render::Renderer`] trait frontends implement,
+//! and [`app`] drives the [`scene`] machine on any of them.
+
+pub mod achievements;
+pub mod app;
+pub mod bench;
+pub mod bot;
+pub mod campaign;
+pub mod challenge;
+pub mod cli;
+pub mod collision;
+pub mod config;
+pub mod debug;
+pub mod ecs;
+pub mod entity;
+pub mod env;
+pub mod error;
+pub mod game;
+#[cfg(feature = "gif")]
+pub mod gif;
+#[cfg(all(feature = "gamepad", target_os = "linux"))]
+pub mod gamepad;
+pub mod highscore;
+#[cfg(feature = "gif")]
+pub mod highlight;
+pub mod input;
+pub mod keymap;
+pub mod leaderboard;
+pub mod level;
+pub mod locale;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod logging;
+pub mod mods;
+pub mod net;
+#[cfg(feature = "online")]
+pub mod online;
+pub mod pack;
+pub mod particles;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod record;
+pub mod render;
+pub mod replay;
+pub mod rollback;
+pub mod scene;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod screenshot;
+#[cfg(feature = "scripting")]
+pub mod script;
+#[cfg(feature = "sound")]
+pub mod sound;
+pub mod spectate;
+pub mod stats;
+pub mod storage;
+pub mod tournament;
+#[cfg(feature = "web")]
+pub mod web;
+
+#[cfg(all(target_arch = "wasm32", not(feature = "web")))]
+compile_error!("wasm32 builds need the web feature, which brings the clock and randomness");
+#[cfg(all(windows, any(feature = "sixel", feature = "kitty")))]
+compile_error!("the sixel and kitty backends read the terminal with unix calls; Windows builds draw with crossterm");
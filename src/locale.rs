@@ -0,0 +1,221 @@
+//! The text the game shows, in the player's language.
+//!
+//! Every text has a key, and a language is a file of `key = "text"` entries
+//! in the format of [`crate::pack`]. `{}` in a text is a hole for what the
+//! game fills in, in order; `{0}`, `{1}` name the one to fill in, so a
+//! translation can put them in the order its language wants:
+//!
+//! ```toml
+//! wave_cleared = "VAGUE {} TERMINÉE"
+//! level_of = "NIVEAU {0} SUR {1}"
+//! ```
+//!
+//! English comes with the game, and is what the texts a translation leaves
+//! out are shown in; so does French. A `<language>.toml` file in the
+//! `locales` directory of the data directory brings another language, or
+//! changes texts of one that comes with the game. The language is the one
+//! the config file names, else the one `LC_ALL`, `LC_MESSAGES` or `LANG`
+//! say, as in `fr_FR.UTF-8`.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::sync::OnceLock;
+
+use crate::pack::Parser;
+use crate::storage;
+
+const DIR_NAME: &str = "locales";
+const ENGLISH: &str = include_str!("../locales/en.toml");
+/// The translations that come with the game, by language code.
+const BUILT_IN: [(&str, &str); 1] = [("fr", include_str!("../locales/fr.toml"))];
+
+/// The language in use, once [`init`] has picked it; English until then.
+static LOCALE: OnceLock<Locale> = OnceLock::new();
+
+pub struct Locale {
+    /// The language code, as in `fr`.
+    pub language: &'static str,
+    texts: HashMap<&'static str, &'static str>,
+}
+
+impl Locale {
+    pub fn english() -> Locale {
+        let mut locale = Locale { language: "en", texts: HashMap::new() };
+        locale.read(ENGLISH, false).expect("the English texts are well formed");
+        locale
+    }
+
+    /// The texts of `language` over the English ones, or `None` if there is
+    /// no translation to it.
+    pub fn load(language: &str) -> Result<Option<Locale>, String> {
+        let mut locale = Locale::english();
+        if language == locale.language {
+            return Ok(Some(locale));
+        }
+        let built_in = BUILT_IN.iter().find(|(code, _)| *code == language).map(|(_, text)| *text);
+        let file = storage::data_file(DIR_NAME)
+            .map(|dir| dir.join(format!("{}.toml", language)))
+            .filter(|path| path.exists());
+        if built_in.is_none() && file.is_none() {
+            return Ok(None);
+        }
+        if let Some(text) = built_in {
+            locale.read(text, true).map_err(|err| format!("{} translation: {}", language, err))?;
+        }
+        if let Some(path) = file {
+            let text = fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+            locale.read(String::leak(text), true).map_err(|err| format!("{}: {}", path.display(), err))?;
+        }
+        locale.language = String::leak(language.to_string());
+        Ok(Some(locale))
+    }
+
+    /// Reads the texts of a file over the ones there are. A translation
+    /// may only have the keys English has, with as many holes.
+    fn read(&mut self, text: &'static str, translation: bool) -> Result<(), String> {
+        let mut parser = Parser::new(text);
+        while let Some(key) = parser.key()? {
+            let line = parser.line();
+            let value = parser.value()?.text().ok_or_else(|| format!("line {}: {} must be a string", line, key))?;
+            if translation {
+                let Some(english) = self.texts.get(key) else {
+                    return Err(format!("line {}: unknown text '{}'", line, key));
+                };
+                if holes(value) != holes(english) {
+                    return Err(format!("line {}: {} must have as many {{}} as in English", line, key));
+                }
+            }
+            self.texts.insert(key, value);
+        }
+        Ok(())
+    }
+}
+
+fn holes(text: &str) -> usize {
+    text.matches('{').count()
+}
+
+/// Puts `locale` in use for the rest of the run. Only the first call counts.
+pub fn init(locale: Locale) {
+    let _ = LOCALE.set(locale);
+}
+
+/// The language the environment asks for: the first of `LC_ALL`,
+/// `LC_MESSAGES` and `LANG` set, unless it is the plain `C` locale.
+pub fn from_env() -> Option<String> {
+    let value = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())?;
+    let language = value.split(['_', '.', '@']).next().unwrap_or_default().to_ascii_lowercase();
+    Some(language).filter(|language| !language.is_empty() && language != "c" && language != "posix")
+}
+
+fn current() -> &'static Locale {
+    LOCALE.get_or_init(Locale::english)
+}
+
+/// The text for `key`, in the language in use.
+pub fn tr(key: &'static str) -> &'static str {
+    current().texts.get(key).copied().unwrap_or(key)
+}
+
+/// The text for `key` with `args` filled into its holes.
+pub fn tr_with(key: &'static str, args: &[&dyn Display]) -> String {
+    let text = tr(key);
+    let mut filled = String::new();
+    let mut next = 0;
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+        filled.push_str(&rest[..start]);
+        let index = rest[start + 1..end].parse().unwrap_or(next);
+        next = index + 1;
+        if let Some(arg) = args.get(index) {
+            filled.push_str(&arg.to_string());
+        }
+        rest = &rest[end + 1..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// The keys of the texts in `text`, a language's file.
+    fn keys(text: &'static str) -> Vec<&'static str> {
+        let mut parser = Parser::new(text);
+        let mut keys = Vec::new();
+        while let Some(key) = parser.key().unwrap() {
+            parser.value().unwrap();
+            keys.push(key);
+        }
+        keys
+    }
+
+    /// The keys `source` passes to [`tr`] and [`tr_with`]: the strings in
+    /// their first argument, such as those of a `match` picking the key.
+    fn looked_up(source: &str, found: &mut Vec<String>) {
+        for (start, _) in source.match_indices("tr(").chain(source.match_indices("tr_with(")) {
+            let before = source[..start].chars().next_back();
+            if before.is_some_and(|ch| ch.is_ascii_alphanumeric() || ch == '_') {
+                continue;
+            }
+            let argument = &source[start + source[start..].find('(').unwrap() + 1..];
+            let mut depth = 0;
+            let mut string: Option<String> = None;
+            for ch in argument.chars() {
+                match (string.as_mut(), ch) {
+                    (Some(_), '"') => found.extend(string.take()),
+                    (Some(text), ch) => text.push(ch),
+                    (None, '"') => string = Some(String::new()),
+                    (None, '(' | '[' | '{') => depth += 1,
+                    (None, ')' | ']' | '}' | ',') if depth == 0 => break,
+                    (None, ')' | ']' | '}') => depth -= 1,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn sources(dir: &Path, found: &mut Vec<String>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                sources(&path, found);
+            } else if path.extension().is_some_and(|extension| extension == "rs") {
+                let source = fs::read_to_string(&path).unwrap();
+                // Tests look nothing up the players see
+                looked_up(source.split("#[cfg(test)]").next().unwrap(), found);
+            }
+        }
+    }
+
+    #[test]
+    fn every_language_has_every_text_the_game_shows() {
+        let mut used = Vec::new();
+        sources(&Path::new(env!("CARGO_MANIFEST_DIR")).join("src"), &mut used);
+        used.sort();
+        used.dedup();
+        // Texts the game shows are looked up all over it
+        assert!(used.len() > 100, "found only {} keys", used.len());
+
+        let english = keys(ENGLISH);
+        let missing: Vec<_> = used.iter().filter(|key| !english.contains(&key.as_str())).collect();
+        assert!(missing.is_empty(), "English has no text for {:?}", missing);
+        for (language, text) in BUILT_IN {
+            let translated = keys(text);
+            let missing: Vec<_> = english.iter().filter(|key| !translated.contains(key)).collect();
+            assert!(missing.is_empty(), "{} has no text for {:?}", language, missing);
+            // Nor any text English does not have, or with other holes
+            Locale::english().read(text, true).unwrap();
+        }
+    }
+}
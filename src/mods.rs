@@ -0,0 +1,174 @@
+//! Mods, found in the `mods` directory of the data directory, one to a
+//! directory named after the mod:
+//!
+//! ```text
+//! mods/neon/
+//!     mod.toml       name = "Neon Nights" and about = "...", both optional
+//!     sprites.toml   a sprite pack, as in render::sprites
+//!     theme.toml     a color theme, as in render::theme
+//!     levels/        level files, played in file name order in place of
+//!                    the campaign, scripts and all
+//! ```
+//!
+//! A mod brings any of these, and at most one mod is in use at a time, the
+//! one picked on the title screen. Mods are checked as they are found, so
+//! a broken one is listed with what is wrong with it rather than failing
+//! mid-game. The directory name keeps a mod's content apart from the
+//! game's and other mods': its theme is named `neon/<name>`, its campaign
+//! progress is kept on its own, and replays of its levels name it.
+
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::level::{Level, LevelPack};
+use crate::locale::{tr, tr_with};
+use crate::pack::Parser;
+use crate::render::sprites::{SpriteSet, PACK_FILE};
+use crate::render::theme::Theme;
+use crate::storage;
+
+const DIR_NAME: &str = "mods";
+const MANIFEST_FILE: &str = "mod.toml";
+const THEME_FILE: &str = "theme.toml";
+const LEVELS_DIR: &str = "levels";
+/// The longest an `about` line can be, so it fits the smallest playfield.
+const MAX_ABOUT_WIDTH: usize = 40;
+
+#[derive(Debug)]
+pub struct Mod {
+    /// The name of its directory, which namespaces its content.
+    pub id: &'static str,
+    /// What the title screen calls it: the manifest's name, or the id.
+    pub name: &'static str,
+    /// A line saying what it is about; may be empty.
+    pub about: &'static str,
+    pub sprites: Option<&'static SpriteSet>,
+    pub theme: Option<&'static Theme>,
+    pub levels: Option<&'static LevelPack>,
+}
+
+impl Mod {
+    /// What it brings, for the title screen: "sprites, theme, 8 levels".
+    pub fn contents(&self) -> String {
+        let mut contents = Vec::new();
+        if self.sprites.is_some() {
+            contents.push(tr("mod_sprites").to_string());
+        }
+        if self.theme.is_some() {
+            contents.push(tr("mod_theme").to_string());
+        }
+        if let Some(pack) = self.levels {
+            contents.push(tr_with("mod_levels", &[&pack.levels.len()]));
+        }
+        if contents.is_empty() {
+            return tr("mod_nothing").to_string();
+        }
+        contents.join(", ")
+    }
+
+    fn load(dir: &Path, id: &'static str) -> Result<Mod, String> {
+        let mut found = Mod { id, name: id, about: "", sprites: None, theme: None, levels: None };
+        let manifest = dir.join(MANIFEST_FILE);
+        if manifest.exists() {
+            let text = fs::read_to_string(&manifest).map_err(|err| format!("{}: {}", MANIFEST_FILE, err))?;
+            found.read_manifest(&text).map_err(|err| format!("{}: {}", MANIFEST_FILE, err))?;
+        }
+        let sprites = dir.join(PACK_FILE);
+        if sprites.exists() {
+            found.sprites = Some(SpriteSet::load(&sprites, SpriteSet::detect()).map_err(|err| relative(dir, err))?);
+        }
+        let theme = dir.join(THEME_FILE);
+        if theme.exists() {
+            found.theme = Some(Theme::load(&theme, id).map_err(|err| relative(dir, err))?);
+        }
+        let levels = dir.join(LEVELS_DIR);
+        if levels.is_dir() {
+            found.levels = Some(Box::leak(Box::new(LevelPack { id, levels: load_levels(&levels)? })));
+        }
+        Ok(found)
+    }
+
+    fn read_manifest(&mut self, text: &str) -> Result<(), String> {
+        let mut parser = Parser::new(text);
+        while let Some(key) = parser.key()? {
+            let line = parser.line();
+            if key != "name" && key != "about" {
+                return Err(format!("line {}: unknown setting '{}'", line, key));
+            }
+            let value = parser.value()?;
+            let text = value.text().ok_or_else(|| format!("line {}: {} must be a string", line, key))?;
+            match key {
+                "name" if !text.trim().is_empty() => self.name = text,
+                "name" => return Err(format!("line {}: name cannot be blank", line)),
+                "about" if text.chars().count() <= MAX_ABOUT_WIDTH => self.about = text,
+                _ => return Err(format!("line {}: about can be at most {} characters", line, MAX_ABOUT_WIDTH)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads the level files of a pack, in file name order.
+fn load_levels(dir: &Path) -> Result<Vec<Level>, String> {
+    let entries = fs::read_dir(dir).map_err(|err| format!("{}: {}", LEVELS_DIR, err))?;
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+        .collect();
+    paths.sort();
+    let mut levels = Vec::new();
+    for path in paths {
+        let file = path.file_name().unwrap_or_default().to_string_lossy();
+        let text = fs::read_to_string(&path).map_err(|err| format!("{}/{}: {}", LEVELS_DIR, file, err))?;
+        levels.push(Level::parse(&text).map_err(|err| format!("{}/{}: {}", LEVELS_DIR, file, err))?);
+    }
+    if levels.is_empty() {
+        return Err(format!("{} has no level files", LEVELS_DIR));
+    }
+    Ok(levels)
+}
+
+/// An error about a file of the mod in `dir`, naming it from there.
+fn relative(dir: &Path, err: String) -> String {
+    let prefix = format!("{}/", dir.display());
+    err.strip_prefix(&prefix).map(str::to_string).unwrap_or(err)
+}
+
+/// A directory of the mods directory, and the mod in it, or what is wrong
+/// with it.
+#[derive(Debug)]
+pub struct Entry {
+    pub id: &'static str,
+    pub loaded: Result<Mod, String>,
+}
+
+/// Every mod in the mods directory, by id. They are looked for once per
+/// run, the first time they are asked for.
+pub fn all() -> &'static [Entry] {
+    static MODS: OnceLock<Vec<Entry>> = OnceLock::new();
+    MODS.get_or_init(|| {
+        let Some(dir) = storage::data_file(DIR_NAME) else {
+            return Vec::new();
+        };
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let dirs = entries.filter_map(Result::ok).map(|entry| entry.path());
+        let mut dirs: Vec<_> = dirs.filter(|path| path.is_dir()).collect();
+        dirs.sort();
+        dirs.iter()
+            .filter_map(|dir| {
+                let id = &*String::leak(dir.file_name()?.to_str()?.to_string());
+                Some(Entry { id, loaded: Mod::load(dir, id) })
+            })
+            .collect()
+    })
+}
+
+/// The mod by the id `id`, if it is there and not broken.
+pub fn find(id: &str) -> Result<&'static Mod, String> {
+    let entry = all().iter().find(|entry| entry.id == id).ok_or_else(|| format!("no mod '{}' installed", id))?;
+    entry.loaded.as_ref().map_err(|err| format!("mod '{}': {}", id, err))
+}
+
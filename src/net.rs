@@ -0,0 +1,281 @@
+//! Network games, between a host and a guest who joins it.
+//!
+//! Both sides run the same game and only inputs cross the network; see
+//! [`crate::rollback`] for how they keep in step. The protocol is plain text
+//! over TCP, one line at a time:
+//!
+//! - On joining, the guest is sent the header of a replay (see
+//!   [`crate::replay`]), up to its `events` line: the settings of the run.
+//! - Each side sends its inputs for every tick as an `input` line with the
+//!   tick and the replay codes of the actions, or `-` for none, e.g.
+//!   `input 1234 LF`.
+//! - Now and then each side sends a `check` line with a tick and the digest
+//!   of its game as of that tick, e.g. `check 1200 9f3c2e61a4b7d850`, to tell
+//!   whether the two games still agree.
+//!
+//! Either side closing the connection leaves the other to play on alone.
+
+use std::cmp::Ordering;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::entity::Sprite;
+use crate::game::{Action, AlienKind, GameState, Player, Position, Settings, Weapon};
+use crate::replay::{self, Replay};
+
+/// The port `--host` listens on unless told another.
+pub const DEFAULT_PORT: u16 = 7_777;
+/// How long joining waits for the host to send the run's settings.
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long sending may hold up the game before the other side counts as
+/// gone.
+const SEND_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What arrived from the other side.
+pub enum Message {
+    /// The other side's actions at a tick.
+    Input { tick: u64, actions: Vec<Action> },
+    /// The digest of the other side's game as of a tick; see [`digest`].
+    Check { tick: u64, digest: u64 },
+}
+
+/// A connection to the other side of a network game.
+pub struct Link {
+    stream: TcpStream,
+    received: Receiver<Message>,
+    closed: bool,
+}
+
+impl Link {
+    /// Waits for a guest to join on `port`.
+    pub fn host(port: u16) -> io::Result<Link> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let (stream, _) = listener.accept()?;
+        Link::new(stream, None)
+    }
+
+    /// Joins the host at `address`, on the default port unless it names
+    /// one, handing back the settings of its run along with the link.
+    pub fn join(address: &str) -> Result<(Link, Settings), String> {
+        let address = if address.contains(':') { address.to_string() } else { format!("{}:{}", address, DEFAULT_PORT) };
+        let address = address.as_str();
+        let stream = TcpStream::connect(address).map_err(|err| format!("{}: {}", address, err))?;
+        let failed = |err: io::Error| format!("{}: {}", address, err);
+        stream.set_read_timeout(Some(JOIN_TIMEOUT)).map_err(failed)?;
+        let mut reader = BufReader::new(stream.try_clone().map_err(failed)?);
+        let mut header = String::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).map_err(failed)? == 0 {
+                return Err(format!("{}: the host hung up", address));
+            }
+            header.push_str(&line);
+            if line.trim() == "events" {
+                break;
+            }
+        }
+        // A host of another version, or a broken one, may send settings
+        // no run can be played with, which are turned down before the run
+        let replay = Replay::from_text(&header)
+            .map_err(|err| format!("{}: the host's run cannot be played here: {}", address, err))?;
+        stream.set_read_timeout(None).map_err(failed)?;
+        Ok((Link::new(stream, Some(reader)).map_err(failed)?, replay.settings))
+    }
+
+    /// Starts reading what the other side sends, on a thread of its own so
+    /// the game never waits on it. The guest's `reader` may have read ahead
+    /// of the settings already.
+    fn new(stream: TcpStream, reader: Option<BufReader<TcpStream>>) -> io::Result<Link> {
+        stream.set_write_timeout(Some(SEND_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        let reader = match reader {
+            Some(reader) => reader,
+            None => BufReader::new(stream.try_clone()?),
+        };
+        let (sender, received) = mpsc::channel();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                // Lines this version does not know are skipped
+                if let Some(message) = parse_message(&line) {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Link { stream, received, closed: false })
+    }
+
+    /// Sends the guest the settings of the run `state` is of.
+    pub fn send_settings(&mut self, state: &GameState) {
+        let settings = Settings { seed: Some(state.seed), ..state.settings.clone() };
+        self.send(&Replay { settings, events: Vec::new(), saved_at: None }.to_text());
+    }
+
+    /// Sends the actions `player`, this side's, takes at `tick`.
+    pub fn send_inputs(&mut self, tick: u64, player: usize, actions: &[Action]) {
+        let codes: String = actions.iter().map(|&action| replay::action_code(player, action)).collect();
+        self.send(&format!("input {} {}\n", tick, if codes.is_empty() { "-" } else { &codes }));
+    }
+
+    pub fn send_check(&mut self, tick: u64, digest: u64) {
+        self.send(&format!("check {} {:016x}\n", tick, digest));
+    }
+
+    fn send(&mut self, text: &str) {
+        if !self.closed && self.stream.write_all(text.as_bytes()).is_err() {
+            self.closed = true;
+        }
+    }
+
+    /// Everything that arrived since the last look.
+    pub fn receive(&mut self) -> Vec<Message> {
+        let mut messages = Vec::new();
+        loop {
+            match self.received.try_recv() {
+                Ok(message) => messages.push(message),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+        messages
+    }
+
+    /// Whether the other side has gone.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
+
+/// Reads one line from the other side.
+fn parse_message(line: &str) -> Option<Message> {
+    let mut fields = line.split_whitespace();
+    let kind = fields.next()?;
+    let tick = fields.next()?.parse().ok()?;
+    let value = fields.next()?;
+    match kind {
+        "input" if value == "-" => Some(Message::Input { tick, actions: Vec::new() }),
+        "input" => {
+            let actions = value.chars().map(|code| replay::action_from_code(code).map(|(_, action)| action));
+            Some(Message::Input { tick, actions: actions.collect::<Option<_>>()? })
+        }
+        "check" => Some(Message::Check { tick, digest: u64::from_str_radix(value, 16).ok()? }),
+        _ => None,
+    }
+}
+
+// --- Snapshots ---
+
+/// Everything of `state` there is to see, as `key value` lines. Lists of
+/// things are items separated by spaces, each a comma-separated list of
+/// numbers; times are in milliseconds of game time.
+pub fn snapshot(state: &GameState) -> String {
+    let ms = |time: Duration| time.as_millis() as u64;
+    let list = |items: Vec<Vec<u64>>| -> String {
+        let items: Vec<String> =
+            items.iter().map(|item| item.iter().map(u64::to_string).collect::<Vec<_>>().join(",")).collect();
+        items.join(" ")
+    };
+    let cell = |position: Position| {
+        let (x, y) = position.cell();
+        vec![x as u64, y as u64]
+    };
+    let world = &state.world;
+    let players = world.ships.iter().map(|ship| {
+        let Player { score, next_extra_life, invincible_until, one_up_until } = *ship;
+        let (x, lives) = (ship.x(), ship.lives());
+        vec![x as u64, score as u64, lives as u64, next_extra_life as u64, ms(invincible_until), ms(one_up_until)]
+    });
+    // Spread shots are drawn by the way they lean: 1 for left, 2 for right
+    let shots = world.shots.iter().map(|shot| {
+        let lean = match shot.sprite {
+            Sprite::Shot(Ordering::Less) => 1,
+            Sprite::Shot(Ordering::Greater) => 2,
+            _ => 0,
+        };
+        let owner = shot.faction.player().unwrap_or(0);
+        [cell(shot.position), vec![owner as u64, lean]].concat()
+    });
+    // Each alien with its kind, 1 for armored, and the hits it has taken
+    let aliens = world.aliens.iter().map(|alien| {
+        let kind = u64::from(alien.kind == AlienKind::Armored);
+        let alien_fields = vec![alien.x() as u64, alien.y() as u64, kind, alien.health.taken() as u64];
+        match &alien.dive {
+            Some(dive) => [alien_fields, vec![dive.slot_x as u64, dive.slot_y as u64]].concat(),
+            None => alien_fields,
+        }
+    });
+    let boss = world.boss().map(|boss| [cell(boss.position), vec![boss.health.hp as u64, boss.health.max as u64]]);
+    let boss = boss.map(|fields| fields.concat());
+    let lines = [
+        ("ticks", state.ticks.to_string()),
+        ("clock", ms(state.clock).to_string()),
+        ("score", state.score.to_string()),
+        ("wave", state.wave.to_string()),
+        ("combo", state.combo.to_string()),
+        ("bombs", state.bombs.to_string()),
+        ("weapon", u64::from(state.weapon == Weapon::Spread).to_string()),
+        ("shake_until", ms(state.shake_until).to_string()),
+        ("game_over", u64::from(state.game_over).to_string()),
+        ("intermission_until", list(state.intermission_until.iter().map(|&until| vec![ms(until)]).collect())),
+        ("laser_charge", list(state.laser_charge.iter().map(|&charge| vec![charge as u64]).collect())),
+        ("laser", list(state.laser.iter().map(|l| vec![l.x as u64, l.owner as u64, l.age as u64]).collect())),
+        (
+            "summary",
+            list(
+                state
+                    .summary
+                    .iter()
+                    .map(|s| vec![s.wave as u64, s.shots_fired as u64, s.accuracy as u64, ms(s.time), s.bonus as u64])
+                    .collect(),
+            ),
+        ),
+        ("players", list(players.collect())),
+        ("shots", list(shots.collect())),
+        ("alien_shots", list(world.alien_shots.iter().map(|shot| cell(shot.position)).collect())),
+        ("pickups", list(world.pickups.iter().map(|pickup| cell(pickup.position)).collect())),
+        ("aliens", list(aliens.collect())),
+        ("bunkers", list(state.bunkers.iter().map(|&(x, y)| vec![x as u64, y as u64]).collect())),
+        ("boss", list(boss.into_iter().collect())),
+        ("ufo", list(world.ufo().iter().map(|ufo| vec![ufo.x() as u64, ufo.points as u64]).collect())),
+        ("effects", list(state.effects.iter().map(|e| vec![e.x as u64, e.y as u64, e.age as u64]).collect())),
+        (
+            "popups",
+            list(state.popups.iter().map(|p| vec![p.x as u64, p.y as u64, p.points as u64, p.age as u64]).collect()),
+        ),
+    ];
+    lines.iter().map(|(key, value)| format!("{} {}\n", key, value)).collect()
+}
+
+/// The digest of `state`: the 64-bit FNV-1a hash of its [`snapshot`], as
+/// replays are digested. Two games that agree have the same one.
+pub fn digest(state: &GameState) -> u64 {
+    snapshot(state).bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_host_sending_a_run_no_one_can_play_is_turned_down() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let host = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"term_invaders replay 3\nseed 1\nsize 0x0\nevents\n").unwrap();
+        });
+        let err = Link::join(&address).err().unwrap();
+        assert_eq!(err, format!("{}: the host's run cannot be played here: line 3: size must be at least 32x19", address));
+        host.join().unwrap();
+    }
+}
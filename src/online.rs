@@ -0,0 +1,154 @@
+//! The online leaderboard, for builds with the `online` feature whose
+//! config file names a server with an `online = http://host:port/path` line.
+//!
+//! The server speaks plain HTTP, without TLS, at that one URL:
+//!
+//! - `POST` sends a finished score as a form, e.g.
+//!   `name=ABC&score=1230&wave=4&replay=9f3c2e61a4b7d850`, where `replay`
+//!   is the digest of the run's replay (see [`crate::replay::Replay::digest`]).
+//! - `GET` answers with the global top ten as text, one `NAME SCORE` line
+//!   per entry, best first; anything after the score is ignored.
+//!
+//! Every request runs on a thread of its own with short timeouts, so a slow
+//! or missing server never holds up the game: scores it does not take are
+//! lost, and the table reads as offline.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::leaderboard::{Entry, INITIALS_LEN, MAX_ENTRIES};
+
+/// How long to wait on the server at each step before giving up on it.
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A finished score, as sent to the server.
+#[derive(Clone, Debug)]
+pub struct Submission {
+    pub initials: String,
+    pub score: u32,
+    pub wave: u32,
+    /// The digest of the run's replay, to tell runs apart.
+    pub replay: u64,
+}
+
+/// The server the scores go to and the top ten come from.
+#[derive(Clone, Debug)]
+pub struct Client {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Client {
+    /// Reads the server's address out of an `http://host[:port][/path]` URL.
+    pub fn new(url: &str) -> Result<Self, String> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| format!("'{}' is not an http:// URL", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => rest.split_at(slash),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| format!("bad port in '{}'", url))?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("no host in '{}'", url));
+        }
+        Ok(Client { host: host.to_string(), port, path: path.to_string() })
+    }
+
+    /// Sends `submission` in the background.
+    pub fn submit(&self, submission: &Submission) {
+        let client = self.clone();
+        let body = format!(
+            "name={}&score={}&wave={}&replay={:016x}",
+            submission.initials, submission.score, submission.wave, submission.replay
+        );
+        // Nobody waits for the answer; a lost score is the price of going
+        // offline
+        thread::spawn(move || client.request("POST", &body));
+    }
+
+    /// Starts fetching the global top ten.
+    pub fn fetch(&self) -> GlobalTable {
+        let client = self.clone();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(client.request("GET", "").ok().map(|body| parse_table(&body)));
+        });
+        GlobalTable::Loading(receiver)
+    }
+
+    /// Makes one HTTP/1.0 request, which keeps the answer free of chunked
+    /// encoding, and returns the body of a successful answer.
+    fn request(&self, method: &str, body: &str) -> io::Result<String> {
+        let address = (self.host.as_str(), self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address"))?;
+        let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        write!(
+            stream,
+            "{} {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: term_invaders/{}\r\n\
+             Content-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            self.path,
+            self.host,
+            env!("CARGO_PKG_VERSION"),
+            body.len(),
+            body,
+        )?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+        let status = head.split_whitespace().nth(1).unwrap_or_default();
+        if !status.starts_with('2') {
+            return Err(io::Error::other(format!("server answered {}", status)));
+        }
+        Ok(body.to_string())
+    }
+}
+
+/// Reads the `NAME SCORE` lines of the top ten, dropping malformed ones.
+fn parse_table(body: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = body
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let initials = fields.next().filter(|initials| initials.chars().count() == INITIALS_LEN)?;
+            let score = fields.next()?.parse().ok()?;
+            Some(Entry { initials: initials.to_string(), score, assisted: false })
+        })
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+    entries.truncate(MAX_ENTRIES);
+    entries
+}
+
+/// The global top ten, as far as it has come from the server.
+pub enum GlobalTable {
+    Loading(Receiver<Option<Vec<Entry>>>),
+    Loaded(Vec<Entry>),
+    /// The server could not be reached, or did not answer as it should.
+    Offline,
+}
+
+impl GlobalTable {
+    /// Takes the table in, if it has arrived.
+    pub fn poll(&mut self) {
+        let GlobalTable::Loading(receiver) = self else {
+            return;
+        };
+        *self = match receiver.try_recv() {
+            Ok(Some(entries)) => GlobalTable::Loaded(entries),
+            Err(TryRecvError::Empty) => return,
+            Ok(None) | Err(TryRecvError::Disconnected) => GlobalTable::Offline,
+        };
+    }
+}
@@ -0,0 +1,275 @@
+//! The small subset of TOML the game's data files are written in, sprite
+//! packs and levels alike: `name = value` entries, one to a line, with `#`
+//! comments. A value is a quoted string, a list of them in brackets, or a
+//! whole number.
+//!
+//! The strings read live as long as the game does. Each is leaked once,
+//! however often it is read again: reopening a file, or the level editor
+//! rebuilding its level, reuses what the last time left.
+
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+/// Every string and list of them handed out so far.
+static STRINGS: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+static LISTS: Mutex<BTreeSet<&'static [&'static str]>> = Mutex::new(BTreeSet::new());
+
+/// A copy of `text` that lasts as long as the game, the same one each time.
+pub fn intern(text: &str) -> &'static str {
+    let mut strings = STRINGS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(&found) = strings.get(text) {
+        return found;
+    }
+    let text = String::leak(text.to_string());
+    strings.insert(text);
+    text
+}
+
+/// A copy of `rows` that lasts as long as the game, the same one each time.
+pub fn intern_rows(rows: &[impl AsRef<str>]) -> &'static [&'static str] {
+    let rows: Vec<&'static str> = rows.iter().map(|row| intern(row.as_ref())).collect();
+    let mut lists = LISTS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(&found) = lists.get(&rows[..]) {
+        return found;
+    }
+    let rows = rows.leak();
+    lists.insert(rows);
+    rows
+}
+
+/// The value of an entry: a string, a list of strings, or a whole number.
+pub enum Value {
+    Text(&'static str),
+    List(&'static [&'static str]),
+    Number(u32),
+}
+
+impl Value {
+    pub fn text(&self) -> Option<&'static str> {
+        match self {
+            Value::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn rows(&self) -> Option<&'static [&'static str]> {
+        match self {
+            Value::List(rows) => Some(rows),
+            _ => None,
+        }
+    }
+
+    pub fn number(&self) -> Option<u32> {
+        match self {
+            Value::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the entries of a file one at a time, each a key then a value.
+pub struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Parser { text, pos: 0 }
+    }
+
+    fn rest(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
+    }
+
+    /// The line the parser is on, counted from 1.
+    pub fn line(&self) -> usize {
+        self.text[..self.pos].matches('\n').count() + 1
+    }
+
+    fn error(&self, what: &str) -> String {
+        format!("line {}: {}", self.line(), what)
+    }
+
+    /// Skips whitespace, newlines included, and comments.
+    fn skip_blank(&mut self) {
+        loop {
+            match self.peek() {
+                Some(ch) if ch.is_whitespace() => {
+                    self.bump();
+                }
+                Some('#') => {
+                    while self.peek().is_some_and(|ch| ch != '\n') {
+                        self.bump();
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// Reads the key and `=` of the next entry, or `None` at the end.
+    pub fn key(&mut self) -> Result<Option<&'a str>, String> {
+        self.skip_blank();
+        if self.peek().is_none() {
+            return Ok(None);
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-') {
+            self.bump();
+        }
+        let key = &self.text[start..self.pos];
+        self.skip_blank();
+        if key.is_empty() || self.bump() != Some('=') {
+            return Err(self.error("expected 'name = value'"));
+        }
+        Ok(Some(key))
+    }
+
+    pub fn value(&mut self) -> Result<Value, String> {
+        self.skip_blank();
+        if self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+            return self.number().map(Value::Number);
+        }
+        if self.peek() != Some('[') {
+            return Ok(Value::Text(self.string()?));
+        }
+        self.bump();
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank();
+            if self.peek() == Some(']') {
+                self.bump();
+                return Ok(Value::List(intern_rows(&items)));
+            }
+            items.push(self.string()?);
+            self.skip_blank();
+            match self.bump() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::List(intern_rows(&items))),
+                _ => return Err(self.error("expected ',' or ']' in list")),
+            }
+        }
+    }
+
+    /// Reads a whole number, which may have `_` between its digits.
+    fn number(&mut self) -> Result<u32, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|ch| ch.is_ascii_digit() || ch == '_') {
+            self.bump();
+        }
+        self.text[start..self.pos].replace('_', "").parse().map_err(|_| self.error("number out of range"))
+    }
+
+    /// Reads a `"basic"` string with backslash escapes or a `'literal'` one.
+    fn string(&mut self) -> Result<&'static str, String> {
+        let quote = match self.bump() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => return Err(self.error("expected a quoted string")),
+        };
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some(ch) if ch == quote => break,
+                Some('\\') if quote == '"' => {
+                    let escaped = match self.bump() {
+                        Some('\\') => '\\',
+                        Some('"') => '"',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let hex: String = (0..4).filter_map(|_| self.bump()).collect();
+                            u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("invalid \\u escape"))?
+                        }
+                        _ => return Err(self.error("unknown escape in string")),
+                    };
+                    text.push(escaped);
+                }
+                Some(ch) => text.push(ch),
+            }
+        }
+        Ok(intern(&text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::SpriteSize;
+    use crate::render::sprites::{SpriteSet, ASCII};
+
+    /// The sprite pack the README shows.
+    const PACK: &str = r#"
+        player = ["_/^\\_", "[===]"]      # up to 5x2
+        alien = ["(oo)", "/||\\", "^  ^"]  # up to 4x3
+        shot = "'"
+        shot_left = "\\"
+        shot_right = "/"
+        alien_shot = "*"
+        bunker = '#'
+        explosion = [" * ", "***", ". ."]
+    "#;
+
+    #[test]
+    fn a_well_formed_pack_reads_entry_by_entry() {
+        let mut parser = Parser::new("# a comment\nname = \"caf\\u00e9\"\nrows = ['a', \"b\",]\nevery = 1_000\n");
+        assert_eq!(parser.key(), Ok(Some("name")));
+        assert_eq!(parser.value().unwrap().text(), Some("café"));
+        assert_eq!(parser.key(), Ok(Some("rows")));
+        assert_eq!(parser.value().unwrap().rows(), Some(&["a", "b"][..]));
+        assert_eq!(parser.key(), Ok(Some("every")));
+        assert_eq!(parser.line(), 4);
+        assert_eq!(parser.value().unwrap().number(), Some(1000));
+        assert_eq!(parser.key(), Ok(None));
+
+        let sprites = SpriteSet::from_pack(PACK, &ASCII).unwrap();
+        assert_eq!(sprites.player, ["_/^\\_", "[===]"]);
+        assert_eq!(sprites.alien_size(), SpriteSize { width: 4, height: 3 });
+        assert_eq!(sprites.shots, ["'", "\\", "/"]);
+        assert_eq!(sprites.explosion.len(), 3);
+        // Whatever a pack leaves out keeps its usual look
+        assert_eq!(SpriteSet::from_pack("shot = '!'", &ASCII).unwrap().alien, ASCII.alien);
+    }
+
+    #[test]
+    fn a_missing_or_misshapen_sprite_is_turned_down() {
+        let error = |pack: &str| SpriteSet::from_pack(pack, &ASCII).unwrap_err();
+        assert_eq!(error("player = []"), "line 1: player needs rows of one and the same width");
+        assert_eq!(error("alien = ['', '']"), "line 1: alien needs rows of one and the same width");
+        assert_eq!(error("\nalien = ['oo', 'ooo']"), "line 2: alien needs rows of one and the same width");
+        assert_eq!(error("player = 'A'"), "line 1: player must be a list of rows");
+        assert_eq!(error("bunker = '##'"), "line 1: bunker must be a single character");
+        assert_eq!(error("explosion = []"), "line 1: explosion needs at least one non-empty frame");
+        assert_eq!(error("ship = ['A']"), "line 1: unknown sprite 'ship'");
+        assert_eq!(error("player = ['@@@@@@']"), "the player sprite is 6x1, but can be at most 5x2");
+        assert_eq!(error("alien = ['o', 'o', 'o', 'o']"), "the alien sprite is 1x4, but can be at most 4x3");
+        assert_eq!(error("shot = \"|"), "line 1: unterminated string");
+        assert_eq!(error("shot '|'"), "line 1: expected 'name = value'");
+    }
+
+    #[test]
+    fn reading_a_file_again_reuses_its_strings() {
+        let read = || {
+            let mut parser = Parser::new("rows = ['interned', 'twice']");
+            parser.key().unwrap();
+            parser.value().unwrap().rows().unwrap()
+        };
+        let (first, second) = (read(), read());
+        assert_eq!(first, ["interned", "twice"]);
+        assert!(std::ptr::eq(first, second));
+        assert!(std::ptr::eq(first[0], intern("interned")));
+    }
+}
@@ -0,0 +1,126 @@
+//! Short-lived particles thrown off by the action: debris flying out of
+//! explosions, exhaust trailing the ship as it moves and sparks where shots
+//! glance off the boss's armor.
+//!
+//! Particles are cosmetic only, so they draw on a random generator of their
+//! own: spawning more or fewer of them never changes how a seeded run plays
+//! out, and replays recorded without them still replay the same.
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Hundredths of a cell, the unit particle positions and speeds are kept in.
+const CELL: i32 = 100;
+/// How much falling debris speeds up every tick, in hundredths of a cell.
+const GRAVITY: i32 = 15;
+const DEBRIS_PER_EXPLOSION: usize = 6;
+const SPARKS_PER_HIT: usize = 3;
+
+const DEBRIS_GLYPHS: [&str; 4] = [".", ",", "'", "`"];
+const EXHAUST_GLYPHS: [&str; 2] = ["~", "-"];
+const SPARK_GLYPHS: [&str; 2] = ["*", "+"];
+
+/// What threw a particle off, which decides how it is drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParticleKind {
+    Debris,
+    Exhaust,
+    Spark,
+}
+
+#[derive(Clone)]
+pub struct Particle {
+    pub kind: ParticleKind,
+    /// Position in hundredths of a cell.
+    pub x: i32,
+    pub y: i32,
+    /// Velocity in hundredths of a cell per tick.
+    pub dx: i32,
+    pub dy: i32,
+    /// Ticks since the particle appeared, and how many it lasts.
+    pub age: u16,
+    pub lifetime: u16,
+    pub glyph: &'static str,
+}
+
+impl Particle {
+    /// The cell the particle is in, if it is on a `width` x `height` field
+    /// below the HUD row.
+    pub fn cell(&self, width: u16, height: u16) -> Option<(u16, u16)> {
+        let (x, y) = (self.x.div_euclid(CELL), self.y.div_euclid(CELL));
+        let on_field = (0..width as i32).contains(&x) && (1..=height as i32).contains(&y);
+        on_field.then_some((x as u16, y as u16))
+    }
+}
+
+#[derive(Clone)]
+pub struct Particles {
+    particles: Vec<Particle>,
+    rng: ChaCha8Rng,
+}
+
+impl Particles {
+    pub fn new(seed: u64) -> Self {
+        Particles { particles: Vec::new(), rng: ChaCha8Rng::seed_from_u64(seed) }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Particle> {
+        self.particles.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    /// Throws debris out of an explosion centered on the cell (x, y), in
+    /// every direction but mostly upwards, to fall back under gravity.
+    pub fn debris(&mut self, x: u16, y: u16) {
+        for _ in 0..DEBRIS_PER_EXPLOSION {
+            let dx = self.rng.gen_range(-80..=80);
+            let dy = self.rng.gen_range(-90..=20);
+            let lifetime = self.rng.gen_range(3..=6);
+            let glyph = DEBRIS_GLYPHS[self.rng.gen_range(0..DEBRIS_GLYPHS.len())];
+            self.spawn(ParticleKind::Debris, (x, y), (dx, dy), lifetime, glyph);
+        }
+    }
+
+    /// Puffs exhaust out of the cell (x, y) behind a ship moving in the
+    /// direction of `heading`, -1 for left and 1 for right.
+    pub fn exhaust(&mut self, x: u16, y: u16, heading: i32) {
+        let dx = -heading * self.rng.gen_range(40..=70);
+        let dy = self.rng.gen_range(-20..=0);
+        let glyph = EXHAUST_GLYPHS[self.rng.gen_range(0..EXHAUST_GLYPHS.len())];
+        self.spawn(ParticleKind::Exhaust, (x, y), (dx, dy), 2, glyph);
+    }
+
+    /// Scatters sparks back down from a shot glancing off armor at (x, y).
+    pub fn sparks(&mut self, x: u16, y: u16) {
+        for _ in 0..SPARKS_PER_HIT {
+            let dx = self.rng.gen_range(-120..=120);
+            let dy = self.rng.gen_range(30..=90);
+            let glyph = SPARK_GLYPHS[self.rng.gen_range(0..SPARK_GLYPHS.len())];
+            self.spawn(ParticleKind::Spark, (x, y), (dx, dy), 2, glyph);
+        }
+    }
+
+    fn spawn(&mut self, kind: ParticleKind, (x, y): (u16, u16), (dx, dy): (i32, i32), lifetime: u16, glyph: &'static str) {
+        // Start in the middle of the cell, so small speeds still round evenly
+        let (x, y) = (x as i32 * CELL + CELL / 2, y as i32 * CELL + CELL / 2);
+        self.particles.push(Particle { kind, x, y, dx, dy, age: 0, lifetime, glyph });
+    }
+
+    /// Moves every particle one tick along, dropping the ones that burned
+    /// out or left the `width` x `height` field.
+    pub fn update(&mut self, width: u16, height: u16) {
+        for particle in &mut self.particles {
+            particle.x += particle.dx;
+            particle.y += particle.dy;
+            particle.age += 1;
+            if particle.kind == ParticleKind::Debris {
+                particle.dy += GRAVITY;
+            }
+        }
+        self.particles
+            .retain(|particle| particle.age < particle.lifetime && particle.cell(width, height).is_some());
+    }
+}
@@ -0,0 +1,220 @@
+//! Recording a game as an [asciinema] cast, to share or embed it.
+//!
+//! A game started with `--record run.cast` plays as usual and writes every
+//! frame it presents to the cast as well, in the escape codes of a 24-bit
+//! color terminal. Frames are composed in a [`FrameBuffer`] of their own,
+//! whatever the backend draws with, so ncurses and the graphics backends
+//! record the same as crossterm does; only the cells that changed go out,
+//! timed from the first frame. The cast is in the asciinema v2 format: a
+//! JSON header line with the screen size, then a JSON array line for each
+//! frame, and a resize event when the screen changes size.
+//!
+//! [asciinema]: https://asciinema.org
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::input::{Input, Key};
+use crate::render::buffer::FrameBuffer;
+use crate::render::crossterm::write_changes;
+use crate::render::sprites::SpriteSet;
+use crate::render::theme::{ColorDepth, Theme, CLASSIC};
+use crate::render::{Backend, Color, EntityKind, Layout, Renderer};
+
+/// A cast file being written.
+pub struct Cast {
+    out: BufWriter<File>,
+    /// When the first frame was written, which the header goes out with.
+    started: Option<Instant>,
+    size: (i32, i32),
+    /// The first write that failed, after which nothing more is written.
+    error: Option<io::Error>,
+}
+
+impl Cast {
+    pub fn create(path: &Path) -> io::Result<Cast> {
+        let out = BufWriter::new(File::create(path)?);
+        Ok(Cast { out, started: None, size: (0, 0), error: None })
+    }
+
+    /// Writes the escape codes `data` of a frame the size of `size`.
+    fn frame(&mut self, size: (i32, i32), data: &[u8]) {
+        if self.error.is_none() {
+            if let Err(err) = self.write_frame(size, data) {
+                self.error = Some(err);
+            }
+        }
+    }
+
+    fn write_frame(&mut self, size: (i32, i32), data: &[u8]) -> io::Result<()> {
+        let Some(started) = self.started else {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            writeln!(
+                self.out,
+                "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}, \
+                 \"env\": {{\"TERM\": \"xterm-256color\"}}}}",
+                size.0, size.1, timestamp
+            )?;
+            self.started = Some(Instant::now());
+            self.size = size;
+            return self.write_event(Duration::ZERO, 'o', &String::from_utf8_lossy(data));
+        };
+        let elapsed = started.elapsed();
+        if size != self.size {
+            self.size = size;
+            self.write_event(elapsed, 'r', &format!("{}x{}", size.0, size.1))?;
+        }
+        self.write_event(elapsed, 'o', &String::from_utf8_lossy(data))
+    }
+
+    fn write_event(&mut self, elapsed: Duration, kind: char, data: &str) -> io::Result<()> {
+        writeln!(self.out, "[{:.6}, \"{}\", \"{}\"]", elapsed.as_secs_f64(), kind, json_escape(data))
+    }
+
+    /// Writes out what is left of the cast, reporting the first write that
+    /// failed, if one did.
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => self.out.flush(),
+        }
+    }
+}
+
+/// `text` as the inside of a JSON string.
+fn json_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            ch if (ch as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// A backend that plays as usual and records every frame it presents to
+/// a [`Cast`] as well.
+pub struct Recording<'a> {
+    backend: &'a mut dyn Backend,
+    cast: &'a mut Cast,
+    /// Whether frames are recorded in color, or in text attributes.
+    color: bool,
+    theme: &'static Theme,
+    frame: FrameBuffer,
+    /// What the cast shows, as of the last frame recorded.
+    shown: FrameBuffer,
+}
+
+impl<'a> Recording<'a> {
+    pub fn new(backend: &'a mut dyn Backend, cast: &'a mut Cast, color: bool) -> Self {
+        let (width, height) = backend.size();
+        let mut recording = Recording {
+            backend,
+            cast,
+            color,
+            theme: &CLASSIC,
+            frame: FrameBuffer::new(width as usize, height as usize),
+            shown: FrameBuffer::new(width as usize, height as usize),
+        };
+        recording.shown.invalidate(0, 0, width, height);
+        recording
+    }
+}
+
+impl Renderer for Recording<'_> {
+    fn clear(&mut self) {
+        self.backend.clear();
+        // The frame follows the screen, repainting all of it when it resizes
+        let (width, height) = self.backend.size();
+        if self.frame.size() != (width, height) {
+            self.frame.resize(width as usize, height as usize);
+            self.shown.resize(width as usize, height as usize);
+            self.shown.invalidate(0, 0, width, height);
+        }
+        self.frame.clear();
+    }
+
+    fn draw_str(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        self.backend.draw_str(x, y, text, color);
+        self.frame.draw_str(x, y, text, color);
+    }
+
+    fn present(&mut self) {
+        self.backend.present();
+        let (_, height) = self.frame.size();
+        if (0..height as usize).all(|y| self.frame.row(y) == self.shown.row(y)) {
+            return;
+        }
+        let mut out = Vec::new();
+        // Writing to memory does not fail
+        let _ = write_changes(&mut out, &self.frame, &self.shown, self.color, ColorDepth::TrueColor, self.theme);
+        self.shown.copy_from(&self.frame);
+        self.cast.frame(self.frame.size(), &out);
+    }
+
+    fn size(&self) -> (i32, i32) {
+        self.backend.size()
+    }
+
+    fn set_layout(&mut self, layout: &Layout) {
+        self.backend.set_layout(layout);
+    }
+
+    fn set_theme(&mut self, theme: &'static Theme) {
+        self.backend.set_theme(theme);
+        self.theme = theme;
+        // The same logical colors come out differently now
+        let (width, height) = self.frame.size();
+        self.shown.invalidate(0, 0, width, height);
+    }
+
+    fn rich_colors(&self) -> bool {
+        self.backend.rich_colors()
+    }
+
+    fn sprites(&self) -> &'static SpriteSet {
+        self.backend.sprites()
+    }
+
+    fn set_sprites(&mut self, sprites: &'static SpriteSet) {
+        self.backend.set_sprites(sprites);
+    }
+
+    fn bell(&mut self, flash: bool) {
+        self.backend.bell(flash);
+    }
+
+    // The cast gets the sprites as text, whatever the player's backend draws
+    fn draw_entity(&mut self, x: i32, y: i32, entity: EntityKind, rows: &[&str], color: Color) {
+        self.backend.draw_entity(x, y, entity, rows, color);
+        for (i, row) in rows.iter().enumerate() {
+            self.frame.draw_str(x, y + i as i32, row, color);
+        }
+    }
+
+    fn draw_pixels(&mut self, x: i32, y: i32, top: Option<Color>, bottom: Option<Color>) {
+        self.backend.draw_pixels(x, y, top, bottom);
+        self.frame.draw_pixels(x, y, top, bottom);
+    }
+}
+
+impl Input for Recording<'_> {
+    fn poll_key(&mut self, timeout: Duration) -> Option<Key> {
+        self.backend.poll_key(timeout)
+    }
+
+    fn capture_mouse(&mut self) {
+        self.backend.capture_mouse();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.backend.is_closed()
+    }
+}
@@ -0,0 +1,107 @@
+use super::pixels::{self, UPPER_HALF};
+use super::{Color, Renderer};
+
+/// A single character cell of a [`FrameBuffer`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub color: Option<Color>,
+    /// Only set by half-block pixels, for the lower pixel.
+    pub background: Option<Color>,
+}
+
+const BLANK: Cell = Cell { ch: ' ', color: None, background: None };
+/// A cell no frame ever holds, standing for content that must be repainted.
+const UNKNOWN: Cell = Cell { ch: '\0', color: None, background: None };
+
+/// An in-memory grid of cells, used by backends that have to paint whole
+/// frames themselves.
+pub struct FrameBuffer {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        FrameBuffer { width, height, cells: vec![BLANK; width * height] }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Changes the dimensions of the buffer, blanking its contents.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.cells = vec![BLANK; width * height];
+    }
+
+    /// Returns one row of cells.
+    pub fn row(&self, y: usize) -> &[Cell] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    /// Makes the contents of `other`, which must be as big, this buffer's.
+    pub fn copy_from(&mut self, other: &FrameBuffer) {
+        self.cells.copy_from_slice(&other.cells);
+    }
+
+    /// Marks the `width` x `height` cells from (x, y) as holding something no
+    /// frame matches, for buffers tracking what is on screen.
+    pub fn invalidate(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        let columns = x.max(0) as usize..((x + width).max(0) as usize).min(self.width);
+        for row in y.max(0) as usize..((y + height).max(0) as usize).min(self.height) {
+            self.cells[row * self.width..][columns.clone()].fill(UNKNOWN);
+        }
+    }
+}
+
+impl Renderer for FrameBuffer {
+    fn clear(&mut self) {
+        self.cells.fill(BLANK);
+    }
+
+    fn draw_str(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        if y < 0 || y as usize >= self.height {
+            return;
+        }
+        for (i, ch) in text.chars().enumerate() {
+            let cx = x + i as i32;
+            if cx < 0 {
+                continue;
+            }
+            if cx as usize >= self.width {
+                break;
+            }
+            self.cells[y as usize * self.width + cx as usize] = Cell { ch, color: Some(color), background: None };
+        }
+    }
+
+    fn draw_pixels(&mut self, x: i32, y: i32, top: Option<Color>, bottom: Option<Color>) {
+        match (top, bottom) {
+            (Some(top), Some(bottom)) if top != bottom => {
+                if (0..self.width as i32).contains(&x) && (0..self.height as i32).contains(&y) {
+                    let cell = Cell { ch: UPPER_HALF, color: Some(top), background: Some(bottom) };
+                    self.cells[y as usize * self.width + x as usize] = cell;
+                }
+            }
+            _ => {
+                if let Some((glyph, color)) = pixels::single_color_glyph(top, bottom) {
+                    self.draw_str(x, y, glyph, color);
+                }
+            }
+        }
+    }
+
+    fn present(&mut self) {}
+
+    fn size(&self) -> (i32, i32) {
+        (self.width as i32, self.height as i32)
+    }
+}
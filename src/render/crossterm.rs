@@ -0,0 +1,308 @@
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+    KeyboardEnhancementFlags, MouseButton, MouseEventKind, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{self, Attribute, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, queue, terminal};
+
+use super::buffer::FrameBuffer;
+use super::pixels;
+use super::sprites::SpriteSet;
+use super::theme::{ColorDepth, Paint, Theme, CLASSIC};
+use super::{Color, Emphasis, Renderer};
+use crate::input::{Input, Key};
+
+/// Renders through crossterm, so no curses library is needed at runtime.
+///
+/// Frames are composed in a [`FrameBuffer`] and written out in one go on
+/// `present`, but only the cells that changed since the frame before, which
+/// keeps slow links from repainting the whole screen ten times a second.
+/// Creating the renderer switches the terminal to raw mode on the alternate
+/// screen; dropping it restores it.
+pub struct CrosstermRenderer {
+    out: Stdout,
+    frame: FrameBuffer,
+    /// What the terminal shows, as of the last frame written.
+    shown: FrameBuffer,
+    color: bool,
+    depth: ColorDepth,
+    theme: &'static Theme,
+    sprites: &'static SpriteSet,
+    /// Whether the terminal was asked to report key releases.
+    enhanced: bool,
+    /// Whether the terminal was asked to report the mouse.
+    mouse: bool,
+}
+
+impl CrosstermRenderer {
+    /// Sets up the terminal; `color` can be turned off for monochrome output.
+    pub fn new(color: bool) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        // Failing past this point leaves the terminal as it was found
+        Self::set_up(color).inspect_err(|_| {
+            let mut out = io::stdout();
+            let _ = queue!(out, cursor::Show, terminal::LeaveAlternateScreen);
+            let _ = out.flush();
+            let _ = terminal::disable_raw_mode();
+        })
+    }
+
+    fn set_up(color: bool) -> io::Result<Self> {
+        let mut out = io::stdout();
+        queue!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+        // Terminals speaking the kitty keyboard protocol can report key
+        // releases, which lets the laser fire the moment space is let go
+        let enhanced = matches!(terminal::supports_keyboard_enhancement(), Ok(true));
+        if enhanced {
+            queue!(out, PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES))?;
+        }
+        out.flush()?;
+
+        let (width, height) = terminal::size()?;
+        let mut shown = FrameBuffer::new(width as usize, height as usize);
+        shown.invalidate(0, 0, width as i32, height as i32);
+        Ok(CrosstermRenderer {
+            out,
+            frame: FrameBuffer::new(width as usize, height as usize),
+            shown,
+            color,
+            depth: if color { ColorDepth::detect() } else { ColorDepth::Basic },
+            theme: &CLASSIC,
+            sprites: SpriteSet::detect(),
+            enhanced,
+            mouse: false,
+        })
+    }
+
+    /// Has the cells from (x, y) repainted with the next frame, whatever they
+    /// hold, for overlays drawn over the text.
+    pub fn invalidate(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        self.shown.invalidate(x, y, width, height);
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        write_changes(&mut self.out, &self.frame, &self.shown, self.color, self.depth, self.theme)?;
+        self.out.flush()?;
+        self.shown.copy_from(&self.frame);
+        Ok(())
+    }
+}
+
+impl Drop for CrosstermRenderer {
+    fn drop(&mut self) {
+        if self.enhanced {
+            let _ = queue!(self.out, PopKeyboardEnhancementFlags);
+        }
+        if self.mouse {
+            let _ = queue!(self.out, DisableMouseCapture);
+        }
+        let _ = queue!(self.out, ResetColor, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = self.out.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Queues the escape codes that turn the screen from `shown` into `frame`,
+/// cell by changed cell, for any terminal reached through `out`.
+pub fn write_changes(
+    out: &mut impl Write,
+    frame: &FrameBuffer,
+    shown: &FrameBuffer,
+    color: bool,
+    depth: ColorDepth,
+    theme: &Theme,
+) -> io::Result<()> {
+    queue!(out, ResetColor)?;
+    let mut current = None;
+    let mut background = None;
+    let mut emphasis = Emphasis::Normal;
+    for y in 0..frame.height() {
+        // The column the cursor stands at, after the last cell written
+        let mut column = None;
+        for (x, (cell, shown)) in frame.row(y).iter().zip(shown.row(y)).enumerate() {
+            if cell == shown {
+                continue;
+            }
+            if column != Some(x) {
+                queue!(out, cursor::MoveTo(x as u16, y as u16))?;
+            }
+            column = Some(x + 1);
+            if color && cell.background != background {
+                // Clearing the background resets the foreground as well
+                if cell.background.is_none() {
+                    queue!(out, ResetColor)?;
+                    current = None;
+                }
+                background = cell.background;
+                if let Some(color) = background {
+                    queue!(out, SetBackgroundColor(terminal_color(theme, depth, color)))?;
+                }
+            }
+            if color && cell.color != current {
+                current = cell.color;
+                match current {
+                    Some(color) => queue!(out, SetForegroundColor(terminal_color(theme, depth, color)))?,
+                    None => {
+                        queue!(out, ResetColor)?;
+                        background = None;
+                    }
+                }
+            }
+            // Without colors, entities stand out through text attributes
+            let wanted = if color { Emphasis::Normal } else { cell.color.map_or(Emphasis::Normal, Color::emphasis) };
+            if wanted != emphasis {
+                emphasis = wanted;
+                queue!(out, SetAttribute(Attribute::Reset))?;
+                if let Some(attribute) = terminal_attribute(emphasis) {
+                    queue!(out, SetAttribute(attribute))?;
+                }
+            }
+            queue!(out, Print(cell.ch))?;
+        }
+    }
+    if emphasis != Emphasis::Normal {
+        queue!(out, SetAttribute(Attribute::Reset))?;
+    }
+    queue!(out, ResetColor)?;
+    Ok(())
+}
+
+/// Picks the closest color the terminal can show for a logical color.
+fn terminal_color(theme: &Theme, depth: ColorDepth, color: Color) -> style::Color {
+    let rgb = theme.rgb(color);
+    match depth {
+        ColorDepth::Basic => basic_color(theme.paint(color)),
+        ColorDepth::Palette256 => style::Color::AnsiValue(rgb.to_palette256()),
+        ColorDepth::TrueColor => style::Color::Rgb { r: rgb.0, g: rgb.1, b: rgb.2 },
+    }
+}
+
+fn terminal_attribute(emphasis: Emphasis) -> Option<Attribute> {
+    match emphasis {
+        Emphasis::Normal => None,
+        Emphasis::Bold => Some(Attribute::Bold),
+        Emphasis::Dim => Some(Attribute::Dim),
+        Emphasis::Reverse => Some(Attribute::Reverse),
+    }
+}
+
+fn basic_color(paint: Paint) -> style::Color {
+    match paint {
+        Paint::Black => style::Color::Black,
+        Paint::Red => style::Color::Red,
+        Paint::Green => style::Color::Green,
+        Paint::Yellow => style::Color::Yellow,
+        Paint::Blue => style::Color::Blue,
+        Paint::Magenta => style::Color::Magenta,
+        Paint::Cyan => style::Color::Cyan,
+        Paint::White => style::Color::White,
+    }
+}
+
+impl Renderer for CrosstermRenderer {
+    fn clear(&mut self) {
+        self.frame.clear();
+    }
+
+    fn draw_str(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        self.frame.draw_str(x, y, text, color);
+    }
+
+    fn draw_pixels(&mut self, x: i32, y: i32, top: Option<Color>, bottom: Option<Color>) {
+        if self.color {
+            self.frame.draw_pixels(x, y, top, bottom);
+        } else if let Some((glyph, color)) = pixels::single_color_glyph(top, bottom) {
+            self.frame.draw_str(x, y, glyph, color);
+        }
+    }
+
+    fn present(&mut self) {
+        // A failed write only loses this frame; the next one repaints everything.
+        let _ = self.write_frame();
+    }
+
+    fn size(&self) -> (i32, i32) {
+        self.frame.size()
+    }
+
+    fn set_theme(&mut self, theme: &'static Theme) {
+        self.theme = theme;
+        // The same logical colors come out differently now
+        let (width, height) = self.frame.size();
+        self.shown.invalidate(0, 0, width, height);
+    }
+
+    fn rich_colors(&self) -> bool {
+        self.depth > ColorDepth::Basic
+    }
+
+    fn sprites(&self) -> &'static SpriteSet {
+        self.sprites
+    }
+
+    fn set_sprites(&mut self, sprites: &'static SpriteSet) {
+        self.sprites = sprites;
+    }
+
+    // A terminal set to flash on the bell does so on its own
+    fn bell(&mut self, _flash: bool) {
+        let _ = self.out.write_all(b"\x07");
+        let _ = self.out.flush();
+    }
+}
+
+impl Input for CrosstermRenderer {
+    fn poll_key(&mut self, timeout: Duration) -> Option<Key> {
+        if !event::poll(timeout).ok()? {
+            return None;
+        }
+        match event::read().ok()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => {
+                // Raw mode swallows SIGINT, so treat Ctrl-C like the quit key
+                if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+                    return Some(Key::Char('q'));
+                }
+                match key.code {
+                    KeyCode::Char(c) => Some(Key::Char(c)),
+                    KeyCode::Left => Some(Key::Left),
+                    KeyCode::Right => Some(Key::Right),
+                    KeyCode::Up => Some(Key::Up),
+                    KeyCode::Down => Some(Key::Down),
+                    KeyCode::Enter => Some(Key::Enter),
+                    KeyCode::Esc => Some(Key::Escape),
+                    KeyCode::Backspace => Some(Key::Backspace),
+                    KeyCode::F(n) => Some(Key::Function(n)),
+                    _ => None,
+                }
+            }
+            Event::Key(key) => match key.code {
+                KeyCode::Char(c) => Some(Key::Released(c)),
+                _ => None,
+            },
+            Event::Mouse(mouse) => {
+                let clicked = match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left) => true,
+                    MouseEventKind::Moved | MouseEventKind::Drag(_) => false,
+                    _ => return None,
+                };
+                Some(Key::Mouse { x: mouse.column as i32, y: mouse.row as i32, clicked })
+            }
+            Event::Resize(width, height) => {
+                self.frame.resize(width as usize, height as usize);
+                self.shown.resize(width as usize, height as usize);
+                self.shown.invalidate(0, 0, width as i32, height as i32);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn capture_mouse(&mut self) {
+        if queue!(self.out, EnableMouseCapture).and_then(|()| self.out.flush()).is_ok() {
+            self.mouse = true;
+        }
+    }
+}
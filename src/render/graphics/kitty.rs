@@ -0,0 +1,163 @@
+//! The kitty graphics protocol, which kitty, WezTerm and Konsole speak.
+//!
+//! Each sprite is sent once as a PNG for every color it is drawn in, and
+//! after that only placed, which keeps frames small. The terminal scales the
+//! images to the cells they cover.
+
+use std::collections::HashMap;
+use std::io::{self, Stdout, Write};
+
+use super::{bitmap, lit, query, Protocol};
+use crate::render::theme::Rgb;
+use crate::render::EntityKind;
+
+/// How many image pixels each bitmap pixel becomes, so the terminal's
+/// scaling keeps the edges sharp.
+const SCALE: usize = 4;
+/// The largest payload an escape sequence may carry.
+const CHUNK_SIZE: usize = 4096;
+
+/// The ids of the images already sent, by sprite and color.
+#[derive(Default)]
+pub struct Kitty {
+    images: HashMap<(EntityKind, Rgb), u32>,
+}
+
+impl Protocol for Kitty {
+    fn begin_frame(&mut self, out: &mut Stdout) -> io::Result<()> {
+        // Drop the previous frame's placements, keeping the images
+        out.write_all(b"\x1b_Ga=d,d=a,q=2\x1b\\")
+    }
+
+    fn place(&mut self, out: &mut Stdout, entity: EntityKind, columns: u16, rows: u16, rgb: Rgb) -> io::Result<()> {
+        let id = match self.images.get(&(entity, rgb)) {
+            Some(&id) => id,
+            None => {
+                let id = self.images.len() as u32 + 1;
+                transmit(out, id, &png(bitmap(entity), rgb))?;
+                self.images.insert((entity, rgb), id);
+                id
+            }
+        };
+        // C=1 leaves the cursor where it is
+        write!(out, "\x1b_Ga=p,i={},c={},r={},C=1,q=2\x1b\\", id, columns, rows)
+    }
+
+    fn finish(&mut self, out: &mut Stdout) -> io::Result<()> {
+        out.write_all(b"\x1b_Ga=d,d=A,q=2\x1b\\")
+    }
+}
+
+/// Sends a PNG as image `id`, in as many chunks as it takes.
+fn transmit(out: &mut Stdout, id: u32, png: &[u8]) -> io::Result<()> {
+    let payload = base64(png);
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(CHUNK_SIZE).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        if i == 0 {
+            write!(out, "\x1b_Ga=t,f=100,i={},q=2,m={};", id, more)?;
+        } else {
+            write!(out, "\x1b_Gm={};", more)?;
+        }
+        out.write_all(chunk)?;
+        out.write_all(b"\x1b\\")?;
+    }
+    Ok(())
+}
+
+/// Whether the terminal answers a graphics query before its device
+/// attributes; others ignore the query and only send the latter.
+pub fn detect() -> bool {
+    query(b"\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\").contains("\x1b_Gi=31;OK")
+}
+
+// --- PNG Encoding ---
+
+/// Encodes `bitmap` scaled up by [`SCALE`] as an RGBA PNG in a single color,
+/// leaving unlit pixels transparent. The image data is stored without
+/// compression; sprites are tiny and sent once.
+fn png(bitmap: &[&str], rgb: Rgb) -> Vec<u8> {
+    let width = bitmap.first().map_or(0, |row| row.len()) * SCALE;
+    let height = bitmap.len() * SCALE;
+    let mut pixels = Vec::with_capacity(height * (width * 4 + 1));
+    for y in 0..height {
+        // Every scanline starts with its filter type, none
+        pixels.push(0);
+        for x in 0..width {
+            let alpha = if lit(bitmap, width, height, x, y) { 255 } else { 0 };
+            pixels.extend_from_slice(&[rgb.0, rgb.1, rgb.2, alpha]);
+        }
+    }
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, RGBA, default compression, filtering and no interlace
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut png, b"IHDR", &header);
+    chunk(&mut png, b"IDAT", &zlib_stored(&pixels));
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps `data` in a zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = data.chunks(u16::MAX as usize).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        stream.push((i + 1 == blocks.len()) as u8);
+        let len = block.len() as u16;
+        stream.extend_from_slice(&len.to_le_bytes());
+        stream.extend_from_slice(&(!len).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { crc >> 1 ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::with_capacity(data.len().div_ceil(3) * 4);
+    for group in data.chunks(3) {
+        let bits = group.iter().enumerate().fold(0u32, |bits, (i, &byte)| bits | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= group.len() {
+                text.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
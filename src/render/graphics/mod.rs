@@ -0,0 +1,258 @@
+//! Backends drawing the ship and the aliens as images.
+//!
+//! Everything else is still text, drawn by a [`CrosstermRenderer`] underneath;
+//! after every frame the entities are painted over it as pixel art, in
+//! whichever image protocol the backend speaks. Whether the terminal
+//! understands it is asked once at startup, and terminals that don't get the
+//! text sprites instead.
+
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::{cursor, queue};
+
+use super::crossterm::CrosstermRenderer;
+use super::sprites::SpriteSet;
+use super::theme::{Rgb, Theme, CLASSIC};
+use super::{Color, EntityKind, Renderer};
+use crate::input::{Input, Key};
+
+#[cfg(feature = "kitty")]
+mod kitty;
+#[cfg(feature = "sixel")]
+mod sixel;
+
+/// How long to wait for the terminal to describe itself.
+const DETECT_TIMEOUT: Duration = Duration::from_millis(200);
+
+// --- Bitmaps ---
+// Lit pixels are `X`; each bitmap is stretched over the cells of its sprite.
+const PLAYER_BITMAP: [&str; 8] = [
+    "......X......",
+    ".....XXX.....",
+    ".....XXX.....",
+    ".XXXXXXXXXXX.",
+    "XXXXXXXXXXXXX",
+    "XXXXXXXXXXXXX",
+    "XXXXXXXXXXXXX",
+    "XXXXXXXXXXXXX",
+];
+const ALIEN_BITMAP: [&str; 8] = [
+    "..X.....X..",
+    "...X...X...",
+    "..XXXXXXX..",
+    ".XX.XXX.XX.",
+    "XXXXXXXXXXX",
+    "X.XXXXXXX.X",
+    "X.X.....X.X",
+    "...XX.XX...",
+];
+
+fn bitmap(entity: EntityKind) -> &'static [&'static str] {
+    match entity {
+        EntityKind::Player => &PLAYER_BITMAP,
+        EntityKind::Alien => &ALIEN_BITMAP,
+    }
+}
+
+/// Whether the pixel at (x, y) of `bitmap` stretched to `width` x `height`
+/// is lit.
+fn lit(bitmap: &[&str], width: usize, height: usize, x: usize, y: usize) -> bool {
+    let row = bitmap[y * bitmap.len() / height].as_bytes();
+    row[x * row.len() / width] == b'X'
+}
+
+/// A way of putting images on the terminal.
+trait Protocol {
+    /// Runs once per frame, before its images are placed.
+    fn begin_frame(&mut self, _out: &mut Stdout) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Draws the bitmap of `entity` in `rgb` over `columns` x `rows` cells,
+    /// from the cursor position.
+    fn place(&mut self, out: &mut Stdout, entity: EntityKind, columns: u16, rows: u16, rgb: Rgb) -> io::Result<()>;
+
+    /// Whether images replace the text of the cells they cover, which then
+    /// has to be written again to take them away.
+    fn replaces_text(&self) -> bool {
+        false
+    }
+
+    /// Runs when the game ends, before the terminal is restored.
+    fn finish(&mut self, _out: &mut Stdout) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// An entity waiting to be drawn once the text of the frame is out.
+struct Placed {
+    x: i32,
+    y: i32,
+    columns: u16,
+    rows: u16,
+    entity: EntityKind,
+    color: Color,
+}
+
+pub struct GraphicsRenderer {
+    text: CrosstermRenderer,
+    out: Stdout,
+    /// The protocol images are drawn with, if the terminal supports it.
+    protocol: Option<Box<dyn Protocol>>,
+    color: bool,
+    theme: &'static Theme,
+    placed: Vec<Placed>,
+}
+
+impl GraphicsRenderer {
+    /// Sets up the terminal like [`CrosstermRenderer::new`] does, then asks
+    /// it whether it supports Sixel.
+    #[cfg(feature = "sixel")]
+    pub fn sixel(color: bool) -> io::Result<Self> {
+        let text = CrosstermRenderer::new(color)?;
+        let protocol = sixel::detect().then(|| Box::new(sixel::Sixel::default()) as Box<dyn Protocol>);
+        Ok(GraphicsRenderer::with_protocol(text, protocol, color))
+    }
+
+    /// Sets up the terminal like [`CrosstermRenderer::new`] does, then asks
+    /// it whether it supports the kitty graphics protocol.
+    #[cfg(feature = "kitty")]
+    pub fn kitty(color: bool) -> io::Result<Self> {
+        let text = CrosstermRenderer::new(color)?;
+        let protocol = kitty::detect().then(|| Box::new(kitty::Kitty::default()) as Box<dyn Protocol>);
+        Ok(GraphicsRenderer::with_protocol(text, protocol, color))
+    }
+
+    fn with_protocol(text: CrosstermRenderer, protocol: Option<Box<dyn Protocol>>, color: bool) -> Self {
+        GraphicsRenderer { text, out: io::stdout(), protocol, color, theme: &CLASSIC, placed: Vec::new() }
+    }
+
+    fn write_images(&mut self) -> io::Result<()> {
+        let Some(protocol) = self.protocol.as_mut() else {
+            return Ok(());
+        };
+        protocol.begin_frame(&mut self.out)?;
+        for placed in &self.placed {
+            let (x, y) = match (u16::try_from(placed.x), u16::try_from(placed.y)) {
+                (Ok(x), Ok(y)) => (x, y),
+                _ => continue,
+            };
+            let rgb = if self.color { self.theme.rgb(placed.color) } else { Rgb(255, 255, 255) };
+            queue!(self.out, cursor::MoveTo(x, y))?;
+            protocol.place(&mut self.out, placed.entity, placed.columns, placed.rows, rgb)?;
+            if protocol.replaces_text() {
+                self.text.invalidate(placed.x, placed.y, placed.columns as i32, placed.rows as i32);
+            }
+        }
+        self.out.flush()
+    }
+}
+
+impl Drop for GraphicsRenderer {
+    fn drop(&mut self) {
+        if let Some(protocol) = self.protocol.as_mut() {
+            let _ = protocol.finish(&mut self.out);
+            let _ = self.out.flush();
+        }
+    }
+}
+
+// --- Capability Detection ---
+
+/// Writes `request` followed by a query for the primary device attributes,
+/// which every terminal answers, and returns everything read until that
+/// answer ends, e.g. `ESC [ ? 62 ; 4 ; 22 c`.
+fn query(request: &[u8]) -> String {
+    let mut out = io::stdout();
+    if out.write_all(request).and_then(|()| out.write_all(b"\x1b[c")).and_then(|()| out.flush()).is_err() {
+        return String::new();
+    }
+    let deadline = Instant::now() + DETECT_TIMEOUT;
+    let mut response = Vec::new();
+    while let Some(byte) = read_byte(deadline) {
+        response.push(byte);
+        if byte == b'c' && response.windows(3).any(|start| start == b"\x1b[?") {
+            break;
+        }
+    }
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+/// Reads one byte of input, waiting until `deadline` at most.
+fn read_byte(deadline: Instant) -> Option<u8> {
+    let timeout = deadline.saturating_duration_since(Instant::now()).as_millis() as libc::c_int;
+    let mut stdin = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+    // SAFETY: `stdin` and `byte` outlive the calls, which only write to them.
+    if unsafe { libc::poll(&mut stdin, 1, timeout) } <= 0 {
+        return None;
+    }
+    let mut byte = 0u8;
+    let read = unsafe { libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) };
+    (read == 1).then_some(byte)
+}
+
+impl Renderer for GraphicsRenderer {
+    fn clear(&mut self) {
+        self.text.clear();
+        self.placed.clear();
+    }
+
+    fn draw_str(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        self.text.draw_str(x, y, text, color);
+    }
+
+    fn present(&mut self) {
+        self.text.present();
+        // Like the text, a failed write only loses this frame's images
+        let _ = self.write_images();
+    }
+
+    fn size(&self) -> (i32, i32) {
+        self.text.size()
+    }
+
+    fn set_theme(&mut self, theme: &'static Theme) {
+        self.theme = theme;
+        self.text.set_theme(theme);
+    }
+
+    fn rich_colors(&self) -> bool {
+        self.text.rich_colors()
+    }
+
+    fn sprites(&self) -> &'static SpriteSet {
+        self.text.sprites()
+    }
+
+    fn set_sprites(&mut self, sprites: &'static SpriteSet) {
+        self.text.set_sprites(sprites);
+    }
+
+    fn bell(&mut self, flash: bool) {
+        self.text.bell(flash);
+    }
+
+    fn draw_entity(&mut self, x: i32, y: i32, entity: EntityKind, rows: &[&str], color: Color) {
+        if self.protocol.is_none() {
+            self.text.draw_entity(x, y, entity, rows, color);
+            return;
+        }
+        let columns = rows.first().map_or(0, |row| row.chars().count()) as u16;
+        self.placed.push(Placed { x, y, columns, rows: rows.len() as u16, entity, color });
+    }
+
+    fn draw_pixels(&mut self, x: i32, y: i32, top: Option<Color>, bottom: Option<Color>) {
+        self.text.draw_pixels(x, y, top, bottom);
+    }
+}
+
+impl Input for GraphicsRenderer {
+    fn poll_key(&mut self, timeout: Duration) -> Option<Key> {
+        self.text.poll_key(timeout)
+    }
+
+    fn capture_mouse(&mut self) {
+        self.text.capture_mouse();
+    }
+}
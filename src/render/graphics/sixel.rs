@@ -0,0 +1,95 @@
+//! Sixel images, which xterm, mlterm, WezTerm and others can show.
+
+use std::io::{self, Stdout, Write};
+
+use crossterm::terminal;
+
+use super::{bitmap, lit, query, Protocol};
+use crate::render::theme::Rgb;
+use crate::render::EntityKind;
+
+/// Cell size assumed when the terminal doesn't report its size in pixels.
+const DEFAULT_CELL_SIZE: (u16, u16) = (10, 20);
+
+/// Sixel images are sized in pixels, so the cell size is looked up every
+/// frame in case the font changed.
+#[derive(Default)]
+pub struct Sixel {
+    cell_size: (u16, u16),
+}
+
+impl Protocol for Sixel {
+    fn begin_frame(&mut self, _out: &mut Stdout) -> io::Result<()> {
+        let size = terminal::window_size()?;
+        self.cell_size = match (size.width, size.height) {
+            (0, _) | (_, 0) => DEFAULT_CELL_SIZE,
+            (width, height) => (width / size.columns.max(1), height / size.rows.max(1)),
+        };
+        Ok(())
+    }
+
+    fn place(&mut self, out: &mut Stdout, entity: EntityKind, columns: u16, rows: u16, rgb: Rgb) -> io::Result<()> {
+        let (cell_width, cell_height) = self.cell_size;
+        out.write_all(encode(bitmap(entity), columns * cell_width, rows * cell_height, rgb).as_bytes())
+    }
+
+    fn replaces_text(&self) -> bool {
+        true
+    }
+}
+
+/// Encodes `bitmap` stretched to `width` x `height` pixels as a Sixel image in
+/// a single color, leaving unlit pixels transparent.
+fn encode(bitmap: &[&str], width: u16, height: u16, rgb: Rgb) -> String {
+    let (width, height) = (width as usize, height as usize);
+
+    // Sixel color components are percentages
+    let percent = |channel: u8| channel as u32 * 100 / 255;
+    let mut image = format!(
+        "\x1bP0;1;0q\"1;1;{};{}#1;2;{};{};{}#1",
+        width,
+        height,
+        percent(rgb.0),
+        percent(rgb.1),
+        percent(rgb.2)
+    );
+    // Each band covers six pixel rows, one bit per row; runs are compressed
+    for band in (0..height).step_by(6) {
+        let mut run: Option<(u8, usize)> = None;
+        for x in 0..width {
+            let bits = (0..6)
+                .filter(|dy| band + dy < height && lit(bitmap, width, height, x, band + dy))
+                .fold(0, |bits, dy| bits | 1 << dy);
+            run = match run {
+                Some((previous, count)) if previous == bits => Some((bits, count + 1)),
+                Some((previous, count)) => {
+                    push_run(&mut image, previous, count);
+                    Some((bits, 1))
+                }
+                None => Some((bits, 1)),
+            };
+        }
+        if let Some((bits, count)) = run {
+            push_run(&mut image, bits, count);
+        }
+        image.push('-');
+    }
+    image.push_str("\x1b\\");
+    image
+}
+
+fn push_run(image: &mut String, bits: u8, count: usize) {
+    let sixel = (b'?' + bits) as char;
+    if count > 3 {
+        image.push_str(&format!("!{}{}", count, sixel));
+    } else {
+        image.extend(std::iter::repeat_n(sixel, count));
+    }
+}
+
+/// Whether the terminal's device attributes include 4, Sixel graphics.
+pub fn detect() -> bool {
+    let response = query(b"");
+    let attributes = response.rsplit("\x1b[?").next().unwrap_or_default().trim_end_matches('c');
+    attributes.split(';').any(|attribute| attribute == "4")
+}
@@ -0,0 +1,634 @@
+use std::cmp::Ordering;
+
+use crate::collision::Aabb;
+use crate::entity::{Entity, Sprite};
+use crate::game::{
+    GameState, EXPLOSION_FRAMES, LASER_FULL_CHARGE, MIN_FIELD_HEIGHT, MIN_FIELD_WIDTH, UFO_ROW,
+    VERTICAL_SPACING,
+};
+use crate::debug::FrameStats;
+use crate::input::Input;
+use crate::locale::{tr, tr_with};
+use crate::particles::ParticleKind;
+use pixels::{Canvas, PixelMode};
+use sprites::SpriteSet;
+use theme::Theme;
+use widgets::{Align, Block, Hud, Lives, Meter, Rect, Text, Widget};
+
+pub mod buffer;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod crossterm;
+#[cfg(any(feature = "sixel", feature = "kitty"))]
+pub mod graphics;
+#[cfg(all(feature = "ncurses", not(windows)))]
+pub mod ncurses;
+pub mod pixels;
+pub mod snapshot;
+pub mod sprites;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod telnet;
+pub mod theme;
+pub mod widgets;
+
+// --- Logical Colors ---
+// The current theme paints these, and each backend maps the paint to whatever
+// its terminal library understands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Ui,
+    Player,
+    Shot,
+    Alien,
+    GameOver,
+    AlienShot,
+    Border,
+    Highlight,
+    Ufo,
+    Explosion,
+    /// Aliens of one formation row, counted from the top, for themes that
+    /// shade the rows.
+    AlienRow(u8),
+    /// The dim background starfield.
+    Star,
+}
+
+/// How a logical color stands out on a terminal drawing without colors,
+/// where entities are told apart by their glyphs and these attributes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Emphasis {
+    Normal,
+    Bold,
+    Dim,
+    Reverse,
+}
+
+impl Color {
+    pub fn emphasis(self) -> Emphasis {
+        match self {
+            Color::Player | Color::Shot | Color::Highlight | Color::Explosion => Emphasis::Bold,
+            Color::GameOver | Color::Ufo => Emphasis::Reverse,
+            Color::Border | Color::Star => Emphasis::Dim,
+            Color::Ui | Color::Alien | Color::AlienRow(_) | Color::AlienShot => Emphasis::Normal,
+        }
+    }
+}
+
+/// Entities that graphics backends can draw as images instead of text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EntityKind {
+    Player,
+    Alien,
+}
+
+/// A drawing surface the game can be rendered onto.
+///
+/// Backends only need to provide a handful of primitives; the layout of a
+/// frame is shared by all of them through [`render`].
+pub trait Renderer {
+    /// Wipes the back buffer before a new frame is drawn.
+    fn clear(&mut self);
+    /// Draws `text` starting at column `x` of row `y`.
+    fn draw_str(&mut self, x: i32, y: i32, text: &str, color: Color);
+    /// Makes everything drawn since the last `clear` visible.
+    fn present(&mut self);
+    /// The drawable area as `(width, height)` in cells.
+    fn size(&self) -> (i32, i32);
+    /// Tells the renderer where the HUD, playfield and status row of the
+    /// coming frame are, for backends giving each a window of its own.
+    fn set_layout(&mut self, _layout: &Layout) {}
+    /// Paints logical colors with `theme` from the next frame on. Renderers
+    /// that keep colors logical can ignore it.
+    fn set_theme(&mut self, _theme: &'static Theme) {}
+    /// Whether shades beyond the eight basic colors can be shown, which is
+    /// when the background starfield is drawn.
+    fn rich_colors(&self) -> bool {
+        false
+    }
+    /// The glyphs entities are drawn with, which depend on what characters
+    /// the terminal can show.
+    fn sprites(&self) -> &'static SpriteSet {
+        &sprites::ASCII
+    }
+    /// Draws entities with `sprites` instead, such as a loaded sprite pack.
+    fn set_sprites(&mut self, _sprites: &'static SpriteSet) {}
+    /// Draws `entity`, whose sprite is `rows`, with its top-left corner at
+    /// column `x` of row `y`. Text renderers just draw the rows.
+    fn draw_entity(&mut self, x: i32, y: i32, _entity: EntityKind, rows: &[&str], color: Color) {
+        for (i, row) in rows.iter().enumerate() {
+            self.draw_str(x, y + i as i32, row, color);
+        }
+    }
+    /// Draws the cell at (x, y) as two stacked pixels, for half-block layouts.
+    /// Renderers without background colors show both in a single color.
+    fn draw_pixels(&mut self, x: i32, y: i32, top: Option<Color>, bottom: Option<Color>) {
+        if let Some((glyph, color)) = pixels::single_color_glyph(top, bottom) {
+            self.draw_str(x, y, glyph, color);
+        }
+    }
+    /// Rings the terminal bell, or with `flash` flashes the screen instead
+    /// where the terminal can. Renderers with no terminal ignore it.
+    fn bell(&mut self, _flash: bool) {}
+}
+
+/// A complete terminal frontend: something that can both draw and read keys.
+pub trait Backend: Renderer + Input {}
+
+impl<T: Renderer + Input> Backend for T {}
+
+// --- Layout ---
+
+/// Rows taken up by the HUD above the playfield.
+const HUD_HEIGHT: i32 = 2;
+/// Rows around the playfield's interior: the HUD, both border lines and the
+/// status row.
+const CHROME_HEIGHT: i32 = HUD_HEIGHT + 2 + 1;
+
+/// Where the HUD, the bordered playfield and the status row sit on screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    pub hud: Rect,
+    /// The playfield including its border.
+    pub field: Rect,
+    /// The line under the playfield, showing the remaining lives.
+    pub status: Rect,
+    /// How the playfield packs game pixels into its cells, if it is drawn
+    /// in pixels rather than text.
+    pub pixels: Option<PixelMode>,
+}
+
+impl Layout {
+    /// Lays out a playfield of `width` x `height` interior cells, with the
+    /// HUD on the lines above it and the status row below.
+    pub fn new(width: u16, height: u16) -> Self {
+        let screen_width = width as i32 + 2;
+        let field = Rect::new(0, HUD_HEIGHT, screen_width, height as i32 + 2);
+        Layout {
+            hud: Rect::new(0, 0, screen_width, HUD_HEIGHT),
+            field,
+            status: Rect::new(0, field.bottom(), screen_width, 1),
+            pixels: None,
+        }
+    }
+
+    /// Lays out a playfield of `width` x `height` pixels, packed into cells
+    /// the way `mode` does.
+    pub fn with_pixels(width: u16, height: u16, mode: PixelMode) -> Self {
+        let (columns, rows) = mode.cell_size();
+        Layout { pixels: Some(mode), ..Layout::new(width.div_ceil(columns), height.div_ceil(rows)) }
+    }
+
+    /// The same layout moved to the middle of a `(width, height)` screen.
+    /// Layouts bigger than the screen stay in its top-left corner.
+    pub fn centered_in(&self, (width, height): (i32, i32)) -> Layout {
+        let dx = (width - self.width()).max(0) / 2;
+        let dy = (height - self.height()).max(0) / 2;
+        self.offset(dx, dy)
+    }
+
+    /// The same layout with the playfield's window cut down to fit on a
+    /// `(width, height)` screen, which the camera scrolls over playfields
+    /// too big for it, but never smaller than the minimum playfield.
+    pub fn clipped_to(&self, screen: (i32, i32)) -> Layout {
+        let (columns, rows) = Layout::field_size_for(screen);
+        let columns = columns.min((self.field.width - 2).max(0) as u16);
+        let rows = rows.min((self.field.height - 2).max(0) as u16);
+        Layout { pixels: self.pixels, ..Layout::new(columns, rows) }.offset(self.hud.x, self.hud.y)
+    }
+
+    /// The same layout moved `dx` cells right and `dy` cells down.
+    pub fn offset(&self, dx: i32, dy: i32) -> Layout {
+        Layout {
+            hud: self.hud.offset(dx, dy),
+            field: self.field.offset(dx, dy),
+            status: self.status.offset(dx, dy),
+            pixels: self.pixels,
+        }
+    }
+
+    /// The biggest playfield that fits on a `(width, height)` screen, but
+    /// never smaller than the minimum playfield.
+    pub fn field_size_for((width, height): (i32, i32)) -> (u16, u16) {
+        let fit = |cells: i32, min: u16| (cells.clamp(0, u16::MAX as i32) as u16).max(min);
+        (fit(width - 2, MIN_FIELD_WIDTH), fit(height - CHROME_HEIGHT, MIN_FIELD_HEIGHT))
+    }
+
+    /// The game column shown at screen column `x`, or the nearest one if `x`
+    /// is off the playfield.
+    pub fn field_column(&self, x: i32) -> u16 {
+        let (per_column, _) = self.pixels.map_or((1, 1), PixelMode::cell_size);
+        let cell = (x - self.field.x - 1).clamp(0, (self.field.width - 3).max(0));
+        cell as u16 * per_column
+    }
+
+    /// Total cells needed to show the HUD, the playfield and the status row.
+    pub fn width(&self) -> i32 {
+        self.field.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.status.bottom() - self.hud.y
+    }
+
+    /// Whether everything fits on a screen of `(width, height)` cells.
+    pub fn fits(&self, (width, height): (i32, i32)) -> bool {
+        self.width() <= width && self.height() <= height
+    }
+}
+
+/// The part of the playfield a frame shows, in game cells: `width` columns
+/// from column `x`, and `height` rows below row `y`. A playfield that fits
+/// its window on screen is shown whole, from (0, 0).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Camera {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Camera {
+    /// The camera over `state` in `layout`'s window. It follows the ships
+    /// across, keeping them in the middle until it reaches an edge, and on
+    /// playfields taller than the window keeps them in view at the bottom.
+    pub fn follow(state: &GameState, layout: &Layout) -> Self {
+        let (per_column, per_row) = layout.pixels.map_or((1, 1), PixelMode::cell_size);
+        let (world_width, world_height) = (state.settings.width, state.settings.height);
+        let width = ((layout.field.width - 2).max(0) as u16).saturating_mul(per_column).min(world_width);
+        let height = ((layout.field.height - 2).max(0) as u16).saturating_mul(per_row).min(world_height);
+        let ships = state.world.ships.iter().filter(|ship| !ship.is_out());
+        let (sum, count) = ships.fold((0, 0), |(sum, count), ship| (sum + ship.x() as u32, count + 1));
+        let center = match count {
+            0 => world_width / 2,
+            count => (sum / count) as u16 + state.settings.player_size.width / 2,
+        };
+        let x = center.saturating_sub(width / 2).min(world_width - width);
+        Camera { x, y: world_height - height, width, height }
+    }
+
+    /// Where the game position (x, y) is in the camera's view, with the
+    /// part of `text` from there that the view shows, if any of it. Game row
+    /// 0 belongs to the HUD, so the view's first row is row 1.
+    fn view<'a>(&self, x: u16, y: u16, text: &'a str) -> Option<(u16, u16, &'a str)> {
+        let y = y.checked_sub(self.y).filter(|y| (1..=self.height).contains(y))?;
+        let skipped = self.x.saturating_sub(x) as usize;
+        let x = x.saturating_sub(self.x);
+        let room = self.width.checked_sub(x).filter(|&room| room > 0)? as usize;
+        let text = &text[text.char_indices().nth(skipped)?.0..];
+        let end = text.char_indices().nth(room).map_or(text.len(), |(end, _)| end);
+        Some((x, y, &text[..end]))
+    }
+}
+
+/// Draws game-space strings inside the playfield border, as the camera sees
+/// them: as text, or as the pixels of a [`Canvas`] in pixel layouts.
+struct FieldPainter {
+    area: Rect,
+    camera: Camera,
+    canvas: Option<(Canvas, PixelMode)>,
+}
+
+impl FieldPainter {
+    fn new(layout: &Layout, camera: Camera) -> Self {
+        let canvas = layout.pixels.map(|mode| (Canvas::new(camera.width, camera.height), mode));
+        FieldPainter { area: layout.field.inner(), camera, canvas }
+    }
+
+    fn draw(&mut self, renderer: &mut dyn Renderer, x: u16, y: u16, text: &str, color: Color) {
+        let Some((x, y, text)) = self.camera.view(x, y, text) else {
+            return;
+        };
+        match &mut self.canvas {
+            Some((canvas, _)) => canvas.paint(x, y - 1, text, color),
+            None => renderer.draw_str(self.area.x + x as i32, self.area.y + y as i32 - 1, text, color),
+        }
+    }
+
+    /// Draws a sprite of several rows, which graphics backends may replace
+    /// with an image of `entity` while the camera sees all of it.
+    fn draw_entity(&mut self, renderer: &mut dyn Renderer, x: u16, y: u16, entity: EntityKind, rows: &[&str], color: Color) {
+        let camera = self.camera;
+        let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0) as u16;
+        let whole = x >= camera.x
+            && x + width <= camera.x + camera.width
+            && y > camera.y
+            && y + rows.len() as u16 <= camera.y + camera.height + 1;
+        match &mut self.canvas {
+            None if whole => {
+                let (x, y) = (x - camera.x, y - camera.y);
+                renderer.draw_entity(self.area.x + x as i32, self.area.y + y as i32 - 1, entity, rows, color)
+            }
+            _ => {
+                for (i, row) in rows.iter().enumerate() {
+                    self.draw(renderer, x, y + i as u16, row, color);
+                }
+            }
+        }
+    }
+
+    /// Draws each of `entities` in its color with the sprite it is drawn
+    /// as, at the top-left corner of its box in `state`.
+    fn draw_all<E: Entity>(
+        &mut self,
+        renderer: &mut dyn Renderer,
+        theme: &Theme,
+        entities: impl IntoIterator<Item = (E, Color)>,
+    ) {
+        for (entity, color) in entities {
+            let Aabb { x, y, .. } = entity.bbox();
+            self.draw_sprite(renderer, theme, entity.sprite(), x, y, color);
+        }
+    }
+
+    /// Draws what `sprite` looks like with its top-left corner at (x, y).
+    fn draw_sprite(
+        &mut self,
+        renderer: &mut dyn Renderer,
+        theme: &Theme,
+        sprite: Sprite,
+        x: u16,
+        y: u16,
+        color: Color,
+    ) {
+        let sprites = renderer.sprites();
+        let glyph = match sprite {
+            Sprite::Player => return self.draw_entity(renderer, x, y, EntityKind::Player, sprites.player, color),
+            Sprite::Alien => return self.draw_entity(renderer, x, y, EntityKind::Alien, sprites.alien, color),
+            Sprite::Shot(Ordering::Less) => sprites.shots[1],
+            Sprite::Shot(Ordering::Greater) => sprites.shots[2],
+            Sprite::Shot(Ordering::Equal) => sprites.shots[0],
+            Sprite::AlienShot => theme.alien_shot_glyph.unwrap_or(sprites.alien_shot),
+            Sprite::Ufo => sprites.ufo,
+            Sprite::Boss => {
+                for (i, line) in sprites.boss.iter().enumerate() {
+                    self.draw(renderer, x, y + i as u16, line, color);
+                }
+                return;
+            }
+            Sprite::Pickup(kind) => kind.glyph(),
+        };
+        self.draw(renderer, x, y, glyph, color);
+    }
+
+    /// Shows whatever was painted into the canvas.
+    fn finish(&mut self, renderer: &mut dyn Renderer) {
+        if let Some((canvas, mode)) = self.canvas.take() {
+            canvas.draw(renderer, self.area, mode);
+            self.canvas = Some((canvas, mode));
+        }
+    }
+
+    /// Draws `text` as text in the cell holding game position (x, y), over
+    /// whatever [`finish`](Self::finish) showed, even in pixel layouts.
+    fn label(&self, renderer: &mut dyn Renderer, x: u16, y: u16, text: &str, color: Color) {
+        let (columns, rows) = self.canvas.as_ref().map_or((1, 1), |(_, mode)| mode.cell_size());
+        let Some((x, y, text)) = self.camera.view(x, y, text) else {
+            return;
+        };
+        let (x, y) = (x / columns, (y - 1) / rows);
+        renderer.draw_str(self.area.x + x as i32, self.area.y + y as i32, text, color);
+    }
+}
+
+/// Explains that the terminal must grow before the game can be shown.
+pub fn draw_too_small(renderer: &mut dyn Renderer, layout: &Layout) {
+    let (width, height) = renderer.size();
+    let need = tr_with("too_small_need", &[&layout.width(), &layout.height()]);
+    let have = tr_with("too_small_have", &[&width, &height]);
+    let lines = [tr("too_small"), &need, &have, "", tr("too_small_quit")];
+    let top = (height - lines.len() as i32).max(0) / 2;
+    Text::new(&lines, Color::GameOver).align(Align::Center).draw(Rect::new(0, top, width, height - top), renderer);
+}
+
+/// Draws lines of text centered in the middle of the playfield.
+pub fn draw_banner(renderer: &mut dyn Renderer, layout: &Layout, lines: &[&str], color: Color) {
+    let field = layout.field.inner();
+    let height = lines.len() as i32;
+    let area = Rect::new(field.x, field.y + (field.height - height) / 2, field.width, height);
+    Text::new(lines, color).align(Align::Center).draw(area, renderer);
+}
+
+// --- Game Rendering ---
+
+/// Draws the HUD, the playfield frame and every entity of `state`.
+pub fn draw_game(renderer: &mut dyn Renderer, layout: &Layout, state: &GameState, high_score: u32, theme: &Theme) {
+    // Jolt the whole frame around while the screen shakes
+    let shaken;
+    let layout = if state.is_shaking() {
+        let (dx, dy) = SHAKE_OFFSETS[state.ticks as usize % SHAKE_OFFSETS.len()];
+        shaken = layout.offset(dx, dy);
+        &shaken
+    } else {
+        layout
+    };
+
+    // Render UI (Score, High Score, Wave and Bombs), with the remaining lives below
+    // the playfield
+    let one_up = state.is_one_up() && state.ticks.is_multiple_of(2);
+    Hud {
+        scores: state.world.ships.iter().map(|ship| ship.score).collect(),
+        high_score,
+        wave: state.wave,
+        bombs: state.bombs,
+        multiplier: state.multiplier(),
+        one_up,
+    }
+    .draw(layout.hud, renderer);
+    // In co-op each ship's lives take half of the line
+    let status = layout.status;
+    let half = status.width / state.world.ships.len() as i32;
+    for (i, ship) in state.world.ships.iter().enumerate() {
+        let area = Rect::new(status.x + i as i32 * half, status.y, half, status.height);
+        Lives { lives: ship.lives(), color: player_color(i) }.draw(area, renderer);
+    }
+
+    // Render the playfield frame; everything else is drawn inside it
+    draw_frame(renderer, layout);
+    let field = layout.field.inner();
+    let camera = Camera::follow(state, layout);
+    let mut painter = FieldPainter::new(layout, camera);
+    let sprites = renderer.sprites();
+
+    // Render the starfield behind everything, where the terminal can keep it
+    // dim, as far as the camera sees
+    if renderer.rich_colors() {
+        for y in camera.y + 1..=(camera.y + camera.height).min(state.max_y() + 1) {
+            for x in camera.x..camera.x + camera.width {
+                if is_star(x, y) {
+                    painter.draw(renderer, x, y, ".", Color::Star);
+                }
+            }
+        }
+    }
+
+    // Render the ships still in the run, each blinking while it is invincible
+    let ships = state.world.ships.iter().enumerate().filter(|(i, ship)| {
+        let blinked_out = state.is_invincible(*i) && state.ticks / PLAYER_BLINK_TICKS % 2 == 1;
+        !state.game_over && !ship.is_out() && !blinked_out
+    });
+    painter.draw_all(renderer, theme, ships.map(|(i, ship)| (ship, player_color(i))));
+
+    // Render the laser beam, from just above the ships to the top row
+    if let Some(laser) = &state.laser {
+        for y in UFO_ROW..state.max_y() {
+            painter.draw(renderer, laser.x, y, "!", Color::Explosion);
+        }
+    }
+
+    // Render the laser's charge on the bottom border while fire is held
+    if let Some(charge) = state.laser_charge {
+        let area = Rect::new(field.x + 1, field.bottom(), LASER_METER_WIDTH.min(field.width - 2), 1);
+        Meter { label: tr("laser"), value: charge, max: LASER_FULL_CHARGE }.draw(area, renderer);
+    }
+
+    // Render the bunkers, under the shots flying into them
+    for &(x, y) in &state.bunkers {
+        painter.draw(renderer, x, y, sprites.bunker, Color::Player);
+    }
+
+    // Render Shots, falling pickups and Alien Shots
+    let world = &state.world;
+    painter.draw_all(renderer, theme, world.shots.iter().map(|shot| (shot, Color::Shot)));
+    painter.draw_all(renderer, theme, world.pickups.iter().map(|pickup| (pickup, Color::Highlight)));
+    painter.draw_all(renderer, theme, world.alien_shots.iter().map(|shot| (shot, Color::AlienShot)));
+
+    // Render Aliens, shading each formation row a little darker than the last,
+    // armored ones that have yet to be hit apart and the one a versus
+    // commander aims with standing out
+    let top = world.aliens.iter().map(|alien| alien.home().1).min().unwrap_or(0);
+    let aimed = state.aimed_alien();
+    let aliens = world.aliens.iter().enumerate().map(|(i, alien)| {
+        let row = ((alien.home().1 - top) / VERTICAL_SPACING) as u8;
+        let color = if aimed == Some(i) {
+            Color::Highlight
+        } else if alien.is_armored() {
+            Color::Ufo
+        } else {
+            Color::AlienRow(row)
+        };
+        (alien, color)
+    });
+    painter.draw_all(renderer, theme, aliens);
+
+    // Render the boss, with its health bar where the mystery ship would fly
+    if let Some(boss) = world.boss() {
+        painter.draw_all(renderer, theme, [(boss, Color::Alien)]);
+        let bar = health_bar(boss.health.hp, boss.health.max, BOSS_BAR_WIDTH);
+        Text::new(&[&bar], Color::GameOver).align(Align::Center).draw(Rect::new(field.x, field.y, field.width, 1), renderer);
+    }
+
+    // Render explosions on top of everything they were part of
+    for effect in &state.effects {
+        if let Some(frame) = effect.frame() {
+            let frame = sprites.explosion[frame * sprites.explosion.len() / EXPLOSION_FRAMES.len()];
+            painter.draw(renderer, effect.x, effect.y, frame, Color::Explosion);
+        }
+    }
+
+    // Render debris, exhaust and sparks over it all
+    for particle in state.particles.iter() {
+        if let Some((x, y)) = particle.cell(state.settings.width, state.settings.height) {
+            let color = match particle.kind {
+                ParticleKind::Debris => Color::Explosion,
+                ParticleKind::Exhaust => Color::Ui,
+                ParticleKind::Spark => Color::Highlight,
+            };
+            painter.draw(renderer, x, y, particle.glyph, color);
+        }
+    }
+
+    // Render the mystery ship
+    painter.draw_all(renderer, theme, world.ufo().map(|ufo| (ufo, Color::Ufo)));
+
+    painter.finish(renderer);
+
+    // Float the points scored over everything else
+    for popup in &state.popups {
+        painter.label(renderer, popup.x, popup.y, &popup.text(), Color::Highlight);
+    }
+
+    // Announce the next wave while counting down to it, after a stats card
+    // for the one just cleared; a run that is over has no wave to come
+    if let Some(seconds) = state.countdown().filter(|_| !state.game_over) {
+        let title = match state.level() {
+            Some(level) => tr_with("wave_named", &[&state.wave, &level.name.to_uppercase()]),
+            None => tr_with("wave", &[&state.wave]),
+        };
+        let count = seconds.max(1).to_string();
+        match &state.summary {
+            Some(summary) => {
+                let cleared = tr_with("wave_cleared", &[&summary.wave]);
+                let shots = tr_with("summary_shots", &[&summary.shots_fired]);
+                let accuracy = tr_with("summary_accuracy", &[&summary.accuracy]);
+                let secs = summary.time.as_secs();
+                let time = tr_with("summary_time", &[&format!("{}:{:02}", secs / 60, secs % 60)]);
+                let bonus = tr_with("summary_bonus", &[&summary.bonus]);
+                draw_banner(
+                    renderer,
+                    layout,
+                    &[&cleared, "", &shots, &accuracy, &time, &bonus, "", &title, &count],
+                    Color::Highlight,
+                );
+            }
+            None => draw_banner(renderer, layout, &[&title, "", &count], Color::Highlight),
+        }
+    }
+}
+
+/// Draws the debug overlay in the top-right corner of the screen: how fast
+/// the game runs and, during a game, what is on the field and its seed.
+pub fn draw_debug(renderer: &mut dyn Renderer, stats: &FrameStats, state: Option<&GameState>) {
+    let mut lines = vec![
+        format!("TPS {}", stats.ticks_per_second()),
+        format!("Render {:.1}ms", stats.render_time.as_secs_f64() * 1000.0),
+    ];
+    if let Some(state) = state {
+        let shots = state.world.shots.len() + state.world.alien_shots.len();
+        lines.push(format!("Aliens {} Shots {}", state.world.aliens.len(), shots));
+        lines.push(format!("Effects {} Particles {}", state.effects.len(), state.particles.iter().count()));
+        lines.push(format!("Seed {}", state.seed));
+    }
+    let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+    let (width, _) = renderer.size();
+    Text::new(&lines, Color::Highlight).align(Align::Right).draw(Rect::new(0, 0, width, lines.len() as i32), renderer);
+}
+
+/// Whether the playfield cell at (x, y) holds a background star: a fixed,
+/// sparse pattern that looks random.
+fn is_star(x: u16, y: u16) -> bool {
+    let hash = (x as u32).wrapping_mul(73_856_093) ^ (y as u32).wrapping_mul(19_349_663);
+    hash.is_multiple_of(29)
+}
+
+/// The color of player `index`'s ship, so the two of a co-op run tell apart.
+fn player_color(index: usize) -> Color {
+    if index == 0 {
+        Color::Player
+    } else {
+        Color::Highlight
+    }
+}
+
+/// Where a shaking frame is drawn on successive ticks, relative to where it
+/// belongs.
+const SHAKE_OFFSETS: [(i32, i32); 4] = [(1, 0), (-1, 1), (0, -1), (-1, 0)];
+
+/// Ticks the invincible ship spends shown, then hidden, while blinking.
+const PLAYER_BLINK_TICKS: u64 = 1;
+
+/// Width of the laser charge meter, label included.
+const LASER_METER_WIDTH: i32 = 22;
+
+/// Cells of the boss health bar between its brackets.
+const BOSS_BAR_WIDTH: u16 = 20;
+
+/// Renders `hp` out of `max_hp` as a bar of `width` cells, rounding up so a
+/// boss with any health left never shows an empty bar.
+fn health_bar(hp: u16, max_hp: u16, width: u16) -> String {
+    let filled = (hp as u32 * width as u32).div_ceil(max_hp.max(1) as u32) as usize;
+    format!("{} [{}{}]", tr("boss"), "#".repeat(filled), "-".repeat(width as usize - filled))
+}
+
+/// Draws an empty playfield frame, for screens that are not gameplay.
+pub fn draw_frame(renderer: &mut dyn Renderer, layout: &Layout) {
+    Block::new().title(tr("frame_title")).draw(layout.field, renderer);
+}
@@ -0,0 +1,339 @@
+use ncurses::*;
+use std::env;
+use std::io::{self, Write};
+use std::ptr;
+use std::time::Duration;
+
+use super::sprites::SpriteSet;
+use super::theme::{ColorDepth, Paint, Theme, CLASSIC};
+use super::widgets::Rect;
+use super::{Color, Emphasis, Layout, Renderer};
+use crate::error::StartError;
+use crate::input::{Input, Key};
+
+// --- Color Pair Definitions ---
+const COLOR_UI: i16 = 1;
+const COLOR_PLAYER: i16 = 2;
+const COLOR_SHOT: i16 = 3;
+const COLOR_ALIEN: i16 = 4;
+const COLOR_GAMEOVER: i16 = 5;
+const COLOR_ALIEN_SHOT: i16 = 6;
+const COLOR_BORDER: i16 = 7;
+const COLOR_HIGHLIGHT: i16 = 8;
+const COLOR_UFO: i16 = 9;
+const COLOR_EXPLOSION: i16 = 10;
+const COLOR_STAR: i16 = 11;
+/// Pairs from here on hold the shades of the alien rows, top row first.
+const COLOR_ALIEN_ROWS: i16 = 12;
+const ALIEN_ROW_SHADES: u8 = 5;
+
+/// The xterm sequences switching the reporting of every mouse motion on and
+/// off.
+const MOTION_TRACKING_ON: &str = "\x1b[?1003h";
+const MOTION_TRACKING_OFF: &str = "\x1b[?1003l";
+
+/// Renders through ncurses.
+///
+/// Frames are drawn into windows that nothing shows until `present` flushes
+/// them all with a single `doupdate`, so a frame never goes out half drawn.
+/// The HUD, the playfield and the status row each get a window of their own
+/// once the layout is known, and everything else goes onto a backdrop
+/// covering the screen. Creating the renderer initializes the terminal;
+/// dropping it restores it.
+pub struct NcursesRenderer {
+    screen: SCREEN,
+    color: bool,
+    depth: ColorDepth,
+    sprites: &'static SpriteSet,
+    backdrop: Pane,
+    /// The layout the panes were made for.
+    layout: Option<Layout>,
+    panes: Vec<Pane>,
+    /// Whether the terminal was asked to report mouse motion.
+    mouse: bool,
+}
+
+/// A window covering one region of the screen.
+struct Pane {
+    area: Rect,
+    window: WINDOW,
+}
+
+impl Pane {
+    fn new(area: Rect) -> Self {
+        let window = newwin(area.height, area.width, area.y, area.x);
+        leaveok(window, true); // Optimization to reduce cursor movement
+        Pane { area, window }
+    }
+
+    /// A pane covering the whole screen, which also reads the keyboard.
+    fn backdrop() -> Self {
+        let pane = Pane::new(Rect::new(0, 0, COLS(), LINES()));
+        keypad(pane.window, true); // Enable keypad keys
+        pane
+    }
+
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.area.x && x < self.area.right() && y >= self.area.y && y < self.area.bottom()
+    }
+}
+
+impl NcursesRenderer {
+    /// Sets up the terminal, with colors only if `color` is set and the
+    /// terminal supports them. Fails, leaving the terminal alone, on one
+    /// ncurses does not know.
+    pub fn new(color: bool) -> Result<Self, StartError> {
+        // Setup ncurses, in the user's locale so UTF-8 sprites come out whole
+        setlocale(LcCategory::all, "");
+        // initscr would print its own error and exit; newterm hands it back
+        let term = env::var("TERM").unwrap_or_default();
+        if term.is_empty() {
+            return Err(StartError::NoTerm);
+        }
+        let output = unsafe { libc::fdopen(libc::STDOUT_FILENO, c"w".as_ptr()) };
+        let input = unsafe { libc::fdopen(libc::STDIN_FILENO, c"r".as_ptr()) };
+        let screen = if output.is_null() || input.is_null() { ptr::null_mut() } else { newterm(None, output, input) };
+        if screen.is_null() {
+            return Err(StartError::UnknownTerminal(term));
+        }
+        let color = color && has_colors();
+        noecho();
+        curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+
+        // Shades need the 256-color palette; ncurses itself has no truecolor
+        let depth = if color && tigetnum("colors") >= 256 { ColorDepth::Palette256 } else { ColorDepth::Basic };
+        if color {
+            start_color();
+            init_color_pairs(&CLASSIC, depth);
+        }
+
+        Ok(NcursesRenderer {
+            screen,
+            color,
+            depth,
+            sprites: SpriteSet::detect(),
+            backdrop: Pane::backdrop(),
+            layout: None,
+            panes: Vec::new(),
+            mouse: false,
+        })
+    }
+
+    fn close_panes(&mut self) {
+        for pane in self.panes.drain(..) {
+            delwin(pane.window);
+        }
+        self.layout = None;
+    }
+}
+
+/// Every logical color, in the order of their color pairs.
+const COLORS: [Color; 16] = [
+    Color::Ui,
+    Color::Player,
+    Color::Shot,
+    Color::Alien,
+    Color::GameOver,
+    Color::AlienShot,
+    Color::Border,
+    Color::Highlight,
+    Color::Ufo,
+    Color::Explosion,
+    Color::Star,
+    Color::AlienRow(0),
+    Color::AlienRow(1),
+    Color::AlienRow(2),
+    Color::AlienRow(3),
+    Color::AlienRow(4),
+];
+
+fn init_color_pairs(theme: &Theme, depth: ColorDepth) {
+    // Initialize color pairs; redefining a pair repaints everything in it
+    for color in COLORS {
+        let foreground = match depth {
+            ColorDepth::Basic => curses_color(theme.paint(color)),
+            _ => theme.rgb(color).to_palette256() as i16,
+        };
+        init_pair(color_pair(color), foreground, COLOR_BLACK);
+    }
+}
+
+fn curses_color(paint: Paint) -> i16 {
+    match paint {
+        Paint::Black => COLOR_BLACK,
+        Paint::Red => COLOR_RED,
+        Paint::Green => COLOR_GREEN,
+        Paint::Yellow => COLOR_YELLOW,
+        Paint::Blue => COLOR_BLUE,
+        Paint::Magenta => COLOR_MAGENTA,
+        Paint::Cyan => COLOR_CYAN,
+        Paint::White => COLOR_WHITE,
+    }
+}
+
+impl Drop for NcursesRenderer {
+    fn drop(&mut self) {
+        // Cleanup ncurses
+        self.close_panes();
+        delwin(self.backdrop.window);
+        if self.mouse {
+            print!("{}", MOTION_TRACKING_OFF);
+        }
+        endwin();
+        delscreen(self.screen);
+    }
+}
+
+fn color_pair(color: Color) -> i16 {
+    match color {
+        Color::Ui => COLOR_UI,
+        Color::Player => COLOR_PLAYER,
+        Color::Shot => COLOR_SHOT,
+        Color::Alien => COLOR_ALIEN,
+        Color::GameOver => COLOR_GAMEOVER,
+        Color::AlienShot => COLOR_ALIEN_SHOT,
+        Color::Border => COLOR_BORDER,
+        Color::Highlight => COLOR_HIGHLIGHT,
+        Color::Ufo => COLOR_UFO,
+        Color::Explosion => COLOR_EXPLOSION,
+        Color::Star => COLOR_STAR,
+        Color::AlienRow(row) => COLOR_ALIEN_ROWS + row.min(ALIEN_ROW_SHADES - 1) as i16,
+    }
+}
+
+fn emphasis_attributes(emphasis: Emphasis) -> attr_t {
+    match emphasis {
+        Emphasis::Normal => A_NORMAL(),
+        Emphasis::Bold => A_BOLD(),
+        Emphasis::Dim => A_DIM(),
+        Emphasis::Reverse => A_REVERSE(),
+    }
+}
+
+impl Renderer for NcursesRenderer {
+    fn clear(&mut self) {
+        werase(self.backdrop.window);
+        for pane in &self.panes {
+            werase(pane.window);
+        }
+    }
+
+    fn draw_str(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        let attributes = if self.color { COLOR_PAIR(color_pair(color)) } else { emphasis_attributes(color.emphasis()) };
+        // Text running over a window's edge carries on in the next window
+        // instead of wrapping; whatever is off the screen is dropped
+        let mut chars: Vec<char> = text.chars().collect();
+        let mut x = x;
+        while !chars.is_empty() {
+            let Some(pane) = self.panes.iter().chain([&self.backdrop]).find(|pane| pane.contains(x, y)) else {
+                chars.remove(0);
+                x += 1;
+                continue;
+            };
+            let part: String = chars.drain(..chars.len().min((pane.area.right() - x) as usize)).collect();
+            wattron(pane.window, attributes);
+            mvwaddstr(pane.window, y - pane.area.y, x - pane.area.x, &part);
+            wattroff(pane.window, attributes);
+            x += part.chars().count() as i32;
+        }
+    }
+
+    fn present(&mut self) {
+        // Panes go out after the backdrop they sit on, all in a single update
+        wnoutrefresh(self.backdrop.window);
+        for pane in &self.panes {
+            wnoutrefresh(pane.window);
+        }
+        doupdate();
+    }
+
+    fn bell(&mut self, flash: bool) {
+        if flash {
+            ncurses::flash();
+        } else {
+            ncurses::beep();
+        }
+    }
+
+    fn size(&self) -> (i32, i32) {
+        let (mut height, mut width) = (0, 0);
+        getmaxyx(stdscr(), &mut height, &mut width);
+        (width, height)
+    }
+
+    fn set_layout(&mut self, layout: &Layout) {
+        if self.layout.as_ref() == Some(layout) {
+            return;
+        }
+        self.close_panes();
+        // A layout hanging off the screen is drawn onto the backdrop, clipped
+        if layout.fits(self.size()) && layout.hud.x >= 0 && layout.hud.y >= 0 {
+            self.panes = [layout.hud, layout.field, layout.status].into_iter().map(Pane::new).collect();
+            self.layout = Some(*layout);
+        }
+    }
+
+    fn set_theme(&mut self, theme: &'static Theme) {
+        if self.color {
+            init_color_pairs(theme, self.depth);
+        }
+    }
+
+    fn rich_colors(&self) -> bool {
+        self.depth > ColorDepth::Basic
+    }
+
+    fn sprites(&self) -> &'static SpriteSet {
+        self.sprites
+    }
+
+    fn set_sprites(&mut self, sprites: &'static SpriteSet) {
+        self.sprites = sprites;
+    }
+}
+
+impl Input for NcursesRenderer {
+    fn poll_key(&mut self, timeout_after: Duration) -> Option<Key> {
+        // Set non-blocking input
+        wtimeout(self.backdrop.window, timeout_after.as_millis() as i32);
+        match wgetch(self.backdrop.window) {
+            ERR => None,
+            KEY_LEFT => Some(Key::Left),
+            KEY_RIGHT => Some(Key::Right),
+            KEY_UP => Some(Key::Up),
+            KEY_DOWN => Some(Key::Down),
+            KEY_ENTER | 10 | 13 => Some(Key::Enter),
+            KEY_BACKSPACE | 127 | 8 => Some(Key::Backspace),
+            27 => Some(Key::Escape),
+            KEY_MOUSE => {
+                let mut event = MEVENT { id: 0, x: 0, y: 0, z: 0, bstate: 0 };
+                if getmouse(&mut event) != OK {
+                    return None;
+                }
+                let clicked = event.bstate & (BUTTON1_PRESSED | BUTTON1_CLICKED) as mmask_t != 0;
+                Some(Key::Mouse { x: event.x, y: event.y, clicked })
+            }
+            ch if (KEY_F1..=KEY_F12).contains(&ch) => Some(Key::Function((ch - KEY_F0) as u8)),
+            // ncurses has already updated its idea of the screen size; the
+            // windows are made again to match it
+            KEY_RESIZE => {
+                self.close_panes();
+                delwin(self.backdrop.window);
+                self.backdrop = Pane::backdrop();
+                None
+            }
+            // Any other function key is outside the byte range and ignored
+            ch => u8::try_from(ch).ok().map(|byte| Key::Char(byte as char)),
+        }
+    }
+
+    fn capture_mouse(&mut self) {
+        mousemask((ALL_MOUSE_EVENTS | REPORT_MOUSE_POSITION) as mmask_t, None);
+        // Report presses right away instead of waiting to tell clicks apart
+        mouseinterval(0);
+        // ncurses only asks for button events; motion is asked for directly
+        print!("{}", MOTION_TRACKING_ON);
+        let _ = io::stdout().flush();
+        self.mouse = true;
+    }
+}
@@ -0,0 +1,127 @@
+//! Pixel modes, which draw the playfield at a finer resolution than text.
+//!
+//! The game runs on a grid of pixels several times the size of the
+//! playfield's cells, and entities are painted into a [`Canvas`] instead of
+//! being written as text; the canvas is then packed into terminal cells:
+//!
+//! - [`PixelMode::HalfBlocks`] shows two pixels stacked in every cell, the
+//!   upper one as the foreground of `▀` and the lower one as its background.
+//! - [`PixelMode::Braille`] shows 2x4 pixels per cell as the dots of a
+//!   Braille pattern, all in one color.
+
+use super::widgets::Rect;
+use super::{Color, Renderer};
+
+pub const UPPER_HALF: char = '▀';
+
+/// How pixels are packed into terminal cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelMode {
+    HalfBlocks,
+    Braille,
+}
+
+impl PixelMode {
+    /// Pixels per cell, as `(columns, rows)`.
+    pub fn cell_size(self) -> (u16, u16) {
+        match self {
+            PixelMode::HalfBlocks => (1, 2),
+            PixelMode::Braille => (2, 4),
+        }
+    }
+}
+
+/// The glyph and color best showing a pair of pixels in a single color, for
+/// terminals or backends without background colors. Where both pixels are
+/// set but differ, the upper one wins.
+pub fn single_color_glyph(top: Option<Color>, bottom: Option<Color>) -> Option<(&'static str, Color)> {
+    match (top, bottom) {
+        (Some(top), Some(_)) => Some(("█", top)),
+        (Some(top), None) => Some(("▀", top)),
+        (None, Some(bottom)) => Some(("▄", bottom)),
+        (None, None) => None,
+    }
+}
+
+/// The bit of a Braille pattern for the dot in column `x` and row `y` of its
+/// cell, following the Unicode dot numbering.
+fn braille_dot(x: usize, y: usize) -> u32 {
+    const DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+    DOTS[x][y]
+}
+
+/// A grid of pixels, each either empty or painted in a logical color.
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<Option<Color>>,
+}
+
+impl Canvas {
+    pub fn new(width: u16, height: u16) -> Self {
+        let (width, height) = (width as usize, height as usize);
+        Canvas { width, height, pixels: vec![None; width * height] }
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> Option<Color> {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x]
+        } else {
+            None
+        }
+    }
+
+    /// Paints a pixel for every non-blank character of `text`, from (x, y)
+    /// rightwards.
+    pub fn paint(&mut self, x: u16, y: u16, text: &str, color: Color) {
+        let (x, y) = (x as usize, y as usize);
+        if y >= self.height {
+            return;
+        }
+        for (i, ch) in text.chars().enumerate() {
+            if x + i >= self.width {
+                break;
+            }
+            if ch != ' ' {
+                self.pixels[y * self.width + x + i] = Some(color);
+            }
+        }
+    }
+
+    /// Draws the pixels packed the way `mode` does, with their top-left
+    /// corner at the top-left of `area`.
+    pub fn draw(&self, renderer: &mut dyn Renderer, area: Rect, mode: PixelMode) {
+        let (columns, rows) = mode.cell_size();
+        let (columns, rows) = (columns as usize, rows as usize);
+        for line in 0..self.height.div_ceil(rows) {
+            for cell in 0..self.width.div_ceil(columns) {
+                let (x, y) = (area.x + cell as i32, area.y + line as i32);
+                let (left, top) = (cell * columns, line * rows);
+                match mode {
+                    PixelMode::HalfBlocks => {
+                        let (upper, lower) = (self.pixel(left, top), self.pixel(left, top + 1));
+                        if upper.is_some() || lower.is_some() {
+                            renderer.draw_pixels(x, y, upper, lower);
+                        }
+                    }
+                    PixelMode::Braille => {
+                        // A cell has a single color, taken from its first lit dot
+                        let mut pattern = 0;
+                        let mut color = None;
+                        for dy in 0..rows {
+                            for dx in 0..columns {
+                                if let Some(dot) = self.pixel(left + dx, top + dy) {
+                                    pattern |= braille_dot(dx, dy);
+                                    color = color.or(Some(dot));
+                                }
+                            }
+                        }
+                        if let (Some(color), Some(glyph)) = (color, char::from_u32(0x2800 + pattern)) {
+                            renderer.draw_str(x, y, &glyph.to_string(), color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
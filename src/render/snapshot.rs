@@ -0,0 +1,53 @@
+use super::buffer::FrameBuffer;
+use super::{Color, Renderer};
+
+/// A renderer that keeps every frame it presents as lines of text, for
+/// checking what the game draws without a terminal. Colors are dropped.
+pub struct Snapshot {
+    buffer: FrameBuffer,
+    frames: Vec<Vec<String>>,
+}
+
+impl Snapshot {
+    /// A screen of `width` x `height` cells.
+    pub fn new(width: usize, height: usize) -> Self {
+        Snapshot { buffer: FrameBuffer::new(width, height), frames: Vec::new() }
+    }
+
+    /// The frames presented so far, oldest first. Each is one string per
+    /// row, with the blanks at the end of rows trimmed off.
+    pub fn frames(&self) -> &[Vec<String>] {
+        &self.frames
+    }
+
+    /// The frame presented last, if any.
+    pub fn last(&self) -> Option<&[String]> {
+        self.frames.last().map(Vec::as_slice)
+    }
+}
+
+impl Renderer for Snapshot {
+    fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    fn draw_str(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        self.buffer.draw_str(x, y, text, color);
+    }
+
+    fn draw_pixels(&mut self, x: i32, y: i32, top: Option<Color>, bottom: Option<Color>) {
+        self.buffer.draw_pixels(x, y, top, bottom);
+    }
+
+    fn present(&mut self) {
+        let rows = (0..self.buffer.height()).map(|y| {
+            let row: String = self.buffer.row(y).iter().map(|cell| cell.ch).collect();
+            row.trim_end().to_string()
+        });
+        self.frames.push(rows.collect());
+    }
+
+    fn size(&self) -> (i32, i32) {
+        self.buffer.size()
+    }
+}
@@ -0,0 +1,184 @@
+//! The glyphs entities are drawn with.
+//!
+//! The game only knows the size of the ship and alien sprites, which are
+//! also their hitboxes; a [`SpriteSet`] decides what fills them. Besides the
+//! built-in sets, players can load their own from a sprite pack file.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::game::{
+    SpriteSize, ALIEN_SPRITE, BOSS_SPRITE, EXPLOSION_FRAMES, MAX_ALIEN_SIZE, MAX_PLAYER_SIZE, PLAYER_SPRITE,
+    UFO_SPRITE,
+};
+use crate::pack::Parser;
+
+/// The characters a box border is drawn with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BorderGlyphs {
+    pub horizontal: &'static str,
+    pub vertical: &'static str,
+    pub top_left: &'static str,
+    pub top_right: &'static str,
+    pub bottom_left: &'static str,
+    pub bottom_right: &'static str,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpriteSet {
+    pub player: &'static [&'static str],
+    pub alien: &'static [&'static str],
+    pub ufo: &'static str,
+    pub boss: [&'static str; 3],
+    /// Player shots flying straight up, up and left, and up and right.
+    pub shots: [&'static str; 3],
+    pub alien_shot: &'static str,
+    /// A block of a bunker.
+    pub bunker: &'static str,
+    /// An explosion's animation, stretched over however long it lasts.
+    pub explosion: &'static [&'static str],
+    pub border: BorderGlyphs,
+}
+
+/// Plain ASCII, which every terminal can show.
+pub const ASCII: SpriteSet = SpriteSet {
+    player: &PLAYER_SPRITE,
+    alien: &ALIEN_SPRITE,
+    ufo: UFO_SPRITE,
+    boss: BOSS_SPRITE,
+    shots: ["|", "\\", "/"],
+    alien_shot: "v",
+    bunker: "#",
+    explosion: &EXPLOSION_FRAMES,
+    border: BorderGlyphs {
+        horizontal: "-",
+        vertical: "|",
+        top_left: "+",
+        top_right: "+",
+        bottom_left: "+",
+        bottom_right: "+",
+    },
+};
+
+/// Block and box-drawing characters, for terminals running in a UTF-8 locale.
+pub const UNICODE: SpriteSet = SpriteSet {
+    player: &["╱▲╲", "▀▀▀"],
+    alien: &["▄▀▄", "╱▀╲"],
+    ufo: "◄═UFO═►",
+    boss: ["╔═════════╗", "║(O) │ (O)║", "╚═▼══▼══▼═╝"],
+    shots: ["│", "╲", "╱"],
+    alien_shot: "v",
+    bunker: "█",
+    explosion: &["╲*╱", "·:·"],
+    border: BorderGlyphs {
+        horizontal: "─",
+        vertical: "│",
+        top_left: "┌",
+        top_right: "┐",
+        bottom_left: "└",
+        bottom_right: "┘",
+    },
+};
+
+/// Where a sprite pack is picked up from when none is given on the command
+/// line.
+pub const PACK_FILE: &str = "sprites.toml";
+
+impl SpriteSet {
+    /// Picks [`UNICODE`] when the locale's character set is UTF-8, looking at
+    /// the variables in the order the C library does.
+    pub fn detect() -> &'static SpriteSet {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if locale.contains("utf-8") || locale.contains("utf8") {
+            &UNICODE
+        } else {
+            &ASCII
+        }
+    }
+
+    pub fn player_size(&self) -> SpriteSize {
+        measure(self.player)
+    }
+
+    pub fn alien_size(&self) -> SpriteSize {
+        measure(self.alien)
+    }
+
+    /// Reads a sprite pack, taking whatever it leaves out from `base`.
+    pub fn load(path: &Path, base: &SpriteSet) -> Result<&'static SpriteSet, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let pack = SpriteSet::from_pack(&text, base).map_err(|err| format!("{}: {}", path.display(), err))?;
+        Ok(Box::leak(Box::new(pack)))
+    }
+
+    /// Parses a sprite pack: a small subset of TOML made of `name = "glyphs"`
+    /// and `name = ["row", "row"]` lines, with `#` comments.
+    ///
+    /// Packs are loaded once per run, so their strings are simply leaked.
+    pub fn from_pack(text: &str, base: &SpriteSet) -> Result<SpriteSet, String> {
+        let mut sprites = base.clone();
+        let mut parser = Parser::new(text);
+        while let Some(key) = parser.key()? {
+            let line = parser.line();
+            let bad = |what: &str| format!("line {}: {} {}", line, key, what);
+            let value = parser.value()?;
+            let rows = || -> Result<&'static [&'static str], String> {
+                let rows = value.rows().ok_or_else(|| bad("must be a list of rows"))?;
+                let width = rows.first().map_or(0, |row| row.chars().count());
+                if width == 0 || rows.iter().any(|row| row.chars().count() != width) {
+                    return Err(bad("needs rows of one and the same width"));
+                }
+                Ok(rows)
+            };
+            let glyph = || -> Result<&'static str, String> {
+                match value.text() {
+                    Some(glyph) if glyph.chars().count() == 1 => Ok(glyph),
+                    _ => Err(bad("must be a single character")),
+                }
+            };
+            match key {
+                "player" => sprites.player = rows()?,
+                "alien" => sprites.alien = rows()?,
+                "shot" => sprites.shots[0] = glyph()?,
+                "shot_left" => sprites.shots[1] = glyph()?,
+                "shot_right" => sprites.shots[2] = glyph()?,
+                "alien_shot" => sprites.alien_shot = glyph()?,
+                "bunker" => sprites.bunker = glyph()?,
+                "explosion" => {
+                    // Each frame is drawn on a single row
+                    let frames = value.rows().ok_or_else(|| bad("must be a list of frames"))?;
+                    if frames.is_empty() || frames.iter().any(|frame| frame.is_empty()) {
+                        return Err(bad("needs at least one non-empty frame"));
+                    }
+                    sprites.explosion = frames;
+                }
+                _ => return Err(format!("line {}: unknown sprite '{}'", line, key)),
+            }
+        }
+
+        let check = |name: &str, size: SpriteSize, max: SpriteSize| {
+            if size.fits_in(max) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "the {} sprite is {}x{}, but can be at most {}x{}",
+                    name, size.width, size.height, max.width, max.height
+                ))
+            }
+        };
+        check("player", sprites.player_size(), MAX_PLAYER_SIZE)?;
+        check("alien", sprites.alien_size(), MAX_ALIEN_SIZE)?;
+        Ok(sprites)
+    }
+}
+
+fn measure(rows: &[&str]) -> SpriteSize {
+    let width = rows.first().map_or(0, |row| row.chars().count());
+    SpriteSize { width: width as u16, height: rows.len() as u16 }
+}
@@ -0,0 +1,379 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossterm::style::ResetColor;
+use crossterm::{cursor, queue, terminal};
+
+use super::buffer::FrameBuffer;
+use super::crossterm::write_changes;
+use super::pixels;
+use super::sprites::{self, SpriteSet};
+use super::theme::{ColorDepth, Theme, CLASSIC};
+use super::{Color, Renderer};
+use crate::input::{Input, Key};
+
+// --- Telnet Commands ---
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const ECHO: u8 = 1;
+const SUPPRESS_GO_AHEAD: u8 = 3;
+/// Negotiate About Window Size, the option clients report their size with.
+const NAWS: u8 = 31;
+
+/// The screen size assumed until the client reports its own.
+const DEFAULT_SIZE: (u16, u16) = (80, 24);
+/// How long a fresh connection is given to report its size.
+const SIZE_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long sending a frame may take before the client counts as gone.
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Renders to a telnet client at the other end of a TCP connection, in the
+/// escape codes of an ANSI terminal, and reads its keys.
+///
+/// Frames are composed in a [`FrameBuffer`] and only the cells that changed
+/// go out, as with the crossterm backend. The client is asked to leave
+/// echoing to the server and to send every key as it is typed, and to
+/// report its window size, which the screen follows as it changes. Its
+/// locale and colors are unknown, so it gets plain ASCII in the eight basic
+/// colors.
+pub struct TelnetRenderer {
+    stream: TcpStream,
+    received: Receiver<Vec<u8>>,
+    decoder: Decoder,
+    keys: VecDeque<Key>,
+    frame: FrameBuffer,
+    /// What the client shows, as of the last frame written.
+    shown: FrameBuffer,
+    theme: &'static Theme,
+    sprites: &'static SpriteSet,
+    /// Whether the client has hung up, or stopped taking frames.
+    closed: bool,
+}
+
+impl TelnetRenderer {
+    /// Sets up the client's terminal at the other end of `stream`.
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_write_timeout(Some(SEND_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        let mut reader = stream.try_clone()?;
+        let (sender, received) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0; 512];
+            while let Ok(read @ 1..) = reader.read(&mut buffer) {
+                if sender.send(buffer[..read].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let (width, height) = DEFAULT_SIZE;
+        let mut renderer = TelnetRenderer {
+            stream,
+            received,
+            decoder: Decoder::default(),
+            keys: VecDeque::new(),
+            frame: FrameBuffer::new(width as usize, height as usize),
+            shown: FrameBuffer::new(width as usize, height as usize),
+            theme: &CLASSIC,
+            sprites: &sprites::ASCII,
+            closed: false,
+        };
+        let mut setup = vec![IAC, WILL, ECHO, IAC, WILL, SUPPRESS_GO_AHEAD, IAC, DO, NAWS];
+        queue!(setup, terminal::EnterAlternateScreen, terminal::Clear(terminal::ClearType::All), cursor::Hide)?;
+        renderer.stream.write_all(&setup)?;
+
+        // Take the size the client answers with, if it does so soon
+        let deadline = Instant::now() + SIZE_TIMEOUT;
+        while renderer.decoder.size.is_none() && !renderer.closed {
+            renderer.receive(deadline.saturating_duration_since(Instant::now()));
+            if Instant::now() >= deadline {
+                break;
+            }
+        }
+        renderer.resize();
+        Ok(renderer)
+    }
+
+    /// Waits at most `timeout` for bytes from the client, decoding them.
+    fn receive(&mut self, timeout: Duration) {
+        match self.received.recv_timeout(timeout) {
+            Ok(bytes) => {
+                self.keys.extend(self.decoder.decode(&bytes));
+                // Take whatever else arrived as well, for escape codes cut
+                // across reads
+                while let Ok(bytes) = self.received.try_recv() {
+                    self.keys.extend(self.decoder.decode(&bytes));
+                }
+                self.keys.extend(self.decoder.finish());
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => self.closed = true,
+        }
+    }
+
+    /// Follows the size the client last reported.
+    fn resize(&mut self) {
+        let Some((width, height)) = self.decoder.size.take() else {
+            return;
+        };
+        let (width, height) = (width as usize, height as usize);
+        self.frame.resize(width, height);
+        self.shown.resize(width, height);
+        self.shown.invalidate(0, 0, width as i32, height as i32);
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        let mut out = Vec::new();
+        write_changes(&mut out, &self.frame, &self.shown, true, ColorDepth::Basic, self.theme)?;
+        self.stream.write_all(&escape_iac(out))?;
+        self.shown.copy_from(&self.frame);
+        Ok(())
+    }
+}
+
+impl Drop for TelnetRenderer {
+    fn drop(&mut self) {
+        let mut out = Vec::new();
+        let _ = queue!(out, ResetColor, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = self.stream.write_all(&out);
+        // Which also ends the thread reading from the client
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Doubles the bytes of `out` that telnet would take for commands.
+fn escape_iac(out: Vec<u8>) -> Vec<u8> {
+    if !out.contains(&IAC) {
+        return out;
+    }
+    out.into_iter().flat_map(|byte| if byte == IAC { vec![IAC, IAC] } else { vec![byte] }).collect()
+}
+
+impl Renderer for TelnetRenderer {
+    fn clear(&mut self) {
+        self.frame.clear();
+    }
+
+    fn draw_str(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        self.frame.draw_str(x, y, text, color);
+    }
+
+    fn draw_pixels(&mut self, x: i32, y: i32, top: Option<Color>, bottom: Option<Color>) {
+        if let Some((glyph, color)) = pixels::single_color_glyph(top, bottom) {
+            self.frame.draw_str(x, y, glyph, color);
+        }
+    }
+
+    fn present(&mut self) {
+        if !self.closed && self.write_frame().is_err() {
+            self.closed = true;
+        }
+    }
+
+    fn size(&self) -> (i32, i32) {
+        self.frame.size()
+    }
+
+    fn set_theme(&mut self, theme: &'static Theme) {
+        self.theme = theme;
+        // The same logical colors come out differently now
+        let (width, height) = self.frame.size();
+        self.shown.invalidate(0, 0, width, height);
+    }
+
+    fn sprites(&self) -> &'static SpriteSet {
+        self.sprites
+    }
+
+    fn set_sprites(&mut self, sprites: &'static SpriteSet) {
+        self.sprites = sprites;
+    }
+
+    fn bell(&mut self, _flash: bool) {
+        if !self.closed && self.stream.write_all(b"\x07").is_err() {
+            self.closed = true;
+        }
+    }
+}
+
+impl Input for TelnetRenderer {
+    fn poll_key(&mut self, timeout: Duration) -> Option<Key> {
+        if self.keys.is_empty() && !self.closed {
+            self.receive(timeout);
+            self.resize();
+        }
+        self.keys.pop_front()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
+
+// --- Decoding ---
+
+/// Turns what a telnet client sends into keys, picking out the telnet
+/// commands among them.
+#[derive(Default)]
+struct Decoder {
+    /// Bytes of a key or command that has not been sent whole yet.
+    pending: Vec<u8>,
+    /// The window size the client last reported, until it is taken.
+    size: Option<(u16, u16)>,
+}
+
+/// What the bytes at the start of a buffer make.
+enum Token {
+    Key(Key),
+    /// A telnet command, or a byte to ignore.
+    Skip,
+    /// More bytes are needed to tell.
+    Incomplete,
+}
+
+impl Decoder {
+    /// The keys in `bytes`, following on from those sent before.
+    fn decode(&mut self, bytes: &[u8]) -> Vec<Key> {
+        self.pending.extend_from_slice(bytes);
+        let mut keys = Vec::new();
+        let mut start = 0;
+        while start < self.pending.len() {
+            let (token, len) = self.token(start);
+            match token {
+                Token::Key(key) => keys.push(key),
+                Token::Skip => {}
+                Token::Incomplete => break,
+            }
+            start += len;
+        }
+        self.pending.drain(..start);
+        keys
+    }
+
+    /// The keys left once the client has nothing more to send for now: an
+    /// escape key on its own is only told apart from the start of an
+    /// escape code by nothing following it.
+    fn finish(&mut self) -> Option<Key> {
+        if self.pending == [0x1b] {
+            self.pending.clear();
+            return Some(Key::Escape);
+        }
+        None
+    }
+
+    /// Reads the token at `start` of the pending bytes, and how many bytes
+    /// it takes.
+    fn token(&mut self, start: usize) -> (Token, usize) {
+        let bytes = &self.pending[start..];
+        match bytes {
+            [IAC, IAC, ..] => (Token::Skip, 2),
+            [IAC, SB, ..] => {
+                // A subnegotiation runs up to IAC SE
+                let Some(end) = bytes.windows(2).position(|pair| pair == [IAC, SE]) else {
+                    return (Token::Incomplete, 0);
+                };
+                if let [NAWS, width_high, width_low, height_high, height_low, ..] = unescape_iac(&bytes[2..end])[..] {
+                    let size = (u16::from_be_bytes([width_high, width_low]), u16::from_be_bytes([height_high, height_low]));
+                    // Some clients answer 0 for a size they do not know
+                    if size.0 > 0 && size.1 > 0 {
+                        self.size = Some(size);
+                    }
+                }
+                (Token::Skip, end + 2)
+            }
+            [IAC, WILL | WONT | DO | DONT, _, ..] => (Token::Skip, 3),
+            [IAC, WILL | WONT | DO | DONT] | [IAC] => (Token::Incomplete, 0),
+            [IAC, _, ..] => (Token::Skip, 2),
+            // Enter comes as CR LF or CR NUL
+            [b'\r', b'\n' | 0, ..] => (Token::Key(Key::Enter), 2),
+            [b'\r'] => (Token::Incomplete, 0),
+            [b'\r' | b'\n', ..] => (Token::Key(Key::Enter), 1),
+            [0x7f | 0x08, ..] => (Token::Key(Key::Backspace), 1),
+            // No signals come through, so Ctrl-C quits as in the crossterm backend
+            [0x03, ..] => (Token::Key(Key::Char('q')), 1),
+            [0x1b, ..] => escape_code(bytes),
+            [byte, ..] if *byte < 0x20 => (Token::Skip, 1),
+            [byte, ..] => {
+                let len = match byte.leading_ones() {
+                    2 => 2,
+                    3 => 3,
+                    4 => 4,
+                    _ => 1,
+                };
+                if bytes.len() < len {
+                    return (Token::Incomplete, 0);
+                }
+                match std::str::from_utf8(&bytes[..len]).ok().and_then(|text| text.chars().next()) {
+                    Some(c) => (Token::Key(Key::Char(c)), len),
+                    None => (Token::Skip, 1),
+                }
+            }
+            [] => (Token::Incomplete, 0),
+        }
+    }
+}
+
+/// Reads the escape code at the start of `bytes`: the arrows and the
+/// function keys an xterm sends, and anything else skipped whole.
+fn escape_code(bytes: &[u8]) -> (Token, usize) {
+    match bytes {
+        [0x1b] => (Token::Incomplete, 0),
+        // SS3 codes, as for F1 to F4 and arrows in application mode
+        [0x1b, b'O', final_byte, ..] => {
+            let key = match *final_byte {
+                b'A' => Some(Key::Up),
+                b'B' => Some(Key::Down),
+                b'C' => Some(Key::Right),
+                b'D' => Some(Key::Left),
+                byte @ b'P'..=b'S' => Some(Key::Function(byte - b'P' + 1)),
+                _ => None,
+            };
+            (key.map_or(Token::Skip, Token::Key), 3)
+        }
+        [0x1b, b'O'] => (Token::Incomplete, 0),
+        // CSI codes: parameters, then a final byte
+        [0x1b, b'[', rest @ ..] => {
+            let Some(end) = rest.iter().position(|byte| (0x40..=0x7e).contains(byte)) else {
+                return (Token::Incomplete, 0);
+            };
+            let params = std::str::from_utf8(&rest[..end]).unwrap_or_default();
+            let key = match (rest[end], params) {
+                (b'A', _) => Some(Key::Up),
+                (b'B', _) => Some(Key::Down),
+                (b'C', _) => Some(Key::Right),
+                (b'D', _) => Some(Key::Left),
+                (b'~', "11") => Some(Key::Function(1)),
+                (b'~', "12") => Some(Key::Function(2)),
+                (b'~', "13") => Some(Key::Function(3)),
+                (b'~', "14") => Some(Key::Function(4)),
+                _ => None,
+            };
+            (key.map_or(Token::Skip, Token::Key), end + 3)
+        }
+        // Escape followed by anything else was the escape key
+        [0x1b, ..] => (Token::Key(Key::Escape), 1),
+        _ => (Token::Skip, 1),
+    }
+}
+
+/// Halves the doubled IAC bytes of a subnegotiation.
+fn unescape_iac(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut bytes = bytes.iter();
+    while let Some(&byte) = bytes.next() {
+        out.push(byte);
+        if byte == IAC {
+            bytes.next();
+        }
+    }
+    out
+}
@@ -0,0 +1,348 @@
+//! Color themes: how the logical [`Color`]s are painted on screen.
+//!
+//! Every theme is made of the eight basic colors, so it works on any color
+//! terminal. Terminals with 256 colors or truecolor additionally get shaded
+//! variants of them, such as the alien rows' gradient and the dim stars.
+//!
+//! Mods can bring themes of their own, files in the format of
+//! [`crate::pack`] naming a paint for each color they change:
+//!
+//! ```toml
+//! name = "neon"
+//! alien = "magenta"
+//! border = "cyan"
+//! alien_shot_glyph = "Z"   # optional
+//! ```
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use super::Color;
+use crate::pack::Parser;
+
+/// How many colors the terminal can show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorDepth {
+    /// The eight basic colors.
+    Basic,
+    /// The xterm 256-color palette.
+    Palette256,
+    /// Any 24-bit RGB color.
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// Guesses the depth from `$COLORTERM` and `$TERM`, the way most
+    /// terminal programs do.
+    pub fn detect() -> Self {
+        let var = |name| env::var(name).unwrap_or_default().to_ascii_lowercase();
+        let colorterm = var("COLORTERM");
+        if colorterm.contains("truecolor") || colorterm.contains("24bit") {
+            ColorDepth::TrueColor
+        } else if var("TERM").contains("256color") {
+            ColorDepth::Palette256
+        } else {
+            ColorDepth::Basic
+        }
+    }
+}
+
+/// A 24-bit color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// The color at `percent` of its brightness.
+    pub fn scaled(self, percent: u8) -> Rgb {
+        let scale = |channel: u8| (channel as u16 * percent.min(100) as u16 / 100) as u8;
+        Rgb(scale(self.0), scale(self.1), scale(self.2))
+    }
+
+    /// The closest entry of the xterm 256-color palette's 6x6x6 color cube.
+    pub fn to_palette256(self) -> u8 {
+        let level = |channel: u8| ((channel as u16 * 5 + 127) / 255) as u8;
+        16 + 36 * level(self.0) + 6 * level(self.1) + level(self.2)
+    }
+}
+
+/// The eight colors every color terminal can show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Paint {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl Paint {
+    const ALL: [Paint; 8] =
+        [Paint::Black, Paint::Red, Paint::Green, Paint::Yellow, Paint::Blue, Paint::Magenta, Paint::Cyan, Paint::White];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Paint::Black => "black",
+            Paint::Red => "red",
+            Paint::Green => "green",
+            Paint::Yellow => "yellow",
+            Paint::Blue => "blue",
+            Paint::Magenta => "magenta",
+            Paint::Cyan => "cyan",
+            Paint::White => "white",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Paint> {
+        Paint::ALL.into_iter().find(|paint| paint.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn rgb(self) -> Rgb {
+        match self {
+            Paint::Black => Rgb(0, 0, 0),
+            Paint::Red => Rgb(230, 60, 50),
+            Paint::Green => Rgb(80, 220, 90),
+            Paint::Yellow => Rgb(240, 200, 60),
+            Paint::Blue => Rgb(70, 120, 240),
+            Paint::Magenta => Rgb(220, 80, 220),
+            Paint::Cyan => Rgb(60, 210, 230),
+            Paint::White => Rgb(240, 240, 240),
+        }
+    }
+}
+
+/// Brightness lost by each alien row below the top one, in percent.
+const ALIEN_ROW_SHADE: u8 = 12;
+/// Brightness of the background stars, in percent of the border's.
+const STAR_BRIGHTNESS: u8 = 35;
+
+/// A paint for each logical color.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Theme {
+    pub name: &'static str,
+    pub ui: Paint,
+    pub player: Paint,
+    pub shot: Paint,
+    pub alien: Paint,
+    pub game_over: Paint,
+    pub alien_shot: Paint,
+    pub border: Paint,
+    pub highlight: Paint,
+    pub ufo: Paint,
+    pub explosion: Paint,
+    /// Drawn for alien shots instead of the sprite set's, so they can be
+    /// told from the player's by shape as well as by color.
+    pub alien_shot_glyph: Option<&'static str>,
+}
+
+impl Theme {
+    pub fn paint(&self, color: Color) -> Paint {
+        match color {
+            Color::Ui => self.ui,
+            Color::Player => self.player,
+            Color::Shot => self.shot,
+            Color::Alien | Color::AlienRow(_) => self.alien,
+            Color::GameOver => self.game_over,
+            Color::AlienShot => self.alien_shot,
+            Color::Border => self.border,
+            Color::Highlight => self.highlight,
+            Color::Ufo => self.ufo,
+            Color::Explosion => self.explosion,
+            Color::Star => self.border,
+        }
+    }
+
+    /// The exact color for terminals that can show more than `paint` does.
+    pub fn rgb(&self, color: Color) -> Rgb {
+        let base = self.paint(color).rgb();
+        match color {
+            Color::AlienRow(row) => base.scaled(100 - ALIEN_ROW_SHADE.saturating_mul(row).min(60)),
+            Color::Star => base.scaled(STAR_BRIGHTNESS),
+            _ => base,
+        }
+    }
+
+    /// Reads a theme file, whose name is put after `prefix` so it cannot be
+    /// taken for a built-in theme's; whatever it leaves out is as in
+    /// [`CLASSIC`].
+    pub fn load(path: &Path, prefix: &str) -> Result<&'static Theme, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let theme = Theme::parse(&text, prefix).map_err(|err| format!("{}: {}", path.display(), err))?;
+        Ok(Box::leak(Box::new(theme)))
+    }
+
+    fn parse(text: &str, prefix: &str) -> Result<Theme, String> {
+        let mut theme = CLASSIC;
+        let mut name = None;
+        let mut parser = Parser::new(text);
+        while let Some(key) = parser.key()? {
+            let line = parser.line();
+            let bad = |what: &str| format!("line {}: {} {}", line, key, what);
+            let value = parser.value()?;
+            let text = value.text().ok_or_else(|| bad("must be a string"))?;
+            let paint = || Paint::from_name(text).ok_or_else(|| bad("must be one of the eight basic colors"));
+            match key {
+                "name" => name = Some(text),
+                "ui" => theme.ui = paint()?,
+                "player" => theme.player = paint()?,
+                "shot" => theme.shot = paint()?,
+                "alien" => theme.alien = paint()?,
+                "game_over" => theme.game_over = paint()?,
+                "alien_shot" => theme.alien_shot = paint()?,
+                "border" => theme.border = paint()?,
+                "highlight" => theme.highlight = paint()?,
+                "ufo" => theme.ufo = paint()?,
+                "explosion" => theme.explosion = paint()?,
+                "alien_shot_glyph" if text.chars().count() == 1 => theme.alien_shot_glyph = Some(text),
+                "alien_shot_glyph" => return Err(bad("must be a single character")),
+                _ => return Err(format!("line {}: unknown color '{}'", line, key)),
+            }
+        }
+        let name = name.ok_or("a theme needs a name")?;
+        theme.name = String::leak(format!("{}/{}", prefix, name));
+        Ok(theme)
+    }
+
+    pub fn by_name(name: &str) -> Option<&'static Theme> {
+        THEMES.iter().find(|theme| theme.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The theme after this one in [`THEMES`], wrapping around.
+    pub fn next(&self) -> &'static Theme {
+        let index = THEMES.iter().position(|theme| theme == self).unwrap_or(0);
+        &THEMES[(index + 1) % THEMES.len()]
+    }
+
+    /// The theme before this one in [`THEMES`], wrapping around.
+    pub fn previous(&self) -> &'static Theme {
+        let index = THEMES.iter().position(|theme| theme == self).unwrap_or(0);
+        &THEMES[(index + THEMES.len() - 1) % THEMES.len()]
+    }
+}
+
+/// The game's original colors.
+pub const CLASSIC: Theme = Theme {
+    name: "classic",
+    ui: Paint::Yellow,
+    player: Paint::Cyan,
+    shot: Paint::Red,
+    alien: Paint::Green,
+    game_over: Paint::Red,
+    alien_shot: Paint::Magenta,
+    border: Paint::Blue,
+    highlight: Paint::White,
+    ufo: Paint::Red,
+    explosion: Paint::Yellow,
+    alien_shot_glyph: None,
+};
+
+/// Every theme, in the order the `t` key cycles through them.
+pub const THEMES: [Theme; 8] = [
+    CLASSIC,
+    // A green phosphor monitor
+    Theme {
+        name: "green",
+        ui: Paint::Green,
+        player: Paint::Green,
+        shot: Paint::White,
+        alien: Paint::Green,
+        game_over: Paint::White,
+        alien_shot: Paint::Green,
+        border: Paint::Green,
+        highlight: Paint::White,
+        ufo: Paint::White,
+        explosion: Paint::White,
+        alien_shot_glyph: None,
+    },
+    // An amber CRT
+    Theme {
+        name: "amber",
+        ui: Paint::Yellow,
+        player: Paint::Yellow,
+        shot: Paint::White,
+        alien: Paint::Yellow,
+        game_over: Paint::White,
+        alien_shot: Paint::Red,
+        border: Paint::Yellow,
+        highlight: Paint::White,
+        ufo: Paint::Red,
+        explosion: Paint::White,
+        alien_shot_glyph: None,
+    },
+    Theme {
+        name: "synthwave",
+        ui: Paint::Cyan,
+        player: Paint::Cyan,
+        shot: Paint::Yellow,
+        alien: Paint::Magenta,
+        game_over: Paint::Magenta,
+        alien_shot: Paint::Red,
+        border: Paint::Magenta,
+        highlight: Paint::Yellow,
+        ufo: Paint::Yellow,
+        explosion: Paint::Yellow,
+        alien_shot_glyph: None,
+    },
+    Theme {
+        name: "solarized",
+        ui: Paint::Yellow,
+        player: Paint::Blue,
+        shot: Paint::Cyan,
+        alien: Paint::Green,
+        game_over: Paint::Red,
+        alien_shot: Paint::Magenta,
+        border: Paint::Cyan,
+        highlight: Paint::White,
+        ufo: Paint::Red,
+        explosion: Paint::Yellow,
+        alien_shot_glyph: None,
+    },
+    // The colorblind palettes keep friend and foe on opposite sides of the
+    // blue-yellow axis, or of the red-cyan one when blue and yellow blur
+    // together, and give alien shots a zigzag shape of their own
+    Theme {
+        name: "deuteranopia",
+        ui: Paint::White,
+        player: Paint::Cyan,
+        shot: Paint::Cyan,
+        alien: Paint::Yellow,
+        game_over: Paint::Yellow,
+        alien_shot: Paint::Yellow,
+        border: Paint::Blue,
+        highlight: Paint::White,
+        ufo: Paint::Yellow,
+        explosion: Paint::White,
+        alien_shot_glyph: Some("Z"),
+    },
+    Theme {
+        name: "protanopia",
+        ui: Paint::White,
+        player: Paint::Blue,
+        shot: Paint::Cyan,
+        alien: Paint::Yellow,
+        game_over: Paint::Yellow,
+        alien_shot: Paint::Yellow,
+        border: Paint::Blue,
+        highlight: Paint::White,
+        ufo: Paint::Yellow,
+        explosion: Paint::White,
+        alien_shot_glyph: Some("Z"),
+    },
+    Theme {
+        name: "tritanopia",
+        ui: Paint::White,
+        player: Paint::Cyan,
+        shot: Paint::Cyan,
+        alien: Paint::Red,
+        game_over: Paint::Red,
+        alien_shot: Paint::Red,
+        border: Paint::Cyan,
+        highlight: Paint::White,
+        ufo: Paint::Red,
+        explosion: Paint::White,
+        alien_shot_glyph: Some("Z"),
+    },
+];
@@ -0,0 +1,236 @@
+//! A tiny widget toolkit on top of [`Renderer`] primitives.
+//!
+//! Widgets draw themselves into a [`Rect`], so screens are composed by
+//! splitting areas rather than by hand-placing strings.
+
+use super::{Color, Renderer};
+use crate::locale::{tr, tr_with};
+
+/// A rectangular area of the screen, in terminal cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub const fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Rect { x, y, width, height }
+    }
+
+    /// The area left inside a one-cell border.
+    pub fn inner(&self) -> Rect {
+        Rect::new(self.x + 1, self.y + 1, (self.width - 2).max(0), (self.height - 2).max(0))
+    }
+
+    /// The same area moved by `dx` columns and `dy` rows.
+    pub fn offset(&self, dx: i32, dy: i32) -> Rect {
+        Rect::new(self.x + dx, self.y + dy, self.width, self.height)
+    }
+
+    pub fn right(&self) -> i32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height
+    }
+}
+
+pub trait Widget {
+    fn draw(&self, area: Rect, renderer: &mut dyn Renderer);
+}
+
+// --- Block ---
+
+/// A box border with an optional title set into the top edge.
+pub struct Block<'a> {
+    title: Option<&'a str>,
+    color: Color,
+}
+
+impl<'a> Block<'a> {
+    pub fn new() -> Self {
+        Block { title: None, color: Color::Border }
+    }
+
+    pub fn title(mut self, title: &'a str) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+impl Default for Block<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Widget for Block<'_> {
+    fn draw(&self, area: Rect, renderer: &mut dyn Renderer) {
+        if area.width < 2 || area.height < 2 {
+            return;
+        }
+        let glyphs = &renderer.sprites().border;
+        let horizontal = glyphs.horizontal.repeat((area.width - 2) as usize);
+        let top = format!("{}{}{}", glyphs.top_left, horizontal, glyphs.top_right);
+        let bottom = format!("{}{}{}", glyphs.bottom_left, horizontal, glyphs.bottom_right);
+        renderer.draw_str(area.x, area.y, &top, self.color);
+        renderer.draw_str(area.x, area.bottom() - 1, &bottom, self.color);
+        for y in area.y + 1..area.bottom() - 1 {
+            renderer.draw_str(area.x, y, glyphs.vertical, self.color);
+            renderer.draw_str(area.right() - 1, y, glyphs.vertical, self.color);
+        }
+        if let Some(title) = self.title {
+            let title = format!(" {} ", title);
+            if (title.chars().count() as i32) <= area.width - 4 {
+                renderer.draw_str(area.x + 2, area.y, &title, self.color);
+            }
+        }
+    }
+}
+
+// --- Text ---
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Lines of text aligned horizontally within their area, stacked from the top.
+/// Lines wider than the area are cut short at its edge.
+pub struct Text<'a> {
+    lines: &'a [&'a str],
+    align: Align,
+    color: Color,
+}
+
+impl<'a> Text<'a> {
+    pub fn new(lines: &'a [&'a str], color: Color) -> Self {
+        Text { lines, align: Align::Left, color }
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+}
+
+impl Widget for Text<'_> {
+    fn draw(&self, area: Rect, renderer: &mut dyn Renderer) {
+        for (i, line) in self.lines.iter().take(area.height.max(0) as usize).enumerate() {
+            let width = line.chars().count() as i32;
+            let x = match self.align {
+                Align::Left => area.x,
+                Align::Center => area.x + (area.width - width).max(0) / 2,
+                Align::Right => area.x + (area.width - width).max(0),
+            };
+            let line = match line.char_indices().nth(area.width.max(0) as usize) {
+                Some((end, _)) => &line[..end],
+                None => line,
+            };
+            renderer.draw_str(x, area.y + i as i32, line, self.color);
+        }
+    }
+}
+
+// --- Charge Meter ---
+
+/// A labelled gauge filling up from left to right.
+pub struct Meter<'a> {
+    pub label: &'a str,
+    pub value: u16,
+    pub max: u16,
+}
+
+impl Widget for Meter<'_> {
+    fn draw(&self, area: Rect, renderer: &mut dyn Renderer) {
+        let cells = (area.width - self.label.chars().count() as i32 - 4).max(0) as usize;
+        let filled = cells * self.value.min(self.max) as usize / self.max.max(1) as usize;
+        let color = if self.value >= self.max { Color::Highlight } else { Color::Ui };
+        let meter = format!(" {} [{}{}]", self.label, "#".repeat(filled), "-".repeat(cells - filled));
+        renderer.draw_str(area.x, area.y, &meter, color);
+    }
+}
+
+// --- Lives ---
+
+/// Remaining lives drawn as small ships, like the arcade's bottom-left corner.
+/// Lives that don't fit are summed up as a count after the last icon.
+pub struct Lives {
+    pub lives: u8,
+    /// The color of the ship the lives belong to.
+    pub color: Color,
+}
+
+impl Widget for Lives {
+    fn draw(&self, area: Rect, renderer: &mut dyn Renderer) {
+        let icon = format!("{} ", renderer.sprites().player[0]);
+        let icon_width = icon.chars().count() as i32;
+        let lives = self.lives as i32;
+        // Keep room for a "+NNN" count when not every icon fits
+        let shown = if lives * icon_width <= area.width { lives } else { ((area.width - 4) / icon_width).max(0) };
+        for i in 0..shown {
+            renderer.draw_str(area.x + i * icon_width, area.y, &icon, self.color);
+        }
+        if shown < lives {
+            let more = format!("+{}", lives - shown);
+            renderer.draw_str(area.x + shown * icon_width, area.y, &more, Color::Ui);
+        }
+    }
+}
+
+// --- HUD ---
+
+/// The two status lines: score, best score and wave on the first; smart
+/// bombs on the second, with the combo multiplier or a "1UP!" flash next to
+/// them. In co-op the second player's score goes under the first's. The
+/// best score makes way when it would run into the others.
+pub struct Hud {
+    /// Each player's score, player one's first.
+    pub scores: Vec<u32>,
+    pub high_score: u32,
+    pub wave: u32,
+    pub bombs: u8,
+    pub multiplier: u32,
+    pub one_up: bool,
+}
+
+impl Widget for Hud {
+    fn draw(&self, area: Rect, renderer: &mut dyn Renderer) {
+        let scores: Vec<String> = match self.scores.as_slice() {
+            [score] => vec![tr_with("hud_score", &[score])],
+            scores => {
+                let score = |(i, score): (usize, &u32)| tr_with("hud_player_score", &[&(i + 1), score]);
+                scores.iter().enumerate().map(score).collect()
+            }
+        };
+        let scores: Vec<&str> = scores.iter().map(String::as_str).collect();
+        let high_score = tr_with("hud_high_score", &[&self.high_score]);
+        let wave = tr_with("hud_wave", &[&self.wave]);
+        let bombs = tr_with("hud_bombs", &[&self.bombs]);
+        Text::new(&scores, Color::Ui).draw(area, renderer);
+        Text::new(&[&wave, &bombs], Color::Ui).align(Align::Right).draw(area, renderer);
+        let width = |text: &str| text.chars().count() as i32;
+        let sides = scores.iter().map(|score| width(score)).max().unwrap_or(0).max(width(&wave)) + 1;
+        if width(&high_score) <= area.width - 2 * sides {
+            Text::new(&[&high_score], Color::Ui).align(Align::Center).draw(area, renderer);
+        }
+        let second_line = Rect::new(area.x, area.y + 1, area.width, 1);
+        if self.one_up {
+            Text::new(&[tr("hud_one_up")], Color::Highlight).align(Align::Center).draw(second_line, renderer);
+        } else if self.multiplier > 1 {
+            let combo = tr_with("hud_combo", &[&self.multiplier]);
+            Text::new(&[&combo], Color::Highlight).align(Align::Center).draw(second_line, renderer);
+        }
+    }
+}
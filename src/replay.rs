@@ -0,0 +1,446 @@
+//! Recording runs and playing them back.
+//!
+//! A replay is the settings a run started with (including its seed) plus
+//! every [`Action`] tagged with the tick it happened on. Because updates are
+//! deterministic, feeding the same actions at the same ticks into a fresh
+//! [`GameState`] reproduces the run exactly.
+//!
+//! The file format is plain text: a header of `key value` lines, then an
+//! `events` line followed by one line per tick with input, e.g. `42 LLF`.
+//! In co-op and versus the second player's inputs are written in lowercase,
+//! e.g. `42 Lrf`.
+//!
+//! A run of a mod's levels names the mod in a `levels` header, and plays
+//! back only where that mod is installed.
+//!
+//! Saving a run to continue it later saves its replay so far, with a
+//! `saved_at` header giving the tick it stopped at. Playing that back
+//! restores every bit of the game, down to the state of its randomness.
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::game::{
+    self, Action, AssistOption, Difficulty, GameState, Modifier, Settings, SpriteSize, MAX_ALIEN_SIZE, MAX_PLAYER_SIZE,
+};
+use crate::mods;
+use crate::storage;
+
+const MAGIC: &str = "term_invaders replay 3";
+/// Where the most recent run is saved.
+pub const LAST_RUN_FILE: &str = "last.replay";
+/// Where a run saved to be continued later is kept.
+pub const SAVED_RUN_FILE: &str = "saved.replay";
+
+#[derive(Clone, Debug)]
+pub struct Replay {
+    /// Settings of the recorded run; `seed` is always set.
+    pub settings: Settings,
+    /// The actions, with the tick they happened on and the player taking them.
+    pub events: Vec<(u64, usize, Action)>,
+    /// For a run saved to be continued, the tick it was saved at.
+    pub saved_at: Option<u64>,
+}
+
+/// The code of `action`, in uppercase for player one and lowercase for
+/// player two.
+pub fn action_code(player: usize, action: Action) -> char {
+    let code = match action {
+        Action::MoveLeft => 'L',
+        Action::MoveRight => 'R',
+        Action::Fire => 'F',
+        Action::SwitchWeapon => 'W',
+        Action::ChargeLaser => 'C',
+        Action::ReleaseLaser => 'X',
+        Action::Bomb => 'B',
+        Action::AimLeft => 'A',
+        Action::AimRight => 'D',
+        Action::AlienFire => 'S',
+        Action::TurnFormation => 'T',
+    };
+    if player > 0 {
+        return code.to_ascii_lowercase();
+    }
+    code
+}
+
+/// The player and the action of `code`.
+pub fn action_from_code(code: char) -> Option<(usize, Action)> {
+    let player = usize::from(code.is_ascii_lowercase());
+    let action = match code.to_ascii_uppercase() {
+        'L' => Some(Action::MoveLeft),
+        'R' => Some(Action::MoveRight),
+        'F' => Some(Action::Fire),
+        'W' => Some(Action::SwitchWeapon),
+        'C' => Some(Action::ChargeLaser),
+        'X' => Some(Action::ReleaseLaser),
+        'B' => Some(Action::Bomb),
+        'A' => Some(Action::AimLeft),
+        'D' => Some(Action::AimRight),
+        'S' => Some(Action::AlienFire),
+        'T' => Some(Action::TurnFormation),
+        _ => None,
+    };
+    action.map(|action| (player, action))
+}
+
+impl Replay {
+    pub fn to_text(&self) -> String {
+        let settings = &self.settings;
+        let assist: Vec<&str> = settings.assist.options().map(AssistOption::name).collect();
+        let assist = if assist.is_empty() { "none".to_string() } else { assist.join(",") };
+        let modifiers: Vec<&str> = settings.modifiers.iter().map(Modifier::name).collect();
+        let modifiers = if modifiers.is_empty() { "none".to_string() } else { modifiers.join(",") };
+        let mut text = format!(
+            "{}\nseed {}\ndifficulty {}\nassist {}\nmodifiers {}\nlives {}\nextra_life {}\ntick_ms {}\nsize {}x{}\nplayer_size {}x{}\nalien_size {}x{}\n",
+            MAGIC,
+            settings.seed.unwrap_or_default(),
+            settings.difficulty.name(),
+            assist,
+            modifiers,
+            // Before the extra lives assist, which the header has already
+            settings.lives.unwrap_or_else(|| settings.difficulty.lives()),
+            settings.extra_life_every,
+            settings.tick.as_millis(),
+            settings.width,
+            settings.height,
+            settings.player_size.width,
+            settings.player_size.height,
+            settings.alien_size.width,
+            settings.alien_size.height,
+        );
+        if let Some(day) = settings.daily {
+            text.push_str(&format!("daily {}\n", day));
+        }
+        if settings.players > 1 {
+            text.push_str(&format!("players {}\n", settings.players));
+        }
+        if settings.versus {
+            text.push_str("versus true\n");
+        }
+        if settings.campaign {
+            text.push_str("campaign true\n");
+        }
+        if let Some(pack) = settings.pack {
+            text.push_str(&format!("levels {}\n", pack.id));
+        }
+        if let Some(tick) = self.saved_at {
+            text.push_str(&format!("saved_at {}\n", tick));
+        }
+        text.push_str("events\n");
+        let mut events = self.events.iter().peekable();
+        while let Some(&(tick, player, action)) = events.next() {
+            text.push_str(&format!("{} {}", tick, action_code(player, action)));
+            while let Some(&&(_, player, action)) = events.peek().filter(|(next, ..)| *next == tick) {
+                text.push(action_code(player, action));
+                events.next();
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Replay, String> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, line)| line.trim()) != Some(MAGIC) {
+            return Err("not a replay file".to_string());
+        }
+
+        let mut settings = Settings::default();
+        let mut seed = None;
+        let mut saved_at = None;
+        for (number, line) in lines.by_ref() {
+            let line = line.trim();
+            if line == "events" {
+                break;
+            }
+            let bad = || format!("line {}: cannot read '{}'", number + 1, line);
+            let (key, value) = line.split_once(' ').ok_or_else(bad)?;
+            let size = || -> Result<(u16, u16), String> {
+                let (width, height) = value.split_once('x').ok_or_else(bad)?;
+                Ok((width.parse().map_err(|_| bad())?, height.parse().map_err(|_| bad())?))
+            };
+            // A hitbox no sprite could have would play out no run recorded
+            let sprite_size = |max: SpriteSize| -> Result<SpriteSize, String> {
+                let (width, height) = size()?;
+                let sprite = SpriteSize { width, height };
+                if width == 0 || height == 0 || !sprite.fits_in(max) {
+                    let (line, max) = (number + 1, format!("{}x{}", max.width, max.height));
+                    return Err(format!("line {}: {} must be from 1x1 to {}", line, key, max));
+                }
+                Ok(sprite)
+            };
+            match key {
+                "seed" => seed = Some(value.parse().map_err(|_| bad())?),
+                "saved_at" => saved_at = Some(value.parse().map_err(|_| bad())?),
+                "difficulty" => settings.difficulty = Difficulty::from_name(value).ok_or_else(bad)?,
+                "modifiers" => {
+                    for name in value.split(',').filter(|&name| name != "none") {
+                        settings.modifiers.set(Modifier::from_name(name).ok_or_else(bad)?, true);
+                    }
+                }
+                "daily" => settings.daily = Some(value.parse().map_err(|_| bad())?),
+                "players" => settings.players = value.parse().map_err(|_| bad())?,
+                "versus" => settings.versus = value.parse().map_err(|_| bad())?,
+                "campaign" => settings.campaign = value.parse().map_err(|_| bad())?,
+                "levels" => {
+                    let found = mods::find(value)?;
+                    settings.pack = Some(found.levels.ok_or_else(|| format!("mod '{}' has no levels", value))?);
+                }
+                "assist" => {
+                    for name in value.split(',').filter(|&name| name != "none") {
+                        settings.assist.set(AssistOption::from_name(name).ok_or_else(bad)?, true);
+                    }
+                }
+                "lives" => settings.lives = Some(value.parse().map_err(|_| bad())?),
+                "extra_life" => settings.extra_life_every = value.parse().map_err(|_| bad())?,
+                "tick_ms" => settings.tick = Duration::from_millis(value.parse().map_err(|_| bad())?),
+                "size" => {
+                    (settings.width, settings.height) = size()?;
+                    game::check_field_size(settings.width, settings.height)
+                        .map_err(|err| format!("line {}: {}", number + 1, err))?;
+                }
+                "player_size" => settings.player_size = sprite_size(MAX_PLAYER_SIZE)?,
+                "alien_size" => settings.alien_size = sprite_size(MAX_ALIEN_SIZE)?,
+                // Unknown headers are skipped so newer files stay readable
+                _ => {}
+            }
+        }
+        settings.seed = Some(seed.ok_or("replay has no seed")?);
+
+        let mut events = Vec::new();
+        for (number, line) in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let bad = || format!("line {}: cannot read '{}'", number + 1, line);
+            let (tick, codes) = line.split_once(' ').ok_or_else(bad)?;
+            let tick: u64 = tick.parse().map_err(|_| bad())?;
+            for code in codes.chars() {
+                let (player, action) = action_from_code(code).ok_or_else(bad)?;
+                events.push((tick, player, action));
+            }
+        }
+        Ok(Replay { settings, events, saved_at })
+    }
+
+    /// A digest of the replay, the 64-bit FNV-1a hash of its text: the same
+    /// for the same run wherever it was played.
+    pub fn digest(&self) -> u64 {
+        let text = self.to_text();
+        text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3))
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        Replay::from_text(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+}
+
+// --- Recording ---
+
+/// Collects the actions of a live run.
+pub struct Recorder {
+    replay: Replay,
+}
+
+impl Recorder {
+    /// Starts recording a run that begins from `state`.
+    pub fn new(state: &GameState) -> Self {
+        let settings = Settings { seed: Some(state.seed), ..state.settings.clone() };
+        Recorder { replay: Replay { settings, events: Vec::new(), saved_at: None } }
+    }
+
+    pub fn record(&mut self, tick: u64, player: usize, action: Action) {
+        self.replay.events.push((tick, player, action));
+    }
+
+    /// Picks up recording a run continued from `replay`, the recording of
+    /// how it got to where it is.
+    pub fn resume(replay: Replay) -> Self {
+        Recorder { replay: Replay { saved_at: None, ..replay } }
+    }
+
+    /// Saves the run to be continued later, from `tick`, replacing any run
+    /// saved before.
+    pub fn save_run(&self, tick: u64) {
+        if let Some(path) = storage::data_file(SAVED_RUN_FILE) {
+            let replay = Replay { saved_at: Some(tick), ..self.replay.clone() };
+            let _ = storage::write_atomic(&path, &replay.to_text());
+        }
+    }
+
+    /// The digest of the recording so far.
+    pub fn digest(&self) -> u64 {
+        self.replay.digest()
+    }
+
+    /// Writes the recording over the last-run replay in the data directory.
+    pub fn save_last_run(&self) {
+        if let Some(path) = storage::data_file(LAST_RUN_FILE) {
+            // Losing the replay never interrupts the game
+            let _ = storage::write_atomic(&path, &self.replay.to_text());
+        }
+    }
+}
+
+// --- Playback ---
+
+/// Re-runs a recorded game tick by tick.
+pub struct Playback {
+    pub state: GameState,
+    events: Vec<(u64, usize, Action)>,
+    next: usize,
+}
+
+impl Playback {
+    pub fn new(replay: &Replay) -> Self {
+        Playback { state: GameState::new(&replay.settings), events: replay.events.clone(), next: 0 }
+    }
+
+    /// Feeds the inputs recorded before the next update, then runs it.
+    pub fn tick(&mut self) {
+        self.feed(self.state.ticks);
+        game::update_state(&mut self.state);
+    }
+
+    /// Feeds the inputs recorded up to `tick` not fed yet.
+    fn feed(&mut self, tick: u64) {
+        while let Some(&(_, player, action)) = self.events.get(self.next).filter(|&&(at, ..)| at <= tick) {
+            game::apply_action(&mut self.state, player, action);
+            self.next += 1;
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.state.game_over
+    }
+
+    /// Plays on as fast as it goes until the update of `tick`, and feeds the
+    /// inputs recorded after it, leaving the game just as it was when the
+    /// recording stopped there.
+    pub fn catch_up(&mut self, tick: u64) {
+        while self.state.ticks < tick && !self.state.game_over {
+            self.tick();
+        }
+        self.feed(tick);
+    }
+}
+
+// --- Saved Runs ---
+
+/// The run saved to be continued, if there is one. The file is taken: a
+/// saved run can only be continued once.
+pub fn take_saved_run() -> Option<Replay> {
+    let path = storage::data_file(SAVED_RUN_FILE)?;
+    let replay = Replay::load(&path).ok().filter(|replay| replay.saved_at.is_some());
+    let _ = fs::remove_file(&path);
+    replay
+}
+
+pub fn has_saved_run() -> bool {
+    storage::data_file(SAVED_RUN_FILE).is_some_and(|path| path.exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net;
+
+    fn co_op(seed: u64) -> Settings {
+        let difficulty = Difficulty::Hard;
+        let mut settings = Settings { seed: Some(seed), players: 2, difficulty, lives: Some(2), ..Settings::default() };
+        settings.modifiers.set(Modifier::HeavyFire, true);
+        settings.assist.set(AssistOption::ForgivingHitbox, true);
+        settings
+    }
+
+    #[test]
+    fn text_reads_back_as_the_same_replay() {
+        let events = vec![
+            (0, 0, Action::MoveLeft),
+            (0, 1, Action::MoveRight),
+            (3, 0, Action::Fire),
+            (3, 0, Action::Fire),
+            (9, 1, Action::Bomb),
+        ];
+        let replay = Replay { settings: co_op(7), events, saved_at: Some(12) };
+        let text = replay.to_text();
+        assert_eq!(text.lines().next(), Some(MAGIC));
+        // Player one's inputs in uppercase, player two's in lowercase
+        assert!(text.lines().any(|line| line == "0 Lr"));
+        assert!(text.lines().any(|line| line == "9 b"));
+
+        let read = Replay::from_text(&text).unwrap();
+        assert_eq!(read.events, replay.events);
+        assert_eq!(read.saved_at, Some(12));
+        assert_eq!(read.settings.seed, Some(7));
+        assert_eq!(read.settings.players, 2);
+        assert_eq!(read.settings.lives, Some(2));
+        assert_eq!(read.settings.modifiers, replay.settings.modifiers);
+        assert_eq!(read.settings.assist, replay.settings.assist);
+        assert_eq!(read.to_text(), text);
+        assert_eq!(read.digest(), replay.digest());
+    }
+
+    #[test]
+    fn text_that_is_not_a_replay_is_turned_down() {
+        assert!(Replay::from_text("term_invaders replay 2\nseed 1\nevents\n").is_err());
+        assert_eq!(Replay::from_text(&format!("{}\nevents\n", MAGIC)).unwrap_err(), "replay has no seed");
+        let bad = format!("{}\nseed 1\nevents\n4 L?\n", MAGIC);
+        assert_eq!(Replay::from_text(&bad).unwrap_err(), "line 4: cannot read '4 L?'");
+    }
+
+    #[test]
+    fn sizes_no_run_could_have_are_turned_down() {
+        let read = |header: &str| Replay::from_text(&format!("{}\nseed 1\n{}\nevents\n", MAGIC, header));
+        assert_eq!(read("size 0x0").unwrap_err(), "line 3: size must be at least 32x19");
+        assert_eq!(read("size 31x40").unwrap_err(), "line 3: size must be at least 32x19");
+        assert_eq!(read("size 65535x65535").unwrap_err(), "line 3: size must be at most 1000x1000");
+        assert_eq!(read("size 40x").unwrap_err(), "line 3: cannot read 'size 40x'");
+        assert_eq!(read("player_size 6x2").unwrap_err(), "line 3: player_size must be from 1x1 to 5x2");
+        assert_eq!(read("player_size 0x2").unwrap_err(), "line 3: player_size must be from 1x1 to 5x2");
+        assert_eq!(read("alien_size 4x4").unwrap_err(), "line 3: alien_size must be from 1x1 to 4x3");
+
+        let replay = read("size 32x19\nplayer_size 5x2\nalien_size 4x3").unwrap();
+        assert_eq!((replay.settings.width, replay.settings.height), (32, 19));
+        assert_eq!(replay.settings.player_size, MAX_PLAYER_SIZE);
+        assert_eq!(replay.settings.alien_size, MAX_ALIEN_SIZE);
+        // A replay that reads plays without a hitch
+        let mut playback = Playback::new(&replay);
+        for _ in 0..100 {
+            playback.tick();
+        }
+    }
+
+    #[test]
+    fn playing_the_events_back_ends_the_run_the_same_way() {
+        let mut state = GameState::new(&Settings { seed: Some(42), players: 2, ..Settings::default() });
+        let mut recorder = Recorder::new(&state);
+        while !state.game_over {
+            let tick = state.ticks;
+            // Both players fire now and then, and take turns weaving about
+            let step = if (tick / 40).is_multiple_of(2) { Action::MoveLeft } else { Action::MoveRight };
+            let mut actions = vec![((tick / 80 % 2) as usize, step)];
+            let firing = [0, 1].into_iter().filter(|&player| tick.is_multiple_of(3 + player as u64));
+            actions.extend(firing.map(|player| (player, Action::Fire)));
+            for (player, action) in actions {
+                recorder.record(tick, player, action);
+                game::apply_action(&mut state, player, action);
+            }
+            game::update_state(&mut state);
+        }
+
+        let replay = Replay::from_text(&recorder.replay.to_text()).unwrap();
+        let mut playback = Playback::new(&replay);
+        while playback.state.ticks < state.ticks && !playback.finished() {
+            playback.tick();
+        }
+        assert!(playback.finished());
+        assert_eq!(playback.state.ticks, state.ticks);
+        assert_eq!(playback.state.score, state.score);
+        assert_eq!(net::digest(&playback.state), net::digest(&state));
+    }
+}
@@ -0,0 +1,215 @@
+//! Rollback, which keeps the two sides of a network game in step.
+//!
+//! Both sides run the same deterministic game, and only inputs cross the
+//! network. Each side's inputs take effect [`INPUT_DELAY`] ticks after they
+//! are made, which is often enough for them to reach the other side in
+//! time. When they have not by then, the game goes on as if the other
+//! player had done nothing; once they arrive, it rolls back to the last tick
+//! both sides had all the inputs of and plays the ticks since again, this
+//! time with them. Should the other side fall more than [`MAX_ROLLBACK`]
+//! ticks behind, the game waits for it to catch up.
+
+use std::collections::BTreeMap;
+
+use crate::game::{self, Action, GameState};
+use crate::net::{self, Link, Message};
+use crate::replay::Recorder;
+
+/// Ticks between an input and the tick it takes effect at.
+pub const INPUT_DELAY: u64 = 2;
+/// The furthest the game runs ahead of the other side's inputs.
+pub const MAX_ROLLBACK: u64 = 8;
+/// Ticks between checks that both sides' games still agree.
+const CHECK_INTERVAL: u64 = 50;
+
+/// Each player's actions at a tick, once known.
+type Inputs = [Option<Vec<Action>>; 2];
+
+/// One side of a network game, ticking the game on the inputs of both.
+pub struct Lockstep {
+    link: Link,
+    /// The player this side plays; the other side plays the other one.
+    me: usize,
+    /// The game as of the first tick whose inputs are not all in yet.
+    confirmed: GameState,
+    /// Each player's actions by tick, from the confirmed tick on.
+    inputs: BTreeMap<u64, Inputs>,
+    /// What this side did since the last tick.
+    pressed: Vec<Action>,
+    /// Whether the other side's inputs came in for ticks already played
+    /// without them.
+    stale: bool,
+    /// Digests of the games as of the ticks checked, whichever side's
+    /// arrived first, until the other side's does.
+    checks: BTreeMap<u64, u64>,
+    /// Whether the games were found to disagree since the last look.
+    desynced: bool,
+}
+
+impl Lockstep {
+    /// Starts keeping `state`, as it is on both sides, in step with the
+    /// other side of `link`, with this side playing `me`.
+    pub fn new(link: Link, me: usize, state: &GameState) -> Self {
+        // Nobody did anything before the first inputs could take effect
+        let inputs = (state.ticks..state.ticks + INPUT_DELAY).map(|tick| (tick, [Some(Vec::new()), Some(Vec::new())]));
+        Lockstep {
+            link,
+            me,
+            confirmed: state.clone(),
+            inputs: inputs.collect(),
+            pressed: Vec::new(),
+            stale: false,
+            checks: BTreeMap::new(),
+            desynced: false,
+        }
+    }
+
+    pub fn me(&self) -> usize {
+        self.me
+    }
+
+    /// Takes an action of this side's, for [`INPUT_DELAY`] ticks from now.
+    pub fn press(&mut self, action: Action) {
+        self.pressed.push(action);
+    }
+
+    /// The game as far as both sides' inputs are in.
+    pub fn confirmed(&self) -> &GameState {
+        &self.confirmed
+    }
+
+    /// Whether the other side has gone.
+    pub fn is_closed(&self) -> bool {
+        self.link.is_closed()
+    }
+
+    /// Whether the two games were found to disagree since the last look.
+    pub fn take_desync(&mut self) -> bool {
+        std::mem::take(&mut self.desynced)
+    }
+
+    /// Advances `state`, the game as this side shows it, by a tick, unless
+    /// it has to wait for the other side. What both did is recorded in
+    /// `recorder` as it is confirmed.
+    pub fn tick(&mut self, state: &mut GameState, recorder: &mut Recorder) {
+        let them = 1 - self.me;
+        for message in self.link.receive() {
+            match message {
+                // Inputs of ticks already confirmed can only be repeats
+                Message::Input { tick, actions } if tick >= self.confirmed.ticks => {
+                    self.inputs.entry(tick).or_default()[them] = Some(actions);
+                    self.stale |= tick < state.ticks;
+                }
+                Message::Input { .. } => {}
+                Message::Check { tick, digest } => self.check(tick, digest),
+            }
+        }
+        self.confirm(state.ticks, recorder);
+
+        // Play the ticks since the confirmed one again, with what is known now
+        if self.stale {
+            let ticks = state.ticks;
+            *state = self.confirmed.clone();
+            play_to(state, &self.inputs, ticks);
+            self.stale = false;
+        }
+        if state.ticks - self.confirmed.ticks >= MAX_ROLLBACK {
+            return;
+        }
+
+        let actions = std::mem::take(&mut self.pressed);
+        let due = state.ticks + INPUT_DELAY;
+        self.link.send_inputs(due, self.me, &actions);
+        self.inputs.entry(due).or_default()[self.me] = Some(actions);
+        step(state, self.inputs.get(&state.ticks));
+    }
+
+    /// Moves the confirmed game on through the ticks before `until` whose
+    /// inputs are all in, recording them.
+    fn confirm(&mut self, until: u64, recorder: &mut Recorder) {
+        while self.confirmed.ticks < until {
+            let tick = self.confirmed.ticks;
+            let Some(inputs) = self.inputs.get(&tick).filter(|inputs| inputs.iter().all(Option::is_some)) else {
+                break;
+            };
+            for (player, actions) in inputs.iter().enumerate() {
+                for &action in actions.iter().flatten() {
+                    recorder.record(tick, player, action);
+                }
+            }
+            step(&mut self.confirmed, Some(inputs));
+            self.inputs.remove(&tick);
+            if self.confirmed.ticks.is_multiple_of(CHECK_INTERVAL) {
+                let digest = net::digest(&self.confirmed);
+                self.link.send_check(self.confirmed.ticks, digest);
+                self.check(self.confirmed.ticks, digest);
+            }
+        }
+    }
+
+    /// Takes in one side's digest of the game as of `tick`, comparing it
+    /// with the other's once both are in.
+    fn check(&mut self, tick: u64, digest: u64) {
+        match self.checks.remove(&tick) {
+            Some(other) => self.desynced |= other != digest,
+            None => {
+                self.checks.insert(tick, digest);
+            }
+        }
+    }
+
+    /// Stops keeping in step, the other side having gone, with `state` as
+    /// this side played it: what was played without being confirmed is
+    /// recorded as well, so the replay still plays the run as it went.
+    pub fn leave(self, state: &GameState, recorder: &mut Recorder) {
+        for (&tick, inputs) in self.inputs.range(..state.ticks) {
+            for (player, actions) in inputs.iter().enumerate() {
+                for &action in actions.iter().flatten() {
+                    recorder.record(tick, player, action);
+                }
+            }
+        }
+    }
+}
+
+/// Plays `state` on to tick `until` with the actions known of each tick.
+/// A game that ends on the way stops there, its ticks no longer counting.
+fn play_to(state: &mut GameState, inputs: &BTreeMap<u64, Inputs>, until: u64) {
+    while state.ticks < until && !state.game_over {
+        step(state, inputs.get(&state.ticks));
+    }
+}
+
+/// Plays one tick of `state` with the actions known of it, player one's
+/// first; a player whose actions are not in did nothing, as far as is
+/// known.
+fn step(state: &mut GameState, inputs: Option<&Inputs>) {
+    for (player, actions) in inputs.into_iter().flatten().enumerate() {
+        for &action in actions.iter().flatten() {
+            game::apply_action(state, player, action);
+        }
+    }
+    game::update_state(state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Position, Settings, Velocity};
+
+    #[test]
+    fn playing_again_into_a_game_over_stops_there() {
+        // An alien shot sitting on the last ship ends the run on the first
+        // tick played again, well before the tick the game had got to
+        let mut state = GameState::new(&Settings { seed: Some(1), ..Settings::default() });
+        state.intermission_until = None; // past the countdown to the first wave
+        state.world.ship_mut(0).health.hp = 1;
+        let (x, y) = state.world.ship(0).cell();
+        let x = x + 1;
+        state.world.spawn_alien_shot(Position::at(x, y), Velocity { dx: 0.0, dy: 0.0 });
+        let start = state.ticks;
+        play_to(&mut state, &BTreeMap::new(), start + MAX_ROLLBACK);
+        assert!(state.game_over);
+        assert_eq!(state.ticks, start + 1);
+    }
+}
@@ -0,0 +1,32 @@
+use crate::level::Level;
+use crate::locale::{tr, tr_with};
+use crate::render::widgets::{Align, Rect, Text, Widget};
+use crate::render::{self, Color, Layout, Renderer};
+
+/// Draws the screen between levels of a campaign run: which level comes
+/// next, of how many, its name and its story.
+pub fn draw_interlude(renderer: &mut dyn Renderer, layout: &Layout, number: u32, count: usize, level: &Level) {
+    render::draw_frame(renderer, layout);
+    let field = layout.field.inner();
+    let mut y = field.y + 3;
+
+    let heading = tr_with("level_of", &[&number, &count]);
+    Text::new(&[&heading], Color::Ui)
+        .align(Align::Center)
+        .draw(Rect::new(field.x, y, field.width, 1), renderer);
+    y += 2;
+
+    Text::new(&[&level.name.to_uppercase()], Color::Highlight)
+        .align(Align::Center)
+        .draw(Rect::new(field.x, y, field.width, 1), renderer);
+    y += 2;
+
+    let story = level.story.len() as i32;
+    Text::new(level.story, Color::Alien)
+        .align(Align::Center)
+        .draw(Rect::new(field.x, y, field.width, story), renderer);
+
+    Text::new(&[tr("press_to_continue")], Color::Ui)
+        .align(Align::Center)
+        .draw(Rect::new(field.x, field.bottom() - 3, field.width, 1), renderer);
+}
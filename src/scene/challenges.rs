@@ -0,0 +1,198 @@
+use crate::challenge::{self, RunCode};
+use crate::game::{Modifier, Settings};
+use crate::input::{Key, PadButton};
+use crate::keymap::Binding;
+use crate::locale::{tr, tr_with};
+use crate::render::widgets::{Align, Rect, Text, Widget};
+use crate::render::{self, Color, Layout, Renderer};
+
+use super::Context;
+
+/// A run every player can play alike.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Challenge {
+    /// The challenge of a day, in days since 1970.
+    Daily(u64),
+    /// The challenge of a week, in weeks since 1970.
+    Weekly(u64),
+    /// A challenge somebody passed on.
+    Code(RunCode),
+}
+
+impl Challenge {
+    pub fn code(self) -> RunCode {
+        match self {
+            Challenge::Daily(day) => challenge::daily(day),
+            Challenge::Weekly(week) => challenge::weekly(week),
+            Challenge::Code(code) => code,
+        }
+    }
+
+    pub fn settings(self, base: &Settings) -> Settings {
+        match self {
+            Challenge::Daily(day) => challenge::daily_settings(day, base),
+            Challenge::Weekly(_) | Challenge::Code(_) => self.code().settings(base),
+        }
+    }
+
+    /// What the challenge is called when it starts.
+    pub fn title(self) -> String {
+        match self {
+            Challenge::Daily(day) => tr_with("challenge_daily", &[&challenge::date(day)]),
+            Challenge::Weekly(_) => tr("challenge_weekly").to_string(),
+            Challenge::Code(_) => tr("challenge_code").to_string(),
+        }
+    }
+}
+
+/// The modifiers of `code`, in a line.
+pub fn modifiers(code: &RunCode) -> String {
+    let labels: Vec<&str> = code.modifiers.iter().map(Modifier::label).collect();
+    if labels.is_empty() {
+        tr("no_modifiers").to_string()
+    } else {
+        labels.join(", ")
+    }
+}
+
+/// A line of the challenges screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Row {
+    Daily,
+    Weekly,
+    Code,
+    Back,
+}
+
+const ROWS: [Row; 4] = [Row::Daily, Row::Weekly, Row::Code, Row::Back];
+
+/// What a key press did to the challenges screen.
+pub enum ChallengeEvent {
+    Choosing,
+    Start(Challenge),
+    Closed,
+}
+
+/// Picks the daily or weekly challenge, or takes a code to play.
+///
+/// Up/Down pick a line and Enter plays it. On the code line, keys type the
+/// code, so only the arrow keys move off it.
+pub struct ChallengeScreen {
+    selected: usize,
+    /// The digits of the code typed so far.
+    code: String,
+    /// Why the code typed last could not be played.
+    error: Option<String>,
+}
+
+impl ChallengeScreen {
+    pub fn new() -> Self {
+        ChallengeScreen { selected: 0, code: String::new(), error: None }
+    }
+
+    /// Whether keys go to the code as they are, whatever they normally do.
+    pub fn capturing(&self) -> bool {
+        ROWS[self.selected] == Row::Code
+    }
+
+    pub fn handle_key(&mut self, key: Key, ctx: &Context) -> ChallengeEvent {
+        let binding = if self.capturing() { None } else { ctx.keys.binding(key) };
+        let row = ROWS[self.selected];
+        match key {
+            _ if binding == Some(Binding::MenuUp) || matches!(key, Key::Up | Key::Pad(PadButton::Up)) => {
+                self.selected = (self.selected + ROWS.len() - 1) % ROWS.len();
+            }
+            _ if binding == Some(Binding::MenuDown) || matches!(key, Key::Down | Key::Pad(PadButton::Down)) => {
+                self.selected = (self.selected + 1) % ROWS.len();
+            }
+            Key::Escape => return ChallengeEvent::Closed,
+            Key::Char(' ') | Key::Enter | Key::Pad(PadButton::A) => match row {
+                Row::Daily => return ChallengeEvent::Start(Challenge::Daily(challenge::today())),
+                Row::Weekly => return ChallengeEvent::Start(Challenge::Weekly(challenge::this_week())),
+                Row::Code => match self.code.parse() {
+                    Ok(code) => return ChallengeEvent::Start(Challenge::Code(code)),
+                    Err(err) => self.error = Some(err),
+                },
+                Row::Back => return ChallengeEvent::Closed,
+            },
+            Key::Backspace if row == Row::Code => {
+                self.code.pop();
+                self.error = None;
+            }
+            // Dashes and such are left out, the code reads the same without
+            Key::Char(c) if row == Row::Code && c.is_ascii_alphanumeric() && self.code.len() < challenge::CODE_LEN => {
+                self.code.push(c.to_ascii_uppercase());
+                self.error = None;
+            }
+            _ => {}
+        }
+        ChallengeEvent::Choosing
+    }
+
+    pub fn draw(&self, renderer: &mut dyn Renderer, layout: &Layout) {
+        render::draw_frame(renderer, layout);
+        let field = layout.field.inner();
+        let mut y = field.y + 1;
+
+        Text::new(&[tr("challenges")], Color::Ui)
+            .align(Align::Center)
+            .draw(Rect::new(field.x, y, field.width, 1), renderer);
+        y += 2;
+
+        let week = challenge::weekly(challenge::this_week());
+        for (i, row) in ROWS.into_iter().enumerate() {
+            // Back sits apart from the challenges
+            if row == Row::Back {
+                y += 1;
+            }
+            let label = match row {
+                Row::Daily => tr_with("challenge_daily", &[&challenge::date(challenge::today())]),
+                Row::Weekly => tr("challenge_weekly").to_string(),
+                Row::Code => {
+                    // The digits so far, in the groups codes are written in
+                    let mut code = String::new();
+                    for i in 0..challenge::CODE_LEN {
+                        if i > 0 && i % challenge::GROUP_LEN == 0 {
+                            code.push('-');
+                        }
+                        code.push(self.code.as_bytes().get(i).map_or('_', |&b| b as char));
+                    }
+                    tr_with("challenge_code_digits", &[&code])
+                }
+                Row::Back => tr("back").to_string(),
+            };
+            let (label, color) =
+                if i == self.selected { (format!("> {} <", label), Color::Highlight) } else { (label, Color::Border) };
+            Text::new(&[&label], color).align(Align::Center).draw(Rect::new(field.x, y, field.width, 1), renderer);
+            y += 1;
+        }
+        y += 1;
+
+        // What the selected line plays
+        let describe = |code: RunCode| format!("{}: {}", code.difficulty.label(), modifiers(&code));
+        let about = match ROWS[self.selected] {
+            Row::Daily => describe(challenge::daily(challenge::today())),
+            Row::Weekly => describe(week),
+            Row::Code => match (&self.error, self.code.parse()) {
+                (Some(err), _) => err.clone(),
+                (None, Ok(code)) => describe(code),
+                (None, Err(_)) => tr("type_a_code").to_string(),
+            },
+            Row::Back => String::new(),
+        };
+        Text::new(&[&about], Color::Alien).align(Align::Center).draw(Rect::new(field.x, y, field.width, 1), renderer);
+        y += 2;
+
+        // The weekly code, to pass around
+        let code = week.to_string();
+        Text::new(&[tr("weekly_code"), &code], Color::Ui)
+            .align(Align::Center)
+            .draw(Rect::new(field.x, y, field.width, 2), renderer);
+    }
+}
+
+impl Default for ChallengeScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
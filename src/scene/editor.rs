@@ -0,0 +1,403 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::game::{AlienKind, GameState, ALIEN_COLS, MAX_ALIEN_ROWS};
+use crate::input::Key;
+use crate::level::{Level, MAX_BUNKER_ROWS};
+use crate::locale::{tr, tr_with};
+use crate::pack;
+use crate::render::widgets::{Align, Rect, Text, Widget};
+use crate::render::{self, Color, Layout, Renderer};
+use crate::storage;
+
+/// The widest the formation can be edited, in aliens; its rows repeat across
+/// wider fields.
+const MAX_COLS: usize = 10;
+/// The widest a bunker can be edited, in blocks.
+const BUNKER_COLS: usize = 8;
+/// The longest a level's name can be typed.
+const MAX_NAME: usize = 20;
+/// How far Left/Right change a percentage.
+const PERCENT_STEP: u32 = 10;
+
+/// The levels playtested so far, kept for the runs that play them; playing
+/// one again plays the same copy.
+static PLAYTESTED: Mutex<Vec<&'static Level>> = Mutex::new(Vec::new());
+
+/// One of the level's settings below the grids.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Setting {
+    Name,
+    Speed,
+    Descent,
+    FireInterval,
+}
+
+impl Setting {
+    const ALL: [Setting; 4] = [Setting::Name, Setting::Speed, Setting::Descent, Setting::FireInterval];
+
+    fn label(self) -> &'static str {
+        tr(match self {
+            Setting::Name => "editor_name",
+            Setting::Speed => "editor_speed",
+            Setting::Descent => "editor_descent",
+            Setting::FireInterval => "editor_fire_interval",
+        })
+    }
+}
+
+/// A line of the editor, top to bottom.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Line {
+    Formation(usize),
+    Bunker(usize),
+    Setting(Setting),
+}
+
+impl Line {
+    const COUNT: usize = MAX_ALIEN_ROWS + MAX_BUNKER_ROWS + Setting::ALL.len();
+
+    fn at(index: usize) -> Line {
+        match index {
+            row if row < MAX_ALIEN_ROWS => Line::Formation(row),
+            row if row < MAX_ALIEN_ROWS + MAX_BUNKER_ROWS => Line::Bunker(row - MAX_ALIEN_ROWS),
+            row => Line::Setting(Setting::ALL[row - MAX_ALIEN_ROWS - MAX_BUNKER_ROWS]),
+        }
+    }
+}
+
+/// What a key press did to the editor.
+pub enum EditorEvent {
+    Editing,
+    /// The level as it stands is to be played.
+    Playtest(&'static Level),
+    /// The player is done editing.
+    Closed,
+}
+
+/// Edits a level file on a grid: the formation, with a cell to an alien,
+/// one of the bunkers, and the level's settings.
+///
+/// Up/Down pick a line and Left/Right a cell, or change a setting; Space
+/// cycles a cell through what it can hold and Backspace empties it. F2
+/// saves the level and F5 plays it; Escape leaves.
+pub struct Editor {
+    path: PathBuf,
+    name: String,
+    /// Kept as they were loaded, as the editor has no room to edit them.
+    story: &'static [&'static str],
+    script: &'static [&'static str],
+    boss_script: &'static [&'static str],
+    formation: Vec<Vec<Option<AlienKind>>>,
+    bunker: [[bool; BUNKER_COLS]; MAX_BUNKER_ROWS],
+    speed: u32,
+    descent: u16,
+    fire_interval: u32,
+    line: usize,
+    col: usize,
+    /// Whether there are changes since the level was last saved.
+    dirty: bool,
+    /// Whether Escape was just pressed with changes unsaved, so a second
+    /// one leaves anyway.
+    leaving: bool,
+    /// What the last save or playtest came to.
+    status: String,
+}
+
+impl Editor {
+    /// Edits `level`, to be saved to `path`; a fresh level without one.
+    pub fn new(path: &Path, level: Option<&Level>) -> Self {
+        let cols = level.map_or(ALIEN_COLS, |level| {
+            let widest = level.layout.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+            widest.clamp(ALIEN_COLS, MAX_COLS)
+        });
+        let mut formation = vec![vec![None; cols]; MAX_ALIEN_ROWS];
+        let mut bunker = [[false; BUNKER_COLS]; MAX_BUNKER_ROWS];
+        match level {
+            Some(level) => {
+                for (row, cells) in formation.iter_mut().enumerate() {
+                    for (col, cell) in cells.iter_mut().enumerate() {
+                        *cell = level.alien(row, col);
+                    }
+                }
+                for (row, blocks) in level.bunkers.iter().enumerate() {
+                    for (col, block) in blocks.chars().take(BUNKER_COLS).enumerate() {
+                        bunker[row][col] = block == '#';
+                    }
+                }
+            }
+            // The classic formation to start from
+            None => formation.iter_mut().take(2).for_each(|row| row.fill(Some(AlienKind::Grunt))),
+        }
+        let status = match level {
+            Some(_) => tr_with("editor_editing", &[&path.display()]),
+            None => tr_with("editor_new", &[&path.display()]),
+        };
+        Editor {
+            path: path.to_path_buf(),
+            name: level.map_or(tr("editor_untitled"), |level| level.name).to_string(),
+            story: level.map_or(&[], |level| level.story),
+            script: level.map_or(&[], |level| level.script),
+            boss_script: level.map_or(&[], |level| level.boss_script),
+            formation,
+            bunker,
+            speed: level.map_or(100, |level| level.speed),
+            descent: level.map_or(1, |level| level.descent),
+            fire_interval: level.map_or(100, |level| level.fire_interval),
+            line: 0,
+            col: 0,
+            dirty: false,
+            leaving: false,
+            status,
+        }
+    }
+
+    /// The width of `line`, in cells, or 0 for a setting.
+    fn width(&self, line: Line) -> usize {
+        match line {
+            Line::Formation(_) => self.formation[0].len(),
+            Line::Bunker(_) => BUNKER_COLS,
+            Line::Setting(_) => 0,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: Key) -> EditorEvent {
+        let leaving = std::mem::take(&mut self.leaving);
+        let line = Line::at(self.line);
+        match key {
+            Key::Up => self.line = (self.line + Line::COUNT - 1) % Line::COUNT,
+            Key::Down => self.line = (self.line + 1) % Line::COUNT,
+            Key::Left | Key::Right => {
+                let step = if key == Key::Left { -1 } else { 1 };
+                match line {
+                    Line::Setting(setting) => self.change(setting, step),
+                    _ => self.col = (self.col as i32 + step).clamp(0, self.width(line) as i32 - 1) as usize,
+                }
+            }
+            Key::Char(ch)
+                if line == Line::Setting(Setting::Name) && !ch.is_control() && self.name.chars().count() < MAX_NAME =>
+            {
+                self.set(|editor| editor.name.push(ch));
+            }
+            Key::Char(' ') => self.cycle(line),
+            Key::Backspace => match line {
+                Line::Formation(row) => self.set(|editor| editor.formation[row][editor.col] = None),
+                Line::Bunker(row) => self.set(|editor| editor.bunker[row][editor.col] = false),
+                Line::Setting(Setting::Name) => {
+                    self.set(|editor| {
+                        editor.name.pop();
+                    });
+                }
+                Line::Setting(_) => {}
+            },
+            Key::Function(2) => self.save(),
+            Key::Function(5) => match self.level() {
+                Ok(level) => return EditorEvent::Playtest(playtested(level)),
+                Err(err) => self.status = err,
+            },
+            Key::Escape if self.dirty && !leaving => {
+                self.leaving = true;
+                self.status = tr("editor_unsaved").to_string();
+            }
+            Key::Escape => return EditorEvent::Closed,
+            _ => {}
+        }
+        // Lines of different widths keep the cursor within them
+        let width = self.width(Line::at(self.line));
+        self.col = self.col.min(width.saturating_sub(1));
+        EditorEvent::Editing
+    }
+
+    /// Notes how the playtest that just ended went.
+    pub fn played(&mut self, state: &GameState) {
+        self.status = tr_with("editor_played", &[&state.wave, &state.score]);
+    }
+
+    /// Makes a change to the level, which then needs saving.
+    fn set(&mut self, change: impl FnOnce(&mut Editor)) {
+        change(self);
+        self.dirty = true;
+    }
+
+    /// Moves the cell under the cursor on to the next thing it can hold.
+    fn cycle(&mut self, line: Line) {
+        match line {
+            Line::Formation(row) => self.set(|editor| {
+                let cell = &mut editor.formation[row][editor.col];
+                *cell = match cell {
+                    None => Some(AlienKind::Grunt),
+                    Some(AlienKind::Grunt) => Some(AlienKind::Armored),
+                    Some(AlienKind::Armored) => None,
+                };
+            }),
+            Line::Bunker(row) => self.set(|editor| {
+                let block = &mut editor.bunker[row][editor.col];
+                *block = !*block;
+            }),
+            Line::Setting(setting) => self.change(setting, 1),
+        }
+    }
+
+    /// Steps `setting` up or down, within what level files allow.
+    fn change(&mut self, setting: Setting, step: i32) {
+        let percent = |value: u32| (value as i32 + step * PERCENT_STEP as i32).clamp(10, 1000) as u32;
+        match setting {
+            Setting::Name => {}
+            Setting::Speed => self.set(|editor| editor.speed = percent(editor.speed)),
+            Setting::Descent => self.set(|editor| editor.descent = (editor.descent as i32 + step).clamp(1, 3) as u16),
+            Setting::FireInterval => self.set(|editor| editor.fire_interval = percent(editor.fire_interval)),
+        }
+    }
+
+    /// The level as edited: the formation down to its last row with an
+    /// alien, and the bunker without the empty rows and columns around it.
+    fn level(&self) -> Result<Level, String> {
+        let rows = self.formation.iter().rposition(|row| row.iter().any(Option::is_some));
+        let Some(last) = rows else {
+            return Err(tr("editor_no_aliens").to_string());
+        };
+        let layout: Vec<String> =
+            self.formation[..=last].iter().map(|row| row.iter().copied().map(cell).collect()).collect();
+
+        let filled = |row: &[bool; BUNKER_COLS]| row.iter().any(|&block| block);
+        let top = self.bunker.iter().position(filled);
+        let bottom = self.bunker.iter().rposition(filled);
+        let left = (0..BUNKER_COLS).find(|&col| self.bunker.iter().any(|row| row[col]));
+        let right = (0..BUNKER_COLS).rfind(|&col| self.bunker.iter().any(|row| row[col]));
+        let bunkers: Vec<String> = match (top, bottom, left, right) {
+            (Some(top), Some(bottom), Some(left), Some(right)) => self.bunker[top..=bottom]
+                .iter()
+                .map(|row| row[left..=right].iter().map(|&block| block_char(block)).collect())
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let name = self.name.trim();
+        Ok(Level {
+            name: pack::intern(if name.is_empty() { tr("editor_untitled") } else { name }),
+            story: self.story,
+            layout: pack::intern_rows(&layout),
+            speed: self.speed,
+            descent: self.descent,
+            fire_interval: self.fire_interval,
+            bunkers: pack::intern_rows(&bunkers),
+            script: self.script,
+            boss_script: self.boss_script,
+        })
+    }
+
+    fn save(&mut self) {
+        let level = match self.level() {
+            Ok(level) => level,
+            Err(err) => {
+                self.status = err;
+                return;
+            }
+        };
+        match storage::write_atomic(&self.path, &level.to_text()) {
+            Ok(()) => {
+                self.dirty = false;
+                self.status = tr_with("editor_saved", &[&self.path.display()]);
+            }
+            Err(err) => self.status = tr_with("editor_cannot_save", &[&err]),
+        }
+    }
+
+    pub fn draw(&self, renderer: &mut dyn Renderer, layout: &Layout) {
+        render::draw_frame(renderer, layout);
+        let field = layout.field.inner();
+        let mut y = field.y;
+
+        let title = if self.dirty { format!("{} *", tr("editor")) } else { tr("editor").to_string() };
+        Text::new(&[&title], Color::Ui)
+            .align(Align::Center)
+            .draw(Rect::new(field.x, y, field.width, 1), renderer);
+        y += 1;
+
+        // The grids are drawn centered, three columns to a cell with the
+        // cursor's in brackets, and their headings and the settings in a
+        // column of their own
+        let width = (field.width - 4).min(3 * MAX_COLS as i32);
+        let x = field.x + (field.width - width) / 2;
+        for i in 0..Line::COUNT {
+            let line = Line::at(i);
+            let heading = match line {
+                Line::Formation(0) => Some(tr("editor_formation")),
+                Line::Bunker(0) => Some(tr("editor_bunker")),
+                _ => None,
+            };
+            if let Some(heading) = heading {
+                Text::new(&[heading], Color::Border).draw(Rect::new(x, y, width, 1), renderer);
+                y += 1;
+            }
+            let selected = i == self.line;
+            let cells: Vec<char> = match line {
+                Line::Formation(row) => self.formation[row].iter().copied().map(cell).collect(),
+                Line::Bunker(row) => self.bunker[row].iter().map(|&block| block_char(block)).collect(),
+                Line::Setting(setting) => {
+                    let value = match setting {
+                        Setting::Name if selected => format!("{}_", self.name),
+                        Setting::Name => self.name.clone(),
+                        Setting::Speed => format!("< {}% >", self.speed),
+                        Setting::Descent => format!("< {} >", self.descent),
+                        Setting::FireInterval => format!("< {}% >", self.fire_interval),
+                    };
+                    let color = if selected { Color::Highlight } else { Color::Border };
+                    let label = format!("{} {}", if selected { ">" } else { " " }, setting.label());
+                    let room = width - value.chars().count() as i32 - 1;
+                    Text::new(&[&label], color).draw(Rect::new(x, y, room, 1), renderer);
+                    Text::new(&[&value], color).align(Align::Right).draw(Rect::new(x, y, width, 1), renderer);
+                    y += 1;
+                    continue;
+                }
+            };
+            let left = field.x + (field.width - 3 * cells.len() as i32) / 2;
+            for (col, &cell) in cells.iter().enumerate() {
+                let (text, color) = if selected && col == self.col {
+                    (format!("[{}]", cell), Color::Highlight)
+                } else {
+                    (format!(" {} ", cell), if cell == '.' { Color::Border } else { Color::Alien })
+                };
+                renderer.draw_str(left + 3 * col as i32, y, &text, color);
+            }
+            y += 1;
+        }
+
+        Text::new(&[tr("editor_keys")], Color::Ui)
+            .align(Align::Center)
+            .draw(Rect::new(field.x, field.bottom() - 2, field.width, 1), renderer);
+        Text::new(&[&self.status], Color::Highlight)
+            .align(Align::Center)
+            .draw(Rect::new(field.x, field.bottom() - 1, field.width, 1), renderer);
+    }
+}
+
+/// The one copy of `level` to playtest, shared with every earlier
+/// playtest of the same level.
+fn playtested(level: Level) -> &'static Level {
+    let mut levels = PLAYTESTED.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(&found) = levels.iter().find(|found| ***found == level) {
+        return found;
+    }
+    let level = &*Box::leak(Box::new(level));
+    levels.push(level);
+    level
+}
+
+/// A formation cell as level files write it.
+fn cell(kind: Option<AlienKind>) -> char {
+    match kind {
+        Some(AlienKind::Grunt) => 'A',
+        Some(AlienKind::Armored) => 'B',
+        None => '.',
+    }
+}
+
+/// A bunker cell as level files write it.
+fn block_char(block: bool) -> char {
+    if block {
+        '#'
+    } else {
+        '.'
+    }
+}
@@ -0,0 +1,96 @@
+use crate::game::GameState;
+use crate::input::Key;
+use crate::leaderboard::INITIALS_LEN;
+use crate::locale::{tr, tr_with};
+use crate::render::widgets::{Align, Rect, Text, Widget};
+use crate::render::{self, Color, Layout, Renderer};
+
+use super::scores::Board;
+
+/// Three-letter name entry for a run that made the leaderboard.
+///
+/// Letters can be typed directly or dialled with Up/Down; Left/Right move
+/// between slots and Enter confirms.
+pub struct InitialsEntry {
+    pub score: u32,
+    /// Whether the run had assist options on.
+    pub assisted: bool,
+    /// The wave the run ended on.
+    pub wave: u32,
+    /// The digest of the run's replay.
+    pub replay: u64,
+    /// The table the score goes in.
+    pub board: Board,
+    letters: [u8; INITIALS_LEN],
+    cursor: usize,
+}
+
+/// What a key press did to the entry.
+pub enum EntryEvent {
+    Editing,
+    Confirmed,
+}
+
+impl InitialsEntry {
+    /// Starts the entry for the run that ended as `state`, whose replay has
+    /// the digest `replay`.
+    pub fn new(state: &GameState, replay: u64, board: Board) -> Self {
+        InitialsEntry {
+            score: state.score,
+            assisted: state.settings.assist.any(),
+            wave: state.wave,
+            replay,
+            board,
+            letters: [b'A'; INITIALS_LEN],
+            cursor: 0,
+        }
+    }
+
+    pub fn initials(&self) -> String {
+        self.letters.iter().map(|&b| b as char).collect()
+    }
+
+    /// Moves the letter under the cursor `step` places along the alphabet.
+    fn cycle(&mut self, step: u8) {
+        let letter = &mut self.letters[self.cursor];
+        *letter = b'A' + (*letter - b'A' + step) % 26;
+    }
+
+    pub fn handle_key(&mut self, key: Key) -> EntryEvent {
+        match key {
+            Key::Up => self.cycle(1),
+            Key::Down => self.cycle(25),
+            Key::Left | Key::Backspace => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = (self.cursor + 1).min(INITIALS_LEN - 1),
+            Key::Enter => return EntryEvent::Confirmed,
+            Key::Char(c) if c.is_ascii_alphabetic() => {
+                self.letters[self.cursor] = c.to_ascii_uppercase() as u8;
+                self.cursor = (self.cursor + 1).min(INITIALS_LEN - 1);
+            }
+            _ => {}
+        }
+        EntryEvent::Editing
+    }
+
+    pub fn draw(&self, renderer: &mut dyn Renderer, layout: &Layout) {
+        render::draw_frame(renderer, layout);
+        let field = layout.field.inner();
+        let y = field.y + field.height / 2 - 4;
+
+        let tag = if self.assisted { format!(" {}", tr("assisted")) } else { String::new() };
+        let score = tr_with("score", &[&self.score]) + &tag;
+        Text::new(&[tr("new_high_score"), "", &score, "", tr("enter_initials")], Color::Ui)
+            .align(Align::Center)
+            .draw(Rect::new(field.x, y, field.width, 5), renderer);
+
+        // Letters are spaced out so the cursor marker fits underneath
+        let letters: String = self.letters.iter().map(|&b| format!("{} ", b as char)).collect();
+        let marker: String = (0..INITIALS_LEN).map(|i| if i == self.cursor { "^ " } else { "  " }).collect();
+        Text::new(&[letters.trim_end(), marker.trim_end()], Color::Highlight)
+            .draw(Rect::new(field.x + (field.width - 5) / 2, y + 6, 5, 2), renderer);
+
+        Text::new(&[tr("enter_to_confirm")], Color::Border)
+            .align(Align::Center)
+            .draw(Rect::new(field.x, y + 9, field.width, 1), renderer);
+    }
+}
@@ -0,0 +1,1090 @@
+//! The screens the game moves between.
+//!
+//! Each [`Scene`] owns whatever state it needs and decides, from input and
+//! ticks, which scene comes next. Returning `None` from
+//! [`Scene::handle_key`] ends the program. State that outlives individual
+//! scenes lives in the shared [`Context`].
+
+use std::cmp::Ordering;
+use std::time::Duration;
+
+use crate::achievements::{Achievements, Watcher};
+use crate::bot::{self, Controller};
+use crate::campaign::Progress;
+use crate::config::{Bell, Config, Volume};
+use crate::challenge::{self, RunCode};
+use crate::debug::FrameStats;
+use crate::game::{self, Action, AssistOption, AudioEvent, Difficulty, GameState, Settings, MAX_PLAYERS};
+use crate::highscore::HighScore;
+use crate::input::{Key, PadButton};
+use crate::keymap::{Binding, KeyMap};
+use crate::leaderboard::Leaderboard;
+use crate::level;
+use crate::locale::{tr, tr_with};
+use crate::mods::Mod;
+use crate::net::Link;
+#[cfg(feature = "online")]
+use crate::online::{Client, GlobalTable, Submission};
+use crate::render::widgets::{Align, Rect, Text, Widget};
+use crate::render::pixels::PixelMode;
+use crate::render::sprites::{self, SpriteSet};
+use crate::render::theme::{Theme, CLASSIC};
+use crate::render::{self, Camera, Color, Layout, Renderer};
+use crate::replay::{self, Playback, Recorder};
+use crate::rollback::Lockstep;
+#[cfg(feature = "sound")]
+use crate::sound::Sound;
+use crate::stats::Stats;
+
+pub mod campaign;
+pub mod challenges;
+pub mod editor;
+pub mod initials;
+pub mod mods;
+pub mod pause;
+pub mod scores;
+pub mod settings;
+pub mod stats;
+pub mod title;
+
+use challenges::{ChallengeEvent, ChallengeScreen};
+use editor::{Editor, EditorEvent};
+use initials::{EntryEvent, InitialsEntry};
+use mods::{ModsEvent, ModsScreen};
+use scores::Board;
+use pause::{PauseItem, PauseMenu};
+use settings::{SettingsEvent, SettingsScreen};
+use title::{MenuItem, TitleScreen};
+
+/// Everything scenes share across the whole session.
+pub struct Context {
+    pub settings: Settings,
+    pub high_score: HighScore,
+    pub leaderboard: Leaderboard,
+    /// Today's table of the daily challenge.
+    pub daily: Leaderboard,
+    pub achievements: Achievements,
+    /// The online leaderboard's server, if the config file names one.
+    #[cfg(feature = "online")]
+    pub online: Option<Client>,
+    /// The global top ten, once the High Scores screen has asked for it.
+    #[cfg(feature = "online")]
+    pub global: Option<GlobalTable>,
+    /// The color theme, from the config file until `t` picks another.
+    pub theme: &'static Theme,
+    /// The mod in use, picked on the title screen.
+    pub active_mod: Option<&'static Mod>,
+    /// The mod's sprites, drawn in place of the ones the session started
+    /// with.
+    pub sprites: Option<&'static SpriteSet>,
+    /// The sprites the session started with, which its backend draws with
+    /// unless a mod brings its own.
+    pub base_sprites: &'static SpriteSet,
+    /// How the playfield is drawn in pixels, if not as text.
+    pub pixels: Option<PixelMode>,
+    /// What the debug overlay shows, while F3 has it up.
+    pub debug: Option<FrameStats>,
+    pub keys: KeyMap,
+    /// Whether the ship follows the mouse, as set in the config file.
+    pub mouse: bool,
+    /// The size of the screen the scenes are drawn on, as of the last time
+    /// the game loop looked.
+    pub screen: (i32, i32),
+    /// Whether the session plays one game over the network, which ends the
+    /// session rather than making way for another.
+    pub networked: bool,
+    /// What rings the terminal bell, as set in the config file.
+    pub bell: Bell,
+    /// Whether the bell is to ring with the next frame.
+    pub ring: bool,
+    /// Where the runs' sound effects are played, if anywhere.
+    #[cfg(feature = "sound")]
+    pub sound: Option<Sound>,
+    /// How loud they are played, from the config file until changed.
+    pub volume: Volume,
+}
+
+impl Context {
+    /// Sets up a session playing with `settings`, loading saved scores and
+    /// the config file, and the levels of the mod it names.
+    pub fn load(settings: Settings) -> Self {
+        let config = Config::load();
+        let active_mod = config.mod_id.as_deref().and_then(|id| crate::mods::find(id).ok());
+        // The mod's own theme goes by a name no built-in one has
+        let mod_theme = active_mod.and_then(|found| found.theme);
+        let theme = config.theme.as_deref().and_then(|name| {
+            Theme::by_name(name).or_else(|| mod_theme.filter(|theme| theme.name == name))
+        });
+        Context {
+            settings: Settings { pack: active_mod.and_then(|found| found.levels), ..settings },
+            high_score: HighScore::load(),
+            leaderboard: Leaderboard::load(),
+            daily: Leaderboard::load_daily(challenge::today()),
+            achievements: Achievements::load(),
+            #[cfg(feature = "online")]
+            online: config.online.as_deref().and_then(|url| Client::new(url).ok()),
+            #[cfg(feature = "online")]
+            global: None,
+            theme: theme.unwrap_or(&CLASSIC),
+            active_mod,
+            sprites: active_mod.and_then(|found| found.sprites),
+            base_sprites: &sprites::ASCII,
+            pixels: None,
+            debug: None,
+            keys: KeyMap::from_entries(&config.keys),
+            mouse: config.mouse,
+            screen: (0, 0),
+            networked: false,
+            bell: Bell::from_config(&config),
+            ring: false,
+            #[cfg(feature = "sound")]
+            sound: Some(Sound::new(config.volume)),
+            volume: config.volume,
+        }
+    }
+
+    /// Changes the difficulty of the runs to come, remembering it in the
+    /// config file for the next session.
+    fn set_difficulty(&mut self, difficulty: Difficulty) {
+        self.settings.difficulty = difficulty;
+        let mut config = Config::load();
+        config.difficulty = Some(difficulty.name().to_string());
+        config.save();
+    }
+
+    /// Plays the sound at `volume` from now on.
+    pub fn set_volume(&mut self, volume: Volume) {
+        self.volume = volume;
+        #[cfg(feature = "sound")]
+        if let Some(sound) = &mut self.sound {
+            sound.set_volume(volume);
+        }
+    }
+
+    /// Puts `chosen` in use for the runs to come, or no mod, remembering it
+    /// in the config file for the next session. Its theme is put on, and a
+    /// theme of the mod before goes with it.
+    fn use_mod(&mut self, chosen: Option<&'static Mod>) {
+        let leaving = self.active_mod.and_then(|found| found.theme).filter(|&theme| theme == self.theme);
+        self.active_mod = chosen;
+        self.settings.pack = chosen.and_then(|found| found.levels);
+        self.sprites = chosen.and_then(|found| found.sprites);
+        match chosen.and_then(|found| found.theme) {
+            Some(theme) => self.theme = theme,
+            None if leaving.is_some() => self.theme = &CLASSIC,
+            None => {}
+        }
+        let mut config = Config::load();
+        config.mod_id = chosen.map(|found| found.id.to_string());
+        config.theme = Some(self.theme.name.to_string());
+        config.save();
+    }
+
+    /// The local table `board` is, or `None` for the online one.
+    pub fn board(&self, board: Board) -> Option<&Leaderboard> {
+        match board {
+            Board::AllTime => Some(&self.leaderboard),
+            Board::Daily => Some(&self.daily),
+            #[cfg(feature = "online")]
+            Board::Global => None,
+        }
+    }
+
+    /// The table `step` tables along from `board`, bringing it up to date;
+    /// the online one is only there with a server to ask.
+    fn switch_board(&mut self, board: Board, step: isize) -> Board {
+        let boards: Vec<Board> = Board::ALL
+            .iter()
+            .copied()
+            .filter(|&board| match board {
+                #[cfg(feature = "online")]
+                Board::Global => self.online.is_some(),
+                _ => true,
+            })
+            .collect();
+        let i = boards.iter().position(|&other| other == board).unwrap_or(0) as isize;
+        let next = boards[(i + step).rem_euclid(boards.len() as isize) as usize];
+        match next {
+            Board::AllTime => {}
+            Board::Daily => self.refresh_daily(),
+            #[cfg(feature = "online")]
+            Board::Global => self.global = self.online.as_ref().map(Client::fetch),
+        }
+        next
+    }
+
+    /// The table a finished run's score goes in, if any: a daily challenge
+    /// only makes today's table on the day it was for, and runs of two
+    /// players make none.
+    fn board_for(&mut self, settings: &Settings) -> Option<Board> {
+        if !settings.is_solo() {
+            return None;
+        }
+        let Some(day) = settings.daily else {
+            return Some(Board::AllTime);
+        };
+        self.refresh_daily();
+        (self.daily.day() == Some(day)).then_some(Board::Daily)
+    }
+
+    /// Moves the daily table on to today's, once the day is over.
+    fn refresh_daily(&mut self) {
+        let today = challenge::today();
+        if self.daily.day() != Some(today) {
+            self.daily = Leaderboard::load_daily(today);
+        }
+    }
+
+    /// The settings of another run like the one `settings` are of: as many
+    /// players, in the same mode, but on the session's settings otherwise.
+    fn rematch(&self, settings: &Settings) -> Settings {
+        let (players, versus, campaign) = (settings.players, settings.versus, settings.campaign);
+        Settings { players, versus, campaign, ..self.settings.clone() }
+    }
+
+    /// The layout of a playfield the size `settings` give it.
+    pub fn layout(&self, settings: &Settings) -> Layout {
+        match self.pixels {
+            Some(mode) => Layout::with_pixels(settings.width, settings.height, mode),
+            None => Layout::new(settings.width, settings.height),
+        }
+    }
+}
+
+/// After this many ticks without the fire key repeating, it counts as let go
+/// of, for backends that never report releases.
+const FIRE_HOLD_TICKS: u64 = 3;
+/// The fastest the ship fires, however fast the fire key is pressed or
+/// repeats; holding it down fires at this rate.
+const AUTOFIRE_INTERVAL: Duration = Duration::from_millis(250);
+/// How long a toast, such as the one for a freshly unlocked achievement,
+/// stays up.
+const TOAST_TIME: Duration = Duration::from_secs(3);
+
+/// A live game together with the recording of its inputs.
+pub struct Run {
+    pub state: GameState,
+    recorder: Recorder,
+    /// The tick the fire key was last seen while held down.
+    fire_held: Option<u64>,
+    /// The game time from which each ship may fire again.
+    next_shot: [Duration; MAX_PLAYERS],
+    watcher: Watcher,
+    /// A note on the top border of the playfield, and the game time until
+    /// which it is shown.
+    toast: Option<(String, Duration)>,
+    /// The other side of a network game, for a run that is one.
+    lockstep: Option<Lockstep>,
+    /// The bot playing player one's ship, for a run one plays.
+    controller: Option<Box<dyn Controller>>,
+    /// The wave whose level a campaign run last stopped to introduce.
+    introduced: u32,
+}
+
+impl Run {
+    pub fn new(settings: &Settings) -> Self {
+        let state = GameState::new(settings);
+        let recorder = Recorder::new(&state);
+        let watcher = Watcher::new(&state);
+        let next_shot = [Duration::ZERO; MAX_PLAYERS];
+        let (toast, lockstep, controller) = (None, None, None);
+        Run { state, recorder, fire_held: None, next_shot, watcher, toast, lockstep, controller, introduced: 0 }
+    }
+
+    /// Plays the run, fresh from [`Run::new`], over the network with the
+    /// other side of `link`, this side playing player `me`.
+    pub fn connect(&mut self, link: Link, me: usize) {
+        self.lockstep = Some(Lockstep::new(link, me, &self.state));
+    }
+
+    /// Has `controller` play player one's ship from now on, in place of
+    /// the keys. A bot's runs stay off the leaderboards and the lifetime
+    /// stats, and earn no achievements.
+    pub fn hand_to(&mut self, controller: Box<dyn Controller>) {
+        self.controller = Some(controller);
+        self.toast(tr("toast_bot").to_string());
+    }
+
+    pub fn is_bot_played(&self) -> bool {
+        self.controller.is_some()
+    }
+
+    /// Whether the run plays a level from the editor. It stays off the
+    /// leaderboards and the stats, earns no achievements and leaves no
+    /// replay, which could not tell which level it played.
+    pub fn is_playtest(&self) -> bool {
+        self.state.settings.level.is_some()
+    }
+
+    /// The player this side's keys play: the one of the network game's
+    /// players that is at this side, or else player one.
+    fn me(&self) -> usize {
+        self.lockstep.as_ref().map_or(0, Lockstep::me)
+    }
+
+    pub fn is_networked(&self) -> bool {
+        self.lockstep.is_some()
+    }
+
+    /// Whether a campaign run has moved on to a level it has yet to
+    /// introduce, which it then counts as introduced.
+    fn introduce(&mut self) -> bool {
+        if !self.state.settings.campaign || self.introduced == self.state.wave {
+            return false;
+        }
+        self.introduced = self.state.wave;
+        self.state.level().is_some()
+    }
+
+    /// Continues the run saved with [`Run::save`], if there is one.
+    pub fn resume() -> Option<Self> {
+        let replay = replay::take_saved_run()?;
+        let mut playback = Playback::new(&replay);
+        playback.catch_up(replay.saved_at?);
+        let recorder = Recorder::resume(replay);
+        let watcher = Watcher::new(&playback.state);
+        let next_shot = [Duration::ZERO; MAX_PLAYERS];
+        let (toast, lockstep, controller) = (None, None, None);
+        // The level under way was introduced before the run was saved
+        let introduced = playback.state.wave;
+        let state = playback.state;
+        Some(Run { state, recorder, fire_held: None, next_shot, watcher, toast, lockstep, controller, introduced })
+    }
+
+    /// Saves the run to be continued another time, as it stands.
+    pub fn save(&self) {
+        self.recorder.save_run(self.state.ticks);
+    }
+
+    /// Advances the game, letting go of the fire key if it stopped repeating
+    /// and firing again while it is held, or all the time with the autofire
+    /// assist. A bot decides on its move first. A network game keeps in
+    /// step with the other side, and plays on alone once it has gone.
+    pub fn tick(&mut self) {
+        let me = self.me();
+        for _ in 0..bot::MOVES_PER_TICK {
+            let Some(action) = self.controller.as_mut().and_then(|controller| controller.decide(&self.state)) else {
+                break;
+            };
+            match action {
+                Action::Fire => self.fire(me),
+                action => self.apply(action),
+            }
+            if !matches!(action, Action::MoveLeft | Action::MoveRight) {
+                break;
+            }
+        }
+        if self.fire_held.is_some_and(|tick| self.state.ticks - tick >= FIRE_HOLD_TICKS) {
+            self.release_fire();
+        }
+        if self.fire_held.is_some() && self.state.clock >= self.next_shot[me] {
+            // Keep to the rate on average, even where it falls between ticks
+            self.next_shot[me] += AUTOFIRE_INTERVAL;
+            self.apply(Action::Fire);
+        } else if self.state.settings.assist.is_on(AssistOption::Autofire) {
+            for player in 0..self.state.world.ships.len() {
+                self.fire(player);
+            }
+        }
+        let Some(lockstep) = &mut self.lockstep else {
+            game::update_state(&mut self.state);
+            return;
+        };
+        lockstep.tick(&mut self.state, &mut self.recorder);
+        let (desynced, left) = (lockstep.take_desync(), lockstep.is_closed() && !lockstep.confirmed().game_over);
+        if desynced {
+            self.toast(tr("toast_desync").to_string());
+        }
+        if let Some(lockstep) = self.lockstep.take_if(|_| left) {
+            lockstep.leave(&self.state, &mut self.recorder);
+            self.toast(tr("toast_left").to_string());
+        }
+    }
+
+    /// Whether the game is over: in a network game, once both sides agree
+    /// it is.
+    pub fn is_over(&self) -> bool {
+        match &self.lockstep {
+            Some(lockstep) => lockstep.confirmed().game_over,
+            None => self.state.game_over,
+        }
+    }
+
+    /// Fires from `player`'s ship, unless its last shot was too recent.
+    fn fire(&mut self, player: usize) {
+        if self.state.clock >= self.next_shot[player] {
+            self.next_shot[player] = self.state.clock + AUTOFIRE_INTERVAL;
+            self.apply_as(player, Action::Fire);
+        }
+    }
+
+    /// Notes the fire key going down (or repeating). Pressing it fires and
+    /// starts charging the laser; repeats only tell it is still held, since
+    /// the game fires on its own while it is.
+    fn hold_fire(&mut self) {
+        if self.fire_held.is_none() {
+            self.fire(self.me());
+            self.apply(Action::ChargeLaser);
+        }
+        self.fire_held = Some(self.state.ticks);
+    }
+
+    /// Moves the ship a step at a time, as the movement keys do, until it
+    /// is centered on `column` or as close as it gets.
+    fn steer_to(&mut self, column: u16) {
+        let width = self.state.settings.player_size.width;
+        let last = (self.state.max_x() + 1).saturating_sub(width);
+        let target = column.saturating_sub(width / 2).min(last);
+        let me = self.me();
+        // The alien commander has no ship to steer
+        while let Some(x) = self.state.world.ships.get(me).map(|ship| ship.x()) {
+            let action = match target.cmp(&x) {
+                Ordering::Less => Action::MoveLeft,
+                Ordering::Greater => Action::MoveRight,
+                Ordering::Equal => return,
+            };
+            self.apply(action);
+            // Nothing moves during the countdown before a wave, nor in a
+            // network game before the input takes effect
+            if self.state.world.ship(me).x() == x {
+                return;
+            }
+        }
+    }
+
+    fn release_fire(&mut self) {
+        if self.fire_held.take().is_some() {
+            self.apply(Action::ReleaseLaser);
+        }
+    }
+
+    /// Applies `action` for the player this side's keys play.
+    pub fn apply(&mut self, action: Action) {
+        self.apply_as(self.me(), action);
+    }
+
+    /// Applies `action` for `player`; in a network game, only for this
+    /// side's, and once it takes effect on both.
+    pub fn apply_as(&mut self, player: usize, action: Action) {
+        match &mut self.lockstep {
+            Some(lockstep) if player == lockstep.me() => lockstep.press(action),
+            Some(_) => {}
+            None => {
+                self.recorder.record(self.state.ticks, player, action);
+                game::apply_action(&mut self.state, player, action);
+            }
+        }
+    }
+
+    /// Shows `text` on the top border of the playfield for a while.
+    fn toast(&mut self, text: String) {
+        self.toast = Some((text, self.state.clock + TOAST_TIME));
+    }
+
+    fn draw_toast(&self, renderer: &mut dyn Renderer, layout: &Layout) {
+        let Some((text, _)) = self.toast.as_ref().filter(|&&(_, until)| self.state.clock < until) else {
+            return;
+        };
+        let field = layout.field;
+        // Cut short rather than run over the corners
+        let room = (field.width - 8).max(0) as usize;
+        let toast = format!(" * {} * ", text.chars().take(room).collect::<String>());
+        Text::new(&[&toast], Color::Highlight)
+            .align(Align::Center)
+            .draw(Rect::new(field.x, field.y, field.width, 1), renderer);
+    }
+
+    /// Ends the run, keeping its replay and adding it to the lifetime
+    /// stats, and hands back the final state: in a network game, as far as
+    /// both sides agree on it.
+    pub fn finish(mut self) -> GameState {
+        if let Some(lockstep) = &self.lockstep {
+            self.state = lockstep.confirmed().clone();
+        }
+        if self.is_playtest() {
+            return self.state;
+        }
+        self.recorder.save_last_run();
+        if self.controller.is_none() {
+            Stats::record(&self.state);
+            if self.state.settings.campaign {
+                Progress::record(&self.state);
+            }
+        }
+        self.state
+    }
+}
+
+pub enum Scene {
+    TitleScreen(TitleScreen),
+    /// A leaderboard, optionally highlighting a freshly entered rank.
+    HighScores(Board, Option<usize>),
+    /// The lifetime stats, as of entering the screen.
+    Stats(Stats),
+    Challenges(ChallengeScreen),
+    Mods(ModsScreen),
+    Playing(Run),
+    /// A run on hold, with the menu shown over it.
+    Paused(Run, PauseMenu),
+    /// A campaign run on hold while the level it moved on to is introduced.
+    Interlude(Run),
+    /// A finished run, and what became of saving its highlight, if that
+    /// was asked for.
+    GameOver(GameState, Option<String>),
+    EnterInitials(InitialsEntry),
+    /// The settings screen, and the scene to go back to when it closes.
+    Settings(SettingsScreen, Box<Scene>),
+    /// Watching a recorded run; input other than quitting is ignored.
+    Replay(Playback),
+    /// Editing a level file.
+    Editor(Editor),
+    /// Playing the level being edited, and the editor to go back to.
+    Playtest(Run, Editor),
+}
+
+impl Scene {
+    /// What the scene is, for the log.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scene::TitleScreen(_) => "title screen",
+            Scene::HighScores(..) => "high scores",
+            Scene::Stats(_) => "stats",
+            Scene::Challenges(_) => "challenges",
+            Scene::Mods(_) => "mods",
+            Scene::Playing(_) => "playing",
+            Scene::Paused(..) => "paused",
+            Scene::Interlude(_) => "interlude",
+            Scene::GameOver(..) => "game over",
+            Scene::EnterInitials(_) => "enter initials",
+            Scene::Settings(..) => "settings",
+            Scene::Replay(_) => "replay",
+            Scene::Editor(_) => "editor",
+            Scene::Playtest(..) => "playtest",
+        }
+    }
+
+    /// Advances the scene by one game tick.
+    pub fn update(self, ctx: &mut Context) -> Scene {
+        #[cfg(feature = "online")]
+        if let Some(table) = &mut ctx.global {
+            table.poll();
+        }
+        // The heartbeat only beats while a wave is played
+        #[cfg(feature = "sound")]
+        if let Some(sound) = &mut ctx.sound {
+            sound.set_heartbeat(match &self {
+                Scene::Playing(run) | Scene::Playtest(run, _) => crate::sound::heartbeat(&run.state),
+                _ => None,
+            });
+        }
+        match self {
+            Scene::Playing(mut run) => {
+                if run.introduce() {
+                    return Scene::Interlude(run);
+                }
+                run.tick();
+                let sounds = std::mem::take(&mut run.state.audio);
+                ctx.ring |= ctx.bell.player_hit && sounds.contains(&AudioEvent::PlayerHit);
+                #[cfg(feature = "sound")]
+                if let Some(sound) = &mut ctx.sound {
+                    sound.play(&sounds);
+                }
+                // Only people's runs count, and not their playtests
+                let human = !run.is_bot_played() && !run.is_playtest();
+                if run.state.settings.is_solo() && human {
+                    ctx.high_score.submit(run.state.score);
+                }
+                if human {
+                    let earned = run.watcher.earned(&run.state);
+                    if let Some(&achievement) = ctx.achievements.unlock(earned).last() {
+                        run.toast(tr_with("toast_achievement", &[&achievement.title()]));
+                    }
+                }
+                if !run.is_over() {
+                    return Scene::Playing(run);
+                }
+                ctx.ring |= ctx.bell.game_over;
+                let replay = run.recorder.digest();
+                let state = run.finish();
+                let qualifies = |table: &Leaderboard| table.qualifies(state.score);
+                let board = if human { ctx.board_for(&state.settings) } else { None };
+                match board.filter(|&board| ctx.board(board).is_some_and(qualifies)) {
+                    Some(board) => Scene::EnterInitials(InitialsEntry::new(&state, replay, board)),
+                    None => Scene::GameOver(state, None),
+                }
+            }
+            Scene::TitleScreen(mut title) => {
+                title.tick();
+                Scene::TitleScreen(title)
+            }
+            Scene::Replay(mut playback) => {
+                if !playback.finished() {
+                    playback.tick();
+                }
+                #[cfg(feature = "sound")]
+                if let Some(sound) = &mut ctx.sound {
+                    sound.play(&playback.state.audio);
+                }
+                playback.state.audio.clear();
+                Scene::Replay(playback)
+            }
+            // Back to the editor once the playtest is over
+            Scene::Playtest(run, mut editor) => match Scene::Playing(run).update(ctx) {
+                Scene::Playing(run) => Scene::Playtest(run, editor),
+                Scene::GameOver(state, _) => {
+                    editor.played(&state);
+                    Scene::Editor(editor)
+                }
+                _ => Scene::Editor(editor),
+            },
+            other => other,
+        }
+    }
+
+    /// Pauses a game in progress, e.g. when the terminal got too small to
+    /// play in; other scenes are left as they are.
+    pub fn suspend(self) -> Scene {
+        match self {
+            // The other side of a network game plays on, so it waits instead
+            Scene::Playing(run) if !run.is_networked() => Scene::Paused(run, PauseMenu::new()),
+            other => other,
+        }
+    }
+
+    /// Reacts to a key press, returning the next scene or `None` to quit.
+    pub fn handle_key(self, key: Key, ctx: &mut Context) -> Option<Scene> {
+        let binding = ctx.keys.binding(key);
+        // Releases only matter to the fire key during play
+        if let Key::Released(_) = key {
+            return Some(match self {
+                Scene::Playing(mut run) => {
+                    if binding == Some(Binding::Action(Action::Fire)) {
+                        run.release_fire();
+                    }
+                    Scene::Playing(run)
+                }
+                other => other,
+            });
+        }
+        // The quit key works in every scene except where keys are read as
+        // they are, such as letters of a name
+        let raw = self.reads_raw_keys();
+        if binding == Some(Binding::Quit) && !raw {
+            // A playtest only goes back to the editor
+            if let Scene::Playtest(run, mut editor) = self {
+                editor.played(&run.finish());
+                return Some(Scene::Editor(editor));
+            }
+            self.end();
+            return None;
+        }
+        // 't' cycles the color theme in those scenes too, unless it was bound
+        // to something else
+        if key == Key::Char('t') && binding.is_none() && !raw {
+            ctx.theme = ctx.theme.next();
+            return Some(self);
+        }
+        // 'm' mutes or unmutes the sound, remembering it for next time
+        #[cfg(feature = "sound")]
+        if key == Key::Char('m') && binding.is_none() && !raw {
+            ctx.set_volume(Volume { muted: !ctx.volume.muted, ..ctx.volume });
+            let mut config = Config::load();
+            config.volume = ctx.volume;
+            config.save();
+            let mut scene = self;
+            scene.toast(tr(if ctx.volume.muted { "toast_muted" } else { "toast_unmuted" }).to_string());
+            return Some(scene);
+        }
+        // F3 shows or hides the debug overlay everywhere else
+        if key == Key::Function(3) && !raw {
+            ctx.debug = match ctx.debug {
+                Some(_) => None,
+                None => Some(FrameStats::default()),
+            };
+            return Some(self);
+        }
+        let next = match self {
+            Scene::TitleScreen(mut title) => match key {
+                _ if binding == Some(Binding::MenuUp) => {
+                    title.select_previous();
+                    Scene::TitleScreen(title)
+                }
+                _ if binding == Some(Binding::MenuDown) => {
+                    title.select_next();
+                    Scene::TitleScreen(title)
+                }
+                Key::Left | Key::Right | Key::Pad(PadButton::Left | PadButton::Right)
+                    if title.selected() == MenuItem::Difficulty =>
+                {
+                    let difficulty = ctx.settings.difficulty;
+                    let left = matches!(key, Key::Left | Key::Pad(PadButton::Left));
+                    ctx.set_difficulty(if left { difficulty.previous() } else { difficulty.next() });
+                    Scene::TitleScreen(title)
+                }
+                Key::Left | Key::Right | Key::Pad(PadButton::Left | PadButton::Right)
+                    if title.selected() == MenuItem::TwoPlayers =>
+                {
+                    title.switch_two_players();
+                    Scene::TitleScreen(title)
+                }
+                Key::Char(' ') | Key::Enter | Key::Pad(PadButton::A) => match title.selected() {
+                    MenuItem::Continue => match Run::resume() {
+                        Some(run) => Scene::Paused(run, PauseMenu::new()),
+                        // Unreadable; it is gone now, and so is the entry
+                        None => Scene::TitleScreen(TitleScreen::new(ctx)),
+                    },
+                    MenuItem::Start => Scene::Playing(Run::new(&ctx.settings)),
+                    MenuItem::Campaign => {
+                        Scene::Playing(Run::new(&Settings { campaign: true, ..ctx.settings.clone() }))
+                    }
+                    MenuItem::TwoPlayers => {
+                        let mut run = Run::new(&title.two_players().settings(&ctx.settings));
+                        if run.state.settings.versus {
+                            run.toast(tr("toast_versus").to_string());
+                        }
+                        Scene::Playing(run)
+                    }
+                    MenuItem::Difficulty => {
+                        ctx.set_difficulty(ctx.settings.difficulty.next());
+                        Scene::TitleScreen(title)
+                    }
+                    MenuItem::Challenges => Scene::Challenges(ChallengeScreen::new()),
+                    MenuItem::HighScores => Scene::HighScores(Board::AllTime, None),
+                    MenuItem::Stats => Scene::Stats(Stats::load()),
+                    MenuItem::Mods => Scene::Mods(ModsScreen::new(ctx)),
+                    MenuItem::Settings => Scene::Settings(SettingsScreen::new(), Box::new(Scene::TitleScreen(title))),
+                    MenuItem::Quit => return None,
+                },
+                _ => Scene::TitleScreen(title),
+            },
+            Scene::Mods(mut screen) => match screen.handle_key(key, ctx) {
+                ModsEvent::Choosing => Scene::Mods(screen),
+                ModsEvent::Picked(chosen) => {
+                    ctx.use_mod(chosen);
+                    Scene::TitleScreen(TitleScreen::new(ctx))
+                }
+                ModsEvent::Closed => Scene::TitleScreen(TitleScreen::new(ctx)),
+            },
+            Scene::Challenges(mut screen) => match screen.handle_key(key, ctx) {
+                ChallengeEvent::Choosing => Scene::Challenges(screen),
+                ChallengeEvent::Start(challenge) => {
+                    let mut run = Run::new(&challenge.settings(&ctx.settings));
+                    run.toast(format!("{}: {}", challenge.title(), challenges::modifiers(&challenge.code())));
+                    Scene::Playing(run)
+                }
+                ChallengeEvent::Closed => Scene::TitleScreen(TitleScreen::new(ctx)),
+            },
+            // Any key goes back to the title screen
+            Scene::HighScores(board, _) if matches!(key, Key::Left | Key::Right) => {
+                Scene::HighScores(ctx.switch_board(board, if key == Key::Left { -1 } else { 1 }), None)
+            }
+            Scene::HighScores(..) | Scene::Stats(_) => Scene::TitleScreen(TitleScreen::new(ctx)),
+            Scene::EnterInitials(mut entry) => match entry.handle_key(key) {
+                EntryEvent::Confirmed => {
+                    // Only all-time scores played without assists go online
+                    #[cfg(feature = "online")]
+                    if let Some(client) = ctx.online.as_ref().filter(|_| entry.board == Board::AllTime && !entry.assisted) {
+                        client.submit(&Submission {
+                            initials: entry.initials(),
+                            score: entry.score,
+                            wave: entry.wave,
+                            replay: entry.replay,
+                        });
+                    }
+                    let board = match entry.board {
+                        Board::Daily => &mut ctx.daily,
+                        _ => &mut ctx.leaderboard,
+                    };
+                    Scene::HighScores(entry.board, board.insert(&entry.initials(), entry.score, entry.assisted))
+                }
+                EntryEvent::Editing => Scene::EnterInitials(entry),
+            },
+            // In co-op at one keyboard the second ship's keys come before
+            // anything else's
+            Scene::Playing(mut run)
+                if run.state.is_co_op() && !run.is_networked() && ctx.keys.player_two(key).is_some() =>
+            {
+                match ctx.keys.player_two(key) {
+                    Some(Action::Fire) => run.fire(1),
+                    Some(action) => run.apply_as(1, action),
+                    None => {}
+                }
+                Scene::Playing(run)
+            }
+            // and in versus the alien commander's, where they are at this side
+            Scene::Playing(mut run) if run.state.settings.versus && ctx.keys.aliens(key).is_some() => {
+                if let Some(action) = ctx.keys.aliens(key) {
+                    run.apply_as(1, action);
+                }
+                Scene::Playing(run)
+            }
+            // The commander of a network game has no ship for the other keys,
+            // and neither does anyone watching a bot play
+            Scene::Playing(run) if run.state.settings.versus && run.me() == 1 => Scene::Playing(run),
+            Scene::Playing(run) if run.is_bot_played() && binding != Some(Binding::Pause) => Scene::Playing(run),
+            Scene::Playing(mut run) => match binding {
+                Some(Binding::Pause) if !run.is_networked() => Scene::Paused(run, PauseMenu::new()),
+                // Holding fire keeps shooting and charges the laser as well
+                Some(Binding::Action(Action::Fire)) => {
+                    run.hold_fire();
+                    Scene::Playing(run)
+                }
+                Some(Binding::Action(action)) => {
+                    run.apply(action);
+                    Scene::Playing(run)
+                }
+                // The ship follows the mouse, and a click fires
+                None => {
+                    if let Key::Mouse { x, clicked, .. } = key {
+                        let layout = ctx.layout(&run.state.settings).clipped_to(ctx.screen).centered_in(ctx.screen);
+                        let camera = Camera::follow(&run.state, &layout);
+                        run.steer_to(camera.x + layout.field_column(x));
+                        if clicked {
+                            run.fire(run.me());
+                        }
+                    }
+                    Scene::Playing(run)
+                }
+                _ => Scene::Playing(run),
+            },
+            Scene::Paused(run, mut menu) => match key {
+                _ if binding == Some(Binding::Pause) => Scene::Playing(run),
+                _ if binding == Some(Binding::MenuUp) => {
+                    menu.select_previous();
+                    Scene::Paused(run, menu)
+                }
+                _ if binding == Some(Binding::MenuDown) => {
+                    menu.select_next();
+                    Scene::Paused(run, menu)
+                }
+                // The run picks up exactly where it was left
+                Key::Char(' ') | Key::Enter | Key::Pad(PadButton::A) => match menu.selected() {
+                    PauseItem::Resume => Scene::Playing(run),
+                    PauseItem::Settings => Scene::Settings(SettingsScreen::new(), Box::new(Scene::Paused(run, menu))),
+                    PauseItem::Restart => {
+                        let settings = ctx.rematch(&run.state.settings);
+                        run.finish();
+                        Scene::Playing(Run::new(&settings))
+                    }
+                    PauseItem::QuitToTitle => {
+                        run.finish();
+                        Scene::TitleScreen(TitleScreen::new(ctx))
+                    }
+                    // The replay of the run so far waits for it to be
+                    // continued, so the last-run one stays as it was
+                    PauseItem::SaveAndQuit => {
+                        run.save();
+                        return None;
+                    }
+                },
+                _ => Scene::Paused(run, menu),
+            },
+            // The run carries on from where it stood, countdown and all
+            Scene::Interlude(run) => match key {
+                Key::Char(' ') | Key::Enter | Key::Pad(PadButton::A) => Scene::Playing(run),
+                _ => Scene::Interlude(run),
+            },
+            Scene::Settings(mut screen, back) => match screen.handle_key(key, ctx) {
+                SettingsEvent::Editing => Scene::Settings(screen, back),
+                SettingsEvent::Closed => *back,
+            },
+            Scene::GameOver(state, saved) => match key {
+                // A fresh state resets score, lives and the player's position,
+                // with as many players as before
+                Key::Char('r') if !ctx.networked => Scene::Playing(Run::new(&ctx.rematch(&state.settings))),
+                #[cfg(feature = "gif")]
+                Key::Char('g') => {
+                    let sprites = ctx.sprites.unwrap_or(ctx.base_sprites);
+                    let layout = ctx.layout(&state.settings);
+                    let saved = match crate::highlight::export(&state, &layout, ctx.high_score.best(), ctx.theme, sprites) {
+                        Ok(path) => tr_with("highlight_saved", &[&path.display()]),
+                        Err(err) => err,
+                    };
+                    Scene::GameOver(state, Some(saved))
+                }
+                _ => Scene::GameOver(state, saved),
+            },
+            Scene::Replay(playback) => Scene::Replay(playback),
+            Scene::Editor(mut editor) => match editor.handle_key(key) {
+                EditorEvent::Editing => Scene::Editor(editor),
+                EditorEvent::Playtest(level) => {
+                    let mut run = Run::new(&Settings { level: Some(level), ..ctx.settings.clone() });
+                    run.toast(tr_with("toast_playtest", &[&ctx.keys.key_name(Binding::Pause)]));
+                    Scene::Playtest(run, editor)
+                }
+                EditorEvent::Closed => return None,
+            },
+            // The pause key goes back to the editor; the others play
+            Scene::Playtest(run, mut editor) if binding == Some(Binding::Pause) => {
+                editor.played(&run.finish());
+                Scene::Editor(editor)
+            }
+            Scene::Playtest(run, editor) => match Scene::Playing(run).handle_key(key, ctx) {
+                Some(Scene::Playing(run)) => Scene::Playtest(run, editor),
+                _ => Scene::Editor(editor),
+            },
+        };
+        Some(next)
+    }
+
+    /// Draws a complete frame for the scene.
+    pub fn render(&self, renderer: &mut dyn Renderer, ctx: &Context) {
+        // Erase the screen instead of clearing it to prevent flicker
+        renderer.clear();
+        let layout = self.layout(ctx, renderer.size()).centered_in(renderer.size());
+        renderer.set_layout(&layout);
+        if !layout.fits(renderer.size()) {
+            render::draw_too_small(renderer, &layout);
+            renderer.present();
+            return;
+        }
+
+        match self {
+            Scene::TitleScreen(title) => title.draw(renderer, &layout, ctx.settings.difficulty),
+            Scene::HighScores(board, highlight) => match ctx.board(*board) {
+                Some(leaderboard) => scores::draw_table(renderer, &layout, leaderboard, *highlight),
+                #[cfg(feature = "online")]
+                None => scores::draw_global(renderer, &layout, ctx.global.as_ref()),
+                #[cfg(not(feature = "online"))]
+                None => {}
+            },
+            Scene::Stats(stats) => stats::draw_stats(renderer, &layout, stats, &ctx.achievements),
+            Scene::Challenges(screen) => screen.draw(renderer, &layout),
+            Scene::Mods(screen) => screen.draw(renderer, &layout, ctx),
+            Scene::EnterInitials(entry) => entry.draw(renderer, &layout),
+            Scene::Settings(screen, _) => screen.draw(renderer, &layout, ctx),
+            Scene::Playing(run) | Scene::Playtest(run, _) => {
+                render::draw_game(renderer, &layout, &run.state, ctx.high_score.best(), ctx.theme);
+                run.draw_toast(renderer, &layout);
+            }
+            Scene::Editor(editor) => editor.draw(renderer, &layout),
+            Scene::Paused(run, menu) => {
+                render::draw_game(renderer, &layout, &run.state, ctx.high_score.best(), ctx.theme);
+                run.draw_toast(renderer, &layout);
+                // Paused scenes never tick, so the game clock (and with it
+                // the alien fire timer) stands still until play resumes
+                let resume = tr_with("pause_resumes", &[&ctx.keys.key_name(Binding::Pause)]);
+                menu.draw(renderer, &layout, &resume);
+            }
+            Scene::Interlude(run) => {
+                if let (Some(number), Some(level)) = (level::number(run.state.wave), run.state.level()) {
+                    campaign::draw_interlude(renderer, &layout, number, run.state.settings.levels().len(), level);
+                }
+            }
+            Scene::GameOver(state, saved) => {
+                render::draw_game(renderer, &layout, state, ctx.high_score.best(), ctx.theme);
+                let title = tr(if state.victory { "campaign_cleared" } else { "game_over" });
+                let final_score = final_score(state);
+                // The code to play the run's challenge again, or pass it on,
+                // left out for a run no code plays the same
+                let code = RunCode::of(state).map(|code| tr_with("challenge_code_digits", &[&code]));
+                let exit = tr_with("press_to_exit", &[&ctx.keys.key_name(Binding::Quit)]);
+                // A network game is played only the once
+                let restart = if ctx.networked { "" } else { tr("press_to_restart") };
+                // Builds that can save highlights say how, then what came of it
+                let mut lines = vec![title, &final_score];
+                lines.extend(code.as_deref());
+                lines.push(restart);
+                match saved {
+                    Some(saved) => lines.push(saved),
+                    None if cfg!(feature = "gif") => lines.push(tr("press_for_highlight")),
+                    None => {}
+                }
+                lines.push(&exit);
+                render::draw_banner(renderer, &layout, &lines, Color::GameOver);
+            }
+            Scene::Replay(playback) => {
+                render::draw_game(renderer, &layout, &playback.state, ctx.high_score.best(), ctx.theme);
+                // Tag the top border so a replay is never mistaken for live play
+                let field = layout.field;
+                Text::new(&[&format!(" {} ", tr("replay"))], Color::Highlight)
+                    .align(Align::Right)
+                    .draw(Rect::new(field.x, field.y, field.width - 2, 1), renderer);
+                if playback.finished() {
+                    let final_score = final_score(&playback.state);
+                    let exit = tr_with("press_to_exit", &[&ctx.keys.key_name(Binding::Quit)]);
+                    render::draw_banner(
+                        renderer,
+                        &layout,
+                        &[tr("replay_over"), &final_score, &exit],
+                        Color::GameOver,
+                    );
+                }
+            }
+        }
+
+        if let Some(stats) = &ctx.debug {
+            render::draw_debug(renderer, stats, self.state());
+        }
+
+        // Refresh the screen to show changes
+        renderer.present();
+    }
+
+    /// The layout the scene is drawn in on a `screen` of cells: that of the
+    /// game it shows, whose playfield a challenge may have sized apart from
+    /// the session's, or else the session's, cut down to fit the screen.
+    pub fn layout(&self, ctx: &Context, screen: (i32, i32)) -> Layout {
+        ctx.layout(self.state().map_or(&ctx.settings, |state| &state.settings)).clipped_to(screen)
+    }
+
+    /// The game the scene shows, if any.
+    fn state(&self) -> Option<&GameState> {
+        match self {
+            Scene::Playing(run) | Scene::Paused(run, _) | Scene::Interlude(run) | Scene::Playtest(run, _) => {
+                Some(&run.state)
+            }
+            Scene::GameOver(state, _) => Some(state),
+            Scene::Replay(playback) => Some(&playback.state),
+            Scene::Settings(_, back) => back.state(),
+            Scene::TitleScreen(_)
+            | Scene::HighScores(..)
+            | Scene::Stats(_)
+            | Scene::Challenges(_)
+            | Scene::Mods(_)
+            | Scene::EnterInitials(_)
+            | Scene::Editor(_) => None,
+        }
+    }
+
+    /// Whether the scene takes every key as it is, so keys that work
+    /// everywhere else, like the quit key, go to it as well.
+    fn reads_raw_keys(&self) -> bool {
+        match self {
+            Scene::EnterInitials(_) | Scene::Editor(_) => true,
+            Scene::Settings(screen, _) => screen.capturing(),
+            Scene::Challenges(screen) => screen.capturing(),
+            _ => false,
+        }
+    }
+
+    /// Shows `text` over the playfield for a while, in scenes with a run.
+    pub fn toast(&mut self, text: String) {
+        if let Scene::Playing(run) | Scene::Paused(run, _) | Scene::Interlude(run) | Scene::Playtest(run, _) = self {
+            run.toast(text);
+        }
+    }
+
+    /// Leaves the scene for good, ending any run in it so its replay is kept.
+    pub fn end(self) {
+        match self {
+            Scene::Playing(run) | Scene::Paused(run, _) | Scene::Interlude(run) | Scene::Playtest(run, _) => {
+                run.finish();
+            }
+            Scene::Settings(_, back) => back.end(),
+            _ => {}
+        }
+    }
+}
+
+/// The score line of a finished game: each player's in co-op.
+fn final_score(state: &GameState) -> String {
+    match state.world.ships.len() {
+        1 => tr_with("final_score", &[&state.score]),
+        _ => {
+            let ships = state.world.ships.iter().enumerate();
+            let scores: Vec<String> = ships.map(|(i, ship)| format!("{}P {}", i + 1, ship.score)).collect();
+            tr_with("final_score", &[&scores.join("  ")])
+        }
+    }
+}
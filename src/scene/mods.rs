@@ -0,0 +1,137 @@
+use crate::input::{Key, PadButton};
+use crate::keymap::Binding;
+use crate::locale::{tr, tr_with};
+use crate::mods::{self, Mod};
+use crate::render::widgets::{Align, Rect, Text, Widget};
+use crate::render::{self, Color, Layout, Renderer};
+
+use super::Context;
+
+/// Lines the details of a mod, or what is wrong with it, can take up.
+const DETAIL_LINES: usize = 3;
+
+/// What a key press did to the mods screen.
+pub enum ModsEvent {
+    Choosing,
+    /// The player put a mod in use, or `None` for the game as it comes.
+    Picked(Option<&'static Mod>),
+    Closed,
+}
+
+/// Lists the mods found, to pick the one to play with, or none.
+///
+/// Up/Down pick a line and Enter puts it in use. Broken mods are listed
+/// too, with what is wrong with them, but cannot be picked.
+pub struct ModsScreen {
+    /// The line picked: 0 for no mod, then one per mod, then Back.
+    selected: usize,
+}
+
+impl ModsScreen {
+    /// Starts on the mod in use.
+    pub fn new(ctx: &Context) -> Self {
+        let active = ctx.active_mod.map(|found| found.id);
+        let selected = mods::all().iter().position(|entry| Some(entry.id) == active).map_or(0, |i| i + 1);
+        ModsScreen { selected }
+    }
+
+    fn lines() -> usize {
+        mods::all().len() + 2
+    }
+
+    pub fn handle_key(&mut self, key: Key, ctx: &Context) -> ModsEvent {
+        let binding = ctx.keys.binding(key);
+        match key {
+            _ if binding == Some(Binding::MenuUp) => {
+                self.selected = (self.selected + Self::lines() - 1) % Self::lines();
+            }
+            _ if binding == Some(Binding::MenuDown) => self.selected = (self.selected + 1) % Self::lines(),
+            Key::Escape => return ModsEvent::Closed,
+            Key::Char(' ') | Key::Enter | Key::Pad(PadButton::A) => match self.selected {
+                0 => return ModsEvent::Picked(None),
+                i if i == Self::lines() - 1 => return ModsEvent::Closed,
+                i => {
+                    if let Ok(found) = &mods::all()[i - 1].loaded {
+                        return ModsEvent::Picked(Some(found));
+                    }
+                }
+            },
+            _ => {}
+        }
+        ModsEvent::Choosing
+    }
+
+    pub fn draw(&self, renderer: &mut dyn Renderer, layout: &Layout, ctx: &Context) {
+        render::draw_frame(renderer, layout);
+        let field = layout.field.inner();
+        let mut y = field.y + 1;
+
+        Text::new(&[tr("mods")], Color::Ui)
+            .align(Align::Center)
+            .draw(Rect::new(field.x, y, field.width, 1), renderer);
+        y += 2;
+
+        let entries = mods::all();
+        let active = ctx.active_mod.map(|found| found.id);
+        let mut labels = vec![(tr("mods_none").to_string(), active.is_none())];
+        for entry in entries {
+            let label = match &entry.loaded {
+                Ok(found) => found.name.to_string(),
+                Err(_) => tr_with("mods_broken", &[&entry.id]),
+            };
+            labels.push((label, Some(entry.id) == active));
+        }
+        labels.push((tr("back").to_string(), false));
+
+        // Scroll to keep the selection in sight, leaving the details room
+        let room = (field.bottom() - y - DETAIL_LINES as i32 - 1).max(1) as usize;
+        let first = (self.selected + 1).saturating_sub(room);
+        for (i, (label, in_use)) in labels.iter().enumerate().skip(first).take(room) {
+            let label = if *in_use { format!("{} *", label) } else { label.clone() };
+            let (label, color) = if i == self.selected {
+                (format!("> {} <", label), Color::Highlight)
+            } else {
+                (label, Color::Border)
+            };
+            Text::new(&[&label], color)
+                .align(Align::Center)
+                .draw(Rect::new(field.x, y, field.width, 1), renderer);
+            y += 1;
+        }
+
+        // What the mod picked is, or what is wrong with it
+        let (details, color) = match self.selected.checked_sub(1).and_then(|i| entries.get(i)) {
+            Some(entry) => match &entry.loaded {
+                Ok(found) if found.about.is_empty() => (tr_with("mods_brings", &[&found.contents()]), Color::Ui),
+                Ok(found) => (format!("{}. {}", found.about, tr_with("mods_brings", &[&found.contents()])), Color::Ui),
+                Err(err) => (err.clone(), Color::GameOver),
+            },
+            None if self.selected == 0 => (tr("mods_about_none").to_string(), Color::Ui),
+            None => (String::new(), Color::Ui),
+        };
+        let lines = wrap(&details, (field.width - 2).max(1) as usize);
+        let lines: Vec<&str> = lines.iter().take(DETAIL_LINES).map(String::as_str).collect();
+        Text::new(&lines, color)
+            .align(Align::Center)
+            .draw(Rect::new(field.x, field.bottom() - DETAIL_LINES as i32, field.width, DETAIL_LINES as i32), renderer);
+    }
+}
+
+/// Breaks `text` into lines of at most `width` characters, between words
+/// where it can.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => {
+                let chars: Vec<char> = word.chars().collect();
+                lines.extend(chars.chunks(width).map(|chunk| chunk.iter().collect()));
+            }
+        }
+    }
+    lines
+}
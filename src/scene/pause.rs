@@ -0,0 +1,92 @@
+use crate::locale::tr;
+use crate::render::widgets::{Align, Block, Rect, Text, Widget};
+use crate::render::{Color, Layout, Renderer};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseItem {
+    Resume,
+    Restart,
+    Settings,
+    QuitToTitle,
+    /// Saves the run to continue from the title screen, and quits.
+    SaveAndQuit,
+}
+
+impl PauseItem {
+    fn label(self) -> &'static str {
+        tr(match self {
+            PauseItem::Resume => "pause_resume",
+            PauseItem::Restart => "pause_restart",
+            PauseItem::Settings => "menu_settings",
+            PauseItem::QuitToTitle => "pause_quit_to_title",
+            PauseItem::SaveAndQuit => "pause_save_and_quit",
+        })
+    }
+}
+
+const MENU: [PauseItem; 5] =
+    [PauseItem::Resume, PauseItem::Restart, PauseItem::Settings, PauseItem::QuitToTitle, PauseItem::SaveAndQuit];
+
+/// Width of the box the menu is drawn in, borders included, unless its
+/// lines need more.
+const BOX_WIDTH: i32 = 21;
+
+/// The menu shown over a paused game, which stays on screen behind it.
+pub struct PauseMenu {
+    selected: usize,
+}
+
+impl PauseMenu {
+    pub fn new() -> Self {
+        PauseMenu { selected: 0 }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = (self.selected + MENU.len() - 1) % MENU.len();
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % MENU.len();
+    }
+
+    pub fn selected(&self) -> PauseItem {
+        MENU[self.selected]
+    }
+
+    /// Draws the menu in a box in the middle of the playfield, with `hint`
+    /// on its last line.
+    pub fn draw(&self, renderer: &mut dyn Renderer, layout: &Layout, hint: &str) {
+        let field = layout.field.inner();
+        let height = MENU.len() as i32 + 5;
+        // Room for the selection marks and the border around the widest line
+        let widest = MENU.iter().map(|item| item.label().chars().count() + 6).chain([hint.chars().count() + 4]).max();
+        let width = (widest.unwrap_or(0) as i32).max(BOX_WIDTH).min(field.width);
+        let area = Rect::new(field.x + (field.width - width) / 2, field.y + (field.height - height) / 2, width, height);
+
+        // Blank out the game behind the box
+        let blank = " ".repeat((area.width - 2).max(0) as usize);
+        for y in area.y + 1..area.bottom() - 1 {
+            renderer.draw_str(area.x + 1, y, &blank, Color::Ui);
+        }
+        Block::new().title(tr("paused")).color(Color::Ui).draw(area, renderer);
+
+        let inner = Rect::new(area.x + 1, area.y + 2, area.width - 2, 1);
+        for (i, item) in MENU.iter().enumerate() {
+            let (label, color) = if i == self.selected {
+                (format!("> {} <", item.label()), Color::Highlight)
+            } else {
+                (item.label().to_string(), Color::Border)
+            };
+            Text::new(&[&label], color).align(Align::Center).draw(Rect { y: inner.y + i as i32, ..inner }, renderer);
+        }
+        Text::new(&[hint], Color::Border)
+            .align(Align::Center)
+            .draw(Rect { y: area.bottom() - 2, ..inner }, renderer);
+    }
+}
+
+impl Default for PauseMenu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,85 @@
+use crate::challenge;
+use crate::leaderboard::{Entry, Leaderboard};
+use crate::locale::{tr, tr_with};
+#[cfg(feature = "online")]
+use crate::online::GlobalTable;
+use crate::render::widgets::{Align, Rect, Text, Widget};
+use crate::render::{self, Color, Layout, Renderer};
+
+/// One of the leaderboards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Board {
+    AllTime,
+    /// Today's table of the daily challenge.
+    Daily,
+    /// The top ten of the online leaderboard.
+    #[cfg(feature = "online")]
+    Global,
+}
+
+impl Board {
+    pub const ALL: &'static [Board] = &[
+        Board::AllTime,
+        Board::Daily,
+        #[cfg(feature = "online")]
+        Board::Global,
+    ];
+}
+
+/// Draws the ranked leaderboard, highlighting `highlight` if given.
+pub fn draw_table(renderer: &mut dyn Renderer, layout: &Layout, leaderboard: &Leaderboard, highlight: Option<usize>) {
+    let title = match leaderboard.day() {
+        Some(day) => tr_with("table_daily", &[&challenge::date(day)]),
+        None => tr("table_high_scores").to_string(),
+    };
+    draw_entries(renderer, layout, &title, leaderboard.entries(), tr("table_empty"), highlight);
+}
+
+/// Draws the online leaderboard, as far as it has come.
+#[cfg(feature = "online")]
+pub fn draw_global(renderer: &mut dyn Renderer, layout: &Layout, table: Option<&GlobalTable>) {
+    let (entries, note) = match table {
+        Some(GlobalTable::Loaded(entries)) => (&entries[..], tr("table_empty")),
+        Some(GlobalTable::Offline) => (&[][..], tr("table_offline")),
+        Some(GlobalTable::Loading(_)) | None => (&[][..], tr("table_loading")),
+    };
+    draw_entries(renderer, layout, tr("table_global"), entries, note, None);
+}
+
+/// Draws `entries` under `title`, or `empty` when there are none.
+fn draw_entries(
+    renderer: &mut dyn Renderer,
+    layout: &Layout,
+    title: &str,
+    entries: &[Entry],
+    empty: &str,
+    highlight: Option<usize>,
+) {
+    render::draw_frame(renderer, layout);
+    let field = layout.field.inner();
+    let mut y = field.y + 3;
+
+    Text::new(&[title], Color::Ui)
+        .align(Align::Center)
+        .draw(Rect::new(field.x, y, field.width, 1), renderer);
+    y += 2;
+
+    if entries.is_empty() {
+        Text::new(&[empty], Color::Border)
+            .align(Align::Center)
+            .draw(Rect::new(field.x, y + 1, field.width, 1), renderer);
+    }
+    for (rank, entry) in entries.iter().enumerate() {
+        // Tagged or not, the lines are as long, so the scores line up
+        let tag = if entry.assisted { tr("assisted") } else { "" };
+        let line = format!("{:>2}.  {}  {:>7} {:<10}", rank + 1, entry.initials, entry.score, tag);
+        let color = if highlight == Some(rank) { Color::Highlight } else { Color::Alien };
+        Text::new(&[&line], color)
+            .align(Align::Center)
+            .draw(Rect::new(field.x, y + rank as i32, field.width, 1), renderer);
+    }
+
+    Text::new(&[tr("table_switch"), tr("table_back")], Color::Ui)
+        .align(Align::Center)
+        .draw(Rect::new(field.x, field.bottom() - 3, field.width, 2), renderer);
+}
@@ -0,0 +1,262 @@
+use crate::config::{Config, Volume};
+use crate::game::AssistOption;
+use crate::input::Key;
+use crate::keymap::{self, Binding};
+use crate::locale::tr;
+use crate::render::widgets::{Align, Rect, Text, Widget};
+use crate::render::{self, Color, Layout, Renderer};
+
+use super::Context;
+
+/// A line of the settings screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Row {
+    Theme,
+    Difficulty,
+    Assist(AssistOption),
+    Key(Binding),
+    Volume(Channel),
+    Mute,
+    Back,
+}
+
+/// One of the volumes of the sound.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Channel {
+    Master,
+    Effects,
+    Music,
+}
+
+impl Channel {
+    const ALL: [Channel; 3] = [Channel::Master, Channel::Effects, Channel::Music];
+
+    fn level(self, mut volume: Volume) -> u8 {
+        *self.level_mut(&mut volume)
+    }
+
+    fn level_mut(self, volume: &mut Volume) -> &mut u8 {
+        match self {
+            Channel::Master => &mut volume.master,
+            Channel::Effects => &mut volume.effects,
+            Channel::Music => &mut volume.music,
+        }
+    }
+}
+
+/// How much Left/Right change a volume by, in percent.
+const VOLUME_STEP: i32 = 10;
+
+impl Row {
+    fn label(self) -> &'static str {
+        match self {
+            Row::Theme => tr("settings_theme"),
+            Row::Difficulty => tr("settings_difficulty"),
+            Row::Assist(option) => option.label(),
+            Row::Key(binding) => binding.label(),
+            Row::Volume(Channel::Master) => tr("settings_volume"),
+            Row::Volume(Channel::Effects) => tr("settings_sfx_volume"),
+            Row::Volume(Channel::Music) => tr("settings_music_volume"),
+            Row::Mute => tr("settings_mute"),
+            Row::Back => tr("back"),
+        }
+    }
+}
+
+/// What a key press did to the settings screen.
+pub enum SettingsEvent {
+    Editing,
+    /// The player is done; the changes have been saved.
+    Closed,
+}
+
+/// Changes the theme, the difficulty and assist options of the runs to come,
+/// the key bindings and, with the `sound` feature, the volumes, and saves
+/// them to the config file on the way out.
+///
+/// Up/Down pick a line, Left/Right change its value and Enter on a binding
+/// waits for the key to bind to it instead.
+pub struct SettingsScreen {
+    rows: Vec<Row>,
+    selected: usize,
+    /// The binding waiting for a key, if there is one.
+    rebinding: Option<Binding>,
+}
+
+impl SettingsScreen {
+    pub fn new() -> Self {
+        let mut rows = vec![Row::Theme, Row::Difficulty];
+        rows.extend(AssistOption::ALL.map(Row::Assist));
+        rows.extend(Binding::all().map(Row::Key));
+        if cfg!(feature = "sound") {
+            rows.extend(Channel::ALL.map(Row::Volume));
+            rows.push(Row::Mute);
+        }
+        rows.push(Row::Back);
+        SettingsScreen { rows, selected: 0, rebinding: None }
+    }
+
+    /// Whether the next key goes to a binding, whatever it normally does.
+    pub fn capturing(&self) -> bool {
+        self.rebinding.is_some()
+    }
+
+    pub fn handle_key(&mut self, key: Key, ctx: &mut Context) -> SettingsEvent {
+        if let Some(binding) = self.rebinding {
+            match key {
+                Key::Mouse { .. } | Key::Released(_) => return SettingsEvent::Editing,
+                // Escape gives up on rebinding rather than binding itself
+                Key::Escape => {}
+                key => ctx.keys.bind(binding, &[key]),
+            }
+            self.rebinding = None;
+            return SettingsEvent::Editing;
+        }
+
+        let binding = ctx.keys.binding(key);
+        let row = self.rows[self.selected];
+        let step = match key {
+            Key::Left => -1,
+            Key::Right => 1,
+            _ if binding == Some(Binding::MenuUp) => {
+                self.selected = (self.selected + self.rows.len() - 1) % self.rows.len();
+                return SettingsEvent::Editing;
+            }
+            _ if binding == Some(Binding::MenuDown) => {
+                self.selected = (self.selected + 1) % self.rows.len();
+                return SettingsEvent::Editing;
+            }
+            Key::Escape => return self.close(ctx),
+            Key::Char(' ') | Key::Enter => match row {
+                Row::Key(binding) => {
+                    self.rebinding = Some(binding);
+                    return SettingsEvent::Editing;
+                }
+                Row::Back => return self.close(ctx),
+                Row::Theme | Row::Difficulty | Row::Assist(_) | Row::Volume(_) | Row::Mute => 1,
+            },
+            _ => return SettingsEvent::Editing,
+        };
+        match row {
+            Row::Theme => ctx.theme = if step < 0 { ctx.theme.previous() } else { ctx.theme.next() },
+            Row::Difficulty => {
+                let difficulty = ctx.settings.difficulty;
+                ctx.settings.difficulty = if step < 0 { difficulty.previous() } else { difficulty.next() };
+            }
+            // Either way flips an option, there being only two values
+            Row::Assist(option) => {
+                let on = ctx.settings.assist.is_on(option);
+                ctx.settings.assist.set(option, !on);
+            }
+            // Volumes stop at either end rather than wrapping around
+            Row::Volume(channel) => {
+                let mut volume = ctx.volume;
+                let level = channel.level_mut(&mut volume);
+                *level = (*level as i32 + step * VOLUME_STEP).clamp(0, 100) as u8;
+                ctx.set_volume(volume);
+            }
+            Row::Mute => ctx.set_volume(Volume { muted: !ctx.volume.muted, ..ctx.volume }),
+            Row::Key(_) | Row::Back => {}
+        }
+        SettingsEvent::Editing
+    }
+
+    fn close(&self, ctx: &Context) -> SettingsEvent {
+        let mut config = Config::load();
+        config.theme = Some(ctx.theme.name.to_string());
+        config.difficulty = Some(ctx.settings.difficulty.name().to_string());
+        config.assist = ctx.settings.assist.options().map(|option| option.name().to_string()).collect();
+        config.keys = ctx.keys.entries();
+        config.volume = ctx.volume;
+        config.save();
+        SettingsEvent::Closed
+    }
+
+    pub fn draw(&self, renderer: &mut dyn Renderer, layout: &Layout, ctx: &Context) {
+        render::draw_frame(renderer, layout);
+        let field = layout.field.inner();
+        let mut y = field.y + 1;
+
+        Text::new(&[tr("settings")], Color::Ui)
+            .align(Align::Center)
+            .draw(Rect::new(field.x, y, field.width, 1), renderer);
+        y += 2;
+
+        // Gaps set the assist options, the bindings and the sound apart from
+        // the other settings, and Back; `None` is a gap
+        let mut lines = Vec::new();
+        for (i, &row) in self.rows.iter().enumerate() {
+            let previous = i.checked_sub(1).map(|i| self.rows[i]);
+            let group_starts = match row {
+                Row::Assist(_) => !matches!(previous, Some(Row::Assist(_))),
+                Row::Key(_) => !matches!(previous, Some(Row::Key(_))),
+                Row::Volume(_) => !matches!(previous, Some(Row::Volume(_))),
+                Row::Back => true,
+                Row::Theme | Row::Difficulty | Row::Mute => false,
+            };
+            if group_starts {
+                lines.push(None);
+            }
+            lines.push(Some(i));
+        }
+        // Scroll to keep the selection in sight when the field is short
+        let room = (field.bottom() - y).max(1) as usize;
+        let line = lines.iter().position(|&line| line == Some(self.selected)).unwrap_or(0);
+        let first = (line + 1).saturating_sub(room).min(lines.len().saturating_sub(room));
+
+        // Labels and values line up in two columns in the middle
+        let width = field.width - 4;
+        let x = field.x + (field.width - width) / 2;
+        for line in lines.into_iter().skip(first).take(room) {
+            let Some(i) = line else {
+                y += 1;
+                continue;
+            };
+            let row = self.rows[i];
+            let selected = i == self.selected;
+            let color = if selected { Color::Highlight } else { Color::Border };
+            let value = match row {
+                Row::Theme => format!("< {} >", ctx.theme.name),
+                Row::Difficulty => format!("< {} >", ctx.settings.difficulty.label()),
+                Row::Assist(option) => {
+                    format!("< {} >", tr(if ctx.settings.assist.is_on(option) { "on" } else { "off" }))
+                }
+                Row::Volume(channel) => format!("< {}% >", channel.level(ctx.volume)),
+                Row::Mute => format!("< {} >", tr(if ctx.volume.muted { "on" } else { "off" })),
+                Row::Key(binding) if self.rebinding == Some(binding) => tr("press_a_key").to_string(),
+                Row::Key(binding) => {
+                    // As many of the keys as fit next to the label
+                    let room = (width - row.label().chars().count() as i32 - 3).max(0) as usize;
+                    let mut keys = String::new();
+                    for name in ctx.keys.keys_for(binding).into_iter().map(keymap::key_name) {
+                        if !keys.is_empty() && keys.len() + 1 + name.len() > room {
+                            break;
+                        }
+                        keys = if keys.is_empty() { name } else { format!("{} {}", keys, name) };
+                    }
+                    keys
+                }
+                Row::Back => {
+                    let back = if selected { format!("> {} <", row.label()) } else { row.label().to_string() };
+                    Text::new(&[&back], color)
+                        .align(Align::Center)
+                        .draw(Rect::new(field.x, y, field.width, 1), renderer);
+                    y += 1;
+                    continue;
+                }
+            };
+            // A long label is cut short to leave its value room
+            let label = format!("{} {}", if selected { ">" } else { " " }, row.label());
+            let room = width - value.chars().count() as i32 - 1;
+            Text::new(&[&label], color).draw(Rect::new(x, y, room, 1), renderer);
+            Text::new(&[&value], color).align(Align::Right).draw(Rect::new(x, y, width, 1), renderer);
+            y += 1;
+        }
+    }
+}
+
+impl Default for SettingsScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
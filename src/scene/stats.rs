@@ -0,0 +1,45 @@
+use crate::achievements::{Achievement, Achievements};
+use crate::locale::tr;
+use crate::render::widgets::{Align, Rect, Text, Widget};
+use crate::render::{self, Color, Layout, Renderer};
+use crate::stats::Stats;
+
+/// Draws the lifetime stats, one counter to a line, and how many of the
+/// achievements are unlocked.
+pub fn draw_stats(renderer: &mut dyn Renderer, layout: &Layout, stats: &Stats, achievements: &Achievements) {
+    render::draw_frame(renderer, layout);
+    let field = layout.field.inner();
+    let mut y = field.y + 3;
+
+    Text::new(&[tr("statistics")], Color::Ui)
+        .align(Align::Center)
+        .draw(Rect::new(field.x, y, field.width, 1), renderer);
+    y += 2;
+
+    let seconds = stats.play_time.as_secs();
+    let accuracy = stats.accuracy().map_or_else(|| "-".to_string(), |accuracy| format!("{}%", accuracy));
+    let lines = [
+        (tr("stats_games_played"), stats.games_played.to_string()),
+        (tr("stats_aliens_destroyed"), stats.aliens_destroyed.to_string()),
+        (tr("stats_shots_fired"), stats.shots_fired.to_string()),
+        (tr("stats_accuracy"), accuracy),
+        (tr("stats_best_wave"), stats.best_wave.to_string()),
+        (tr("stats_play_time"), format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)),
+        (tr("stats_achievements"), format!("{}/{}", achievements.unlocked().len(), Achievement::ALL.len())),
+    ];
+    // Labels and values line up in two columns in the middle, as wide as
+    // the longest line needs; labels are cut short to leave their value room
+    let widest = lines.iter().map(|(label, value)| label.chars().count() + value.chars().count() + 2).max();
+    let width = (widest.unwrap_or(0) as i32).max(28).min(field.width);
+    let x = field.x + (field.width - width) / 2;
+    for (label, value) in lines {
+        let room = width - value.chars().count() as i32 - 1;
+        Text::new(&[label], Color::Alien).draw(Rect::new(x, y, room, 1), renderer);
+        Text::new(&[&value], Color::Alien).align(Align::Right).draw(Rect::new(x, y, width, 1), renderer);
+        y += 1;
+    }
+
+    Text::new(&[tr("press_any_key")], Color::Ui)
+        .align(Align::Center)
+        .draw(Rect::new(field.x, field.bottom() - 3, field.width, 1), renderer);
+}
@@ -0,0 +1,213 @@
+use crate::campaign::Progress;
+use crate::game::{Difficulty, Settings};
+use crate::locale::{tr, tr_with};
+use crate::mods;
+use crate::render::widgets::{Align, Rect, Text, Widget};
+use crate::render::{self, Color, Layout, Renderer};
+use crate::replay;
+
+use super::Context;
+
+const LOGO: [&str; 5] = [
+    "### ##  # #  #  ##  ### ##   ##",
+    " #  # # # # # # # # #   # # #  ",
+    " #  # # # # ### # # ##  ##   # ",
+    " #  # # # # # # # # #   # #   #",
+    "### # #  #  # # ##  ### # # ## ",
+];
+
+/// Ticks the "Press SPACE" prompt stays on (and then off) while blinking.
+const BLINK_TICKS: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuItem {
+    /// Continues the saved run; only there when there is one.
+    Continue,
+    Start,
+    /// Plays the campaign's levels in order, once through; shows how far
+    /// it has been played.
+    Campaign,
+    /// Starts a run for two players sharing the keyboard, in the mode
+    /// Left/Right pick.
+    TwoPlayers,
+    /// Offers the daily and weekly challenges, and codes to play.
+    Challenges,
+    /// Shows the difficulty runs start at, which Left/Right change.
+    Difficulty,
+    HighScores,
+    Stats,
+    /// Picks a mod to play with; only there when some are installed.
+    Mods,
+    Settings,
+    Quit,
+}
+
+/// The ways two players can share a run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TwoPlayers {
+    /// A ship each, against the aliens together.
+    CoOp,
+    /// Player two commands the aliens against player one's ship.
+    Versus,
+}
+
+impl TwoPlayers {
+    pub fn name(self) -> &'static str {
+        match self {
+            TwoPlayers::CoOp => tr("co_op"),
+            TwoPlayers::Versus => tr("versus"),
+        }
+    }
+
+    /// The other mode; there are only the two.
+    pub fn next(self) -> Self {
+        match self {
+            TwoPlayers::CoOp => TwoPlayers::Versus,
+            TwoPlayers::Versus => TwoPlayers::CoOp,
+        }
+    }
+
+    /// The session's `base` settings, for a run in this mode.
+    pub fn settings(self, base: &Settings) -> Settings {
+        match self {
+            TwoPlayers::CoOp => Settings { players: 2, versus: false, ..base.clone() },
+            TwoPlayers::Versus => Settings { players: 1, versus: true, ..base.clone() },
+        }
+    }
+}
+
+impl MenuItem {
+    fn label(self, title: &TitleScreen, difficulty: Difficulty) -> String {
+        let campaign = title.campaign;
+        match self {
+            MenuItem::Continue => tr("menu_continue").to_string(),
+            MenuItem::Start => tr("menu_start").to_string(),
+            MenuItem::Campaign if campaign.completions > 0 => tr("menu_campaign_cleared").to_string(),
+            MenuItem::Campaign if campaign.best_cleared > 0 => {
+                tr_with("menu_campaign_progress", &[&campaign.best_cleared, &title.levels])
+            }
+            MenuItem::Campaign => tr("menu_campaign").to_string(),
+            MenuItem::TwoPlayers => tr_with("menu_two_players", &[&title.two_players.name()]),
+            MenuItem::Challenges => tr("menu_challenges").to_string(),
+            MenuItem::Difficulty => tr_with("menu_difficulty", &[&difficulty.label()]),
+            MenuItem::HighScores => tr("menu_high_scores").to_string(),
+            MenuItem::Stats => tr("menu_stats").to_string(),
+            MenuItem::Mods => match title.active_mod {
+                Some(name) => tr_with("menu_mods_in_use", &[&name]),
+                None => tr("menu_mods").to_string(),
+            },
+            MenuItem::Settings => tr("menu_settings").to_string(),
+            MenuItem::Quit => tr("menu_quit").to_string(),
+        }
+    }
+}
+
+const MENU: [MenuItem; 9] = [
+    MenuItem::Start,
+    MenuItem::Campaign,
+    MenuItem::TwoPlayers,
+    MenuItem::Challenges,
+    MenuItem::Difficulty,
+    MenuItem::HighScores,
+    MenuItem::Stats,
+    MenuItem::Settings,
+    MenuItem::Quit,
+];
+
+/// The logo, a blinking prompt and the main menu.
+pub struct TitleScreen {
+    menu: Vec<MenuItem>,
+    selected: usize,
+    two_players: TwoPlayers,
+    /// How far the levels in use have been played as a campaign, and how
+    /// many there are.
+    campaign: Progress,
+    levels: usize,
+    /// The name of the mod in use, if there is one.
+    active_mod: Option<&'static str>,
+    ticks: u32,
+}
+
+impl TitleScreen {
+    pub fn new(ctx: &Context) -> Self {
+        let mut menu = MENU.to_vec();
+        if replay::has_saved_run() {
+            menu.insert(0, MenuItem::Continue);
+        }
+        if !mods::all().is_empty() {
+            let settings = menu.iter().position(|&item| item == MenuItem::Settings).unwrap_or(menu.len());
+            menu.insert(settings, MenuItem::Mods);
+        }
+        TitleScreen {
+            menu,
+            selected: 0,
+            two_players: TwoPlayers::CoOp,
+            campaign: Progress::load(ctx.settings.pack),
+            levels: ctx.settings.levels().len(),
+            active_mod: ctx.active_mod.map(|found| found.name),
+            ticks: 0,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        self.ticks = self.ticks.wrapping_add(1);
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = (self.selected + self.menu.len() - 1) % self.menu.len();
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.menu.len();
+    }
+
+    pub fn selected(&self) -> MenuItem {
+        self.menu[self.selected]
+    }
+
+    pub fn two_players(&self) -> TwoPlayers {
+        self.two_players
+    }
+
+    pub fn switch_two_players(&mut self) {
+        self.two_players = self.two_players.next();
+    }
+
+    pub fn draw(&self, renderer: &mut dyn Renderer, layout: &Layout, difficulty: Difficulty) {
+        render::draw_frame(renderer, layout);
+        let field = layout.field.inner();
+        // Blank lines the menu can spare the room for, beyond the one
+        // under the logo: under the prompt, then under the title, and
+        // the one above it goes first in the smallest playfield
+        let spare = field.height - (LOGO.len() + 4 + self.menu.len()) as i32;
+        let mut y = if spare >= 0 { field.y + 1 } else { field.y };
+
+        Text::new(&["T E R M I N A L"], Color::Ui)
+            .align(Align::Center)
+            .draw(Rect::new(field.x, y, field.width, 1), renderer);
+        y += if spare >= 1 { 2 } else { 1 };
+
+        Text::new(&LOGO, Color::Alien)
+            .align(Align::Center)
+            .draw(Rect::new(field.x, y, field.width, LOGO.len() as i32), renderer);
+        y += LOGO.len() as i32 + 1;
+
+        if (self.ticks / BLINK_TICKS).is_multiple_of(2) {
+            Text::new(&[tr("press_to_start")], Color::Ui)
+                .align(Align::Center)
+                .draw(Rect::new(field.x, y, field.width, 1), renderer);
+        }
+        y += if spare >= 2 { 2 } else { 1 };
+
+        for (i, item) in self.menu.iter().enumerate() {
+            let (label, color) = if i == self.selected {
+                (format!("> {} <", item.label(self, difficulty)), Color::Highlight)
+            } else {
+                (item.label(self, difficulty), Color::Border)
+            };
+            Text::new(&[&label], color)
+                .align(Align::Center)
+                .draw(Rect::new(field.x, y + i as i32, field.width, 1), renderer);
+        }
+    }
+}
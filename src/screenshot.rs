@@ -0,0 +1,103 @@
+//! Screenshots, taken with F12: the frame on screen written to the current
+//! directory twice, as `screenshot-<date>-<time>.txt` with just its glyphs
+//! and as a `.ans` file of the same name with its colors, in the 24-bit
+//! color escape codes `cat` shows in a terminal.
+//!
+//! The frame is drawn again into a [`FrameBuffer`] of its own for this, so
+//! a screenshot comes out the same whichever backend the game is played
+//! on, sprites as text.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::render::buffer::FrameBuffer;
+use crate::render::sprites::SpriteSet;
+use crate::render::theme::Theme;
+use crate::render::{Color, Renderer};
+use crate::scene::{Context, Scene};
+use crate::storage;
+
+/// Writes the frame `scene` shows on a screen of `size`, drawn with
+/// `sprites`, and returns where the text file went; the ANSI one is next
+/// to it.
+pub fn save(scene: &Scene, ctx: &Context, size: (i32, i32), sprites: &'static SpriteSet) -> Result<PathBuf, String> {
+    let mut shot = Shot { frame: FrameBuffer::new(size.0.max(0) as usize, size.1.max(0) as usize), sprites };
+    scene.render(&mut shot, ctx);
+    let path = storage::timestamped("screenshot", "txt");
+    let ansi = path.with_extension("ans");
+    fs::write(&path, text(&shot.frame)).map_err(|err| format!("{}: {}", path.display(), err))?;
+    fs::write(&ansi, ansi_text(&shot.frame, ctx.theme)).map_err(|err| format!("{}: {}", ansi.display(), err))?;
+    Ok(path)
+}
+
+/// The frame being taken, drawn as the player's backend would.
+struct Shot {
+    frame: FrameBuffer,
+    sprites: &'static SpriteSet,
+}
+
+impl Renderer for Shot {
+    fn clear(&mut self) {
+        self.frame.clear();
+    }
+
+    fn draw_str(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        self.frame.draw_str(x, y, text, color);
+    }
+
+    fn present(&mut self) {}
+
+    fn size(&self) -> (i32, i32) {
+        self.frame.size()
+    }
+
+    fn rich_colors(&self) -> bool {
+        true
+    }
+
+    fn sprites(&self) -> &'static SpriteSet {
+        self.sprites
+    }
+
+    fn draw_pixels(&mut self, x: i32, y: i32, top: Option<Color>, bottom: Option<Color>) {
+        self.frame.draw_pixels(x, y, top, bottom);
+    }
+}
+
+/// The glyphs of `frame`, a line to a row, without trailing blanks.
+fn text(frame: &FrameBuffer) -> String {
+    let mut text = String::new();
+    for y in 0..frame.height() {
+        let row: String = frame.row(y).iter().map(|cell| cell.ch).collect();
+        text.push_str(row.trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+/// The glyphs of `frame` in the colors `theme` has for them, a line to a
+/// row, each line ending with the colors reset.
+fn ansi_text(frame: &FrameBuffer, theme: &Theme) -> String {
+    let mut text = String::new();
+    for y in 0..frame.height() {
+        let mut colors = (None, None);
+        for cell in frame.row(y) {
+            if (cell.color, cell.background) != colors {
+                colors = (cell.color, cell.background);
+                text.push_str("\x1b[0m");
+                if let Some(color) = cell.color {
+                    let rgb = theme.rgb(color);
+                    let _ = write!(text, "\x1b[38;2;{};{};{}m", rgb.0, rgb.1, rgb.2);
+                }
+                if let Some(color) = cell.background {
+                    let rgb = theme.rgb(color);
+                    let _ = write!(text, "\x1b[48;2;{};{};{}m", rgb.0, rgb.1, rgb.2);
+                }
+            }
+            text.push(cell.ch);
+        }
+        text.push_str("\x1b[0m\n");
+    }
+    text
+}
@@ -0,0 +1,647 @@
+//! Scripts that level files attach to their aliens and bosses, written in a
+//! small subset of the Rhai language. A script runs once per tick for every
+//! alien of the formation, or for the boss, after they have moved:
+//!
+//! ```rhai
+//! // Drift towards the ship, and fire when right above it
+//! if player.x < alien.x { alien.x -= 1; } else if player.x > alien.x { alien.x += 1; }
+//! if player.x == alien.x && rand(4) == 0 { alien.fire(); }
+//! ```
+//!
+//! What a script can see and do:
+//!
+//! - `alien.x`, `alien.y`: where the alien, or the boss, is; assigning them
+//!   moves it, within the field.
+//! - `alien.hits`: the shots it has taken; `alien.index`: its place in the
+//!   formation, counted from 0.
+//! - `alien.fire()`: fires once this tick, a shot for an alien and the next
+//!   attack for the boss, on top of the usual fire.
+//! - `player.x`, `player.y`: the ship nearest the alien.
+//! - `wave`, `tick`, `width`, `height`: the wave, the ticks since it started
+//!   and the size of the field.
+//! - `rand(n)`, a whole number from 0 to below `n`, drawn from the run's own
+//!   randomness so replays stay true; `abs(n)`, `min(a, b)`, `max(a, b)`.
+//!
+//! Statements are `let` bindings, assignments (`=`, `+=`, `-=`), calls and
+//! `if`/`else`; expressions have the usual arithmetic, comparisons, `!`,
+//! `&&` and `||`, and `//` starts a comment. Every value is a whole number,
+//! `true` being 1 and `false` 0, and dividing by 0 gives 0. There are no
+//! loops, so a script always finishes, and nothing can fail once it has
+//! compiled.
+
+use rand::Rng;
+
+/// The thing a script runs for: an alien, or the boss.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Actor {
+    pub x: i64,
+    pub y: i64,
+    pub hits: i64,
+    pub index: i64,
+    /// Set by `alien.fire()`.
+    pub fire: bool,
+}
+
+/// What a script can read of the rest of the game.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct World {
+    pub player_x: i64,
+    pub player_y: i64,
+    pub wave: i64,
+    pub tick: i64,
+    pub width: i64,
+    pub height: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    AlienX,
+    AlienY,
+    AlienHits,
+    AlienIndex,
+    PlayerX,
+    PlayerY,
+    Wave,
+    Tick,
+    Width,
+    Height,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Function {
+    Rand,
+    Abs,
+    Min,
+    Max,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<(Function, usize)> {
+        match name {
+            "rand" => Some((Function::Rand, 1)),
+            "abs" => Some((Function::Abs, 1)),
+            "min" => Some((Function::Min, 2)),
+            "max" => Some((Function::Max, 2)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+impl Op {
+    fn apply(self, a: i64, b: i64) -> i64 {
+        match self {
+            Op::Add => a.wrapping_add(b),
+            Op::Sub => a.wrapping_sub(b),
+            Op::Mul => a.wrapping_mul(b),
+            Op::Div => a.checked_div(b).unwrap_or(0),
+            Op::Rem => a.checked_rem(b).unwrap_or(0),
+            Op::Eq => i64::from(a == b),
+            Op::Ne => i64::from(a != b),
+            Op::Lt => i64::from(a < b),
+            Op::Le => i64::from(a <= b),
+            Op::Gt => i64::from(a > b),
+            Op::Ge => i64::from(a >= b),
+            Op::And => i64::from(a != 0 && b != 0),
+            Op::Or => i64::from(a != 0 || b != 0),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Expr {
+    Number(i64),
+    /// A `let` binding, by its slot.
+    Local(usize),
+    Field(Field),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Target {
+    Local(usize),
+    AlienX,
+    AlienY,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Stmt {
+    Assign(Target, Expr),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    Fire,
+}
+
+/// A compiled script, ready to run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Script {
+    body: Vec<Stmt>,
+    /// How many `let` bindings it makes, each with a slot of its own.
+    locals: usize,
+}
+
+impl Script {
+    /// Compiles a script given a line to a string, as level files hold it.
+    pub fn compile(lines: &[&str]) -> Result<Script, String> {
+        let tokens = lex(lines)?;
+        let mut parser = Parser { tokens, pos: 0, scopes: vec![Vec::new()], locals: 0 };
+        let mut body = Vec::new();
+        while parser.peek().is_some() {
+            body.push(parser.statement()?);
+        }
+        Ok(Script { body, locals: parser.locals })
+    }
+
+    /// Runs the script once for `actor`, drawing any random numbers from
+    /// `rng`.
+    pub fn run(&self, actor: &mut Actor, world: &World, rng: &mut impl Rng) {
+        let mut run = Run { actor, world, rng, locals: vec![0; self.locals] };
+        run.block(&self.body);
+    }
+}
+
+// --- Lexing ---
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    /// Punctuation and operators, `+=` and `&&` as much as `(`.
+    Symbol(&'static str),
+}
+
+/// Symbols longest first, so `<=` is never read as `<` then `=`.
+const SYMBOLS: [&str; 24] = [
+    "+=", "-=", "==", "!=", "<=", ">=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "=", "!", "(", ")", "{", "}",
+    ",", ";", ".",
+];
+
+/// Splits the script into tokens, each with the line it is on.
+fn lex(lines: &[&str]) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    for (number, line) in lines.iter().enumerate() {
+        let number = number + 1;
+        let mut rest = line.split("//").next().unwrap_or_default().trim_start();
+        while !rest.is_empty() {
+            let first = rest.chars().next().unwrap_or_default();
+            let length = if first.is_ascii_digit() {
+                let length = rest.find(|ch: char| !ch.is_ascii_digit()).unwrap_or(rest.len());
+                let value = rest[..length].parse().map_err(|_| format!("line {}: number too big", number))?;
+                tokens.push((Token::Number(value), number));
+                length
+            } else if first.is_ascii_alphabetic() || first == '_' {
+                let length = rest.find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '_').unwrap_or(rest.len());
+                tokens.push((Token::Name(rest[..length].to_string()), number));
+                length
+            } else if let Some(symbol) = SYMBOLS.into_iter().find(|symbol| rest.starts_with(symbol)) {
+                tokens.push((Token::Symbol(symbol), number));
+                symbol.len()
+            } else {
+                return Err(format!("line {}: unexpected '{}'", number, first));
+            };
+            rest = rest[length..].trim_start();
+        }
+    }
+    Ok(tokens)
+}
+
+// --- Parsing ---
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// The names bound in each enclosing block, innermost last, with their
+    /// slots.
+    scopes: Vec<Vec<(String, usize)>>,
+    locals: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(token, _)| token.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn error(&self, what: &str) -> String {
+        match self.tokens.get(self.pos).or(self.tokens.last()) {
+            Some((_, line)) => format!("line {}: {}", line, what),
+            None => what.to_string(),
+        }
+    }
+
+    /// Takes `symbol` if it comes next.
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(next)) if *next == symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", symbol)))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Name(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.error("expected a name")),
+        }
+    }
+
+    fn local(&self, name: &str) -> Option<usize> {
+        // The latest binding of `name`, so a `let` shadows one before it
+        self.scopes.iter().flatten().rev().find(|(bound, _)| bound == name).map(|&(_, slot)| slot)
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, String> {
+        self.expect("{")?;
+        self.scopes.push(Vec::new());
+        let mut body = Vec::new();
+        while !self.eat("}") {
+            if self.peek().is_none() {
+                return Err(self.error("expected '}'"));
+            }
+            body.push(self.statement()?);
+        }
+        self.scopes.pop();
+        Ok(body)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, String> {
+        let name = self.name()?;
+        match name.as_str() {
+            "if" => {
+                let condition = self.expression()?;
+                let then = self.block()?;
+                let otherwise = match self.peek() {
+                    Some(Token::Name(next)) if next == "else" => {
+                        self.pos += 1;
+                        if matches!(self.peek(), Some(Token::Name(next)) if next == "if") {
+                            vec![self.statement()?]
+                        } else {
+                            self.block()?
+                        }
+                    }
+                    _ => Vec::new(),
+                };
+                Ok(Stmt::If(condition, then, otherwise))
+            }
+            "let" => {
+                let name = self.name()?;
+                self.expect("=")?;
+                let value = self.expression()?;
+                self.expect(";")?;
+                // Bound only once its value is worked out, so `let x = x + 1`
+                // reads the outer `x`
+                let slot = self.locals;
+                self.locals += 1;
+                self.scopes.last_mut().expect("a scope is always open").push((name, slot));
+                Ok(Stmt::Assign(Target::Local(slot), value))
+            }
+            "alien" if self.eat(".") => {
+                let member = self.name()?;
+                let target = match member.as_str() {
+                    "fire" => {
+                        self.expect("(")?;
+                        self.expect(")")?;
+                        self.expect(";")?;
+                        return Ok(Stmt::Fire);
+                    }
+                    "x" => Target::AlienX,
+                    "y" => Target::AlienY,
+                    "hits" | "index" => return Err(self.error(&format!("alien.{} cannot be set", member))),
+                    _ => return Err(self.error(&format!("there is no alien.{}", member))),
+                };
+                let current = match target {
+                    Target::AlienX => Expr::Field(Field::AlienX),
+                    _ => Expr::Field(Field::AlienY),
+                };
+                self.assignment(target, current)
+            }
+            _ => match self.local(&name) {
+                Some(slot) => self.assignment(Target::Local(slot), Expr::Local(slot)),
+                None => Err(self.error(&format!("'{}' is not something a script can set", name))),
+            },
+        }
+    }
+
+    /// The rest of an assignment to `target`, whose value now is `current`.
+    fn assignment(&mut self, target: Target, current: Expr) -> Result<Stmt, String> {
+        let op = if self.eat("=") {
+            None
+        } else if self.eat("+=") {
+            Some(Op::Add)
+        } else if self.eat("-=") {
+            Some(Op::Sub)
+        } else {
+            return Err(self.error("expected '=', '+=' or '-='"));
+        };
+        let value = self.expression()?;
+        self.expect(";")?;
+        let value = match op {
+            Some(op) => Expr::Binary(op, Box::new(current), Box::new(value)),
+            None => value,
+        };
+        Ok(Stmt::Assign(target, value))
+    }
+
+    fn expression(&mut self) -> Result<Expr, String> {
+        self.binary(0)
+    }
+
+    /// Operators by how tightly they bind, loosest first.
+    const LEVELS: [&'static [(&'static str, Op)]; 5] = [
+        &[("||", Op::Or)],
+        &[("&&", Op::And)],
+        &[("==", Op::Eq), ("!=", Op::Ne), ("<=", Op::Le), (">=", Op::Ge), ("<", Op::Lt), (">", Op::Gt)],
+        &[("+", Op::Add), ("-", Op::Sub)],
+        &[("*", Op::Mul), ("/", Op::Div), ("%", Op::Rem)],
+    ];
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        let Some(ops) = Self::LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        let next = |parser: &Self| match parser.peek() {
+            Some(Token::Symbol(next)) => ops.iter().find(|(symbol, _)| symbol == next).map(|&(_, op)| op),
+            _ => None,
+        };
+        while let Some(op) = next(self) {
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let inner = self.expression()?;
+            self.expect(")")?;
+            return Ok(inner);
+        }
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Name(name)) => self.name_expression(&name),
+            _ => {
+                self.pos -= 1;
+                Err(self.error("expected a value"))
+            }
+        }
+    }
+
+    /// A value made of `name`, just read: a binding, a field or a call.
+    fn name_expression(&mut self, name: &str) -> Result<Expr, String> {
+        if let Some(slot) = self.local(name) {
+            return Ok(Expr::Local(slot));
+        }
+        let field = match name {
+            "true" => return Ok(Expr::Number(1)),
+            "false" => return Ok(Expr::Number(0)),
+            "alien" | "player" => {
+                self.expect(".")?;
+                let member = self.name()?;
+                match (name, member.as_str()) {
+                    ("alien", "x") => Field::AlienX,
+                    ("alien", "y") => Field::AlienY,
+                    ("alien", "hits") => Field::AlienHits,
+                    ("alien", "index") => Field::AlienIndex,
+                    ("player", "x") => Field::PlayerX,
+                    ("player", "y") => Field::PlayerY,
+                    _ => return Err(self.error(&format!("there is no {}.{}", name, member))),
+                }
+            }
+            "wave" => Field::Wave,
+            "tick" => Field::Tick,
+            "width" => Field::Width,
+            "height" => Field::Height,
+            _ => {
+                let Some((function, arity)) = Function::from_name(name) else {
+                    return Err(self.error(&format!("there is no '{}'", name)));
+                };
+                self.expect("(")?;
+                let mut args = Vec::new();
+                while !self.eat(")") {
+                    if !args.is_empty() {
+                        self.expect(",")?;
+                    }
+                    args.push(self.expression()?);
+                }
+                if args.len() != arity {
+                    return Err(self.error(&format!("{} takes {} argument(s)", name, arity)));
+                }
+                return Ok(Expr::Call(function, args));
+            }
+        };
+        Ok(Expr::Field(field))
+    }
+}
+
+// --- Running ---
+struct Run<'a, R> {
+    actor: &'a mut Actor,
+    world: &'a World,
+    rng: &'a mut R,
+    locals: Vec<i64>,
+}
+
+impl<R: Rng> Run<'_, R> {
+    fn block(&mut self, body: &[Stmt]) {
+        for stmt in body {
+            match stmt {
+                Stmt::Assign(target, value) => {
+                    let value = self.eval(value);
+                    match *target {
+                        Target::Local(slot) => self.locals[slot] = value,
+                        Target::AlienX => self.actor.x = value,
+                        Target::AlienY => self.actor.y = value,
+                    }
+                }
+                Stmt::If(condition, then, otherwise) => {
+                    if self.eval(condition) != 0 {
+                        self.block(then);
+                    } else {
+                        self.block(otherwise);
+                    }
+                }
+                Stmt::Fire => self.actor.fire = true,
+            }
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr) -> i64 {
+        match expr {
+            Expr::Number(value) => *value,
+            Expr::Local(slot) => self.locals[*slot],
+            Expr::Field(field) => match field {
+                Field::AlienX => self.actor.x,
+                Field::AlienY => self.actor.y,
+                Field::AlienHits => self.actor.hits,
+                Field::AlienIndex => self.actor.index,
+                Field::PlayerX => self.world.player_x,
+                Field::PlayerY => self.world.player_y,
+                Field::Wave => self.world.wave,
+                Field::Tick => self.world.tick,
+                Field::Width => self.world.width,
+                Field::Height => self.world.height,
+            },
+            Expr::Neg(inner) => self.eval(inner).wrapping_neg(),
+            Expr::Not(inner) => i64::from(self.eval(inner) == 0),
+            // The right of `&&` and `||` is only worked out when it matters,
+            // so a `rand` there draws nothing otherwise
+            Expr::Binary(op @ (Op::And | Op::Or), left, right) => {
+                let left = self.eval(left) != 0;
+                match op {
+                    Op::And => i64::from(left && self.eval(right) != 0),
+                    _ => i64::from(left || self.eval(right) != 0),
+                }
+            }
+            Expr::Binary(op, left, right) => {
+                let left = self.eval(left);
+                op.apply(left, self.eval(right))
+            }
+            Expr::Call(function, args) => {
+                let args: Vec<i64> = args.iter().map(|arg| self.eval(arg)).collect();
+                match function {
+                    Function::Rand if args[0] > 0 => self.rng.gen_range(0..args[0]),
+                    Function::Rand => 0,
+                    Function::Abs => args[0].wrapping_abs(),
+                    Function::Min => args[0].min(args[1]),
+                    Function::Max => args[0].max(args[1]),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    /// Runs `lines` once for an alien at (5, 3) with the ship at (10, 20),
+    /// returning the alien as the script left it.
+    fn run(lines: &[&str], rng: &mut ChaCha8Rng) -> Actor {
+        let script = Script::compile(lines).unwrap_or_else(|err| panic!("{:?} compiles: {}", lines, err));
+        let mut actor = Actor { x: 5, y: 3, ..Actor::default() };
+        let world = World { player_x: 10, player_y: 20, width: 80, height: 24, ..World::default() };
+        script.run(&mut actor, &world, rng);
+        actor
+    }
+
+    fn run_alone(lines: &[&str]) -> Actor {
+        run(lines, &mut ChaCha8Rng::seed_from_u64(1))
+    }
+
+    #[test]
+    fn operators_bind_as_usual() {
+        assert_eq!(run_alone(&["alien.x = 1 + 2 * 3;"]).x, 7);
+        assert_eq!(run_alone(&["alien.x = (1 + 2) * 3 - 10 / 3 % 2;"]).x, 8);
+        assert_eq!(run_alone(&["alien.x = 10 - 4 - 3;"]).x, 3);
+        assert_eq!(run_alone(&["alien.x = -2 * 3 + !0;"]).x, -5);
+        assert_eq!(run_alone(&["alien.x = 1 + 1 == 2 && 3 < 2 || 4 >= 4;"]).x, 1);
+        assert_eq!(run_alone(&["alien.x = 0 || 1 && 0;"]).x, 0);
+    }
+
+    #[test]
+    fn a_let_reads_the_binding_it_shadows() {
+        assert_eq!(run_alone(&["let x = 1;", "let x = x + 1;", "alien.x = x;"]).x, 2);
+        // One made in a block is gone once it ends
+        let alien = run_alone(&["let x = 1;", "if true { let x = x + 10; alien.y = x; }", "alien.x = x;"]);
+        assert_eq!((alien.x, alien.y), (1, 11));
+    }
+
+    #[test]
+    fn if_runs_the_branch_its_condition_picks() {
+        let follow = ["if player.x < alien.x { alien.x -= 1; } else if player.x > alien.x { alien.x += 1; }"];
+        assert_eq!(run_alone(&follow).x, 6);
+        assert_eq!(run_alone(&["if alien.y > 3 { alien.y = 0; } else { alien.y = 9; }"]).y, 9);
+        assert_eq!(run_alone(&["if alien.y == 3 { alien.y = 0; }"]).y, 0);
+    }
+
+    #[test]
+    fn compound_assignments_move_the_alien() {
+        let alien = run_alone(&["alien.x += 2;", "alien.y -= 1 + 1;"]);
+        assert_eq!((alien.x, alien.y), (7, 1));
+    }
+
+    #[test]
+    fn fire_fires_only_when_reached() {
+        assert!(run_alone(&["alien.fire();"]).fire);
+        assert!(!run_alone(&["if false { alien.fire(); }"]).fire);
+    }
+
+    #[test]
+    fn and_and_or_draw_on_the_right_only_when_it_matters() {
+        let untouched = ChaCha8Rng::seed_from_u64(1);
+        for lines in [["alien.x = 0 && rand(10);"], ["alien.x = 1 || rand(10);"]] {
+            let mut rng = untouched.clone();
+            run(&lines, &mut rng);
+            assert_eq!(rng, untouched, "{:?} draws nothing", lines);
+        }
+        // The left is worked out once, however it comes out
+        let mut once = untouched.clone();
+        once.gen_range(0..5);
+        for lines in [["alien.x = rand(5) + 1 && 1;"], ["alien.x = rand(5) - 9 || 0;"]] {
+            let mut rng = untouched.clone();
+            assert_eq!(run(&lines, &mut rng).x, 1);
+            assert_eq!(rng, once, "{:?} draws once", lines);
+        }
+    }
+
+    #[test]
+    fn dividing_by_zero_gives_zero() {
+        let alien = run_alone(&["alien.x = 7 / 0;", "alien.y = 7 % (alien.y - 3);"]);
+        assert_eq!((alien.x, alien.y), (0, 0));
+    }
+
+    #[test]
+    fn errors_name_the_line_they_are_on() {
+        let error = |lines: &[&str]| Script::compile(lines).expect_err("does not compile");
+        assert_eq!(error(&["alien.x = 1;", "while true { }"]), "line 2: 'while' is not something a script can set");
+        assert_eq!(error(&["alien.hits = 1;"]), "line 1: alien.hits cannot be set");
+        assert_eq!(error(&["", "let x = 1 $ 2;"]), "line 2: unexpected '$'");
+        assert_eq!(error(&["if true {", "  alien.x = 1;"]), "line 2: expected '}'");
+        assert_eq!(error(&["alien.x = rand(1, 2);"]), "line 1: rand takes 1 argument(s)");
+        assert_eq!(error(&["alien.x = player.z;"]), "line 1: there is no player.z");
+        assert_eq!(error(&["alien.x = 1"]), "line 1: expected ';'");
+    }
+}
@@ -0,0 +1,344 @@
+//! Sound effects, with the `sound` feature: a pew for every shot, a burst
+//! of noise for every alien destroyed, a longer rumble when a ship is hit
+//! and a warble while the mystery ship flies over. Under them goes the
+//! arcade's heartbeat, four low notes stepping down over and over, faster
+//! as the wave thins out.
+//!
+//! The effects are synthesized here, from the [`AudioEvent`]s runs leave in
+//! [`GameState::audio`], and played through
+//! whichever of the systems' own players there is: `pw-cat` for PipeWire,
+//! `pacat` for PulseAudio, `aplay` for ALSA, or SoX's `play`. A thread of
+//! its own mixes them and feeds the player raw samples as they are due, a
+//! little ahead so they never run dry. Without a player the game is silent,
+//! as it is without the feature.
+//!
+//! How loud the effects and the heartbeat are goes by the [`Volume`] set in
+//! the config file, changed on the settings screen or muted with `m` as the
+//! game plays.
+
+use std::f32::consts::TAU;
+use std::io::Write;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Volume;
+use crate::game::{AudioEvent, GameState};
+
+/// Samples per second, 16-bit and mono.
+const RATE: u32 = 22_050;
+/// How many samples are mixed at a time.
+const CHUNK: usize = 256;
+/// How far ahead of what is being heard the player is fed.
+const LEAD: Duration = Duration::from_millis(60);
+/// The most effects heard at once; more cut the oldest short.
+const MAX_VOICES: usize = 8;
+/// The heartbeat's notes, in hertz: G, F, E and D, two octaves down.
+const HEARTBEAT_NOTES: [f32; 4] = [98.0, 87.3, 82.4, 73.4];
+/// The time between beats of the heartbeat with the whole formation
+/// there, and with its last alien.
+const SLOWEST_BEAT: Duration = Duration::from_millis(900);
+const FASTEST_BEAT: Duration = Duration::from_millis(160);
+
+/// The players tried, in order, each taking raw 16-bit little-endian mono
+/// samples at [`RATE`] on its standard input.
+const PLAYERS: [&[&str]; 4] = [
+    &["pw-cat", "--playback", "--format=s16", "--rate=22050", "--channels=1", "--latency=50ms", "-"],
+    &["pacat", "--format=s16le", "--rate=22050", "--channels=1", "--latency-msec=50"],
+    &["aplay", "-q", "-t", "raw", "-f", "S16_LE", "-r", "22050", "-c", "1", "--buffer-time=80000"],
+    &["play", "-q", "-t", "raw", "-r", "22050", "-e", "signed", "-b", "16", "-c", "1", "-"],
+];
+
+/// What the mixer is told to play.
+enum Message {
+    Effect(AudioEvent),
+    /// The time between beats of the heartbeat from now on, or `None` to
+    /// stop it.
+    Heartbeat(Option<Duration>),
+    Volume(Volume),
+}
+
+/// The sound of a session, started on the first thing played.
+pub struct Sound {
+    /// Where what to play goes to be mixed, once the mixer runs; `None` too
+    /// if it found no player.
+    mixer: Option<Sender<Message>>,
+    started: bool,
+    heartbeat: Option<Duration>,
+    volume: Volume,
+}
+
+impl Sound {
+    pub fn new(volume: Volume) -> Self {
+        Sound { mixer: None, started: false, heartbeat: None, volume }
+    }
+
+    /// Plays at `volume` from now on.
+    pub fn set_volume(&mut self, volume: Volume) {
+        if volume != self.volume {
+            self.volume = volume;
+            // A mixer yet to start gets it when it does
+            if self.started {
+                self.send(Message::Volume(volume));
+            }
+        }
+    }
+
+    /// Plays the effects `events` asks for.
+    pub fn play(&mut self, events: &[AudioEvent]) {
+        for &event in events {
+            self.send(Message::Effect(event));
+        }
+    }
+
+    /// Keeps the heartbeat going with `beat` between beats, or stops it.
+    pub fn set_heartbeat(&mut self, beat: Option<Duration>) {
+        if beat != self.heartbeat {
+            self.heartbeat = beat;
+            self.send(Message::Heartbeat(beat));
+        }
+    }
+
+    fn send(&mut self, message: Message) {
+        if !self.started {
+            self.started = true;
+            self.mixer = start(self.volume);
+        }
+        if self.mixer.as_ref().is_some_and(|mixer| mixer.send(message).is_err()) {
+            // The player went away; the rest of the session is silent
+            self.mixer = None;
+        }
+    }
+}
+
+/// The time between beats of the heartbeat for a run in `state`, quicker
+/// the fewer aliens are left; `None` between waves, in boss fights and
+/// once the run is over.
+pub fn heartbeat(state: &GameState) -> Option<Duration> {
+    let formation = &state.world.aliens;
+    if state.game_over || state.intermission_until.is_some() || state.world.boss().is_some() || formation.is_empty() {
+        return None;
+    }
+    let left = formation.len() as f32 / state.formation_size.max(1) as f32;
+    Some(FASTEST_BEAT + (SLOWEST_BEAT - FASTEST_BEAT).mul_f32(left.min(1.0)))
+}
+
+/// Starts the first player there is, and the mixer feeding it at `volume`.
+fn start(volume: Volume) -> Option<Sender<Message>> {
+    let mut child = PLAYERS.iter().find_map(|command| {
+        Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()
+    })?;
+    let stdin = child.stdin.take()?;
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || mix(child, stdin, receiver, volume));
+    Some(sender)
+}
+
+/// Mixes what `messages` brings into what `child` plays, until the session
+/// is over or the player goes away.
+fn mix(mut child: Child, mut out: ChildStdin, messages: Receiver<Message>, mut volume: Volume) {
+    let started = Instant::now();
+    let mut voices: Vec<Voice> = Vec::new();
+    let mut noise = Noise(0x2545_f491);
+    let mut heartbeat = Heartbeat { beat: None, until_next: 0, note: 0 };
+    let mut written = 0u64;
+    let mut bytes = Vec::with_capacity(CHUNK * 2);
+    loop {
+        // Wait until the player needs more, or something comes to play
+        let ahead = Duration::from_secs_f64(written as f64 / RATE as f64).saturating_sub(started.elapsed());
+        let mut message = match messages.recv_timeout(ahead.saturating_sub(LEAD)) {
+            Ok(message) => Some(message),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        while let Some(next) = message {
+            match next {
+                Message::Effect(event) => add_effect(&mut voices, event),
+                Message::Heartbeat(beat) => heartbeat.set(beat),
+                Message::Volume(new) => volume = new,
+            }
+            message = messages.try_recv().ok();
+        }
+        if started.elapsed() + LEAD < Duration::from_secs_f64(written as f64 / RATE as f64) {
+            continue;
+        }
+        bytes.clear();
+        let (effects, music) = (volume.gain(volume.effects), volume.gain(volume.music));
+        for _ in 0..CHUNK {
+            if let Some(pitch) = heartbeat.next() {
+                add_voice(&mut voices, Voice::new(Tone::Beat(pitch)));
+            }
+            let gain = |voice: &Voice| if let Tone::Beat(_) = voice.tone { music } else { effects };
+            let sample: f32 = voices.iter_mut().map(|voice| gain(voice) * voice.next(&mut noise)).sum();
+            bytes.extend_from_slice(&((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
+        }
+        voices.retain(|voice| voice.age < voice.length);
+        if out.write_all(&bytes).is_err() {
+            break;
+        }
+        written += CHUNK as u64;
+    }
+    drop(out);
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+fn add_effect(voices: &mut Vec<Voice>, event: AudioEvent) {
+    let warble = voices.iter_mut().find(|voice| event == AudioEvent::Ufo && voice.tone == Tone::Effect(event));
+    match warble {
+        // The warble goes on for as long as the ship is there
+        Some(voice) => voice.length = voice.age + Tone::Effect(event).length(),
+        None => add_voice(voices, Voice::new(Tone::Effect(event))),
+    }
+}
+
+fn add_voice(voices: &mut Vec<Voice>, voice: Voice) {
+    if voices.len() == MAX_VOICES {
+        voices.remove(0);
+    }
+    voices.push(voice);
+}
+
+/// The heartbeat, counting down samples to its next beat.
+struct Heartbeat {
+    /// Samples between beats, while it beats.
+    beat: Option<u32>,
+    until_next: u32,
+    /// The note the next beat plays, as an index into [`HEARTBEAT_NOTES`].
+    note: usize,
+}
+
+impl Heartbeat {
+    fn set(&mut self, beat: Option<Duration>) {
+        let beat = beat.map(|beat| (beat.as_secs_f32() * RATE as f32) as u32);
+        // Quickening brings the next beat closer; starting afresh plays it now
+        self.until_next = match (self.beat, beat) {
+            (Some(_), Some(beat)) => self.until_next.min(beat),
+            _ => 0,
+        };
+        if self.beat.is_none() {
+            self.note = 0;
+        }
+        self.beat = beat;
+    }
+
+    /// Counts a sample played, returning the pitch of the note to start
+    /// with it, if one is due.
+    fn next(&mut self) -> Option<f32> {
+        let beat = self.beat?;
+        if self.until_next > 0 {
+            self.until_next -= 1;
+            return None;
+        }
+        self.until_next = beat;
+        let pitch = HEARTBEAT_NOTES[self.note];
+        self.note = (self.note + 1) % HEARTBEAT_NOTES.len();
+        Some(pitch)
+    }
+}
+
+// --- Synthesis ---
+
+/// What a voice plays.
+#[derive(Clone, Copy, PartialEq)]
+enum Tone {
+    Effect(AudioEvent),
+    /// A beat of the heartbeat, at a pitch in hertz.
+    Beat(f32),
+}
+
+impl Tone {
+    /// How many samples it lasts.
+    fn length(self) -> u32 {
+        let seconds = match self {
+            Tone::Effect(AudioEvent::Shot) => 0.12,
+            Tone::Effect(AudioEvent::AlienExplosion) => 0.25,
+            Tone::Effect(AudioEvent::PlayerHit) => 0.7,
+            // Longer than a tick, so one tick's warble runs into the next one's
+            Tone::Effect(AudioEvent::Ufo) => 0.3,
+            Tone::Beat(_) => 0.12,
+        };
+        (seconds * RATE as f32) as u32
+    }
+}
+
+/// Something being played.
+struct Voice {
+    tone: Tone,
+    /// Samples played so far.
+    age: u32,
+    length: u32,
+    /// Where the tone is in its cycle, from 0 to 1.
+    phase: f32,
+    /// The noise, low-passed.
+    low: f32,
+}
+
+impl Voice {
+    fn new(tone: Tone) -> Voice {
+        Voice { tone, age: 0, length: tone.length(), phase: 0.0, low: 0.0 }
+    }
+
+    /// The next sample, from -1 to 1.
+    fn next(&mut self, noise: &mut Noise) -> f32 {
+        let t = self.age as f32 / RATE as f32;
+        let left = 1.0 - self.age as f32 / self.length as f32;
+        self.age += 1;
+        match self.tone {
+            // A square wave falling from high to low
+            Tone::Effect(AudioEvent::Shot) => {
+                let pitch = 1400.0 * (400.0f32 / 1400.0).powf(1.0 - left);
+                self.square(pitch) * 0.2 * left
+            }
+            Tone::Effect(AudioEvent::AlienExplosion) => self.rumble(noise, 0.35) * 0.5 * left * left,
+            // Deeper noise over a falling growl
+            Tone::Effect(AudioEvent::PlayerHit) => {
+                let growl = self.square(40.0 + 120.0 * left) * 0.3;
+                (self.rumble(noise, 0.08) + growl) * 0.6 * left
+            }
+            // A tone wavering up and down
+            Tone::Effect(AudioEvent::Ufo) => {
+                let pitch = 700.0 + 250.0 * (TAU * 10.0 * t).sin();
+                self.phase = (self.phase + pitch / RATE as f32).fract();
+                (TAU * self.phase).sin() * 0.12 * left.min(0.1) * 10.0
+            }
+            // A square wave, softened so its low note does not buzz
+            Tone::Beat(pitch) => {
+                let square = self.square(pitch);
+                self.low += (square - self.low) * 0.2;
+                self.low * 0.3 * left.sqrt()
+            }
+        }
+    }
+
+    fn square(&mut self, pitch: f32) -> f32 {
+        self.phase = (self.phase + pitch / RATE as f32).fract();
+        if self.phase < 0.5 { 1.0 } else { -1.0 }
+    }
+
+    /// Noise low-passed by `smoothing`, from 1 for none to near 0 for a lot.
+    fn rumble(&mut self, noise: &mut Noise, smoothing: f32) -> f32 {
+        self.low += (noise.next() - self.low) * smoothing;
+        self.low
+    }
+}
+
+/// White noise, from an xorshift generator; the game's own randomness is
+/// none of its business.
+struct Noise(u32);
+
+impl Noise {
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
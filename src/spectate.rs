@@ -0,0 +1,413 @@
+//! Spectating, where other terminals watch a game as it is played.
+//!
+//! A game started with `--broadcast` draws every frame on its own terminal
+//! as usual and sends it to every watcher attached to it as well, over TCP
+//! or a unix socket. Watchers draw the frames they are sent on their own
+//! terminal, centered, and send nothing back. The protocol is plain text,
+//! one line at a time, a frame being:
+//!
+//! - a `frame` line with the size of the player's screen and the name of
+//!   its theme, e.g. `frame 80 24 classic`;
+//! - a `text` line for every string drawn, with its column, its row, its
+//!   color and the text itself after a single space, e.g. `text 3 0 ui
+//!   Score: 120`;
+//! - a `pixels` line for every cell drawn as two stacked pixels, with the
+//!   colors of both or `-` for none, e.g. `pixels 10 5 alien -`;
+//! - an `end` line.
+//!
+//! Frames go out whole, so a watcher falling behind misses some and
+//! catches up with the next one it gets.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::input::{Input, Key};
+use crate::render::sprites::SpriteSet;
+use crate::render::theme::{Theme, CLASSIC};
+use crate::render::{Backend, Color, EntityKind, Layout, Renderer};
+
+/// The port `--broadcast` listens on unless told another.
+pub const DEFAULT_PORT: u16 = 7_778;
+/// Frames a watcher may be behind before the newer ones are dropped.
+const BACKLOG: usize = 2;
+/// How long sending a frame may take before the watcher counts as gone.
+const SEND_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where a game is broadcast: a TCP address, or the path of a unix socket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Place {
+    Address(String),
+    Socket(PathBuf),
+}
+
+impl Place {
+    /// Reads `text` as the path of a socket if it has a `/` in it, and as a
+    /// TCP address otherwise, on the default port unless it names one.
+    pub fn parse(text: &str) -> Place {
+        if text.contains('/') {
+            Place::Socket(PathBuf::from(text))
+        } else if text.contains(':') {
+            Place::Address(text.to_string())
+        } else {
+            Place::Address(format!("{}:{}", text, DEFAULT_PORT))
+        }
+    }
+}
+
+impl fmt::Display for Place {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Place::Address(address) => write!(f, "{}", address),
+            Place::Socket(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+// --- Broadcasting ---
+
+/// The watchers attached to a game, each sent its frames by a thread of
+/// its own so none can hold up the game.
+pub struct Spectators {
+    watchers: Arc<Mutex<Vec<SyncSender<Arc<str>>>>>,
+    /// The socket file to remove once the game is over.
+    socket: Option<PathBuf>,
+}
+
+impl Spectators {
+    /// Starts taking watchers at `place`.
+    pub fn listen(place: &Place) -> io::Result<Spectators> {
+        let watchers = Arc::new(Mutex::new(Vec::new()));
+        let attached = Arc::clone(&watchers);
+        match place {
+            Place::Address(address) => {
+                let listener = TcpListener::bind(address.as_str())?;
+                thread::spawn(move || {
+                    // Watchers whose connection fails before it is set up are skipped
+                    let streams = listener.incoming().flatten().filter(|stream| {
+                        stream.set_write_timeout(Some(SEND_TIMEOUT)).is_ok() && stream.set_nodelay(true).is_ok()
+                    });
+                    attach(streams, &attached)
+                });
+                Ok(Spectators { watchers, socket: None })
+            }
+            #[cfg(unix)]
+            Place::Socket(path) => {
+                let listener = UnixListener::bind(path)?;
+                thread::spawn(move || {
+                    let streams = listener.incoming().flatten();
+                    attach(streams.filter(|stream| stream.set_write_timeout(Some(SEND_TIMEOUT)).is_ok()), &attached)
+                });
+                Ok(Spectators { watchers, socket: Some(path.clone()) })
+            }
+            #[cfg(not(unix))]
+            Place::Socket(_) => Err(io::Error::new(io::ErrorKind::Unsupported, "unix sockets need a unix system")),
+        }
+    }
+
+    /// Sends `frame` to every watcher, dropping those that have gone.
+    fn send(&self, frame: &str) {
+        let frame: Arc<str> = frame.into();
+        let mut watchers = self.watchers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        watchers.retain(|watcher| !matches!(watcher.try_send(Arc::clone(&frame)), Err(TrySendError::Disconnected(_))));
+    }
+}
+
+impl Drop for Spectators {
+    fn drop(&mut self) {
+        if let Some(path) = &self.socket {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Attaches every watcher in `streams` as it connects, until the listener
+/// fails.
+fn attach<S: Write + Send + 'static>(streams: impl Iterator<Item = S>, watchers: &Mutex<Vec<SyncSender<Arc<str>>>>) {
+    for mut stream in streams {
+        let (sender, frames) = mpsc::sync_channel::<Arc<str>>(BACKLOG);
+        thread::spawn(move || {
+            for frame in frames {
+                if stream.write_all(frame.as_bytes()).is_err() {
+                    break;
+                }
+            }
+        });
+        watchers.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(sender);
+    }
+}
+
+/// A backend that plays as usual and sends every frame it presents to the
+/// game's [`Spectators`] as well.
+pub struct Broadcasting<'a> {
+    backend: &'a mut dyn Backend,
+    spectators: Spectators,
+    theme: &'static Theme,
+    /// The lines of the frame being drawn.
+    frame: String,
+}
+
+impl<'a> Broadcasting<'a> {
+    pub fn new(backend: &'a mut dyn Backend, spectators: Spectators) -> Self {
+        Broadcasting { backend, spectators, theme: &CLASSIC, frame: String::new() }
+    }
+}
+
+impl Renderer for Broadcasting<'_> {
+    fn clear(&mut self) {
+        self.backend.clear();
+        self.frame.clear();
+    }
+
+    fn draw_str(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        self.backend.draw_str(x, y, text, color);
+        self.frame.push_str(&format!("text {} {} {} {}\n", x, y, color_code(color), text));
+    }
+
+    fn present(&mut self) {
+        self.backend.present();
+        let (width, height) = self.backend.size();
+        let header = format!("frame {} {} {}\n", width, height, self.theme.name);
+        self.spectators.send(&format!("{}{}end\n", header, self.frame));
+    }
+
+    fn size(&self) -> (i32, i32) {
+        self.backend.size()
+    }
+
+    fn set_layout(&mut self, layout: &Layout) {
+        self.backend.set_layout(layout);
+    }
+
+    fn set_theme(&mut self, theme: &'static Theme) {
+        self.backend.set_theme(theme);
+        self.theme = theme;
+    }
+
+    fn rich_colors(&self) -> bool {
+        self.backend.rich_colors()
+    }
+
+    fn sprites(&self) -> &'static SpriteSet {
+        self.backend.sprites()
+    }
+
+    fn set_sprites(&mut self, sprites: &'static SpriteSet) {
+        self.backend.set_sprites(sprites);
+    }
+
+    fn bell(&mut self, flash: bool) {
+        self.backend.bell(flash);
+    }
+
+    // Watchers get the sprites as text, whatever the player's backend draws
+    fn draw_entity(&mut self, x: i32, y: i32, entity: EntityKind, rows: &[&str], color: Color) {
+        self.backend.draw_entity(x, y, entity, rows, color);
+        for (i, row) in rows.iter().enumerate() {
+            self.frame.push_str(&format!("text {} {} {} {}\n", x, y + i as i32, color_code(color), row));
+        }
+    }
+
+    fn draw_pixels(&mut self, x: i32, y: i32, top: Option<Color>, bottom: Option<Color>) {
+        self.backend.draw_pixels(x, y, top, bottom);
+        let code = |color: Option<Color>| color.map_or("-".to_string(), color_code);
+        self.frame.push_str(&format!("pixels {} {} {} {}\n", x, y, code(top), code(bottom)));
+    }
+}
+
+impl Input for Broadcasting<'_> {
+    fn poll_key(&mut self, timeout: Duration) -> Option<Key> {
+        self.backend.poll_key(timeout)
+    }
+
+    fn capture_mouse(&mut self) {
+        self.backend.capture_mouse();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.backend.is_closed()
+    }
+}
+
+// --- Watching ---
+
+/// Something drawn in a frame.
+enum Draw {
+    Text { x: i32, y: i32, color: Color, text: String },
+    Pixels { x: i32, y: i32, top: Option<Color>, bottom: Option<Color> },
+}
+
+/// A frame of the game being watched.
+pub struct Frame {
+    /// The size of the player's screen.
+    size: (i32, i32),
+    pub theme: &'static Theme,
+    draws: Vec<Draw>,
+}
+
+impl Frame {
+    /// Shows the frame on `renderer`, in the middle of its screen if that is
+    /// bigger than the player's.
+    pub fn show(&self, renderer: &mut dyn Renderer) {
+        let (width, height) = renderer.size();
+        let dx = (width - self.size.0).max(0) / 2;
+        let dy = (height - self.size.1).max(0) / 2;
+        renderer.clear();
+        for draw in &self.draws {
+            match draw {
+                Draw::Text { x, y, color, text } => renderer.draw_str(x + dx, y + dy, text, *color),
+                Draw::Pixels { x, y, top, bottom } => renderer.draw_pixels(x + dx, y + dy, *top, *bottom),
+            }
+        }
+        renderer.present();
+    }
+}
+
+/// The frames of a game being watched.
+pub struct Feed {
+    frames: Receiver<Frame>,
+    closed: bool,
+}
+
+impl Feed {
+    /// Attaches to the game broadcast at `place`.
+    pub fn connect(place: &Place) -> Result<Feed, String> {
+        let reader: Box<dyn BufRead + Send> = match place {
+            Place::Address(address) => {
+                let stream = TcpStream::connect(address.as_str()).map_err(|err| format!("{}: {}", address, err))?;
+                Box::new(BufReader::new(stream))
+            }
+            #[cfg(unix)]
+            Place::Socket(path) => {
+                let stream = UnixStream::connect(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+                Box::new(BufReader::new(stream))
+            }
+            #[cfg(not(unix))]
+            Place::Socket(path) => return Err(format!("{}: unix sockets need a unix system", path.display())),
+        };
+        let (sender, frames) = mpsc::channel();
+        thread::spawn(move || {
+            let mut frame = None;
+            for line in reader.lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                // A frame with a line this version does not know is dropped whole
+                if line == "end" {
+                    if let Some(done) = frame.take() {
+                        if sender.send(done).is_err() {
+                            break;
+                        }
+                    }
+                } else if line.starts_with("frame ") {
+                    frame = parse_header(&line);
+                } else if let Some(current) = &mut frame {
+                    match parse_draw(&line) {
+                        Some(draw) => current.draws.push(draw),
+                        None => frame = None,
+                    }
+                }
+            }
+        });
+        Ok(Feed { frames, closed: false })
+    }
+
+    /// The newest frame that arrived since the last look, if any.
+    pub fn latest(&mut self) -> Option<Frame> {
+        let mut latest = None;
+        loop {
+            match self.frames.try_recv() {
+                Ok(frame) => latest = Some(frame),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+        latest
+    }
+
+    /// Whether the game has ended, or stopped being broadcast.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
+
+/// Reads a `frame` line. Themes this version does not know are drawn in
+/// the classic one.
+fn parse_header(line: &str) -> Option<Frame> {
+    let mut fields = line.split(' ').skip(1);
+    let width = fields.next()?.parse().ok()?;
+    let height = fields.next()?.parse().ok()?;
+    let theme = fields.next().and_then(Theme::by_name).unwrap_or(&CLASSIC);
+    Some(Frame { size: (width, height), theme, draws: Vec::new() })
+}
+
+fn parse_draw(line: &str) -> Option<Draw> {
+    let (kind, rest) = line.split_once(' ')?;
+    match kind {
+        "text" => {
+            let mut fields = rest.splitn(4, ' ');
+            let x = fields.next()?.parse().ok()?;
+            let y = fields.next()?.parse().ok()?;
+            let color = color_from_code(fields.next()?)?;
+            Some(Draw::Text { x, y, color, text: fields.next()?.to_string() })
+        }
+        "pixels" => {
+            let mut fields = rest.split(' ');
+            let x = fields.next()?.parse().ok()?;
+            let y = fields.next()?.parse().ok()?;
+            let mut color = || match fields.next()? {
+                "-" => Some(None),
+                code => color_from_code(code).map(Some),
+            };
+            Some(Draw::Pixels { x, y, top: color()?, bottom: color()? })
+        }
+        _ => None,
+    }
+}
+
+// --- Colors ---
+
+fn color_code(color: Color) -> String {
+    let code = match color {
+        Color::Ui => "ui",
+        Color::Player => "player",
+        Color::Shot => "shot",
+        Color::Alien => "alien",
+        Color::GameOver => "game_over",
+        Color::AlienShot => "alien_shot",
+        Color::Border => "border",
+        Color::Highlight => "highlight",
+        Color::Ufo => "ufo",
+        Color::Explosion => "explosion",
+        Color::AlienRow(row) => return format!("row{}", row),
+        Color::Star => "star",
+    };
+    code.to_string()
+}
+
+fn color_from_code(code: &str) -> Option<Color> {
+    Some(match code {
+        "ui" => Color::Ui,
+        "player" => Color::Player,
+        "shot" => Color::Shot,
+        "alien" => Color::Alien,
+        "game_over" => Color::GameOver,
+        "alien_shot" => Color::AlienShot,
+        "border" => Color::Border,
+        "highlight" => Color::Highlight,
+        "ufo" => Color::Ufo,
+        "explosion" => Color::Explosion,
+        "star" => Color::Star,
+        _ => Color::AlienRow(code.strip_prefix("row")?.parse().ok()?),
+    })
+}
@@ -0,0 +1,94 @@
+//! Lifetime statistics over every run played, persisted in the data
+//! directory.
+//!
+//! The file holds one `name value` line per counter; counters it lacks
+//! start from zero, so a damaged file only loses what it garbled.
+
+use std::fs;
+use std::time::Duration;
+
+use crate::game::GameState;
+use crate::storage;
+
+const FILE_NAME: &str = "stats";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub games_played: u32,
+    pub aliens_destroyed: u32,
+    pub shots_fired: u32,
+    /// Shots that flew off the field without hitting anything.
+    pub shots_missed: u32,
+    pub best_wave: u32,
+    /// Game time played, pauses not included.
+    pub play_time: Duration,
+}
+
+impl Stats {
+    /// Reads the stored stats; a missing file means nothing played yet.
+    pub fn load() -> Self {
+        storage::data_file(FILE_NAME)
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| Stats::from_text(&text))
+            .unwrap_or_default()
+    }
+
+    fn from_text(text: &str) -> Self {
+        let mut stats = Stats::default();
+        for line in text.lines() {
+            let Some((name, value)) = line.split_once(' ') else {
+                continue;
+            };
+            let Ok(value) = value.trim().parse::<u64>() else {
+                continue;
+            };
+            let count = value.min(u32::MAX as u64) as u32;
+            match name {
+                "games_played" => stats.games_played = count,
+                "aliens_destroyed" => stats.aliens_destroyed = count,
+                "shots_fired" => stats.shots_fired = count,
+                "shots_missed" => stats.shots_missed = count,
+                "best_wave" => stats.best_wave = count,
+                "play_time_ms" => stats.play_time = Duration::from_millis(value),
+                _ => {}
+            }
+        }
+        stats
+    }
+
+    fn to_text(self) -> String {
+        format!(
+            "games_played {}\naliens_destroyed {}\nshots_fired {}\nshots_missed {}\nbest_wave {}\nplay_time_ms {}\n",
+            self.games_played,
+            self.aliens_destroyed,
+            self.shots_fired,
+            self.shots_missed,
+            self.best_wave,
+            self.play_time.as_millis(),
+        )
+    }
+
+    /// Adds a finished run to the stored stats.
+    pub fn record(state: &GameState) {
+        let Some(path) = storage::data_file(FILE_NAME) else {
+            return;
+        };
+        let mut stats = Stats::load();
+        let run = state.run_stats;
+        stats.games_played = stats.games_played.saturating_add(1);
+        stats.aliens_destroyed = stats.aliens_destroyed.saturating_add(run.aliens_destroyed);
+        stats.shots_fired = stats.shots_fired.saturating_add(run.shots_fired);
+        stats.shots_missed = stats.shots_missed.saturating_add(run.shots_missed);
+        stats.best_wave = stats.best_wave.max(state.wave);
+        stats.play_time += state.clock;
+        // Losing the file only costs the stats, never the running game
+        let _ = storage::write_atomic(&path, &stats.to_text());
+    }
+
+    /// Percentage of shots that hit something, if any were fired.
+    pub fn accuracy(&self) -> Option<u32> {
+        let fired = self.shots_fired as u64;
+        let hits = fired.saturating_sub(self.shots_missed as u64);
+        (fired > 0).then(|| (hits * 100 / fired) as u32)
+    }
+}
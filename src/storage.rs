@@ -0,0 +1,94 @@
+//! Where the game keeps files between runs.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::challenge;
+
+const APP_DIR: &str = "terminal_space_invader";
+
+/// Held by every [`update`] across its read and write, so sessions served
+/// side by side never write over each other's records.
+static UPDATING: Mutex<()> = Mutex::new(());
+
+/// Numbers the temporary files, so no two writes share one.
+static WRITES: AtomicU64 = AtomicU64::new(0);
+
+fn env_path(name: &str) -> Option<PathBuf> {
+    env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from)
+}
+
+/// The per-user data directory, following the XDG base directory spec
+/// (`$XDG_DATA_HOME`, else `~/.local/share`) and `%APPDATA%` on Windows.
+pub fn data_dir() -> Option<PathBuf> {
+    let base = env_path("XDG_DATA_HOME")
+        .or_else(|| env_path("HOME").map(|home| home.join(".local").join("share")))
+        .or_else(|| env_path("APPDATA"))?;
+    Some(base.join(APP_DIR))
+}
+
+/// The path of `name` inside the data directory.
+pub fn data_file(name: &str) -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join(name))
+}
+
+/// The per-user config directory: `$XDG_CONFIG_HOME`, else `~/.config`, and
+/// `%APPDATA%` on Windows.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = env_path("XDG_CONFIG_HOME")
+        .or_else(|| env_path("HOME").map(|home| home.join(".config")))
+        .or_else(|| env_path("APPDATA"))?;
+    Some(base.join(APP_DIR))
+}
+
+/// The path of `name` inside the config directory.
+pub fn config_file(name: &str) -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(name))
+}
+
+/// A name for a file saved now to the current directory, such as a
+/// screenshot: `<prefix>-YYYY-MM-DD-HHMMSS.<extension>`, in UTC.
+pub fn timestamped(prefix: &str, extension: &str) -> PathBuf {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let time = seconds % 86_400;
+    let date = challenge::date(seconds / 86_400);
+    PathBuf::from(format!("{}-{}-{:02}{:02}{:02}.{}", prefix, date, time / 3600, time / 60 % 60, time % 60, extension))
+}
+
+/// Replaces the contents of `path`, creating parent directories as needed.
+///
+/// The data goes to a temporary sibling first and is renamed into place, so a
+/// crash mid-write never leaves a truncated file behind. The sibling's name is
+/// the writer's own, so two writes at once cannot mix their contents.
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}-{}.tmp", process::id(), WRITES.fetch_add(1, Ordering::Relaxed)));
+    let tmp = path.with_file_name(name);
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+/// Rewrites `path` from what it holds now: `change` gets the current
+/// contents (empty for a missing file) and returns the new ones, or `None`
+/// to leave it be. Updates in this process take turns, so each sees the
+/// last one's result.
+pub fn update(path: &Path, change: impl FnOnce(&str) -> Option<String>) -> io::Result<()> {
+    // A writer that panicked left the file whole, renames being atomic
+    let _turn = UPDATING.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let old = fs::read_to_string(path).unwrap_or_default();
+    match change(&old) {
+        Some(new) => write_atomic(path, &new),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,89 @@
+//! Tournaments, which pit bots against each other over many runs.
+//!
+//! Every bot plays the same games, one for each seed, headless: the game
+//! ticks as fast as it can with nothing drawn and no keys read. Nothing
+//! is recorded, and the high scores and stats are left alone.
+
+use crate::bot::Maker;
+use crate::game::{GameState, Settings};
+use crate::scene::Run;
+
+/// The most ticks a headless run plays before it is called off, so a bot
+/// that can neither win nor lose cannot hold up a tournament forever.
+pub const MAX_TICKS: u64 = 100_000;
+
+/// Plays a run of `settings` through with a bot of `maker`'s, headless,
+/// handing back how it ended.
+pub fn play_headless(maker: Maker, settings: &Settings) -> GameState {
+    let mut run = Run::new(settings);
+    run.hand_to(maker());
+    while !run.is_over() && run.state.ticks < MAX_TICKS {
+        run.tick();
+    }
+    run.state
+}
+
+/// How a bot did over a tournament's games.
+pub struct Standing {
+    pub name: &'static str,
+    pub scores: Vec<u32>,
+    /// The wave each game ended on.
+    pub waves: Vec<u32>,
+    pub shots_fired: u32,
+    pub aliens_destroyed: u32,
+}
+
+impl Standing {
+    fn mean(values: &[u32]) -> f64 {
+        values.iter().map(|&value| value as f64).sum::<f64>() / values.len().max(1) as f64
+    }
+
+    /// Percentage of shots that destroyed an alien.
+    pub fn accuracy(&self) -> f64 {
+        self.aliens_destroyed as f64 * 100.0 / self.shots_fired.max(1) as f64
+    }
+}
+
+/// Has each of `bots` play `games` runs of `settings`, the same ones for
+/// all: game `i` is played with the seed of `settings` plus `i`, or `i`
+/// from 1 if it has none.
+pub fn run(bots: &[(&'static str, Maker)], games: u32, settings: &Settings) -> Vec<Standing> {
+    let first = settings.seed.unwrap_or(1);
+    bots.iter()
+        .map(|&(name, maker)| {
+            let mut standing = Standing { name, scores: Vec::new(), waves: Vec::new(), shots_fired: 0, aliens_destroyed: 0 };
+            for game in 0..games as u64 {
+                let state = play_headless(maker, &Settings { seed: Some(first.wrapping_add(game)), ..settings.clone() });
+                standing.scores.push(state.score);
+                standing.waves.push(state.wave);
+                standing.shots_fired += state.run_stats.shots_fired;
+                standing.aliens_destroyed += state.run_stats.aliens_destroyed;
+            }
+            standing
+        })
+        .collect()
+}
+
+/// Lays `standings` out as a table, the best mean score first.
+pub fn table(standings: &[Standing]) -> String {
+    let mut rows: Vec<&Standing> = standings.iter().collect();
+    rows.sort_by(|a, b| Standing::mean(&b.scores).total_cmp(&Standing::mean(&a.scores)));
+    let width = rows.iter().map(|standing| standing.name.len()).max().unwrap_or(0).max("bot".len());
+    let mut table = format!(
+        "{:<width$}  {:>5}  {:>10}  {:>10}  {:>9}  {:>9}  {:>8}\n",
+        "bot", "games", "mean score", "best score", "mean wave", "best wave", "accuracy"
+    );
+    for standing in rows {
+        table.push_str(&format!(
+            "{:<width$}  {:>5}  {:>10.1}  {:>10}  {:>9.1}  {:>9}  {:>7.1}%\n",
+            standing.name,
+            standing.scores.len(),
+            Standing::mean(&standing.scores),
+            standing.scores.iter().max().unwrap_or(&0),
+            Standing::mean(&standing.waves),
+            standing.waves.iter().max().unwrap_or(&0),
+            standing.accuracy(),
+        ));
+    }
+    table
+}
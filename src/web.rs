@@ -0,0 +1,319 @@
+//! The game in a browser page, built for `wasm32-unknown-unknown` with the
+//! `web` feature.
+//!
+//! The page owns a terminal emulator, [xterm.js] in `web/`, and drives the
+//! game through the `invaders_*` functions exported here: it creates a
+//! game the size of its terminal, hands over key presses as they come, and
+//! calls [`invaders_frame`] on every animation frame, writing out what that
+//! leaves in the output buffer. Nothing blocks, so the game runs as an
+//! [`app::Session`] stepped from the page rather than in a loop of its own.
+//!
+//! The page brings what the browser has and a bare WebAssembly module does
+//! not, as imports from the `env` module: the date, and random bytes to
+//! seed runs with. With no file system there is no config, no high scores
+//! kept and no mods; the game plays as it comes.
+//!
+//! [xterm.js]: https://xtermjs.org
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use crate::app::{self, Session};
+use crate::game::Settings;
+use crate::input::{Input, Key};
+use crate::render::buffer::FrameBuffer;
+use crate::render::sprites::{self, SpriteSet};
+use crate::render::theme::{Theme, CLASSIC};
+use crate::render::{Color, Renderer};
+use crate::scene::title::TitleScreen;
+use crate::scene::{Context, Scene};
+
+// --- Imports From the Page ---
+
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+    /// Milliseconds since 1970-01-01 (UTC), as `Date.now()` has it.
+    fn invaders_date_now() -> f64;
+    /// Fills the `len` bytes at `ptr` with `crypto.getRandomValues`.
+    fn invaders_random(ptr: *mut u8, len: usize);
+}
+
+/// The time since 1970-01-01 (UTC), by the page's clock.
+#[cfg(target_arch = "wasm32")]
+pub fn since_epoch() -> Duration {
+    Duration::from_millis(unsafe { invaders_date_now() } as u64)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn fill_random(bytes: &mut [u8]) -> Result<(), getrandom::Error> {
+    unsafe { invaders_random(bytes.as_mut_ptr(), bytes.len()) };
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+getrandom::register_custom_getrandom!(fill_random);
+
+// --- Renderer ---
+
+/// Renders into the escape codes of an xterm, for the page to write to its
+/// terminal, and takes the keys the page hands over.
+///
+/// Frames are composed in a [`FrameBuffer`] and only the cells that changed
+/// go out, as with the crossterm backend. xterm.js shows any RGB color and
+/// any character, so colors come out as the theme has them, shaded
+/// starfield and all, and entities are drawn in Unicode.
+pub struct WebRenderer {
+    frame: FrameBuffer,
+    /// What the page's terminal shows, as of the last frame written.
+    shown: FrameBuffer,
+    /// Escape codes written since the page last took them.
+    output: String,
+    keys: VecDeque<Key>,
+    theme: &'static Theme,
+    sprites: &'static SpriteSet,
+}
+
+impl WebRenderer {
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut renderer = WebRenderer {
+            frame: FrameBuffer::new(width, height),
+            shown: FrameBuffer::new(width, height),
+            // Hide the cursor and clear the screen
+            output: String::from("\x1b[?25l\x1b[2J"),
+            keys: VecDeque::new(),
+            theme: &CLASSIC,
+            sprites: &sprites::UNICODE,
+        };
+        renderer.shown.invalidate(0, 0, width as i32, height as i32);
+        renderer
+    }
+
+    /// Follows the page's terminal to a new size, repainting it whole.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.frame.resize(width, height);
+        self.shown.resize(width, height);
+        self.shown.invalidate(0, 0, width as i32, height as i32);
+        self.output.push_str("\x1b[0m\x1b[2J");
+    }
+
+    /// Queues a key press for the game to read.
+    pub fn press(&mut self, key: Key) {
+        self.keys.push_back(key);
+    }
+
+    fn write_frame(&mut self) {
+        let out = &mut self.output;
+        let mut foreground = None;
+        let mut background = None;
+        out.push_str("\x1b[0m");
+        for y in 0..self.frame.height() {
+            // The column the cursor stands at, after the last cell written
+            let mut column = None;
+            for (x, (cell, shown)) in self.frame.row(y).iter().zip(self.shown.row(y)).enumerate() {
+                if cell == shown {
+                    continue;
+                }
+                if column != Some(x) {
+                    let _ = write!(out, "\x1b[{};{}H", y + 1, x + 1);
+                }
+                column = Some(x + 1);
+                if cell.color != foreground || cell.background != background {
+                    out.push_str("\x1b[0m");
+                    if let Some(color) = cell.color {
+                        push_color(out, 38, self.theme, color);
+                    }
+                    if let Some(color) = cell.background {
+                        push_color(out, 48, self.theme, color);
+                    }
+                    foreground = cell.color;
+                    background = cell.background;
+                }
+                out.push(cell.ch);
+            }
+        }
+        out.push_str("\x1b[0m");
+        self.shown.copy_from(&self.frame);
+    }
+}
+
+/// Writes the SGR code setting the foreground (`layer` 38) or background
+/// (`layer` 48) to the RGB `theme` has for `color`.
+fn push_color(out: &mut String, layer: u8, theme: &Theme, color: Color) {
+    let rgb = theme.rgb(color);
+    let _ = write!(out, "\x1b[{};2;{};{};{}m", layer, rgb.0, rgb.1, rgb.2);
+}
+
+impl Renderer for WebRenderer {
+    fn clear(&mut self) {
+        self.frame.clear();
+    }
+
+    fn draw_str(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        self.frame.draw_str(x, y, text, color);
+    }
+
+    fn draw_pixels(&mut self, x: i32, y: i32, top: Option<Color>, bottom: Option<Color>) {
+        self.frame.draw_pixels(x, y, top, bottom);
+    }
+
+    fn present(&mut self) {
+        self.write_frame();
+    }
+
+    fn size(&self) -> (i32, i32) {
+        self.frame.size()
+    }
+
+    fn set_theme(&mut self, theme: &'static Theme) {
+        self.theme = theme;
+        // The same logical colors come out differently now
+        let (width, height) = self.frame.size();
+        self.shown.invalidate(0, 0, width, height);
+    }
+
+    fn rich_colors(&self) -> bool {
+        true
+    }
+
+    fn sprites(&self) -> &'static SpriteSet {
+        self.sprites
+    }
+
+    fn set_sprites(&mut self, sprites: &'static SpriteSet) {
+        self.sprites = sprites;
+    }
+
+    // For the page to hear of through xterm.js's onBell
+    fn bell(&mut self, _flash: bool) {
+        self.output.push('\x07');
+    }
+}
+
+impl Input for WebRenderer {
+    /// Never waits: keys only arrive between calls from the page.
+    fn poll_key(&mut self, _timeout: Duration) -> Option<Key> {
+        self.keys.pop_front()
+    }
+}
+
+// --- Exports ---
+
+/// A game running in the page, from its title screen until the player quits.
+pub struct WebGame {
+    renderer: WebRenderer,
+    session: Option<Session>,
+    /// The page's time, in milliseconds, as of the last frame.
+    now: f64,
+}
+
+/// The names of the keys the page hands over with [`invaders_key`], by
+/// number. F1 to F12 come after them, as 101 to 112.
+const NAMED_KEYS: [Key; 7] = [Key::Left, Key::Right, Key::Up, Key::Down, Key::Enter, Key::Escape, Key::Backspace];
+const FUNCTION_KEYS: u32 = 100;
+
+/// Starts a game whose playfield fills a terminal of `width` x `height`
+/// cells, for the page to hand to the other functions.
+#[no_mangle]
+pub extern "C" fn invaders_new(width: u32, height: u32) -> *mut WebGame {
+    let mut renderer = WebRenderer::new(width as usize, height as usize);
+    let settings = app::fit(&renderer, Settings::default(), None, None);
+    let ctx = Context::load(settings);
+    let title = TitleScreen::new(&ctx);
+    let session = Session::new(&mut renderer, ctx, Scene::TitleScreen(title), Duration::ZERO);
+    Box::into_raw(Box::new(WebGame { renderer, session: Some(session), now: 0.0 }))
+}
+
+/// Sets the game's terminal to `width` x `height` cells.
+///
+/// # Safety
+/// `game` must come from [`invaders_new`].
+#[no_mangle]
+pub unsafe extern "C" fn invaders_resize(game: *mut WebGame, width: u32, height: u32) {
+    let game = &mut *game;
+    game.renderer.resize(width as usize, height as usize);
+}
+
+/// Hands over the character key `code`, a Unicode scalar value.
+///
+/// # Safety
+/// `game` must come from [`invaders_new`].
+#[no_mangle]
+pub unsafe extern "C" fn invaders_char(game: *mut WebGame, code: u32) {
+    let game = &mut *game;
+    if let Some(ch) = char::from_u32(code) {
+        game.renderer.press(Key::Char(ch));
+    }
+}
+
+/// Hands over the key numbered `code`: a [`NAMED_KEYS`] index, or a
+/// function key past [`FUNCTION_KEYS`].
+///
+/// # Safety
+/// `game` must come from [`invaders_new`].
+#[no_mangle]
+pub unsafe extern "C" fn invaders_key(game: *mut WebGame, code: u32) {
+    let key = match code.checked_sub(FUNCTION_KEYS) {
+        Some(number @ 1..=12) => Some(Key::Function(number as u8)),
+        _ => NAMED_KEYS.get(code as usize).copied(),
+    };
+    let game = &mut *game;
+    if let Some(key) = key {
+        game.renderer.press(key);
+    }
+}
+
+/// Runs the game up to `now`, the page's time in milliseconds, leaving
+/// what to write to the terminal in the output buffer. Returns whether the
+/// game goes on; once it does not, `game` may only be freed.
+///
+/// # Safety
+/// `game` must come from [`invaders_new`].
+#[no_mangle]
+pub unsafe extern "C" fn invaders_frame(game: *mut WebGame, now: f64) -> bool {
+    let game = &mut *game;
+    game.now = game.now.max(now);
+    let clock = Duration::from_secs_f64(game.now / 1000.0);
+    game.renderer.output.clear();
+    game.session = match game.session.take() {
+        Some(session) => session.step(&mut game.renderer, VecDeque::new(), &|| clock),
+        None => None,
+    };
+    if game.session.is_none() {
+        // Give the terminal its cursor back
+        game.renderer.output.push_str("\x1b[0m\x1b[?25h");
+    }
+    game.session.is_some()
+}
+
+/// Where the output of the last frame starts in memory, UTF-8 encoded.
+///
+/// # Safety
+/// `game` must come from [`invaders_new`].
+#[no_mangle]
+pub unsafe extern "C" fn invaders_output(game: *const WebGame) -> *const u8 {
+    let game = &*game;
+    game.renderer.output.as_ptr()
+}
+
+/// How many bytes of output the last frame left.
+///
+/// # Safety
+/// `game` must come from [`invaders_new`].
+#[no_mangle]
+pub unsafe extern "C" fn invaders_output_len(game: *const WebGame) -> usize {
+    let game = &*game;
+    game.renderer.output.len()
+}
+
+/// Ends the game and frees it.
+///
+/// # Safety
+/// `game` must come from [`invaders_new`], and is not to be used again.
+#[no_mangle]
+pub unsafe extern "C" fn invaders_free(game: *mut WebGame) {
+    let game = Box::from_raw(game);
+    if let Some(session) = game.session {
+        session.end();
+    }
+}
@@ -0,0 +1,112 @@
+//! Golden frames: key scenes are drawn onto a snapshot renderer and checked
+//! against the frames in `tests/golden`, row for row.
+//!
+//! After a change to how the game looks, run the tests with
+//! `UPDATE_GOLDEN=1` to rewrite the golden frames, and look over the diff.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Once;
+
+use term_invaders::game::{self, GameState, Position, Settings, Velocity, SHOT_SPEED};
+use term_invaders::render::snapshot::Snapshot;
+use term_invaders::scene::{Context, Run, Scene};
+
+static ISOLATE: Once = Once::new();
+
+/// The settings every scene is played with: a small field and a fixed seed.
+fn settings() -> Settings {
+    Settings { seed: Some(7), width: 40, height: 16, ..Settings::default() }
+}
+
+/// A session with nothing saved, whatever the machine running the tests has
+/// in its data and config directories.
+fn context() -> Context {
+    ISOLATE.call_once(|| {
+        let home = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden-home");
+        env::set_var("XDG_DATA_HOME", home.join("data"));
+        env::set_var("XDG_CONFIG_HOME", home.join("config"));
+    });
+    Context::load(settings())
+}
+
+/// A run just past the countdown to its first wave, with the aliens in.
+fn spawned() -> Run {
+    let mut run = Run::new(&settings());
+    while run.state.intermission_until.is_some() {
+        game::update_state(&mut run.state);
+    }
+    run
+}
+
+/// Draws `scene` on a screen just big enough for it and checks the frame
+/// against the golden one called `name`.
+fn check(name: &str, scene: &Scene) {
+    // Any screen at least as big as the layout, which the snapshot then is
+    let layout = scene.layout(&context(), (200, 100));
+    check_on(name, scene, (layout.width(), layout.height()));
+}
+
+/// Draws `scene` on a screen of `(width, height)` cells and checks the
+/// frame against the golden one called `name`.
+fn check_on(name: &str, scene: &Scene, (width, height): (i32, i32)) {
+    let ctx = context();
+    let mut snapshot = Snapshot::new(width as usize, height as usize);
+    scene.render(&mut snapshot, &ctx);
+    let frame = snapshot.last().expect("the scene presents a frame").join("\n") + "\n";
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.txt", name));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &frame).unwrap();
+        return;
+    }
+    let golden = fs::read_to_string(&path).unwrap_or_else(|err| panic!("cannot read {}: {}", path.display(), err));
+    assert!(frame == golden, "{} no longer matches its golden frame:\n{}\nexpected:\n{}", name, frame, golden);
+}
+
+#[test]
+fn wave_spawn() {
+    check("wave_spawn", &Scene::Playing(spawned()));
+}
+
+#[test]
+fn collision_tick() {
+    // A shot just under the bottom-left alien flies into it this tick
+    let mut run = spawned();
+    let alien = run.state.world.aliens.iter().map(|alien| alien.cell()).max_by_key(|&(x, y)| (y, u16::MAX - x));
+    let (x, y) = alien.unwrap();
+    let y = y + run.state.settings.alien_size.height;
+    let velocity = Velocity { dx: 0.0, dy: -SHOT_SPEED };
+    run.state.world.spawn_shot(Position::at(x, y), velocity, 0);
+    let aliens = run.state.world.aliens.len();
+    game::update_state(&mut run.state);
+    assert_eq!(run.state.world.aliens.len(), aliens - 1);
+    check("collision_tick", &Scene::Playing(run));
+}
+
+#[test]
+fn game_over() {
+    let mut state = GameState::new(&settings());
+    state.score = 1230;
+    let mut ship = state.world.ship_mut(0);
+    ship.score = 1230;
+    ship.health.hp = 0;
+    state.game_over = true;
+    // Builds that save highlights say how on the banner
+    let name = if cfg!(feature = "gif") { "game_over_gif" } else { "game_over" };
+    check(name, &Scene::GameOver(state, None));
+}
+
+#[test]
+fn scrolling_camera() {
+    // A playfield three screens wide, with the ship moved off to the right
+    let mut run = Run::new(&Settings { width: 120, ..settings() });
+    while run.state.intermission_until.is_some() {
+        game::update_state(&mut run.state);
+    }
+    let y = run.state.world.ship(0).y();
+    run.state.world.ship_mut(0).place(90, y);
+    check_on("scrolling_camera", &Scene::Playing(run), (42, 21));
+}
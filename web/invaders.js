@@ -0,0 +1,74 @@
+// Runs term_invaders.wasm, built with `make web`, in an xterm.js terminal.
+//
+// The module draws into a buffer of escape codes on every animation frame,
+// which are written to the terminal as they are; see src/web.rs for the
+// functions it exports and the ones it imports from here.
+
+// The keys invaders_key takes, by number; F1 to F12 are 101 to 112
+const NAMED_KEYS = ["ArrowLeft", "ArrowRight", "ArrowUp", "ArrowDown", "Enter", "Escape", "Backspace"];
+const FUNCTION_KEYS = 100;
+
+export async function start(element, url) {
+  const terminal = new Terminal({ cursorBlink: false, fontSize: 16 });
+  terminal.open(element);
+  fit(terminal, element);
+
+  let memory;
+  const env = {
+    invaders_date_now: () => Date.now(),
+    invaders_random: (ptr, len) => crypto.getRandomValues(new Uint8Array(memory.buffer, ptr, len)),
+  };
+  const { instance } = await WebAssembly.instantiateStreaming(fetch(url), { env });
+  const game = instance.exports;
+  memory = game.memory;
+  const handle = game.invaders_new(terminal.cols, terminal.rows);
+  const decoder = new TextDecoder();
+
+  // Keys go to the game rather than to the terminal's own input handling
+  terminal.attachCustomKeyEventHandler((event) => {
+    if (event.type !== "keydown") {
+      return false;
+    }
+    const named = NAMED_KEYS.indexOf(event.key);
+    const function_key = /^F([1-9]|1[0-2])$/.exec(event.key);
+    if (named >= 0) {
+      game.invaders_key(handle, named);
+    } else if (function_key) {
+      game.invaders_key(handle, FUNCTION_KEYS + Number(function_key[1]));
+    } else if ([...event.key].length === 1 && !event.ctrlKey && !event.metaKey) {
+      game.invaders_char(handle, event.key.codePointAt(0));
+    } else if (event.ctrlKey && event.key === "c") {
+      // Ctrl-C quits, as in the other backends
+      game.invaders_char(handle, "q".codePointAt(0));
+    } else {
+      return false;
+    }
+    event.preventDefault();
+    return false;
+  });
+
+  window.addEventListener("resize", () => {
+    fit(terminal, element);
+    game.invaders_resize(handle, terminal.cols, terminal.rows);
+  });
+
+  const frame = (now) => {
+    const going = game.invaders_frame(handle, now);
+    const output = new Uint8Array(memory.buffer, game.invaders_output(handle), game.invaders_output_len(handle));
+    terminal.write(decoder.decode(output));
+    if (going) {
+      requestAnimationFrame(frame);
+    } else {
+      game.invaders_free(handle);
+    }
+  };
+  requestAnimationFrame(frame);
+}
+
+// Sizes the terminal to fill `element`, by the size of a character cell
+function fit(terminal, element) {
+  const cell = terminal._core._renderService.dimensions.css.cell;
+  const cols = Math.max(1, Math.floor(element.clientWidth / cell.width));
+  const rows = Math.max(1, Math.floor(element.clientHeight / cell.height));
+  terminal.resize(cols, rows);
+}